@@ -3,14 +3,16 @@ use chrono::{DateTime, TimeZone, Utc};
 use log;
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use crate::process::stream::{StreamEvent, StreamLine};
+
 use super::{
     storage::{self, CheckpointStorage},
-    Checkpoint, CheckpointMetadata, CheckpointPaths, CheckpointResult, CheckpointStrategy,
-    FileSnapshot, FileState, FileTracker, SessionTimeline,
+    Checkpoint, CheckpointLimits, CheckpointMetadata, CheckpointPaths, CheckpointResult,
+    CheckpointStrategy, FileSnapshot, FileState, FileTracker, SessionTimeline,
 };
 
 /// Manages checkpoint operations for a session
@@ -45,10 +47,19 @@ impl CheckpointManager {
             SessionTimeline::new(session_id.clone())
         };
 
+        // Rebuild state from disk so checkpoints taken after a restart stay incremental
+        let current_messages = Self::read_session_messages(&paths.session_file, None);
         let file_tracker = FileTracker {
-            tracked_files: HashMap::new(),
+            tracked_files: Self::rebuild_file_index(&storage, &project_id, &session_id, &timeline),
         };
 
+        log::info!(
+            "Loaded checkpoint manager for session {} with {} messages and {} tracked files",
+            session_id,
+            current_messages.len(),
+            file_tracker.tracked_files.len()
+        );
+
         Ok(Self {
             project_id,
             session_id,
@@ -56,29 +67,122 @@ impl CheckpointManager {
             file_tracker: Arc::new(RwLock::new(file_tracker)),
             storage,
             timeline: Arc::new(RwLock::new(timeline)),
-            current_messages: Arc::new(RwLock::new(Vec::new())),
+            current_messages: Arc::new(RwLock::new(current_messages)),
         })
     }
 
+    /// Read the messages of a session JSONL file, optionally up to (and including) an index
+    fn read_session_messages(session_file: &Path, up_to: Option<usize>) -> Vec<String> {
+        let content = match fs::read_to_string(session_file) {
+            Ok(content) => content,
+            Err(_) => return Vec::new(),
+        };
+
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .take(up_to.map_or(usize::MAX, |index| index + 1))
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    /// Rebuild the file index from the current checkpoint and its ancestors
+    ///
+    /// Checkpoints only store the files that changed since their parent, so the
+    /// latest known state of each file is found by walking up the parent chain.
+    fn rebuild_file_index(
+        storage: &CheckpointStorage,
+        project_id: &str,
+        session_id: &str,
+        timeline: &SessionTimeline,
+    ) -> HashMap<PathBuf, FileState> {
+        let mut tracked_files = HashMap::new();
+        let mut next_id = timeline.current_checkpoint_id.clone();
+
+        while let Some(checkpoint_id) = next_id {
+            let node = match timeline.find_checkpoint(&checkpoint_id) {
+                Some(node) => node,
+                None => {
+                    log::warn!("Checkpoint {} missing from timeline", checkpoint_id);
+                    break;
+                }
+            };
+
+            match storage.load_file_references(project_id, session_id, &checkpoint_id) {
+                Ok(references) => {
+                    for reference in references {
+                        // Newer checkpoints take precedence over their ancestors
+                        tracked_files
+                            .entry(reference.file_path)
+                            .or_insert_with(|| FileState {
+                                last_hash: if reference.is_deleted {
+                                    String::new()
+                                } else {
+                                    reference.hash
+                                },
+                                is_modified: false,
+                                last_modified: node.checkpoint.timestamp,
+                                exists: !reference.is_deleted,
+                            });
+                    }
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Failed to load file references for checkpoint {}: {}",
+                        checkpoint_id,
+                        e
+                    );
+                }
+            }
+
+            next_id = node.checkpoint.parent_checkpoint_id.clone();
+        }
+
+        tracked_files
+    }
+
+    /// Synchronize tracked messages with the session JSONL file
+    ///
+    /// Only messages that were not tracked yet are inspected for tool usage. If the
+    /// session file no longer extends the tracked messages (e.g. it was rewritten by
+    /// a restore), the tracked messages are replaced wholesale.
+    pub async fn sync_messages_from_session(&self, up_to: Option<usize>) -> Result<()> {
+        let claude_dir = self.storage.claude_dir.clone();
+        let paths = CheckpointPaths::new(&claude_dir, &self.project_id, &self.session_id);
+        let session_messages = Self::read_session_messages(&paths.session_file, up_to);
+
+        let new_messages = {
+            let mut messages = self.current_messages.write().await;
+            let is_continuation = messages.len() <= session_messages.len()
+                && messages
+                    .iter()
+                    .zip(session_messages.iter())
+                    .all(|(tracked, current)| tracked == current);
+
+            if !is_continuation {
+                *messages = session_messages;
+                return Ok(());
+            }
+
+            session_messages[messages.len()..].to_vec()
+        };
+
+        for message in new_messages {
+            self.track_message(message).await?;
+        }
+
+        Ok(())
+    }
+
     /// Track a new message in the session
     pub async fn track_message(&self, jsonl_message: String) -> Result<()> {
         let mut messages = self.current_messages.write().await;
         messages.push(jsonl_message.clone());
 
         // Parse message to check for tool usage
-        if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&jsonl_message) {
-            if let Some(content) = msg.get("message").and_then(|m| m.get("content")) {
-                if let Some(content_array) = content.as_array() {
-                    for item in content_array {
-                        if item.get("type").and_then(|t| t.as_str()) == Some("tool_use") {
-                            if let Some(tool_name) = item.get("name").and_then(|n| n.as_str()) {
-                                if let Some(input) = item.get("input") {
-                                    self.track_tool_operation(tool_name, input).await?;
-                                }
-                            }
-                        }
-                    }
-                }
+        if let Some(line) = StreamLine::parse(&jsonl_message) {
+            for (tool_name, input) in line.event.tool_uses() {
+                self.track_tool_operation(tool_name, input).await?;
             }
         }
 
@@ -106,12 +210,13 @@ impl CheckpointManager {
 
     /// Track a file modification
     pub async fn track_file_modification(&self, file_path: &str) -> Result<()> {
+        // Read limits before locking the tracker to keep lock order consistent with restore
+        let limits = self.timeline.read().await.limits.clone();
         let mut tracker = self.file_tracker.write().await;
         let full_path = self.project_path.join(file_path);
 
         // Read current file state
         let (hash, exists, _size, modified) = if full_path.exists() {
-            let content = fs::read_to_string(&full_path).unwrap_or_default();
             let metadata = fs::metadata(&full_path)?;
             let modified = metadata
                 .modified()
@@ -123,12 +228,20 @@ impl CheckpointManager {
                 })
                 .unwrap_or_else(Utc::now);
 
-            (
-                storage::CheckpointStorage::calculate_file_hash(&content),
-                true,
-                metadata.len(),
-                modified,
-            )
+            // Files that will never be snapshotted are fingerprinted by metadata instead of
+            // being read into memory
+            let hash = if limits.skip_reason(&full_path, metadata.len()).is_some() {
+                format!(
+                    "meta:{}:{}",
+                    metadata.len(),
+                    modified.timestamp_nanos_opt().unwrap_or(0)
+                )
+            } else {
+                let content = fs::read(&full_path).unwrap_or_default();
+                storage::CheckpointStorage::calculate_file_hash(&String::from_utf8_lossy(&content))
+            };
+
+            (hash, true, metadata.len(), modified)
         } else {
             (String::new(), false, 0, Utc::now())
         };
@@ -237,8 +350,16 @@ impl CheckpointManager {
         // Generate checkpoint ID early so snapshots reference it
         let checkpoint_id = storage::CheckpointStorage::generate_checkpoint_id();
 
-        // Create file snapshots
-        let file_snapshots = self.create_file_snapshots(&checkpoint_id).await?;
+        // Create file snapshots, leaving out files that exceed the configured limits
+        let limits = self.timeline.read().await.limits.clone();
+        let (file_snapshots, skipped) = self.create_file_snapshots(&checkpoint_id, &limits).await?;
+        let skip_warnings: Vec<String> = skipped
+            .iter()
+            .map(|(path, reason)| format!("Skipped {}: {}", path.display(), reason))
+            .collect();
+        for warning in &skip_warnings {
+            log::warn!("{}", warning);
+        }
 
         // Generate checkpoint struct
         let checkpoint = Checkpoint {
@@ -266,18 +387,20 @@ impl CheckpointManager {
                     &messages.join("\n"),
                     &file_snapshots,
                 ),
+                skipped_files: skipped.into_iter().map(|(path, _)| path).collect(),
             },
         };
 
         // Save checkpoint
         let messages_content = messages.join("\n");
-        let result = self.storage.save_checkpoint(
+        let mut result = self.storage.save_checkpoint(
             &self.project_id,
             &self.session_id,
             &checkpoint,
             file_snapshots,
             &messages_content,
         )?;
+        result.warnings.extend(skip_warnings);
 
         // Reload timeline from disk so in-memory timeline has updated nodes and total_checkpoints
         let claude_dir = self.storage.claude_dir.clone();
@@ -311,86 +434,18 @@ impl CheckpointManager {
         let mut total_tokens = 0u64;
 
         // Iterate through messages in reverse to find the last user prompt
-        for msg_str in messages.iter().rev() {
-            if let Ok(msg) = serde_json::from_str::<serde_json::Value>(msg_str) {
-                // Check for user message
-                if msg.get("type").and_then(|t| t.as_str()) == Some("user") {
-                    if let Some(content) = msg
-                        .get("message")
-                        .and_then(|m| m.get("content"))
-                        .and_then(|c| c.as_array())
-                    {
-                        for item in content {
-                            if item.get("type").and_then(|t| t.as_str()) == Some("text") {
-                                if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
-                                    user_prompt = text.to_string();
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-
-                // Extract model info
-                if let Some(model) = msg.get("model").and_then(|m| m.as_str()) {
-                    model_used = model.to_string();
-                }
-
-                // Also check for model in message.model (assistant messages)
-                if let Some(message) = msg.get("message") {
-                    if let Some(model) = message.get("model").and_then(|m| m.as_str()) {
-                        model_used = model.to_string();
-                    }
-                }
+        for line in messages.iter().rev().filter_map(|m| StreamLine::parse(m)) {
+            if let Some(text) = line.event.user_prompt() {
+                user_prompt = text.to_string();
+            }
 
-                // Count tokens - check both top-level and nested usage
-                // First check for usage in message.usage (assistant messages)
-                if let Some(message) = msg.get("message") {
-                    if let Some(usage) = message.get("usage") {
-                        if let Some(input) = usage.get("input_tokens").and_then(|t| t.as_u64()) {
-                            total_tokens += input;
-                        }
-                        if let Some(output) = usage.get("output_tokens").and_then(|t| t.as_u64()) {
-                            total_tokens += output;
-                        }
-                        // Also count cache tokens
-                        if let Some(cache_creation) = usage
-                            .get("cache_creation_input_tokens")
-                            .and_then(|t| t.as_u64())
-                        {
-                            total_tokens += cache_creation;
-                        }
-                        if let Some(cache_read) = usage
-                            .get("cache_read_input_tokens")
-                            .and_then(|t| t.as_u64())
-                        {
-                            total_tokens += cache_read;
-                        }
-                    }
-                }
+            if let Some(model) = line.event.model() {
+                model_used = model.to_string();
+            }
 
-                // Then check for top-level usage (result messages)
-                if let Some(usage) = msg.get("usage") {
-                    if let Some(input) = usage.get("input_tokens").and_then(|t| t.as_u64()) {
-                        total_tokens += input;
-                    }
-                    if let Some(output) = usage.get("output_tokens").and_then(|t| t.as_u64()) {
-                        total_tokens += output;
-                    }
-                    // Also count cache tokens
-                    if let Some(cache_creation) = usage
-                        .get("cache_creation_input_tokens")
-                        .and_then(|t| t.as_u64())
-                    {
-                        total_tokens += cache_creation;
-                    }
-                    if let Some(cache_read) = usage
-                        .get("cache_read_input_tokens")
-                        .and_then(|t| t.as_u64())
-                    {
-                        total_tokens += cache_read;
-                    }
-                }
+            // Cache writes and reads count towards the checkpoint's token total
+            if let Some(usage) = line.event.usage() {
+                total_tokens += usage.total_with_cache();
             }
         }
 
@@ -398,26 +453,61 @@ impl CheckpointManager {
     }
 
     /// Create file snapshots for all tracked modified files
-    async fn create_file_snapshots(&self, checkpoint_id: &str) -> Result<Vec<FileSnapshot>> {
+    ///
+    /// Returns the snapshots together with the files that were skipped and why.
+    async fn create_file_snapshots(
+        &self,
+        checkpoint_id: &str,
+        limits: &CheckpointLimits,
+    ) -> Result<(Vec<FileSnapshot>, Vec<(PathBuf, String)>)> {
         let tracker = self.file_tracker.read().await;
         let mut snapshots = Vec::new();
+        let mut skipped = Vec::new();
+        let mut checkpoint_size = 0u64;
 
-        for (rel_path, state) in &tracker.tracked_files {
-            // Skip files that haven't been modified
-            if !state.is_modified {
-                continue;
-            }
+        // Visit files in a stable order so the checkpoint size budget is applied predictably
+        let mut modified_files: Vec<&PathBuf> = tracker
+            .tracked_files
+            .iter()
+            .filter(|(_, state)| state.is_modified)
+            .map(|(path, _)| path)
+            .collect();
+        modified_files.sort();
 
+        for rel_path in modified_files {
             let full_path = self.project_path.join(rel_path);
 
             let (content, exists, permissions, size, current_hash) = if full_path.exists() {
-                let content = fs::read_to_string(&full_path).unwrap_or_default();
+                let metadata = fs::metadata(&full_path)?;
+
+                // Check size and type before reading so huge files never hit memory
+                if let Some(reason) = limits.skip_reason(rel_path, metadata.len()) {
+                    skipped.push((rel_path.clone(), reason));
+                    continue;
+                }
+                if checkpoint_size + metadata.len() > limits.max_checkpoint_size {
+                    skipped.push((
+                        rel_path.clone(),
+                        format!(
+                            "checkpoint size limit of {} bytes reached",
+                            limits.max_checkpoint_size
+                        ),
+                    ));
+                    continue;
+                }
+
+                let bytes = fs::read(&full_path)?;
+                if CheckpointLimits::is_binary_content(&bytes) {
+                    skipped.push((rel_path.clone(), "binary content".to_string()));
+                    continue;
+                }
+                let content = String::from_utf8(bytes)?;
                 let current_hash = storage::CheckpointStorage::calculate_file_hash(&content);
+                checkpoint_size += metadata.len();
 
                 // Don't skip based on hash - if is_modified is true, we should snapshot it
                 // The hash check in track_file_modification already determined if it changed
 
-                let metadata = fs::metadata(&full_path)?;
                 let permissions = {
                     #[cfg(unix)]
                     {
@@ -445,7 +535,7 @@ impl CheckpointManager {
             });
         }
 
-        Ok(snapshots)
+        Ok((snapshots, skipped))
     }
 
     /// Restore a checkpoint
@@ -494,11 +584,25 @@ impl CheckpointManager {
             }
         }
 
+        // Files that were never snapshotted must be left untouched rather than deleted
+        let limits = self.timeline.read().await.limits.clone();
+        let skipped_files: std::collections::HashSet<&PathBuf> =
+            checkpoint.metadata.skipped_files.iter().collect();
+
         // Delete files that exist now but shouldn't exist in the checkpoint
         let mut warnings = Vec::new();
         let mut files_processed = 0;
 
         for current_file in current_files {
+            if skipped_files.contains(&current_file) || self.exceeds_limits(&current_file, &limits)
+            {
+                log::info!(
+                    "Leaving file outside checkpoint limits untouched: {:?}",
+                    current_file
+                );
+                continue;
+            }
+
             if !checkpoint_files.contains(&current_file) {
                 // This file exists now but not in the checkpoint, so delete it
                 let full_path = self.project_path.join(&current_file);
@@ -598,6 +702,29 @@ impl CheckpointManager {
         })
     }
 
+    /// Check whether a project file falls outside the checkpoint limits
+    fn exceeds_limits(&self, rel_path: &Path, limits: &CheckpointLimits) -> bool {
+        let full_path = self.project_path.join(rel_path);
+        let size = match fs::metadata(&full_path) {
+            Ok(metadata) => metadata.len(),
+            Err(_) => return false,
+        };
+
+        if limits.skip_reason(rel_path, size).is_some() {
+            return true;
+        }
+
+        // Only sniff the beginning of the file to detect binary content
+        let mut buffer = vec![0u8; 8000];
+        match fs::File::open(&full_path).and_then(|mut file| {
+            use std::io::Read;
+            file.read(&mut buffer)
+        }) {
+            Ok(read) => CheckpointLimits::is_binary_content(&buffer[..read]),
+            Err(_) => false,
+        }
+    }
+
     /// Restore a single file from snapshot
     async fn restore_file_snapshot(&self, snapshot: &FileSnapshot) -> Result<()> {
         let full_path = self.project_path.join(&snapshot.file_path);
@@ -691,56 +818,24 @@ impl CheckpointManager {
             CheckpointStrategy::Manual => false,
             CheckpointStrategy::PerPrompt => {
                 // Check if message is a user prompt
-                if let Ok(msg) = serde_json::from_str::<serde_json::Value>(message) {
-                    msg.get("type").and_then(|t| t.as_str()) == Some("user")
-                } else {
-                    false
-                }
+                StreamLine::parse(message)
+                    .is_some_and(|line| matches!(line.event, StreamEvent::User(_)))
             }
             CheckpointStrategy::PerToolUse => {
                 // Check if message contains tool use
-                if let Ok(msg) = serde_json::from_str::<serde_json::Value>(message) {
-                    if let Some(content) = msg
-                        .get("message")
-                        .and_then(|m| m.get("content"))
-                        .and_then(|c| c.as_array())
-                    {
-                        content.iter().any(|item| {
-                            item.get("type").and_then(|t| t.as_str()) == Some("tool_use")
-                        })
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
+                StreamLine::parse(message)
+                    .is_some_and(|line| line.event.tool_uses().next().is_some())
             }
             CheckpointStrategy::Smart => {
                 // Smart strategy: checkpoint after destructive operations
-                if let Ok(msg) = serde_json::from_str::<serde_json::Value>(message) {
-                    if let Some(content) = msg
-                        .get("message")
-                        .and_then(|m| m.get("content"))
-                        .and_then(|c| c.as_array())
-                    {
-                        content.iter().any(|item| {
-                            if item.get("type").and_then(|t| t.as_str()) == Some("tool_use") {
-                                let tool_name =
-                                    item.get("name").and_then(|n| n.as_str()).unwrap_or("");
-                                matches!(
-                                    tool_name.to_lowercase().as_str(),
-                                    "write" | "edit" | "multiedit" | "bash" | "rm" | "delete"
-                                )
-                            } else {
-                                false
-                            }
-                        })
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
+                StreamLine::parse(message).is_some_and(|line| {
+                    line.event.tool_uses().any(|(tool_name, _)| {
+                        matches!(
+                            tool_name.to_lowercase().as_str(),
+                            "write" | "edit" | "multiedit" | "bash" | "rm" | "delete"
+                        )
+                    })
+                })
             }
         }
     }
@@ -750,10 +845,14 @@ impl CheckpointManager {
         &self,
         auto_checkpoint_enabled: bool,
         checkpoint_strategy: CheckpointStrategy,
+        limits: Option<CheckpointLimits>,
     ) -> Result<()> {
         let mut timeline = self.timeline.write().await;
         timeline.auto_checkpoint_enabled = auto_checkpoint_enabled;
         timeline.checkpoint_strategy = checkpoint_strategy;
+        if let Some(limits) = limits {
+            timeline.limits = limits;
+        }
 
         // Save updated timeline
         let claude_dir = self.storage.claude_dir.clone();
@@ -785,3 +884,108 @@ impl CheckpointManager {
             .max()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_manager_reloads_state_after_restart() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join(".claude");
+        let project_path = temp_dir.path().join("project");
+        std::fs::create_dir_all(&project_path).unwrap();
+        std::fs::write(project_path.join("main.rs"), "fn main() {}").unwrap();
+        std::fs::write(project_path.join("lib.rs"), "pub fn lib() {}").unwrap();
+
+        let paths = CheckpointPaths::new(&claude_dir, "test-project", "test-session");
+        std::fs::create_dir_all(paths.session_file.parent().unwrap()).unwrap();
+        std::fs::write(
+            &paths.session_file,
+            "{\"type\":\"user\"}\n{\"type\":\"assistant\"}\n",
+        )
+        .unwrap();
+
+        let manager = CheckpointManager::new(
+            "test-project".to_string(),
+            "test-session".to_string(),
+            project_path.clone(),
+            claude_dir.clone(),
+        )
+        .await
+        .unwrap();
+        let first = manager.create_checkpoint(None, None).await.unwrap();
+        assert_eq!(first.checkpoint.message_index, 1);
+        assert_eq!(first.checkpoint.metadata.file_changes, 2);
+        drop(manager);
+
+        // Simulate an app restart: new messages arrive and a single file changes
+        std::fs::write(
+            &paths.session_file,
+            "{\"type\":\"user\"}\n{\"type\":\"assistant\"}\n{\"type\":\"user\"}\n",
+        )
+        .unwrap();
+        std::fs::write(project_path.join("main.rs"), "fn main() { run() }").unwrap();
+
+        let manager = CheckpointManager::new(
+            "test-project".to_string(),
+            "test-session".to_string(),
+            project_path,
+            claude_dir,
+        )
+        .await
+        .unwrap();
+        let second = manager.create_checkpoint(None, None).await.unwrap();
+
+        assert_eq!(second.checkpoint.message_index, 2);
+        assert_eq!(second.checkpoint.metadata.file_changes, 1);
+        assert_eq!(
+            second.checkpoint.parent_checkpoint_id.as_deref(),
+            Some(first.checkpoint.id.as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oversized_files_are_skipped_and_preserved_on_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join(".claude");
+        let project_path = temp_dir.path().join("project");
+        std::fs::create_dir_all(&project_path).unwrap();
+        std::fs::write(project_path.join("notes.txt"), "small").unwrap();
+        std::fs::write(project_path.join("image.png"), [0u8, 1, 2]).unwrap();
+
+        let manager = CheckpointManager::new(
+            "test-project".to_string(),
+            "test-session".to_string(),
+            project_path.clone(),
+            claude_dir,
+        )
+        .await
+        .unwrap();
+        let limits = CheckpointLimits {
+            max_file_size: 16,
+            ..CheckpointLimits::default()
+        };
+        manager
+            .update_settings(false, CheckpointStrategy::Manual, Some(limits))
+            .await
+            .unwrap();
+
+        let result = manager.create_checkpoint(None, None).await.unwrap();
+        assert_eq!(result.checkpoint.metadata.file_changes, 1);
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains("image.png"));
+
+        // A file created later that is too large must survive restoring the checkpoint
+        std::fs::write(project_path.join("dump.sql"), "x".repeat(64)).unwrap();
+        manager
+            .restore_checkpoint(&result.checkpoint.id)
+            .await
+            .unwrap();
+
+        assert!(project_path.join("image.png").exists());
+        assert!(project_path.join("dump.sql").exists());
+        assert!(project_path.join("notes.txt").exists());
+    }
+}
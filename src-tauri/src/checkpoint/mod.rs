@@ -43,6 +43,9 @@ pub struct CheckpointMetadata {
     pub file_changes: usize,
     /// Size of all file snapshots in bytes
     pub snapshot_size: u64,
+    /// Files that were left out of this checkpoint because of size or content limits
+    #[serde(default)]
+    pub skipped_files: Vec<PathBuf>,
 }
 
 /// Represents a snapshot of a file at a checkpoint
@@ -93,6 +96,21 @@ pub struct SessionTimeline {
     pub checkpoint_strategy: CheckpointStrategy,
     /// Total number of checkpoints in timeline
     pub total_checkpoints: usize,
+    /// Size and content limits applied when snapshotting files
+    #[serde(default)]
+    pub limits: CheckpointLimits,
+}
+
+/// Size and content limits for checkpointed files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointLimits {
+    /// Files larger than this many bytes are not snapshotted
+    pub max_file_size: u64,
+    /// Total bytes of file content a single checkpoint may hold
+    pub max_checkpoint_size: u64,
+    /// File extensions (without the dot) that are never snapshotted
+    pub excluded_extensions: Vec<String>,
 }
 
 /// Strategy for automatic checkpoint creation
@@ -176,6 +194,62 @@ impl Default for CheckpointStrategy {
     }
 }
 
+impl Default for CheckpointLimits {
+    fn default() -> Self {
+        Self {
+            max_file_size: 10 * 1024 * 1024,
+            max_checkpoint_size: 100 * 1024 * 1024,
+            excluded_extensions: [
+                "png", "jpg", "jpeg", "gif", "webp", "ico", "pdf", "zip", "gz", "tgz", "bz2", "xz",
+                "7z", "rar", "tar", "mp3", "mp4", "mov", "avi", "mkv", "wav", "db", "sqlite",
+                "sqlite3", "dump", "iso", "dmg", "exe", "dll", "so", "dylib", "o", "a", "class",
+                "jar", "wasm", "bin",
+            ]
+            .iter()
+            .map(|ext| ext.to_string())
+            .collect(),
+        }
+    }
+}
+
+impl CheckpointLimits {
+    /// Returns why a file should be left out of checkpoints based on its path and size
+    pub fn skip_reason(&self, path: &std::path::Path, size: u64) -> Option<String> {
+        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+            if self
+                .excluded_extensions
+                .iter()
+                .any(|excluded| excluded.eq_ignore_ascii_case(ext))
+            {
+                return Some(format!("excluded file type .{}", ext));
+            }
+        }
+
+        if size > self.max_file_size {
+            return Some(format!(
+                "file size {} bytes exceeds the per-file limit of {} bytes",
+                size, self.max_file_size
+            ));
+        }
+
+        None
+    }
+
+    /// Checks whether content looks binary (contains NUL bytes or is not valid UTF-8)
+    pub fn is_binary_content(content: &[u8]) -> bool {
+        let sample = &content[..content.len().min(8000)];
+        if sample.contains(&0) {
+            return true;
+        }
+
+        match std::str::from_utf8(content) {
+            Ok(_) => false,
+            // A sequence cut off at the end of a partial read does not indicate binary data
+            Err(e) => e.error_len().is_some(),
+        }
+    }
+}
+
 impl SessionTimeline {
     /// Create a new empty timeline
     pub fn new(session_id: String) -> Self {
@@ -186,6 +260,7 @@ impl SessionTimeline {
             auto_checkpoint_enabled: false,
             checkpoint_strategy: CheckpointStrategy::default(),
             total_checkpoints: 0,
+            limits: CheckpointLimits::default(),
         }
     }
 
@@ -211,25 +286,35 @@ impl SessionTimeline {
     }
 }
 
+/// Reference to a file snapshot without its content
+#[derive(Debug, Clone)]
+pub struct FileReference {
+    /// Relative path from project root
+    pub file_path: PathBuf,
+    /// SHA-256 hash of the snapshot content
+    pub hash: String,
+    /// Whether this file was deleted at this checkpoint
+    pub is_deleted: bool,
+}
+
 /// Checkpoint storage paths
 pub struct CheckpointPaths {
     pub timeline_file: PathBuf,
     pub checkpoints_dir: PathBuf,
     pub files_dir: PathBuf,
+    pub session_file: PathBuf,
 }
 
 impl CheckpointPaths {
     pub fn new(claude_dir: &PathBuf, project_id: &str, session_id: &str) -> Self {
-        let base_dir = claude_dir
-            .join("projects")
-            .join(project_id)
-            .join(".timelines")
-            .join(session_id);
+        let project_dir = claude_dir.join("projects").join(project_id);
+        let base_dir = project_dir.join(".timelines").join(session_id);
 
         Self {
             timeline_file: base_dir.join("timeline.json"),
             checkpoints_dir: base_dir.join("checkpoints"),
             files_dir: base_dir.join("files"),
+            session_file: project_dir.join(format!("{}.jsonl", session_id)),
         }
     }
 
@@ -6,7 +6,8 @@ use uuid::Uuid;
 use zstd::stream::{decode_all, encode_all};
 
 use super::{
-    Checkpoint, CheckpointPaths, CheckpointResult, FileSnapshot, SessionTimeline, TimelineNode,
+    Checkpoint, CheckpointPaths, CheckpointResult, FileReference, FileSnapshot, SessionTimeline,
+    TimelineNode,
 };
 
 /// Manages checkpoint storage operations
@@ -237,6 +238,40 @@ impl CheckpointStorage {
         Ok(snapshots)
     }
 
+    /// Load the file references of a checkpoint without reading their content
+    pub fn load_file_references(
+        &self,
+        project_id: &str,
+        session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<Vec<FileReference>> {
+        let paths = CheckpointPaths::new(&self.claude_dir, project_id, session_id);
+        let refs_dir = paths.files_dir.join("refs").join(checkpoint_id);
+        if !refs_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut references = Vec::new();
+        for entry in fs::read_dir(&refs_dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+
+            let ref_json = fs::read_to_string(&path).context("Failed to read file reference")?;
+            let ref_metadata: serde_json::Value =
+                serde_json::from_str(&ref_json).context("Failed to parse file reference")?;
+
+            references.push(FileReference {
+                file_path: PathBuf::from(ref_metadata["path"].as_str().unwrap_or("")),
+                hash: ref_metadata["hash"].as_str().unwrap_or("").to_string(),
+                is_deleted: ref_metadata["is_deleted"].as_bool().unwrap_or(false),
+            });
+        }
+
+        Ok(references)
+    }
+
     /// Save timeline to disk
     pub fn save_timeline(&self, timeline_path: &Path, timeline: &SessionTimeline) -> Result<()> {
         let timeline_json =
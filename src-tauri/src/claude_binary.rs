@@ -531,19 +531,31 @@ pub fn create_command_with_env(program: &str) -> Command {
         }
     }
 
+    // GUI apps on macOS start with a minimal PATH, so make sure the usual locations are there
+    let mut path = std::env::var("PATH").unwrap_or_default();
+    if !cfg!(target_os = "windows") {
+        for dir in ["/opt/homebrew/bin", "/usr/local/bin", "/usr/bin", "/bin"] {
+            if !path.split(':').any(|p| p == dir) {
+                if !path.is_empty() {
+                    path.push(':');
+                }
+                path.push_str(dir);
+            }
+        }
+    }
+
     // Add NVM support if the program is in an NVM directory
     if program.contains("/.nvm/versions/node/") {
         if let Some(node_bin_dir) = std::path::Path::new(program).parent() {
             // Ensure the Node.js bin directory is in PATH
-            let current_path = std::env::var("PATH").unwrap_or_default();
             let node_bin_str = node_bin_dir.to_string_lossy();
-            if !current_path.contains(&node_bin_str.as_ref()) {
-                let new_path = format!("{}:{}", node_bin_str, current_path);
+            if !path.contains(node_bin_str.as_ref()) {
                 debug!("Adding NVM bin directory to PATH: {}", node_bin_str);
-                cmd.env("PATH", new_path);
+                path = format!("{}:{}", node_bin_str, path);
             }
         }
     }
+    cmd.env("PATH", path);
 
     cmd
 }
@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// The type of an agent input parameter and its validation rules
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ParameterKind {
+    String {
+        /// Regex the whole value must match
+        #[serde(default)]
+        pattern: Option<String>,
+    },
+    Enum {
+        options: Vec<String>,
+    },
+    /// A path, relative to the project unless absolute
+    Path {
+        #[serde(default)]
+        must_exist: bool,
+    },
+    Number {
+        #[serde(default)]
+        min: Option<f64>,
+        #[serde(default)]
+        max: Option<f64>,
+    },
+}
+
+/// A named input an agent declares, substituted for `{{name}}` in its task and system prompt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentParameter {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: ParameterKind,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub default: Option<Value>,
+    /// Parameters without a default must be given a value unless optional
+    #[serde(default = "default_required")]
+    pub required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+/// Parses and checks parameter definitions stored as a JSON string
+pub fn parse_parameters(json: Option<&str>) -> Result<Vec<AgentParameter>, String> {
+    let params: Vec<AgentParameter> = match json.map(str::trim) {
+        None | Some("") => return Ok(Vec::new()),
+        Some(json) => {
+            serde_json::from_str(json).map_err(|e| format!("Invalid agent parameters: {}", e))?
+        }
+    };
+
+    let mut seen = Vec::new();
+    for param in &params {
+        let valid_name = !param.name.is_empty()
+            && param
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(format!(
+                "Invalid parameter name '{}': use letters, digits and underscores",
+                param.name
+            ));
+        }
+        if seen.contains(&&param.name) {
+            return Err(format!("Parameter '{}' is declared twice", param.name));
+        }
+        seen.push(&param.name);
+
+        match &param.kind {
+            ParameterKind::Enum { options } if options.is_empty() => {
+                return Err(format!("Parameter '{}' has no options", param.name));
+            }
+            ParameterKind::String {
+                pattern: Some(pattern),
+            } => {
+                regex::Regex::new(pattern)
+                    .map_err(|e| format!("Invalid pattern for '{}': {}", param.name, e))?;
+            }
+            _ => {}
+        }
+    }
+    Ok(params)
+}
+
+/// Validates the values given for a run, filling in defaults
+///
+/// Returns the resolved values by name; optional parameters without a value are left out.
+pub fn resolve_parameters(
+    params: &[AgentParameter],
+    values: &HashMap<String, Value>,
+    project_path: &str,
+) -> Result<BTreeMap<String, Value>, String> {
+    if let Some(unknown) = values
+        .keys()
+        .find(|name| !params.iter().any(|p| &p.name == *name))
+    {
+        return Err(format!("Unknown parameter '{}'", unknown));
+    }
+
+    let mut resolved = BTreeMap::new();
+    for param in params {
+        let value = match values.get(&param.name).filter(|v| !v.is_null()) {
+            Some(value) => value.clone(),
+            None => match &param.default {
+                Some(default) => default.clone(),
+                None if param.required => {
+                    return Err(format!("Parameter '{}' is required", param.name))
+                }
+                None => continue,
+            },
+        };
+        resolved.insert(param.name.clone(), validate(param, value, project_path)?);
+    }
+    Ok(resolved)
+}
+
+/// Checks a value against its parameter's type, normalising it
+fn validate(param: &AgentParameter, value: Value, project_path: &str) -> Result<Value, String> {
+    let name = &param.name;
+    match &param.kind {
+        ParameterKind::Number { min, max } => {
+            let number = match &value {
+                Value::Number(n) => n.as_f64(),
+                Value::String(s) => s.trim().parse::<f64>().ok(),
+                _ => None,
+            }
+            .ok_or_else(|| format!("Parameter '{}' must be a number", name))?;
+            if min.is_some_and(|min| number < min) || max.is_some_and(|max| number > max) {
+                return Err(format!(
+                    "Parameter '{}' must be between {} and {}",
+                    name,
+                    min.map_or("-∞".to_string(), |m| m.to_string()),
+                    max.map_or("∞".to_string(), |m| m.to_string())
+                ));
+            }
+            Ok(serde_json::Number::from_f64(number)
+                .map(Value::Number)
+                .unwrap_or(value))
+        }
+        kind => {
+            let text = value
+                .as_str()
+                .ok_or_else(|| format!("Parameter '{}' must be a string", name))?;
+            match kind {
+                ParameterKind::String {
+                    pattern: Some(pattern),
+                } => {
+                    let re = regex::Regex::new(&format!("^(?:{})$", pattern))
+                        .map_err(|e| format!("Invalid pattern for '{}': {}", name, e))?;
+                    if !re.is_match(text) {
+                        return Err(format!("Parameter '{}' must match {}", name, pattern));
+                    }
+                }
+                ParameterKind::Enum { options } if !options.iter().any(|o| o == text) => {
+                    return Err(format!(
+                        "Parameter '{}' must be one of: {}",
+                        name,
+                        options.join(", ")
+                    ));
+                }
+                ParameterKind::Path { must_exist: true }
+                    if !Path::new(project_path).join(text).exists() =>
+                {
+                    return Err(format!("Parameter '{}': {} does not exist", name, text));
+                }
+                _ => {}
+            }
+            Ok(value)
+        }
+    }
+}
+
+/// Replaces `{{name}}` placeholders with resolved values; other placeholders are left as is
+pub fn substitute(template: &str, values: &BTreeMap<String, Value>) -> String {
+    let mut result = template.to_string();
+    for (name, value) in values {
+        let text = match value {
+            Value::String(s) => s.clone(),
+            Value::Number(n) => match n.as_f64() {
+                Some(f) if f.fract() == 0.0 && f.abs() < 1e15 => (f as i64).to_string(),
+                _ => n.to_string(),
+            },
+            other => other.to_string(),
+        };
+        result = result.replace(&format!("{{{{{}}}}}", name), &text);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parameters_are_validated_and_substituted() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::create_dir(project.path().join("billing")).unwrap();
+        let project_path = project.path().to_str().unwrap();
+
+        let params = parse_parameters(Some(
+            r#"[
+                {"name": "module", "type": "path", "must_exist": true},
+                {"name": "api", "type": "enum", "options": ["v1", "v2"], "default": "v2"},
+                {"name": "batch", "type": "number", "min": 1, "max": 50, "default": 10},
+                {"name": "ticket", "type": "string", "pattern": "[A-Z]+-[0-9]+", "required": false}
+            ]"#,
+        ))
+        .unwrap();
+
+        let values = HashMap::from([
+            ("module".to_string(), json!("billing")),
+            ("batch".to_string(), json!("25")),
+        ]);
+        let resolved = resolve_parameters(&params, &values, project_path).unwrap();
+        assert_eq!(
+            substitute(
+                "Migrate {{module}} to API {{api}} in batches of {{batch}} ({{ticket}})",
+                &resolved
+            ),
+            "Migrate billing to API v2 in batches of 25 ({{ticket}})"
+        );
+
+        let bad = |name: &str, value: Value| {
+            let mut values = values.clone();
+            values.insert(name.to_string(), value);
+            resolve_parameters(&params, &values, project_path).unwrap_err()
+        };
+        assert!(bad("module", json!("shipping")).contains("does not exist"));
+        assert!(bad("api", json!("v3")).contains("one of: v1, v2"));
+        assert!(bad("batch", json!(99)).contains("between 1 and 50"));
+        assert!(bad("ticket", json!("abc")).contains("must match"));
+        assert!(bad("owner", json!("me")).contains("Unknown parameter"));
+        assert!(resolve_parameters(&params, &HashMap::new(), project_path)
+            .unwrap_err()
+            .contains("'module' is required"));
+
+        assert!(parse_parameters(Some(r#"[{"name": "a b", "type": "string"}]"#)).is_err());
+    }
+}
@@ -0,0 +1,493 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use super::agents::{Agent, AgentDb};
+
+/// Agent columns captured by every version, in the order `AgentVersion::from_row` expects
+/// them after `id, agent_id, version`
+const VERSIONED_COLUMNS: &str = "name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, resource_limits, permissions, parameters, budget, verification, retry_policy";
+
+/// A snapshot of an agent's configuration, recorded whenever it changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentVersion {
+    pub id: i64,
+    pub agent_id: i64,
+    pub version: i64,
+    pub name: String,
+    pub icon: String,
+    pub system_prompt: String,
+    pub default_task: Option<String>,
+    pub model: String,
+    pub enable_file_read: bool,
+    pub enable_file_write: bool,
+    pub enable_network: bool,
+    pub hooks: Option<String>,
+    pub resource_limits: Option<String>,
+    pub permissions: Option<String>,
+    pub parameters: Option<String>,
+    pub budget: Option<String>,
+    pub verification: Option<String>,
+    pub retry_policy: Option<String>,
+    pub note: Option<String>, // Why the change was made, written by its author
+    pub created_at: String,
+}
+
+impl AgentVersion {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(AgentVersion {
+            id: row.get(0)?,
+            agent_id: row.get(1)?,
+            version: row.get(2)?,
+            name: row.get(3)?,
+            icon: row.get(4)?,
+            system_prompt: row.get(5)?,
+            default_task: row.get(6)?,
+            model: row.get(7)?,
+            enable_file_read: row.get(8)?,
+            enable_file_write: row.get(9)?,
+            enable_network: row.get(10)?,
+            hooks: row.get(11)?,
+            resource_limits: row.get(12)?,
+            permissions: row.get(13)?,
+            parameters: row.get(14)?,
+            budget: row.get(15)?,
+            verification: row.get(16)?,
+            retry_policy: row.get(17)?,
+            note: row.get(18)?,
+            created_at: row.get(19)?,
+        })
+    }
+
+    /// The agent as it was configured in this version
+    pub fn to_agent(&self) -> Agent {
+        Agent {
+            id: Some(self.agent_id),
+            name: self.name.clone(),
+            icon: self.icon.clone(),
+            system_prompt: self.system_prompt.clone(),
+            default_task: self.default_task.clone(),
+            model: self.model.clone(),
+            enable_file_read: self.enable_file_read,
+            enable_file_write: self.enable_file_write,
+            enable_network: self.enable_network,
+            hooks: self.hooks.clone(),
+            resource_limits: self.resource_limits.clone(),
+            permissions: self.permissions.clone(),
+            parameters: self.parameters.clone(),
+            budget: self.budget.clone(),
+            verification: self.verification.clone(),
+            retry_policy: self.retry_policy.clone(),
+            created_at: self.created_at.clone(),
+            updated_at: self.created_at.clone(),
+        }
+    }
+
+    /// Field values compared by `diff_agent_versions`
+    fn fields(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("name", Some(self.name.clone())),
+            ("icon", Some(self.icon.clone())),
+            ("system_prompt", Some(self.system_prompt.clone())),
+            ("default_task", self.default_task.clone()),
+            ("model", Some(self.model.clone())),
+            ("enable_file_read", Some(self.enable_file_read.to_string())),
+            (
+                "enable_file_write",
+                Some(self.enable_file_write.to_string()),
+            ),
+            ("enable_network", Some(self.enable_network.to_string())),
+            ("hooks", self.hooks.clone()),
+            ("resource_limits", self.resource_limits.clone()),
+            ("permissions", self.permissions.clone()),
+            ("parameters", self.parameters.clone()),
+            ("budget", self.budget.clone()),
+            ("verification", self.verification.clone()),
+            ("retry_policy", self.retry_policy.clone()),
+        ]
+    }
+}
+
+/// A field whose value differs between two versions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// One line of a line-by-line diff
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+/// Differences between two versions of an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentVersionDiff {
+    pub from_version: i64,
+    pub to_version: i64,
+    pub changes: Vec<FieldChange>,
+    /// Line diff of the system prompt, empty when it didn't change
+    pub system_prompt_diff: Vec<DiffLine>,
+}
+
+/// Creates the `agent_versions` table and records a first version for agents that have none
+pub fn init_agent_versions(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agent_versions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id INTEGER NOT NULL,
+            version INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            icon TEXT NOT NULL,
+            system_prompt TEXT NOT NULL,
+            default_task TEXT,
+            model TEXT NOT NULL,
+            enable_file_read BOOLEAN NOT NULL,
+            enable_file_write BOOLEAN NOT NULL,
+            enable_network BOOLEAN NOT NULL,
+            hooks TEXT,
+            resource_limits TEXT,
+            permissions TEXT,
+            parameters TEXT,
+            budget TEXT,
+            verification TEXT,
+            retry_policy TEXT,
+            note TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (agent_id, version),
+            FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    let _ = conn.execute("ALTER TABLE agent_versions ADD COLUMN parameters TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_versions ADD COLUMN budget TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE agent_versions ADD COLUMN verification TEXT",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE agent_versions ADD COLUMN retry_policy TEXT",
+        [],
+    );
+
+    conn.execute(
+        &format!(
+            "INSERT INTO agent_versions (agent_id, version, {cols}, note)
+             SELECT id, 1, {cols}, NULL FROM agents
+             WHERE id NOT IN (SELECT agent_id FROM agent_versions)",
+            cols = VERSIONED_COLUMNS
+        ),
+        [],
+    )?;
+    Ok(())
+}
+
+/// Snapshots an agent's current configuration as its next version, returning the version number
+pub fn record_agent_version(
+    conn: &Connection,
+    agent_id: i64,
+    note: Option<&str>,
+) -> Result<i64, String> {
+    let version = latest_agent_version(conn, agent_id)?.unwrap_or(0) + 1;
+    conn.execute(
+        &format!(
+            "INSERT INTO agent_versions (agent_id, version, {cols}, note)
+             SELECT id, ?2, {cols}, ?3 FROM agents WHERE id = ?1",
+            cols = VERSIONED_COLUMNS
+        ),
+        params![agent_id, version, note],
+    )
+    .map_err(|e| format!("Failed to record agent version: {}", e))?;
+    Ok(version)
+}
+
+/// The newest version number of an agent
+pub fn latest_agent_version(conn: &Connection, agent_id: i64) -> Result<Option<i64>, String> {
+    conn.query_row(
+        "SELECT MAX(version) FROM agent_versions WHERE agent_id = ?1",
+        params![agent_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Loads one version of an agent
+pub fn load_agent_version(
+    conn: &Connection,
+    agent_id: i64,
+    version: i64,
+) -> Result<AgentVersion, String> {
+    conn.query_row(
+        &format!(
+            "SELECT id, agent_id, version, {}, note, created_at FROM agent_versions WHERE agent_id = ?1 AND version = ?2",
+            VERSIONED_COLUMNS
+        ),
+        params![agent_id, version],
+        AgentVersion::from_row,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Agent {} has no version {}", agent_id, version))
+}
+
+/// Restores an agent's configuration from an earlier version and records that as a new version
+fn rollback_to_version(
+    conn: &Connection,
+    agent_id: i64,
+    version: i64,
+    note: Option<&str>,
+) -> Result<i64, String> {
+    let target = load_agent_version(conn, agent_id, version)?;
+    conn.execute(
+        "UPDATE agents SET name = ?2, icon = ?3, system_prompt = ?4, default_task = ?5, model = ?6,
+            enable_file_read = ?7, enable_file_write = ?8, enable_network = ?9, hooks = ?10,
+            resource_limits = ?11, permissions = ?12, parameters = ?13, budget = ?14,
+            verification = ?15, retry_policy = ?16
+         WHERE id = ?1",
+        params![
+            agent_id,
+            target.name,
+            target.icon,
+            target.system_prompt,
+            target.default_task,
+            target.model,
+            target.enable_file_read,
+            target.enable_file_write,
+            target.enable_network,
+            target.hooks,
+            target.resource_limits,
+            target.permissions,
+            target.parameters,
+            target.budget,
+            target.verification,
+            target.retry_policy,
+        ],
+    )
+    .map_err(|e| format!("Failed to roll back agent: {}", e))?;
+
+    let default_note = format!("Rolled back to version {}", version);
+    record_agent_version(conn, agent_id, Some(note.unwrap_or(&default_note)))
+}
+
+/// Compares two versions field by field
+fn diff_versions(from: &AgentVersion, to: &AgentVersion) -> AgentVersionDiff {
+    let changes: Vec<FieldChange> = from
+        .fields()
+        .into_iter()
+        .zip(to.fields())
+        .filter(|((_, before), (_, after))| before != after)
+        .map(|((field, before), (_, after))| FieldChange {
+            field: field.to_string(),
+            before,
+            after,
+        })
+        .collect();
+
+    let system_prompt_diff = if from.system_prompt == to.system_prompt {
+        Vec::new()
+    } else {
+        diff_lines(&from.system_prompt, &to.system_prompt)
+    };
+
+    AgentVersionDiff {
+        from_version: from.version,
+        to_version: to.version,
+        changes,
+        system_prompt_diff,
+    }
+}
+
+/// Line diff based on the longest common subsequence
+fn diff_lines(before: &str, after: &str) -> Vec<DiffLine> {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+
+    // lcs[i][j] is the LCS length of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let line = |op, text: &str| DiffLine {
+        op,
+        text: text.to_string(),
+    };
+    let (mut i, mut j) = (0, 0);
+    let mut diff = Vec::new();
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            diff.push(line(DiffOp::Equal, a[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(line(DiffOp::Delete, a[i]));
+            i += 1;
+        } else {
+            diff.push(line(DiffOp::Insert, b[j]));
+            j += 1;
+        }
+    }
+    diff.extend(a[i..].iter().map(|t| line(DiffOp::Delete, t)));
+    diff.extend(b[j..].iter().map(|t| line(DiffOp::Insert, t)));
+    diff
+}
+
+/// List an agent's versions, newest first
+#[tauri::command]
+pub async fn list_agent_versions(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+) -> Result<Vec<AgentVersion>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, agent_id, version, {}, note, created_at FROM agent_versions WHERE agent_id = ?1 ORDER BY version DESC",
+            VERSIONED_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let versions = stmt
+        .query_map(params![agent_id], AgentVersion::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(versions)
+}
+
+/// Get a single version of an agent
+#[tauri::command]
+pub async fn get_agent_version(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+    version: i64,
+) -> Result<AgentVersion, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_agent_version(&conn, agent_id, version)
+}
+
+/// Show what changed between two versions of an agent
+#[tauri::command]
+pub async fn diff_agent_versions(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+    from_version: i64,
+    to_version: i64,
+) -> Result<AgentVersionDiff, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let from = load_agent_version(&conn, agent_id, from_version)?;
+    let to = load_agent_version(&conn, agent_id, to_version)?;
+    Ok(diff_versions(&from, &to))
+}
+
+/// Roll an agent back to an earlier version
+///
+/// The rollback is itself recorded as a new version, so it can be undone the same way.
+#[tauri::command]
+pub async fn rollback_agent(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+    version: i64,
+    note: Option<String>,
+) -> Result<AgentVersion, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let new_version = rollback_to_version(&conn, agent_id, version, note.as_deref())?;
+    log::info!(
+        "Rolled agent {} back to version {} as version {}",
+        agent_id,
+        version,
+        new_version
+    );
+    load_agent_version(&conn, agent_id, new_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_record_diff_and_roll_back() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE agents (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL, icon TEXT NOT NULL,
+                system_prompt TEXT NOT NULL, default_task TEXT, model TEXT NOT NULL,
+                enable_file_read BOOLEAN NOT NULL DEFAULT 1, enable_file_write BOOLEAN NOT NULL DEFAULT 1,
+                enable_network BOOLEAN NOT NULL DEFAULT 0, hooks TEXT, resource_limits TEXT, permissions TEXT,
+                parameters TEXT, budget TEXT, verification TEXT, retry_policy TEXT
+            )",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO agents (name, icon, system_prompt, model) VALUES ('Reviewer', 'bot', 'Review the diff.\nBe brief.', 'sonnet')",
+            [],
+        )
+        .unwrap();
+
+        // Existing agents get a first version when the table is created
+        init_agent_versions(&conn).unwrap();
+        assert_eq!(latest_agent_version(&conn, 1).unwrap(), Some(1));
+
+        conn.execute(
+            "UPDATE agents SET system_prompt = 'Review the diff.\nBe thorough.', model = 'opus' WHERE id = 1",
+            [],
+        )
+        .unwrap();
+        assert_eq!(
+            record_agent_version(&conn, 1, Some("More detail")).unwrap(),
+            2
+        );
+
+        let diff = diff_versions(
+            &load_agent_version(&conn, 1, 1).unwrap(),
+            &load_agent_version(&conn, 1, 2).unwrap(),
+        );
+        let fields: Vec<&str> = diff.changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["system_prompt", "model"]);
+        assert_eq!(
+            diff.system_prompt_diff,
+            vec![
+                DiffLine {
+                    op: DiffOp::Equal,
+                    text: "Review the diff.".to_string()
+                },
+                DiffLine {
+                    op: DiffOp::Delete,
+                    text: "Be brief.".to_string()
+                },
+                DiffLine {
+                    op: DiffOp::Insert,
+                    text: "Be thorough.".to_string()
+                },
+            ]
+        );
+
+        assert_eq!(rollback_to_version(&conn, 1, 1, None).unwrap(), 3);
+        let restored = load_agent_version(&conn, 1, 3).unwrap();
+        assert_eq!(restored.model, "sonnet");
+        assert_eq!(restored.system_prompt, "Review the diff.\nBe brief.");
+        assert_eq!(restored.note.as_deref(), Some("Rolled back to version 1"));
+        let current: String = conn
+            .query_row("SELECT model FROM agents WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(current, "sonnet");
+    }
+}
@@ -4,22 +4,41 @@ use dirs;
 use log::{debug, error, info, warn};
 use regex;
 use reqwest;
-use rusqlite::{params, Connection, Result as SqliteResult};
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
-use std::io::{BufRead, BufReader};
-use std::process::Stdio;
-use std::sync::{Arc, Mutex};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
 use tauri::{AppHandle, Emitter, Manager, State};
 use tauri_plugin_shell::ShellExt;
-use tokio::io::{AsyncBufReadExt, BufReader as TokioBufReader};
-use tokio::process::Command;
-
-/// Finds the full path to the claude binary
-/// This is necessary because macOS apps have a limited PATH environment
-fn find_claude_binary(app_handle: &AppHandle) -> Result<String, String> {
-    crate::claude_binary::find_claude_binary(app_handle)
-}
+use crate::process::input::prompt_args;
+use crate::process::budget::{has_known_pricing, BudgetTracker};
+use crate::process::limits::LimitViolation;
+use crate::process::runner::{ClaudeRunner, RunKind, RunObserver, RunRequest};
+use crate::process::stream::{StreamEvent, StreamLine};
+use crate::commands::agent_parameters::{parse_parameters, resolve_parameters, substitute};
+use crate::commands::agent_versions::{
+    init_agent_versions, latest_agent_version, load_agent_version, record_agent_version,
+};
+use crate::commands::env_vars::{delete_agent_env_vars, init_env_vars, run_env};
+use crate::commands::permissions::load_project_permissions;
+use crate::commands::retries::{schedule_retry, RetryPolicy, RunFailure, RESUME_PROMPT};
+use crate::commands::schedules::init_schedules;
+use crate::commands::triggers::init_triggers;
+use crate::commands::verification::{
+    resume_verifications, run_verification_settings, verify_agent_run, AgentVerification,
+};
+use crate::commands::worktrees::create_worktree;
+use crate::commands::workflows::init_workflows;
+use crate::process::permissions::Capabilities;
+use crate::process::probe::{parse_recorded_start, probe_run, RunProbe};
+use crate::process::{KillPolicy, PermissionSettings, ResourceLimits, RunBudget, RunEnv};
+
+/// How long an agent run may stay silent after spawning before it is considered stuck
+const AGENT_STARTUP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Lines of error output kept from a run to tell what kind of failure ended it
+const ERROR_OUTPUT_LINES: usize = 50;
 
 /// Represents a CC Agent stored in the database
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -34,10 +53,47 @@ pub struct Agent {
     pub enable_file_write: bool,
     pub enable_network: bool,
     pub hooks: Option<String>, // JSON string of hooks configuration
+    pub resource_limits: Option<String>, // JSON string of ResourceLimits
+    pub permissions: Option<String>, // JSON string of PermissionSettings
+    pub parameters: Option<String>, // JSON string of AgentParameter definitions
+    pub budget: Option<String>, // JSON string of RunBudget
+    pub verification: Option<String>, // JSON string of AgentVerification
+    pub retry_policy: Option<String>, // JSON string of RetryPolicy
     pub created_at: String,
     pub updated_at: String,
 }
 
+/// Columns selected when loading an `Agent`, in the order `Agent::from_row` expects
+const AGENT_COLUMNS: &str = "id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at, resource_limits, permissions, parameters, budget, verification, retry_policy";
+
+impl Agent {
+    /// Builds an `Agent` from a row selected with `AGENT_COLUMNS`
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Agent {
+            id: Some(row.get(0)?),
+            name: row.get(1)?,
+            icon: row.get(2)?,
+            system_prompt: row.get(3)?,
+            default_task: row.get(4)?,
+            model: row
+                .get::<_, String>(5)
+                .unwrap_or_else(|_| "sonnet".to_string()),
+            enable_file_read: row.get::<_, bool>(6).unwrap_or(true),
+            enable_file_write: row.get::<_, bool>(7).unwrap_or(true),
+            enable_network: row.get::<_, bool>(8).unwrap_or(false),
+            hooks: row.get(9)?,
+            resource_limits: row.get(12)?,
+            permissions: row.get(13)?,
+            parameters: row.get(14)?,
+            budget: row.get(15)?,
+            verification: row.get(16)?,
+            retry_policy: row.get(17)?,
+            created_at: row.get(10)?,
+            updated_at: row.get(11)?,
+        })
+    }
+}
+
 /// Represents an agent execution run
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AgentRun {
@@ -49,11 +105,82 @@ pub struct AgentRun {
     pub model: String,
     pub project_path: String,
     pub session_id: String, // UUID session ID from Claude Code
-    pub status: String,     // 'pending', 'running', 'completed', 'failed', 'cancelled'
+    pub status: String, // 'queued', 'pending', 'running', 'paused', 'completed', 'failed', 'cancelled', 'timed_out', 'oom_killed', 'limit_exceeded', 'budget_exceeded', 'verifying', 'succeeded', 'verification_failed'
     pub pid: Option<u32>,
     pub process_started_at: Option<String>,
     pub created_at: String,
     pub completed_at: Option<String>,
+    pub status_reason: Option<String>, // Why the run ended, e.g. how it was terminated
+    pub priority: i64,                 // Higher runs first when the queue orders by priority
+    pub paused_at: Option<String>,     // RFC3339 time the run was paused, while paused
+    pub paused_ms: i64,                // Time spent in completed pauses
+    pub interactive: bool,             // Launched with stream-json input; stdin stays open
+    pub agent_version: Option<i64>,    // Agent version the run uses; None for runs that predate versioning
+    pub parameters: Option<String>,    // JSON object of the resolved parameter values
+    pub trigger_cause: Option<String>, // What started the run; None when started by hand
+    pub worktree_path: Option<String>, // Where an isolated run works instead of project_path
+    pub worktree_branch: Option<String>, // The worktree's branch; None for copies of non-git projects
+    pub worktree_base: Option<String>,   // Commit the worktree's branch started from
+    pub worktree_state: Option<String>, // 'active', 'merged', 'cherry_picked', 'discarded' or 'retried' once a retry took it over
+    pub budget: Option<String>, // JSON string of the RunBudget enforced on the run
+    pub verification: Option<String>, // JSON string of the VerificationReport once verified
+    pub retry_of: Option<i64>, // First attempt of the run this retries; None for first attempts
+    pub attempt: i64,          // 1 for first attempts, counting up with each retry
+    pub not_before: Option<String>, // Earliest time a queued retry may start
+    pub resume_session: Option<String>, // Claude session the run continues with --resume
+}
+
+/// Columns selected when loading an `AgentRun`, in the order `AgentRun::from_row` expects
+const AGENT_RUN_COLUMNS: &str = "id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at, status_reason, priority, paused_at, paused_ms, interactive, agent_version, parameters, trigger_cause, worktree_path, worktree_branch, worktree_base, worktree_state, budget, verification, retry_of, attempt, not_before, resume_session";
+
+impl AgentRun {
+    /// Builds an `AgentRun` from a row selected with `AGENT_RUN_COLUMNS`
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(AgentRun {
+            id: Some(row.get(0)?),
+            agent_id: row.get(1)?,
+            agent_name: row.get(2)?,
+            agent_icon: row.get(3)?,
+            task: row.get(4)?,
+            model: row.get(5)?,
+            project_path: row.get(6)?,
+            session_id: row.get(7)?,
+            status: row
+                .get::<_, String>(8)
+                .unwrap_or_else(|_| "pending".to_string()),
+            pid: row
+                .get::<_, Option<i64>>(9)
+                .ok()
+                .flatten()
+                .map(|p| p as u32),
+            process_started_at: row.get(10)?,
+            created_at: row.get(11)?,
+            completed_at: row.get(12)?,
+            status_reason: row.get(13)?,
+            priority: row.get::<_, Option<i64>>(14)?.unwrap_or(0),
+            paused_at: row.get(15)?,
+            paused_ms: row.get::<_, Option<i64>>(16)?.unwrap_or(0),
+            interactive: row.get::<_, Option<bool>>(17)?.unwrap_or(false),
+            agent_version: row.get(18)?,
+            parameters: row.get(19)?,
+            trigger_cause: row.get(20)?,
+            worktree_path: row.get(21)?,
+            worktree_branch: row.get(22)?,
+            worktree_base: row.get(23)?,
+            worktree_state: row.get(24)?,
+            budget: row.get(25)?,
+            verification: row.get(26)?,
+            retry_of: row.get(27)?,
+            attempt: row.get::<_, Option<i64>>(28)?.unwrap_or(1),
+            not_before: row.get(29)?,
+            resume_session: row.get(30)?,
+        })
+    }
+
+    /// The directory the run's agent works in
+    pub fn working_dir(&self) -> &str {
+        self.worktree_path.as_deref().unwrap_or(&self.project_path)
+    }
 }
 
 /// Represents runtime metrics calculated from JSONL
@@ -91,6 +218,8 @@ pub struct AgentData {
     pub default_task: Option<String>,
     pub model: String,
     pub hooks: Option<String>,
+    #[serde(default)]
+    pub parameters: Option<String>,
 }
 
 /// Database connection state
@@ -107,11 +236,11 @@ impl AgentRunMetrics {
         let mut end_time: Option<chrono::DateTime<chrono::Utc>> = None;
 
         for line in jsonl_content.lines() {
-            if let Ok(json) = serde_json::from_str::<JsonValue>(line) {
+            if let Some(line) = StreamLine::parse(line) {
                 message_count += 1;
 
                 // Track timestamps
-                if let Some(timestamp_str) = json.get("timestamp").and_then(|t| t.as_str()) {
+                if let Some(timestamp_str) = line.timestamp.as_deref() {
                     if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(timestamp_str) {
                         let utc_time = timestamp.with_timezone(&chrono::Utc);
                         if start_time.is_none() || utc_time < start_time.unwrap() {
@@ -123,23 +252,11 @@ impl AgentRunMetrics {
                     }
                 }
 
-                // Extract token usage - check both top-level and nested message.usage
-                let usage = json
-                    .get("usage")
-                    .or_else(|| json.get("message").and_then(|m| m.get("usage")));
-
-                if let Some(usage) = usage {
-                    if let Some(input_tokens) = usage.get("input_tokens").and_then(|t| t.as_i64()) {
-                        total_tokens += input_tokens;
-                    }
-                    if let Some(output_tokens) = usage.get("output_tokens").and_then(|t| t.as_i64())
-                    {
-                        total_tokens += output_tokens;
-                    }
+                if let Some(usage) = line.event.usage() {
+                    total_tokens += usage.total_tokens() as i64;
                 }
 
-                // Extract cost information
-                if let Some(cost) = json.get("cost").and_then(|c| c.as_f64()) {
+                if let Some(cost) = line.event.cost_usd() {
                     cost_usd += cost;
                 }
             }
@@ -167,8 +284,8 @@ impl AgentRunMetrics {
     }
 }
 
-/// Read JSONL content from a session file
-pub async fn read_session_jsonl(session_id: &str, project_path: &str) -> Result<String, String> {
+/// Path of the JSONL file Claude Code writes for a session in a project
+fn session_jsonl_path(session_id: &str, project_path: &str) -> Result<std::path::PathBuf, String> {
     let claude_dir = dirs::home_dir()
         .ok_or("Failed to get home directory")?
         .join(".claude")
@@ -176,8 +293,14 @@ pub async fn read_session_jsonl(session_id: &str, project_path: &str) -> Result<
 
     // Encode project path to match Claude Code's directory naming
     let encoded_project = project_path.replace('/', "-");
-    let project_dir = claude_dir.join(&encoded_project);
-    let session_file = project_dir.join(format!("{}.jsonl", session_id));
+    Ok(claude_dir
+        .join(&encoded_project)
+        .join(format!("{}.jsonl", session_id)))
+}
+
+/// Read JSONL content from a session file
+pub async fn read_session_jsonl(session_id: &str, project_path: &str) -> Result<String, String> {
+    let session_file = session_jsonl_path(session_id, project_path)?;
 
     if !session_file.exists() {
         return Err(format!(
@@ -192,15 +315,72 @@ pub async fn read_session_jsonl(session_id: &str, project_path: &str) -> Result<
     }
 }
 
+/// Finds a session's JSONL file in any of Claude's project directories
+fn find_session_file(
+    projects_dir: &std::path::Path,
+    session_id: &str,
+) -> Option<std::path::PathBuf> {
+    log::info!(
+        "Searching for session file {} in all project directories",
+        session_id
+    );
+    let Ok(entries) = std::fs::read_dir(projects_dir) else {
+        log::error!("Failed to read projects directory");
+        return None;
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if path.is_dir() {
+            let potential_session_file = path.join(format!("{}.jsonl", session_id));
+            if potential_session_file.exists() {
+                log::info!("Found session file at: {:?}", potential_session_file);
+                return Some(potential_session_file);
+            }
+            log::debug!(
+                "Session file not found in: {}",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            );
+        }
+    }
+    None
+}
+
+/// Reads a session's JSONL from Claude's project directories with secret values masked
+async fn read_redacted_session(
+    projects_dir: &std::path::Path,
+    session_id: &str,
+    env: &RunEnv,
+) -> Result<String, String> {
+    let session_path = find_session_file(projects_dir, session_id)
+        .ok_or_else(|| format!("Session file not found for {}", session_id))?;
+    let content = tokio::fs::read_to_string(&session_path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", session_path.display(), e))?;
+    Ok(env.redact(&content).into_owned())
+}
+
 /// Get agent run with real-time metrics
-pub async fn get_agent_run_with_metrics(run: AgentRun) -> AgentRunWithMetrics {
-    match read_session_jsonl(&run.session_id, &run.project_path).await {
+///
+/// The output is only included once the run's secret values could be masked in it.
+pub async fn get_agent_run_with_metrics(app: &AppHandle, run: AgentRun) -> AgentRunWithMetrics {
+    match read_session_jsonl(&run.session_id, run.working_dir()).await {
         Ok(jsonl_content) => {
-            let metrics = AgentRunMetrics::from_jsonl(&jsonl_content);
+            let mut metrics = AgentRunMetrics::from_jsonl(&jsonl_content);
+            // Paused time falls between message timestamps; it isn't time spent working
+            if let Some(duration_ms) = metrics.duration_ms.as_mut() {
+                *duration_ms = (*duration_ms - run.paused_ms).max(0);
+            }
+            let output = match run_env(app, Some(run.agent_id), &run.project_path) {
+                Ok(env) => Some(env.redact(&jsonl_content).into_owned()),
+                Err(e) => {
+                    log::warn!("Failed to read environment of run {:?}: {}", run.id, e);
+                    None
+                }
+            };
             AgentRunWithMetrics {
                 run,
                 metrics: Some(metrics),
-                output: Some(jsonl_content),
+                output,
             }
         }
         Err(e) => {
@@ -251,6 +431,12 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         [],
     );
     let _ = conn.execute("ALTER TABLE agents ADD COLUMN hooks TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN resource_limits TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN permissions TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN parameters TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN budget TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN verification TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN retry_policy TEXT", []);
     let _ = conn.execute(
         "ALTER TABLE agents ADD COLUMN enable_file_read BOOLEAN DEFAULT 1",
         [],
@@ -264,6 +450,9 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         [],
     );
 
+    // Create agent_versions table, recording a first version of existing agents
+    init_agent_versions(&conn)?;
+
     // Create agent_runs table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS agent_runs (
@@ -296,6 +485,40 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         "ALTER TABLE agent_runs ADD COLUMN process_started_at TEXT",
         [],
     );
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN status_reason TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE agent_runs ADD COLUMN priority INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN queue_order INTEGER", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN paused_at TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE agent_runs ADD COLUMN paused_ms INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE agent_runs ADD COLUMN interactive INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE agent_runs ADD COLUMN agent_version INTEGER",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN parameters TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN trigger_cause TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN worktree_path TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN worktree_branch TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN worktree_base TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN worktree_state TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN budget TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN verification TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN retry_of INTEGER", []);
+    let _ = conn.execute(
+        "ALTER TABLE agent_runs ADD COLUMN attempt INTEGER NOT NULL DEFAULT 1",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN not_before TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN resume_session TEXT", []);
 
     // Drop old columns that are no longer needed (data is now read from JSONL files)
     // Note: SQLite doesn't support DROP COLUMN, so we'll ignore errors for existing columns
@@ -310,6 +533,18 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         [],
     );
 
+    // Create workflow tables
+    init_workflows(&conn)?;
+
+    // Create agent_schedules table
+    init_schedules(&conn)?;
+
+    // Create agent_triggers table
+    init_triggers(&conn)?;
+
+    // Create env_vars table
+    init_env_vars(&conn)?;
+
     // Create trigger to update the updated_at timestamp
     conn.execute(
         "CREATE TRIGGER IF NOT EXISTS update_agent_timestamp 
@@ -353,28 +588,14 @@ pub async fn list_agents(db: State<'_, AgentDb>) -> Result<Vec<Agent>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
 
     let mut stmt = conn
-        .prepare("SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents ORDER BY created_at DESC")
+        .prepare(&format!(
+            "SELECT {} FROM agents ORDER BY created_at DESC",
+            AGENT_COLUMNS
+        ))
         .map_err(|e| e.to_string())?;
 
     let agents = stmt
-        .query_map([], |row| {
-            Ok(Agent {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                icon: row.get(2)?,
-                system_prompt: row.get(3)?,
-                default_task: row.get(4)?,
-                model: row
-                    .get::<_, String>(5)
-                    .unwrap_or_else(|_| "sonnet".to_string()),
-                enable_file_read: row.get::<_, bool>(6).unwrap_or(true),
-                enable_file_write: row.get::<_, bool>(7).unwrap_or(true),
-                enable_network: row.get::<_, bool>(8).unwrap_or(false),
-                hooks: row.get(9)?,
-                created_at: row.get(10)?,
-                updated_at: row.get(11)?,
-            })
-        })
+        .query_map([], Agent::from_row)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
@@ -395,42 +616,40 @@ pub async fn create_agent(
     enable_file_write: Option<bool>,
     enable_network: Option<bool>,
     hooks: Option<String>,
+    resource_limits: Option<String>,
+    permissions: Option<String>,
+    parameters: Option<String>,
+    budget: Option<String>,
+    verification: Option<String>,
+    retry_policy: Option<String>,
 ) -> Result<Agent, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
     let model = model.unwrap_or_else(|| "sonnet".to_string());
     let enable_file_read = enable_file_read.unwrap_or(true);
     let enable_file_write = enable_file_write.unwrap_or(true);
     let enable_network = enable_network.unwrap_or(false);
+    ResourceLimits::from_json(resource_limits.as_deref())?;
+    PermissionSettings::from_json(permissions.as_deref())?;
+    parse_parameters(parameters.as_deref())?;
+    RunBudget::from_json(budget.as_deref())?;
+    AgentVerification::from_json(verification.as_deref())?;
+    RetryPolicy::from_json(retry_policy.as_deref())?;
 
     conn.execute(
-        "INSERT INTO agents (name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-        params![name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks],
+        "INSERT INTO agents (name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, resource_limits, permissions, parameters, budget, verification, retry_policy) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+        params![name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, resource_limits, permissions, parameters, budget, verification, retry_policy],
     )
     .map_err(|e| e.to_string())?;
 
     let id = conn.last_insert_rowid();
+    record_agent_version(&conn, id, None)?;
 
     // Fetch the created agent
     let agent = conn
         .query_row(
-            "SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents WHERE id = ?1",
+            &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
             params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    system_prompt: row.get(3)?,
-                    default_task: row.get(4)?,
-                    model: row.get(5)?,
-                    enable_file_read: row.get(6)?,
-                    enable_file_write: row.get(7)?,
-                    enable_network: row.get(8)?,
-                    hooks: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
+            Agent::from_row,
         )
         .map_err(|e| e.to_string())?;
 
@@ -451,6 +670,13 @@ pub async fn update_agent(
     enable_file_write: Option<bool>,
     enable_network: Option<bool>,
     hooks: Option<String>,
+    resource_limits: Option<String>,
+    permissions: Option<String>,
+    parameters: Option<String>,
+    budget: Option<String>,
+    verification: Option<String>,
+    retry_policy: Option<String>,
+    note: Option<String>,
 ) -> Result<Agent, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
     let model = model.unwrap_or_else(|| "sonnet".to_string());
@@ -484,6 +710,48 @@ pub async fn update_agent(
         query.push_str(&format!(", enable_network = ?{}", param_count));
         params_vec.push(Box::new(en));
     }
+    // An empty string clears the limits
+    if let Some(rl) = resource_limits {
+        ResourceLimits::from_json(Some(&rl))?;
+        param_count += 1;
+        query.push_str(&format!(", resource_limits = ?{}", param_count));
+        params_vec.push(Box::new(rl));
+    }
+    // An empty string resets the agent to the project's settings
+    if let Some(perms) = permissions {
+        PermissionSettings::from_json(Some(&perms))?;
+        param_count += 1;
+        query.push_str(&format!(", permissions = ?{}", param_count));
+        params_vec.push(Box::new(perms));
+    }
+    // An empty string removes all parameters
+    if let Some(ps) = parameters {
+        parse_parameters(Some(&ps))?;
+        param_count += 1;
+        query.push_str(&format!(", parameters = ?{}", param_count));
+        params_vec.push(Box::new(ps));
+    }
+    // An empty string removes the budget
+    if let Some(b) = budget {
+        RunBudget::from_json(Some(&b))?;
+        param_count += 1;
+        query.push_str(&format!(", budget = ?{}", param_count));
+        params_vec.push(Box::new(b));
+    }
+    // An empty string turns verification off
+    if let Some(v) = verification {
+        AgentVerification::from_json(Some(&v))?;
+        param_count += 1;
+        query.push_str(&format!(", verification = ?{}", param_count));
+        params_vec.push(Box::new(v));
+    }
+    // An empty string turns retries off
+    if let Some(rp) = retry_policy {
+        RetryPolicy::from_json(Some(&rp))?;
+        param_count += 1;
+        query.push_str(&format!(", retry_policy = ?{}", param_count));
+        params_vec.push(Box::new(rp));
+    }
 
     param_count += 1;
     query.push_str(&format!(" WHERE id = ?{}", param_count));
@@ -494,28 +762,14 @@ pub async fn update_agent(
         rusqlite::params_from_iter(params_vec.iter().map(|p| p.as_ref())),
     )
     .map_err(|e| e.to_string())?;
+    record_agent_version(&conn, id, note.as_deref())?;
 
     // Fetch the updated agent
     let agent = conn
         .query_row(
-            "SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents WHERE id = ?1",
+            &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
             params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    system_prompt: row.get(3)?,
-                    default_task: row.get(4)?,
-                    model: row.get(5)?,
-                    enable_file_read: row.get(6)?,
-                    enable_file_write: row.get(7)?,
-                    enable_network: row.get(8)?,
-                    hooks: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
+            Agent::from_row,
         )
         .map_err(|e| e.to_string())?;
 
@@ -529,6 +783,7 @@ pub async fn delete_agent(db: State<'_, AgentDb>, id: i64) -> Result<(), String>
 
     conn.execute("DELETE FROM agents WHERE id = ?1", params![id])
         .map_err(|e| e.to_string())?;
+    delete_agent_env_vars(&conn, id)?;
 
     Ok(())
 }
@@ -540,24 +795,9 @@ pub async fn get_agent(db: State<'_, AgentDb>, id: i64) -> Result<Agent, String>
 
     let agent = conn
         .query_row(
-            "SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents WHERE id = ?1",
+            &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
             params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    system_prompt: row.get(3)?,
-                    default_task: row.get(4)?,
-                    model: row.get::<_, String>(5).unwrap_or_else(|_| "sonnet".to_string()),
-                    enable_file_read: row.get::<_, bool>(6).unwrap_or(true),
-                    enable_file_write: row.get::<_, bool>(7).unwrap_or(true),
-                    enable_network: row.get::<_, bool>(8).unwrap_or(false),
-                    hooks: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
+            Agent::from_row,
         )
         .map_err(|e| e.to_string())?;
 
@@ -573,43 +813,23 @@ pub async fn list_agent_runs(
     let conn = db.0.lock().map_err(|e| e.to_string())?;
 
     let query = if agent_id.is_some() {
-        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-         FROM agent_runs WHERE agent_id = ?1 ORDER BY created_at DESC"
+        format!(
+            "SELECT {} FROM agent_runs WHERE agent_id = ?1 ORDER BY created_at DESC",
+            AGENT_RUN_COLUMNS
+        )
     } else {
-        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-         FROM agent_runs ORDER BY created_at DESC"
+        format!(
+            "SELECT {} FROM agent_runs ORDER BY created_at DESC",
+            AGENT_RUN_COLUMNS
+        )
     };
 
-    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
-
-    let run_mapper = |row: &rusqlite::Row| -> rusqlite::Result<AgentRun> {
-        Ok(AgentRun {
-            id: Some(row.get(0)?),
-            agent_id: row.get(1)?,
-            agent_name: row.get(2)?,
-            agent_icon: row.get(3)?,
-            task: row.get(4)?,
-            model: row.get(5)?,
-            project_path: row.get(6)?,
-            session_id: row.get(7)?,
-            status: row
-                .get::<_, String>(8)
-                .unwrap_or_else(|_| "pending".to_string()),
-            pid: row
-                .get::<_, Option<i64>>(9)
-                .ok()
-                .flatten()
-                .map(|p| p as u32),
-            process_started_at: row.get(10)?,
-            created_at: row.get(11)?,
-            completed_at: row.get(12)?,
-        })
-    };
+    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
 
     let runs = if let Some(aid) = agent_id {
-        stmt.query_map(params![aid], run_mapper)
+        stmt.query_map(params![aid], AgentRun::from_row)
     } else {
-        stmt.query_map(params![], run_mapper)
+        stmt.query_map(params![], AgentRun::from_row)
     }
     .map_err(|e| e.to_string())?
     .collect::<Result<Vec<_>, _>>()
@@ -625,26 +845,9 @@ pub async fn get_agent_run(db: State<'_, AgentDb>, id: i64) -> Result<AgentRun,
 
     let run = conn
         .query_row(
-            "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-             FROM agent_runs WHERE id = ?1",
+            &format!("SELECT {} FROM agent_runs WHERE id = ?1", AGENT_RUN_COLUMNS),
             params![id],
-            |row| {
-                Ok(AgentRun {
-                    id: Some(row.get(0)?),
-                    agent_id: row.get(1)?,
-                    agent_name: row.get(2)?,
-                    agent_icon: row.get(3)?,
-                    task: row.get(4)?,
-                    model: row.get(5)?,
-                    project_path: row.get(6)?,
-                    session_id: row.get(7)?,
-                    status: row.get::<_, String>(8).unwrap_or_else(|_| "pending".to_string()),
-                    pid: row.get::<_, Option<i64>>(9).ok().flatten().map(|p| p as u32),
-                    process_started_at: row.get(10)?,
-                    created_at: row.get(11)?,
-                    completed_at: row.get(12)?,
-                })
-            },
+            AgentRun::from_row,
         )
         .map_err(|e| e.to_string())?;
 
@@ -654,16 +857,18 @@ pub async fn get_agent_run(db: State<'_, AgentDb>, id: i64) -> Result<AgentRun,
 /// Get agent run with real-time metrics from JSONL
 #[tauri::command]
 pub async fn get_agent_run_with_real_time_metrics(
+    app: AppHandle,
     db: State<'_, AgentDb>,
     id: i64,
 ) -> Result<AgentRunWithMetrics, String> {
     let run = get_agent_run(db, id).await?;
-    Ok(get_agent_run_with_metrics(run).await)
+    Ok(get_agent_run_with_metrics(&app, run).await)
 }
 
 /// List agent runs with real-time metrics from JSONL
 #[tauri::command]
 pub async fn list_agent_runs_with_metrics(
+    app: AppHandle,
     db: State<'_, AgentDb>,
     agent_id: Option<i64>,
 ) -> Result<Vec<AgentRunWithMetrics>, String> {
@@ -671,7 +876,7 @@ pub async fn list_agent_runs_with_metrics(
     let mut runs_with_metrics = Vec::new();
 
     for run in runs {
-        let run_with_metrics = get_agent_run_with_metrics(run).await;
+        let run_with_metrics = get_agent_run_with_metrics(&app, run).await;
         runs_with_metrics.push(run_with_metrics);
     }
 
@@ -686,18 +891,125 @@ pub async fn execute_agent(
     project_path: String,
     task: String,
     model: Option<String>,
-    db: State<'_, AgentDb>,
-    registry: State<'_, crate::process::ProcessRegistryState>,
+    priority: Option<i64>,
+    interactive: Option<bool>,
+    parameters: Option<HashMap<String, JsonValue>>,
+    isolated: Option<bool>,
+    budget: Option<RunBudget>,
+) -> Result<i64, String> {
+    queue_agent_run(
+        &app,
+        QueueRunRequest {
+            agent_id,
+            project_path,
+            task,
+            model,
+            priority,
+            interactive: interactive.unwrap_or(false),
+            parameters: parameters.unwrap_or_default(),
+            trigger_cause: None,
+            isolated: isolated.unwrap_or(false),
+            budget,
+        },
+    )
+    .await
+}
+
+/// A run to add to the queue
+pub struct QueueRunRequest {
+    pub agent_id: i64,
+    pub project_path: String,
+    pub task: String,
+    pub model: Option<String>,
+    pub priority: Option<i64>,
+    pub interactive: bool,
+    pub parameters: HashMap<String, JsonValue>,
+    /// What started the run when it wasn't started by hand, e.g. a schedule or a trigger
+    pub trigger_cause: Option<String>,
+    /// Run in a temporary worktree on a new branch instead of in the project itself
+    pub isolated: bool,
+    /// Limits for this run; those it leaves unset come from the agent's budget
+    pub budget: Option<RunBudget>,
+}
+
+/// Records a run for an agent and queues it; used by `execute_agent` and by everything
+/// that starts runs on its own
+pub(crate) async fn queue_agent_run(
+    app: &AppHandle,
+    request: QueueRunRequest,
 ) -> Result<i64, String> {
+    let QueueRunRequest {
+        agent_id,
+        project_path,
+        task,
+        model,
+        priority,
+        interactive,
+        parameters,
+        trigger_cause,
+        isolated,
+        budget,
+    } = request;
+    let db = app.state::<AgentDb>();
     info!("Executing agent {} with task: {}", agent_id, task);
 
     // Get the agent from database
     let agent = get_agent(db.clone(), agent_id).await?;
     let execution_model = model.unwrap_or(agent.model.clone());
+
+    // Resolve the agent's declared inputs; the run stores the task with them filled in
+    let parameters = resolve_parameters(
+        &parse_parameters(agent.parameters.as_deref())?,
+        &parameters,
+        &project_path,
+    )?;
+    let task = substitute(&task, &parameters);
+    let parameters_json = serde_json::to_string(&parameters).map_err(|e| e.to_string())?;
+    let budget = budget
+        .unwrap_or_default()
+        .or(&RunBudget::from_json(agent.budget.as_deref())?.unwrap_or_default());
+    if budget.max_cost_usd.is_some() && !has_known_pricing(&execution_model) {
+        return Err(format!(
+            "Can't enforce a cost budget for model {}: its pricing is unknown. Use a token budget instead.",
+            execution_model
+        ));
+    }
+    let budget_json = if budget.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(&budget).map_err(|e| e.to_string())?)
+    };
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        agent_permissions(&conn, &agent, &project_path)?.cli_args(interactive)?;
+    }
+
+    // Isolated runs get their own worktree (or copy) of the project to work in
+    let worktree = if isolated {
+        let worktrees_dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("Failed to get app data dir: {}", e))?
+            .join("worktrees");
+        let project = std::path::PathBuf::from(&project_path);
+        let agent_name = agent.name.clone();
+        let worktree = tauri::async_runtime::spawn_blocking(move || {
+            create_worktree(&project, &worktrees_dir, &agent_name)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        Some(worktree)
+    } else {
+        None
+    };
+    let working_dir = worktree
+        .as_ref()
+        .map(|w| w.path.to_string_lossy().to_string());
     
     // Create .claude/settings.json with agent hooks if it doesn't exist
     if let Some(hooks_json) = &agent.hooks {
-        let claude_dir = std::path::Path::new(&project_path).join(".claude");
+        let claude_dir =
+            std::path::Path::new(working_dir.as_deref().unwrap_or(&project_path)).join(".claude");
         let settings_path = claude_dir.join("settings.json");
         
         // Create .claude directory if it doesn't exist
@@ -731,643 +1043,410 @@ pub async fn execute_agent(
         }
     }
 
-    // Create a new run record
+    // Create a new run record; the queue decides when it actually starts
     let run_id = {
         let conn = db.0.lock().map_err(|e| e.to_string())?;
         conn.execute(
-            "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![agent_id, agent.name, agent.icon, task, execution_model, project_path, ""],
+            "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, priority, interactive, agent_version, parameters, trigger_cause, worktree_path, worktree_branch, worktree_base, worktree_state, budget, queue_order)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 'queued', ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, (SELECT COALESCE(MAX(queue_order), 0) + 1 FROM agent_runs))",
+            params![
+                agent_id,
+                agent.name,
+                agent.icon,
+                task,
+                execution_model,
+                project_path,
+                "",
+                priority.unwrap_or(0),
+                interactive,
+                latest_agent_version(&conn, agent_id)?,
+                parameters_json,
+                trigger_cause,
+                working_dir,
+                worktree.as_ref().and_then(|w| w.branch.clone()),
+                worktree.as_ref().and_then(|w| w.base.clone()),
+                worktree.as_ref().map(|_| "active"),
+                budget_json,
+            ],
         )
         .map_err(|e| e.to_string())?;
         conn.last_insert_rowid()
     };
+    info!("Queued agent '{}' as run {}", agent.name, run_id);
 
-    // Find Claude binary
-    info!("Running agent '{}'", agent.name);
-    let claude_path = match find_claude_binary(&app) {
-        Ok(path) => path,
-        Err(e) => {
-            error!("Failed to find claude binary: {}", e);
-            return Err(e);
+    let _ = app.emit(&format!("agent-queued:{}", run_id), true);
+    crate::commands::queue::dispatch_queued_runs(app).await;
+
+    Ok(run_id)
+}
+
+/// An agent's permission settings, limited by those of the project it runs in and by
+/// its `enable_*` flags
+fn agent_permissions(
+    conn: &Connection,
+    agent: &Agent,
+    project_path: &str,
+) -> Result<PermissionSettings, String> {
+    let project = load_project_permissions(conn, project_path)?;
+    let settings = match PermissionSettings::from_json(agent.permissions.as_deref())? {
+        Some(settings) => settings.within(&project),
+        None => project,
+    };
+    Ok(settings.restricted_to(Capabilities {
+        file_read: agent.enable_file_read,
+        file_write: agent.enable_file_write,
+        network: agent.enable_network,
+    }))
+}
+
+/// Starts a queued run by spawning the Claude process for it
+pub(crate) async fn start_agent_run(app: &AppHandle, run_id: i64) -> Result<i64, String> {
+    let db = app.state::<AgentDb>();
+    let run = get_agent_run(db.clone(), run_id).await?;
+    // Run with the configuration the run was queued with, even if the agent changed since
+    let agent = match run.agent_version {
+        Some(version) => {
+            let conn = db.0.lock().map_err(|e| e.to_string())?;
+            load_agent_version(&conn, run.agent_id, version)?.to_agent()
         }
+        None => get_agent(db.clone(), run.agent_id).await?,
     };
+    let resource_limits = ResourceLimits::from_json(agent.resource_limits.as_deref())?
+        .filter(|limits| !limits.is_empty());
+    let budget = RunBudget::from_json(run.budget.as_deref())?.filter(|budget| !budget.is_empty());
+    let verifies = AgentVerification::from_json(agent.verification.as_deref())?
+        .is_some_and(|verification| !verification.is_empty());
+    let permissions = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        agent_permissions(&conn, &agent, &run.project_path)?
+    };
+    let env = run_env(app, Some(run.agent_id), &run.project_path)?;
+
+    info!("Running agent '{}'", agent.name);
+    let runner = ClaudeRunner::for_app(app).map_err(|e| {
+        error!("Failed to find claude binary: {}", e);
+        e
+    })?;
 
     // Build arguments
-    let args = vec![
-        "-p".to_string(),
-        task.clone(),
+    let parameters: BTreeMap<String, JsonValue> = run
+        .parameters
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default();
+
+    // Retries that resume the failed attempt's session ask Claude to carry on instead
+    let (mut args, prompt) = match &run.resume_session {
+        Some(session_id) => (
+            vec!["--resume".to_string(), session_id.clone()],
+            RESUME_PROMPT.to_string(),
+        ),
+        None => (Vec::new(), run.task.clone()),
+    };
+    args.extend(prompt_args(&prompt, run.interactive));
+    args.extend(permissions.cli_args(run.interactive)?);
+    args.extend([
         "--system-prompt".to_string(),
-        agent.system_prompt.clone(),
+        substitute(&agent.system_prompt, &parameters),
         "--model".to_string(),
-        execution_model.clone(),
+        run.model.clone(),
         "--output-format".to_string(),
         "stream-json".to_string(),
         "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
-    ];
+    ]);
 
-    // Execute based on whether we should use sidecar or system binary
-    if should_use_sidecar(&claude_path) {
-        spawn_agent_sidecar(app, run_id, agent_id, agent.name.clone(), args, project_path, task, execution_model, db, registry).await
-    } else {
-        spawn_agent_system(app, run_id, agent_id, agent.name.clone(), claude_path, args, project_path, task, execution_model, db, registry).await
-    }
-}
-
-/// Determines whether to use sidecar or system binary execution for agents
-fn should_use_sidecar(claude_path: &str) -> bool {
-    claude_path == "claude-code"
-}
-
-/// Creates a sidecar command for agent execution
-fn create_agent_sidecar_command(
-    app: &AppHandle,
-    args: Vec<String>,
-    project_path: &str,
-) -> Result<tauri_plugin_shell::process::Command, String> {
-    let mut sidecar_cmd = app
-        .shell()
-        .sidecar("claude-code")
-        .map_err(|e| format!("Failed to create sidecar command: {}", e))?;
-    
-    // Add all arguments
-    sidecar_cmd = sidecar_cmd.args(args);
-    
-    // Set working directory
-    sidecar_cmd = sidecar_cmd.current_dir(project_path);
-    
-    Ok(sidecar_cmd)
-}
+    let observer = AgentRunObserver::new(app.clone(), run_id, budget, verifies)?;
+    let request = RunRequest {
+        kind: RunKind::Agent {
+            run_id,
+            agent_id: run.agent_id,
+            agent_name: agent.name.clone(),
+        },
+        args,
+        project_path: run.working_dir().to_string(),
+        task: prompt,
+        model: run.model,
+        resource_limits,
+        interactive: run.interactive,
+        startup_timeout: AGENT_STARTUP_TIMEOUT,
+        env,
+    };
+    runner.spawn(request, observer).await?;
 
-/// Creates a system binary command for agent execution
-fn create_agent_system_command(
-    claude_path: &str,
-    args: Vec<String>,
-    project_path: &str,
-) -> Command {
-    let mut cmd = create_command_with_env(claude_path);
-    
-    // Add all arguments
-    for arg in args {
-        cmd.arg(arg);
-    }
-    
-    cmd.current_dir(project_path)
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
-    
-    cmd
+    Ok(run_id)
 }
 
-/// Spawn agent using sidecar command
-async fn spawn_agent_sidecar(
+/// Records an agent run's progress in the database and forwards its output to the frontend
+struct AgentRunObserver {
     app: AppHandle,
+    db_path: std::path::PathBuf,
     run_id: i64,
-    _agent_id: i64,
-    _agent_name: String,
-    args: Vec<String>,
-    project_path: String,
-    _task: String,
-    _execution_model: String,
-    db: State<'_, AgentDb>,
-    registry: State<'_, crate::process::ProcessRegistryState>,
-) -> Result<i64, String> {
-    use std::sync::Mutex;
-
-    // Create the sidecar command
-    let sidecar_cmd = create_agent_sidecar_command(&app, args, &project_path)?;
-    
-    // Spawn the sidecar process
-    let (mut rx, child) = sidecar_cmd
-        .spawn()
-        .map_err(|e| format!("Failed to spawn Claude sidecar: {}", e))?;
-
-    // Get the child PID for logging
-    let pid = child.pid();
-    info!("✅ Spawned Claude sidecar process with PID: {:?}", pid);
+    session_id: String,
+    line_count: usize,
+    timed_out: bool,
+    budget: Option<RunBudget>,
+    spent: BudgetTracker,
+    over_budget: bool,
+    /// Whether the agent has checks to run once the process exits
+    verifies: bool,
+    /// The last lines of stderr and error results, used to classify a failure
+    error_output: VecDeque<String>,
+}
 
-    // Update the database with PID and status
-    let now = chrono::Utc::now().to_rfc3339();
-    {
-        let conn = db.0.lock().map_err(|e| e.to_string())?;
-        conn.execute(
-            "UPDATE agent_runs SET status = 'running', pid = ?1, process_started_at = ?2 WHERE id = ?3",
-            params![pid as i64, now, run_id],
-        ).map_err(|e| e.to_string())?;
-        info!("📝 Updated database with running status and PID");
+impl AgentRunObserver {
+    fn new(
+        app: AppHandle,
+        run_id: i64,
+        budget: Option<RunBudget>,
+        verifies: bool,
+    ) -> Result<Self, String> {
+        let db_path = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("Failed to get app data dir: {}", e))?
+            .join("agents.db");
+        Ok(Self {
+            app,
+            db_path,
+            run_id,
+            session_id: String::new(),
+            line_count: 0,
+            timed_out: false,
+            budget,
+            spent: BudgetTracker::default(),
+            over_budget: false,
+            verifies,
+            error_output: VecDeque::new(),
+        })
     }
 
-    // We'll extract the session ID from Claude's init message
-    let session_id_holder: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
-
-    // Create variables we need for the spawned task
-    let app_dir = app
-        .path()
-        .app_data_dir()
-        .expect("Failed to get app data dir");
-    let db_path = app_dir.join("agents.db");
-    let db_path_for_stream = db_path.clone(); // Clone for the streaming task
-
-    // Spawn task to read events from sidecar
-    let app_handle = app.clone();
-    let session_id_holder_clone = session_id_holder.clone();
-    let live_output = std::sync::Arc::new(Mutex::new(String::new()));
-    let live_output_clone = live_output.clone();
-    let registry_clone = registry.0.clone();
-    let first_output = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
-    let first_output_clone = first_output.clone();
-
-    let sidecar_task = tokio::spawn(async move {
-        info!("📖 Starting to read Claude sidecar events...");
-        let mut line_count = 0;
-
-        while let Some(event) = rx.recv().await {
-            match event {
-                tauri_plugin_shell::process::CommandEvent::Stdout(data) => {
-                    let line = String::from_utf8_lossy(&data).trim().to_string();
-                    if !line.is_empty() {
-                        line_count += 1;
-
-                        // Log first output
-                        if !first_output_clone.load(std::sync::atomic::Ordering::Relaxed) {
-                            info!("🎉 First output received from Claude sidecar! Line: {}", line);
-                            first_output_clone.store(true, std::sync::atomic::Ordering::Relaxed);
-                        }
-
-                        if line_count <= 5 {
-                            info!("sidecar stdout[{}]: {}", line_count, line);
-                        } else {
-                            debug!("sidecar stdout[{}]: {}", line_count, line);
-                        }
-
-                        // Store live output
-                        if let Ok(mut output) = live_output_clone.lock() {
-                            output.push_str(&line);
-                            output.push('\n');
-                        }
-
-                        // Also store in process registry for cross-session access
-                        let _ = registry_clone.append_live_output(run_id, &line);
-
-                        // Extract session ID from JSONL output
-                        if let Ok(json) = serde_json::from_str::<JsonValue>(&line) {
-                            // Claude Code uses "session_id" (underscore), not "sessionId"
-                            if json.get("type").and_then(|t| t.as_str()) == Some("system") &&
-                               json.get("subtype").and_then(|s| s.as_str()) == Some("init") {
-                                if let Some(sid) = json.get("session_id").and_then(|s| s.as_str()) {
-                                    if let Ok(mut current_session_id) = session_id_holder_clone.lock() {
-                                        if current_session_id.is_none() {
-                                            *current_session_id = Some(sid.to_string());
-                                            info!("🔑 Extracted session ID: {}", sid);
-                                            
-                                            // Update database immediately with session ID
-                                            if let Ok(conn) = Connection::open(&db_path_for_stream) {
-                                                match conn.execute(
-                                                    "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
-                                                    params![sid, run_id],
-                                                ) {
-                                                    Ok(rows) => {
-                                                        if rows > 0 {
-                                                            info!("✅ Updated agent run {} with session ID immediately", run_id);
-                                                        }
-                                                    }
-                                                    Err(e) => {
-                                                        error!("❌ Failed to update session ID immediately: {}", e);
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-
-                        // Emit the line to the frontend with run_id for isolation
-                        let _ = app_handle.emit(&format!("agent-output:{}", run_id), &line);
-                        // Also emit to the generic event for backward compatibility
-                        let _ = app_handle.emit("agent-output", &line);
-                    }
-                }
-                tauri_plugin_shell::process::CommandEvent::Stderr(data) => {
-                    let line = String::from_utf8_lossy(&data).trim().to_string();
-                    if !line.is_empty() {
-                        error!("sidecar stderr: {}", line);
-                        // Emit error lines to the frontend with run_id for isolation
-                        let _ = app_handle.emit(&format!("agent-error:{}", run_id), &line);
-                        // Also emit to the generic event for backward compatibility
-                        let _ = app_handle.emit("agent-error", &line);
-                    }
-                }
-                tauri_plugin_shell::process::CommandEvent::Terminated { .. } => {
-                    info!("📖 Claude sidecar process terminated");
-                    break;
-                }
-                tauri_plugin_shell::process::CommandEvent::Error(e) => {
-                    error!("🔥 Claude sidecar error: {}", e);
-                    break;
-                }
-                _ => {
-                    // Handle any other event types we might not know about
-                    debug!("Received unknown sidecar event type");
-                }
-            }
+    /// Adds up the usage reported by a line, killing the run once it is over budget
+    fn track_budget(&mut self, line: &StreamLine) {
+        let Some(budget) = &self.budget else {
+            return;
+        };
+        if self.over_budget {
+            return;
         }
+        self.spent.observe(line);
+        let usage = self.spent.usage();
+        let Some(violation) = budget.check(&usage) else {
+            return;
+        };
 
-        info!("📖 Finished reading Claude sidecar events. Total lines: {}", line_count);
-    });
-
-    // Monitor process status and wait for completion
-    tokio::spawn(async move {
-        info!("🕐 Starting sidecar process monitoring...");
-
-        // Wait for first output with timeout
-        for i in 0..300 {
-            // 30 seconds (300 * 100ms)
-            if first_output.load(std::sync::atomic::Ordering::Relaxed) {
-                info!("✅ Output detected after {}ms, continuing normal execution", i * 100);
-                break;
+        self.over_budget = true;
+        let reason = violation.describe(budget, &usage);
+        warn!("Agent run {} is over budget: {}", self.run_id, reason);
+        self.execute(
+            "UPDATE agent_runs SET status = 'budget_exceeded', status_reason = ?2, completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status IN ('running', 'paused')",
+            params![self.run_id, reason],
+        );
+        let registry = self
+            .app
+            .state::<crate::process::ProcessRegistryState>()
+            .0
+            .clone();
+        let run_id = self.run_id;
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = registry.kill_process(run_id).await {
+                error!("Failed to kill over-budget run {}: {}", run_id, e);
             }
+        });
+    }
 
-            if i == 299 {
-                warn!("⏰ TIMEOUT: No output from Claude sidecar after 30 seconds");
-                warn!("💡 This usually means:");
-                warn!("   1. Claude sidecar is waiting for user input");
-                warn!("   2. Authentication issues (API key not found/invalid)");
-                warn!("   3. Network connectivity issues");
-                warn!("   4. Claude failed to initialize but didn't report an error");
-
-                // Update database with failed status
-                if let Ok(conn) = Connection::open(&db_path) {
-                    let _ = conn.execute(
-                        "UPDATE agent_runs SET status = 'failed', completed_at = CURRENT_TIMESTAMP WHERE id = ?1",
-                        params![run_id],
-                    );
+    fn execute(&self, sql: &str, params: impl rusqlite::Params) {
+        match Connection::open(&self.db_path) {
+            Ok(conn) => {
+                if let Err(e) = conn.execute(sql, params) {
+                    error!("Failed to update agent run {}: {}", self.run_id, e);
                 }
-
-                let _ = app.emit("agent-complete", false);
-                let _ = app.emit(&format!("agent-complete:{}", run_id), false);
-                return;
             }
-
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+            Err(e) => error!(
+                "Failed to open database for agent run {}: {}",
+                self.run_id, e
+            ),
         }
+    }
 
-        // Wait for sidecar task to complete
-        info!("⏳ Waiting for sidecar reading to complete...");
-        let _ = sidecar_task.await;
+    fn record_error_output(&mut self, line: &str) {
+        if self.error_output.len() == ERROR_OUTPUT_LINES {
+            self.error_output.pop_front();
+        }
+        self.error_output.push_back(line.to_string());
+    }
 
-        // Get the session ID that was extracted
-        let extracted_session_id = if let Ok(Some(sid)) = session_id_holder.lock().map(|s| s.clone()) {
-            sid
-        } else {
-            String::new()
-        };
+    /// A failure of the run, judged from the error output seen so far
+    fn failure(&self, exit_code: Option<i32>, startup_timeout: bool) -> RunFailure {
+        RunFailure {
+            exit_code,
+            output: self
+                .error_output
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("\n"),
+            startup_timeout,
+        }
+    }
 
-        // Update the run record with session ID and mark as completed
-        if let Ok(conn) = Connection::open(&db_path) {
-            info!("🔄 Updating database with extracted session ID: {}", extracted_session_id);
-            match conn.execute(
-                "UPDATE agent_runs SET session_id = ?1, status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = ?2",
-                params![extracted_session_id, run_id],
-            ) {
-                Ok(rows_affected) => {
-                    if rows_affected > 0 {
-                        info!("✅ Successfully updated agent run {} with session ID: {}", run_id, extracted_session_id);
-                    } else {
-                        warn!("⚠️ No rows affected when updating agent run {} with session ID", run_id);
-                    }
-                }
-                Err(e) => {
-                    error!("❌ Failed to update agent run {} with session ID: {}", run_id, e);
-                }
+    /// Marks the run failed unless something else ended it first, and queues a retry if
+    /// its agent's retry policy allows one
+    fn fail(&self, reason: &str, failure: RunFailure) {
+        warn!("Agent run {} failed: {}", self.run_id, reason);
+        match self.try_fail(reason, &failure) {
+            Ok(Some(retry_id)) => {
+                let _ = self
+                    .app
+                    .emit(&format!("agent-retrying:{}", self.run_id), retry_id);
             }
-        } else {
-            error!("❌ Failed to open database to update session ID for run {}", run_id);
+            Ok(None) => {}
+            Err(e) => error!(
+                "Failed to record failure of agent run {}: {}",
+                self.run_id, e
+            ),
         }
+    }
 
-        info!("✅ Claude sidecar execution monitoring complete");
-
-        let _ = app.emit("agent-complete", true);
-        let _ = app.emit(&format!("agent-complete:{}", run_id), true);
-    });
+    fn try_fail(&self, reason: &str, failure: &RunFailure) -> Result<Option<i64>, String> {
+        let mut conn = Connection::open(&self.db_path).map_err(|e| e.to_string())?;
+        // Fail and retry together, so nothing sees the run failed without its retry
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let failed = tx
+            .execute(
+                "UPDATE agent_runs SET session_id = ?2, status = 'failed', status_reason = ?3, completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status IN ('running', 'paused')",
+                params![self.run_id, self.session_id, reason],
+            )
+            .map_err(|e| e.to_string())?;
+        let retry = if failed > 0 {
+            schedule_retry(&tx, self.run_id, failure)?
+        } else {
+            None
+        };
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(retry)
+    }
 
-    Ok(run_id)
+    fn emit_complete(&self, success: bool) {
+        let _ = self.app.emit("agent-complete", success);
+        let _ = self
+            .app
+            .emit(&format!("agent-complete:{}", self.run_id), success);
+    }
 }
 
-/// Spawn agent using system binary command
-async fn spawn_agent_system(
-    app: AppHandle,
-    run_id: i64,
-    agent_id: i64,
-    agent_name: String,
-    claude_path: String,
-    args: Vec<String>,
-    project_path: String,
-    task: String,
-    execution_model: String,
-    db: State<'_, AgentDb>,
-    registry: State<'_, crate::process::ProcessRegistryState>,
-) -> Result<i64, String> {
-    // Build the command
-    let mut cmd = create_agent_system_command(&claude_path, args, &project_path);
-
-    // Spawn the process
-    info!("🚀 Spawning Claude system process...");
-    let mut child = cmd.spawn().map_err(|e| {
-        error!("❌ Failed to spawn Claude process: {}", e);
-        format!("Failed to spawn Claude: {}", e)
-    })?;
-
-    info!("🔌 Using Stdio::null() for stdin - no input expected");
-
-    // Get the PID and register the process
-    let pid = child.id().unwrap_or(0);
-    let now = chrono::Utc::now().to_rfc3339();
-    info!("✅ Claude process spawned successfully with PID: {}", pid);
-
-    // Update the database with PID and status
-    {
-        let conn = db.0.lock().map_err(|e| e.to_string())?;
-        conn.execute(
+impl RunObserver for AgentRunObserver {
+    fn on_spawned(&mut self, pid: u32) {
+        let now = chrono::Utc::now().to_rfc3339();
+        self.execute(
             "UPDATE agent_runs SET status = 'running', pid = ?1, process_started_at = ?2 WHERE id = ?3",
-            params![pid as i64, now, run_id],
-        ).map_err(|e| e.to_string())?;
-        info!("📝 Updated database with running status and PID");
+            params![pid as i64, now, self.run_id],
+        );
+        info!("Agent run {} is running as PID {}", self.run_id, pid);
     }
 
-    // Get stdout and stderr
-    let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
-    let stderr = child.stderr.take().ok_or("Failed to get stderr")?;
-    info!("📡 Set up stdout/stderr readers");
-
-    // Create readers
-    let stdout_reader = TokioBufReader::new(stdout);
-    let stderr_reader = TokioBufReader::new(stderr);
-
-    // Create variables we need for the spawned tasks
-    let app_dir = app
-        .path()
-        .app_data_dir()
-        .expect("Failed to get app data dir");
-    let db_path = app_dir.join("agents.db");
-
-    // Shared state for collecting session ID and live output
-    let session_id = std::sync::Arc::new(Mutex::new(String::new()));
-    let live_output = std::sync::Arc::new(Mutex::new(String::new()));
-    let start_time = std::time::Instant::now();
-
-    // Spawn tasks to read stdout and stderr
-    let app_handle = app.clone();
-    let session_id_clone = session_id.clone();
-    let live_output_clone = live_output.clone();
-    let registry_clone = registry.0.clone();
-    let first_output = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
-    let first_output_clone = first_output.clone();
-    let db_path_for_stdout = db_path.clone(); // Clone the db_path for the stdout task
-
-    let stdout_task = tokio::spawn(async move {
-        info!("📖 Starting to read Claude stdout...");
-        let mut lines = stdout_reader.lines();
-        let mut line_count = 0;
-
-        while let Ok(Some(line)) = lines.next_line().await {
-            line_count += 1;
-
-            // Log first output
-            if !first_output_clone.load(std::sync::atomic::Ordering::Relaxed) {
-                info!(
-                    "🎉 First output received from Claude process! Line: {}",
-                    line
-                );
-                first_output_clone.store(true, std::sync::atomic::Ordering::Relaxed);
-            }
-
-            if line_count <= 5 {
-                info!("stdout[{}]: {}", line_count, line);
-            } else {
-                debug!("stdout[{}]: {}", line_count, line);
-            }
-
-            // Store live output in both local buffer and registry
-            if let Ok(mut output) = live_output_clone.lock() {
-                output.push_str(&line);
-                output.push('\n');
-            }
-
-            // Also store in process registry for cross-session access
-            let _ = registry_clone.append_live_output(run_id, &line);
-
-            // Extract session ID from JSONL output
-            if let Ok(json) = serde_json::from_str::<JsonValue>(&line) {
-                // Claude Code uses "session_id" (underscore), not "sessionId"
-                if json.get("type").and_then(|t| t.as_str()) == Some("system") &&
-                   json.get("subtype").and_then(|s| s.as_str()) == Some("init") {
-                    if let Some(sid) = json.get("session_id").and_then(|s| s.as_str()) {
-                        if let Ok(mut current_session_id) = session_id_clone.lock() {
-                            if current_session_id.is_empty() {
-                                *current_session_id = sid.to_string();
-                                info!("🔑 Extracted session ID: {}", sid);
-                                
-                                // Update database immediately with session ID
-                                if let Ok(conn) = Connection::open(&db_path_for_stdout) {
-                                    match conn.execute(
-                                        "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
-                                        params![sid, run_id],
-                                    ) {
-                                        Ok(rows) => {
-                                            if rows > 0 {
-                                                info!("✅ Updated agent run {} with session ID immediately", run_id);
-                                            }
-                                        }
-                                        Err(e) => {
-                                            error!("❌ Failed to update session ID immediately: {}", e);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
-            // Emit the line to the frontend with run_id for isolation
-            let _ = app_handle.emit(&format!("agent-output:{}", run_id), &line);
-            // Also emit to the generic event for backward compatibility
-            let _ = app_handle.emit("agent-output", &line);
-        }
-
-        info!(
-            "📖 Finished reading Claude stdout. Total lines: {}",
-            line_count
+    fn on_session_started(&mut self, _run_id: i64, session_id: &str) {
+        self.session_id = session_id.to_string();
+        // Record the session ID right away so the UI can load the transcript while running
+        self.execute(
+            "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
+            params![session_id, self.run_id],
         );
-    });
-
-    let app_handle_stderr = app.clone();
-    let first_error = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
-    let first_error_clone = first_error.clone();
-
-    let stderr_task = tokio::spawn(async move {
-        info!("📖 Starting to read Claude stderr...");
-        let mut lines = stderr_reader.lines();
-        let mut error_count = 0;
-
-        while let Ok(Some(line)) = lines.next_line().await {
-            error_count += 1;
-
-            // Log first error
-            if !first_error_clone.load(std::sync::atomic::Ordering::Relaxed) {
-                warn!("⚠️ First error output from Claude process! Line: {}", line);
-                first_error_clone.store(true, std::sync::atomic::Ordering::Relaxed);
-            }
-
-            error!("stderr[{}]: {}", error_count, line);
-            // Emit error lines to the frontend with run_id for isolation
-            let _ = app_handle_stderr.emit(&format!("agent-error:{}", run_id), &line);
-            // Also emit to the generic event for backward compatibility
-            let _ = app_handle_stderr.emit("agent-error", &line);
-        }
+    }
 
-        if error_count > 0 {
-            warn!(
-                "📖 Finished reading Claude stderr. Total error lines: {}",
-                error_count
-            );
-        } else {
-            info!("📖 Finished reading Claude stderr. No errors.");
+    fn on_stdout(&mut self, line: &str) {
+        self.line_count += 1;
+        if self.line_count <= 5 {
+            info!("run {} stdout[{}]: {}", self.run_id, self.line_count, line);
         }
-    });
-
-    // Register the process in the registry for live output tracking (after stdout/stderr setup)
-    registry
-        .0
-        .register_process(
-            run_id,
-            agent_id,
-            agent_name,
-            pid,
-            project_path.clone(),
-            task.clone(),
-            execution_model.clone(),
-            child,
-        )
-        .map_err(|e| format!("Failed to register process: {}", e))?;
-    info!("📋 Registered process in registry");
-
-    let db_path_for_monitor = db_path.clone(); // Clone for the monitor task
-
-    // Monitor process status and wait for completion
-    tokio::spawn(async move {
-        info!("🕐 Starting process monitoring...");
-
-        // Wait for first output with timeout
-        for i in 0..300 {
-            // 30 seconds (300 * 100ms)
-            if first_output.load(std::sync::atomic::Ordering::Relaxed) {
-                info!(
-                    "✅ Output detected after {}ms, continuing normal execution",
-                    i * 100
-                );
-                break;
-            }
-
-            if i == 299 {
-                warn!("⏰ TIMEOUT: No output from Claude process after 30 seconds");
-                warn!("💡 This usually means:");
-                warn!("   1. Claude process is waiting for user input");
-                warn!("   3. Claude failed to initialize but didn't report an error");
-                warn!("   4. Network connectivity issues");
-                warn!("   5. Authentication issues (API key not found/invalid)");
-
-                // Process timed out - kill it via PID
-                warn!(
-                    "🔍 Process likely stuck waiting for input, attempting to kill PID: {}",
-                    pid
-                );
-                let kill_result = std::process::Command::new("kill")
-                    .arg("-TERM")
-                    .arg(pid.to_string())
-                    .output();
-
-                match kill_result {
-                    Ok(output) if output.status.success() => {
-                        warn!("🔍 Successfully sent TERM signal to process");
-                    }
-                    Ok(_) => {
-                        warn!("🔍 Failed to kill process with TERM, trying KILL");
-                        let _ = std::process::Command::new("kill")
-                            .arg("-KILL")
-                            .arg(pid.to_string())
-                            .output();
-                    }
-                    Err(e) => {
-                        warn!("🔍 Error killing process: {}", e);
-                    }
-                }
-
-                // Update database
-                if let Ok(conn) = Connection::open(&db_path_for_monitor) {
-                    let _ = conn.execute(
-                        "UPDATE agent_runs SET status = 'failed', completed_at = CURRENT_TIMESTAMP WHERE id = ?1",
-                        params![run_id],
-                    );
-                }
 
-                let _ = app.emit("agent-complete", false);
-                let _ = app.emit(&format!("agent-complete:{}", run_id), false);
-                return;
+        // Runs that require approval wait on the user until a decision is sent to stdin
+        if let Some(parsed) = StreamLine::parse(line) {
+            self.track_budget(&parsed);
+            if let StreamEvent::Result(result) = &parsed.event {
+                if result.is_error {
+                    self.record_error_output(result.result.as_deref().unwrap_or(&result.subtype));
+                }
+            }
+            if let Some((request_id, tool_name, input)) = parsed.event.permission_request() {
+                info!(
+                    "run {} is waiting for approval to use {}",
+                    self.run_id, tool_name
+                );
+                let _ = self.app.emit(
+                    &format!("agent-permission-request:{}", self.run_id),
+                    serde_json::json!({
+                        "request_id": request_id,
+                        "tool_name": tool_name,
+                        "input": input,
+                    }),
+                );
             }
-
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
         }
 
-        // Wait for reading tasks to complete
-        info!("⏳ Waiting for stdout/stderr reading to complete...");
-        let _ = stdout_task.await;
-        let _ = stderr_task.await;
+        // Emit the line to the frontend with run_id for isolation
+        let _ = self
+            .app
+            .emit(&format!("agent-output:{}", self.run_id), line);
+        // Also emit to the generic event for backward compatibility
+        let _ = self.app.emit("agent-output", line);
+    }
 
-        let duration_ms = start_time.elapsed().as_millis() as i64;
-        info!("⏱️ Process execution took {} ms", duration_ms);
+    fn on_stderr(&mut self, line: &str) {
+        self.record_error_output(line);
+        // Emit error lines to the frontend with run_id for isolation
+        let _ = self.app.emit(&format!("agent-error:{}", self.run_id), line);
+        // Also emit to the generic event for backward compatibility
+        let _ = self.app.emit("agent-error", line);
+    }
 
-        // Get the session ID that was extracted
-        let extracted_session_id = if let Ok(sid) = session_id.lock() {
-            sid.clone()
-        } else {
-            String::new()
-        };
+    fn on_limit_exceeded(&mut self, violation: LimitViolation, limits: &ResourceLimits) {
+        self.execute(
+            "UPDATE agent_runs SET status = ?2, status_reason = ?3, completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status IN ('running', 'paused')",
+            params![self.run_id, violation.run_status(), violation.describe(limits)],
+        );
+    }
 
-        // Wait for process completion and update status
-        info!("✅ Claude process execution monitoring complete");
+    fn on_startup_timeout(&mut self, kill_reason: &str) {
+        self.timed_out = true;
+        let reason = format!(
+            "No output after {} seconds; {}",
+            AGENT_STARTUP_TIMEOUT.as_secs(),
+            kill_reason
+        );
+        self.fail(&reason, self.failure(None, true));
+        self.emit_complete(false);
+    }
 
-        // Update the run record with session ID and mark as completed - open a new connection
-        if let Ok(conn) = Connection::open(&db_path_for_monitor) {
-            info!("🔄 Updating database with extracted session ID: {}", extracted_session_id);
-            match conn.execute(
-                "UPDATE agent_runs SET session_id = ?1, status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = ?2",
-                params![extracted_session_id, run_id],
-            ) {
-                Ok(rows_affected) => {
-                    if rows_affected > 0 {
-                        info!("✅ Successfully updated agent run {} with session ID: {}", run_id, extracted_session_id);
-                    } else {
-                        warn!("⚠️ No rows affected when updating agent run {} with session ID", run_id);
-                    }
-                }
-                Err(e) => {
-                    error!("❌ Failed to update agent run {} with session ID: {}", run_id, e);
-                }
-            }
-        } else {
-            error!("❌ Failed to open database to update session ID for run {}", run_id);
+    fn on_exit(&mut self, exit_code: Option<i32>) {
+        if self.timed_out {
+            return;
         }
 
-        // Cleanup will be handled by the cleanup_finished_processes function
-
-        let _ = app.emit("agent-complete", true);
-        let _ = app.emit(&format!("agent-complete:{}", run_id), true);
-    });
-
-    Ok(run_id)
+        info!(
+            "Agent run {} finished after {} lines of output",
+            self.run_id, self.line_count
+        );
+        if exit_code != Some(0) {
+            let failure = self.failure(exit_code, false);
+            self.fail(&failure.describe(), failure);
+            self.emit_complete(false);
+            return;
+        }
+        let finished = if self.verifies {
+            "verifying"
+        } else {
+            "completed"
+        };
+        self.execute(
+            // Keep statuses set while running, such as 'cancelled' or 'timed_out'
+            "UPDATE agent_runs SET session_id = ?1, status = CASE WHEN status IN ('running', 'paused') THEN ?3 ELSE status END, completed_at = CURRENT_TIMESTAMP WHERE id = ?2",
+            params![self.session_id, self.run_id, finished],
+        );
+        self.emit_complete(true);
+        if self.verifies {
+            tauri::async_runtime::spawn(verify_agent_run(self.app.clone(), self.run_id));
+        }
+    }
 }
 
 /// List all currently running agent sessions
@@ -1379,35 +1458,15 @@ pub async fn list_running_sessions(
     let conn = db.0.lock().map_err(|e| e.to_string())?;
 
     // First get all running sessions from the database
-    let mut stmt = conn.prepare(
-        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-         FROM agent_runs WHERE status = 'running' ORDER BY process_started_at DESC"
-    ).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM agent_runs WHERE status IN ('running', 'paused') ORDER BY process_started_at DESC",
+            AGENT_RUN_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
 
     let mut runs = stmt
-        .query_map([], |row| {
-            Ok(AgentRun {
-                id: Some(row.get(0)?),
-                agent_id: row.get(1)?,
-                agent_name: row.get(2)?,
-                agent_icon: row.get(3)?,
-                task: row.get(4)?,
-                model: row.get(5)?,
-                project_path: row.get(6)?,
-                session_id: row.get(7)?,
-                status: row
-                    .get::<_, String>(8)
-                    .unwrap_or_else(|_| "pending".to_string()),
-                pid: row
-                    .get::<_, Option<i64>>(9)
-                    .ok()
-                    .flatten()
-                    .map(|p| p as u32),
-                process_started_at: row.get(10)?,
-                created_at: row.get(11)?,
-                completed_at: row.get(12)?,
-            })
-        })
+        .query_map([], AgentRun::from_row)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
@@ -1437,55 +1496,94 @@ pub async fn list_running_sessions(
 }
 
 /// Kill a running agent session
+///
+/// The process group is interrupted first, then terminated, then killed; the grace
+/// periods default to the registry's kill policy and can be overridden per call.
 #[tauri::command]
 pub async fn kill_agent_session(
     app: AppHandle,
     db: State<'_, AgentDb>,
     registry: State<'_, crate::process::ProcessRegistryState>,
     run_id: i64,
+    interrupt_grace_ms: Option<u64>,
+    terminate_grace_ms: Option<u64>,
 ) -> Result<bool, String> {
     info!("Attempting to kill agent session {}", run_id);
 
+    // A queued run has no process yet
+    if crate::commands::queue::cancel_queued_run(app.clone(), db.clone(), run_id).await? {
+        return Ok(true);
+    }
+
+    let mut policy = registry.0.kill_policy()?;
+    if let Some(ms) = interrupt_grace_ms {
+        policy.interrupt_grace_ms = ms;
+    }
+    if let Some(ms) = terminate_grace_ms {
+        policy.terminate_grace_ms = ms;
+    }
+
     // First try to kill using the process registry
-    let killed_via_registry = match registry.0.kill_process(run_id).await {
-        Ok(success) => {
-            if success {
-                info!("Successfully killed process {} via registry", run_id);
-                true
-            } else {
-                warn!("Process {} not found in registry", run_id);
-                false
-            }
+    let mut report = match registry.0.kill_process_with_policy(run_id, &policy).await {
+        Ok(Some(report)) => {
+            info!("Successfully killed process {} via registry", run_id);
+            Some(report)
+        }
+        Ok(None) => {
+            warn!("Process {} not found in registry", run_id);
+            None
         }
         Err(e) => {
             warn!("Failed to kill process {} via registry: {}", run_id, e);
-            false
+            None
         }
     };
+    let killed_via_registry = report.is_some();
 
     // If registry kill didn't work, try fallback with PID from database
+    let mut gone = None;
     if !killed_via_registry {
-        let pid_result = {
+        let recorded = {
             let conn = db.0.lock().map_err(|e| e.to_string())?;
             conn.query_row(
-                "SELECT pid FROM agent_runs WHERE id = ?1 AND status = 'running'",
+                "SELECT pid, process_started_at FROM agent_runs WHERE id = ?1 AND status IN ('running', 'paused')",
                 params![run_id],
-                |row| row.get::<_, Option<i64>>(0),
+                |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, Option<String>>(1)?)),
             )
+            .optional()
             .map_err(|e| e.to_string())?
         };
 
-        if let Some(pid) = pid_result {
-            info!("Attempting fallback kill for PID {} from database", pid);
-            let _ = registry.0.kill_process_by_pid(run_id, pid as u32)?;
+        if let Some((Some(pid), started_at)) = recorded {
+            // The PID may have exited or been reused since it was recorded
+            let started_at = started_at.as_deref().and_then(parse_recorded_start);
+            match probe_run(pid as u32, started_at.as_ref()) {
+                RunProbe::Alive => {
+                    info!("Attempting fallback kill for PID {} from database", pid);
+                    report = Some(registry.0.kill_process_by_pid(run_id, pid as u32).await?);
+                }
+                probe => {
+                    warn!(
+                        "Not signalling PID {} of agent run {}: {:?}",
+                        pid, run_id, probe
+                    );
+                    gone = Some(pid);
+                }
+            }
         }
     }
 
+    let reason = match (&report, gone) {
+        (Some(report), _) => format!("Cancelled by user: {}", report.reason.description()),
+        (None, Some(pid)) => format!("Cancelled by user: process {} had already exited", pid),
+        (None, None) => "Cancelled by user".to_string(),
+    };
+
     // Update the database to mark as cancelled
     let conn = db.0.lock().map_err(|e| e.to_string())?;
     let updated = conn.execute(
-        "UPDATE agent_runs SET status = 'cancelled', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'running'",
-        params![run_id],
+        "UPDATE agent_runs SET status = 'cancelled', status_reason = ?2, completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status IN ('running', 'paused', 'queued')",
+        params![run_id, reason],
     ).map_err(|e| e.to_string())?;
 
     // Emit cancellation event with run_id for proper isolation
@@ -1494,6 +1592,117 @@ pub async fn kill_agent_session(
     Ok(updated > 0 || killed_via_registry)
 }
 
+/// Get the grace periods used when killing agent sessions
+#[tauri::command]
+pub async fn get_kill_policy(
+    registry: State<'_, crate::process::ProcessRegistryState>,
+) -> Result<KillPolicy, String> {
+    registry.0.kill_policy()
+}
+
+/// Set the grace periods used when killing agent sessions
+#[tauri::command]
+pub async fn set_kill_policy(
+    registry: State<'_, crate::process::ProcessRegistryState>,
+    policy: KillPolicy,
+) -> Result<(), String> {
+    info!(
+        "Setting kill policy: interrupt grace {}ms, terminate grace {}ms",
+        policy.interrupt_grace_ms, policy.terminate_grace_ms
+    );
+    registry.0.set_kill_policy(policy)
+}
+
+/// Pause a running agent session by stopping its process group
+#[tauri::command]
+pub async fn pause_agent_session(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    registry: State<'_, crate::process::ProcessRegistryState>,
+    run_id: i64,
+) -> Result<bool, String> {
+    info!("Pausing agent session {}", run_id);
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let pid = conn
+        .query_row(
+            "SELECT pid FROM agent_runs WHERE id = ?1 AND status = 'running'",
+            params![run_id],
+            |row| row.get::<_, Option<i64>>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let Some(pid) = pid else {
+        warn!("Agent run {} is not running, cannot pause", run_id);
+        return Ok(false);
+    };
+
+    // Runs the registry doesn't track (e.g. sidecar runs) are signalled by PID
+    if !registry.0.pause_process(run_id)? {
+        let pid = pid.ok_or("No process was recorded for this run")?;
+        crate::process::signals::suspend_process_tree(pid as u32)?;
+    }
+
+    conn.execute(
+        "UPDATE agent_runs SET status = 'paused', paused_at = ?2 WHERE id = ?1",
+        params![run_id, chrono::Utc::now().to_rfc3339()],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let _ = app.emit(&format!("agent-paused:{}", run_id), true);
+    Ok(true)
+}
+
+/// Resume a paused agent session
+#[tauri::command]
+pub async fn resume_agent_session(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    registry: State<'_, crate::process::ProcessRegistryState>,
+    run_id: i64,
+) -> Result<bool, String> {
+    info!("Resuming agent session {}", run_id);
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let paused = conn
+        .query_row(
+            "SELECT pid, paused_at FROM agent_runs WHERE id = ?1 AND status = 'paused'",
+            params![run_id],
+            |row| {
+                Ok((
+                    row.get::<_, Option<i64>>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let Some((pid, paused_at)) = paused else {
+        warn!("Agent run {} is not paused, cannot resume", run_id);
+        return Ok(false);
+    };
+
+    if !registry.0.resume_process(run_id)? {
+        let pid = pid.ok_or("No process was recorded for this run")?;
+        crate::process::signals::resume_process_tree(pid as u32)?;
+    }
+
+    let paused_for_ms = paused_at
+        .as_deref()
+        .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+        .map(|at| (chrono::Utc::now() - at.with_timezone(&chrono::Utc)).num_milliseconds())
+        .unwrap_or(0)
+        .max(0);
+    conn.execute(
+        "UPDATE agent_runs SET status = 'running', paused_at = NULL, paused_ms = paused_ms + ?2 WHERE id = ?1",
+        params![run_id, paused_for_ms],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let _ = app.emit(&format!("agent-resumed:{}", run_id), true);
+    Ok(true)
+}
+
 /// Get the status of a specific agent session
 #[tauri::command]
 pub async fn get_session_status(
@@ -1513,6 +1722,195 @@ pub async fn get_session_status(
     }
 }
 
+/// Marks a run that is still recorded as running as failed
+fn mark_run_failed(conn: &Connection, run_id: i64, reason: &str) -> Result<(), String> {
+    conn.execute(
+        "UPDATE agent_runs SET status = 'failed', status_reason = ?2, completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status IN ('running', 'paused')",
+        params![run_id, reason],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Reconcile runs left `running` by a previous app instance with the processes that are still alive
+///
+/// Live runs are re-registered and followed through their session JSONL; the rest are marked failed.
+/// Verification that the app closed during is started again.
+pub async fn reconcile_agent_runs(app: AppHandle) -> Result<(), String> {
+    resume_verifications(&app)?;
+
+    let runs = {
+        let db = app.state::<AgentDb>();
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM agent_runs WHERE status IN ('running', 'paused')",
+                AGENT_RUN_COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+        let runs = stmt
+            .query_map([], AgentRun::from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        runs
+    };
+
+    if runs.is_empty() {
+        return Ok(());
+    }
+    info!(
+        "Reconciling {} agent runs left running by a previous session",
+        runs.len()
+    );
+
+    let registry = app
+        .state::<crate::process::ProcessRegistryState>()
+        .0
+        .clone();
+
+    for run in runs {
+        let Some(run_id) = run.id else { continue };
+        let started_at = run
+            .process_started_at
+            .as_deref()
+            .and_then(parse_recorded_start);
+
+        let failure = match run.pid {
+            None => Some("No process was recorded for this run".to_string()),
+            Some(pid) => match probe_run(pid, started_at.as_ref()) {
+                RunProbe::Alive => None,
+                RunProbe::Dead => Some(format!(
+                    "Process {} exited while the app was not running; exit status unknown",
+                    pid
+                )),
+                RunProbe::PidReused => Some(format!(
+                    "Process {} exited while the app was not running; its PID now belongs to another process",
+                    pid
+                )),
+            },
+        };
+
+        if let Some(reason) = failure {
+            warn!("Marking agent run {} as failed: {}", run_id, reason);
+            let db = app.state::<AgentDb>();
+            let conn = db.0.lock().map_err(|e| e.to_string())?;
+            mark_run_failed(&conn, run_id, &reason)?;
+            continue;
+        }
+
+        let pid = run.pid.unwrap_or(0);
+        info!("Re-attaching to agent run {} (PID {})", run_id, pid);
+        registry.register_reattached_run(
+            run_id,
+            run.agent_id,
+            run.agent_name.clone(),
+            pid,
+            started_at.unwrap_or_else(chrono::Utc::now),
+            run.working_dir().to_string(),
+            run.task.clone(),
+            run.model.clone(),
+        )?;
+        if run.status == "paused" {
+            // Still stopped from the previous session; keep it that way until resumed
+            registry.pause_process(run_id)?;
+        }
+
+        tokio::spawn(follow_reattached_run(app.clone(), run, started_at));
+    }
+
+    Ok(())
+}
+
+/// Follows a re-attached run by tailing its session JSONL until the process exits
+async fn follow_reattached_run(
+    app: AppHandle,
+    run: AgentRun,
+    started_at: Option<chrono::DateTime<chrono::Utc>>,
+) {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    let run_id = run.id.unwrap_or_default();
+    let pid = run.pid.unwrap_or(0);
+    let registry = app
+        .state::<crate::process::ProcessRegistryState>()
+        .0
+        .clone();
+
+    // Without a session ID there is nothing to tail, but the process is still watched
+    let session_file = if run.session_id.is_empty() {
+        None
+    } else {
+        session_jsonl_path(&run.session_id, run.working_dir()).ok()
+    };
+
+    // The process has its variables already; they're only needed to redact its output
+    let env = run_env(&app, Some(run.agent_id), &run.project_path).unwrap_or_default();
+    let mut offset = 0u64;
+    let mut pending = String::new();
+
+    loop {
+        let exited = probe_run(pid, started_at.as_ref()) != RunProbe::Alive;
+
+        // Read whatever was appended since the last poll
+        if let Some(path) = &session_file {
+            if let Ok(mut file) = tokio::fs::File::open(path).await {
+                let mut buf = Vec::new();
+                if file.seek(std::io::SeekFrom::Start(offset)).await.is_ok()
+                    && file.read_to_end(&mut buf).await.is_ok()
+                {
+                    offset += buf.len() as u64;
+                    pending.push_str(&String::from_utf8_lossy(&buf));
+
+                    while let Some(newline) = pending.find('\n') {
+                        let line: String = pending.drain(..=newline).collect();
+                        let line = line.trim_end();
+                        if line.is_empty() {
+                            continue;
+                        }
+                        let line = env.redact(line);
+                        let _ = registry.append_live_output(run_id, &line);
+                        let _ = app.emit(&format!("agent-output:{}", run_id), &line);
+                    }
+                }
+            }
+        }
+
+        if exited {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    }
+
+    info!("Re-attached agent run {} (PID {}) has exited", run_id, pid);
+    let _ = registry.unregister_process(run_id);
+
+    let verifies = {
+        let db = app.state::<AgentDb>();
+        match db.0.lock() {
+            Ok(conn) => {
+                let verifies = run_verification_settings(&conn, &run)
+                    .ok()
+                    .flatten()
+                    .is_some();
+                let finished = if verifies { "verifying" } else { "completed" };
+                let _ = conn.execute(
+                    "UPDATE agent_runs SET status = ?3, status_reason = ?2, completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status IN ('running', 'paused')",
+                    params![run_id, "Process exited after the app re-attached; exit status unknown", finished],
+                );
+                verifies
+            }
+            Err(_) => false,
+        }
+    };
+
+    let _ = app.emit("agent-complete", true);
+    let _ = app.emit(&format!("agent-complete:{}", run_id), true);
+    if verifies {
+        verify_agent_run(app, run_id).await;
+    }
+}
+
 /// Cleanup finished processes and update their status
 #[tauri::command]
 pub async fn cleanup_finished_processes(db: State<'_, AgentDb>) -> Result<Vec<i64>, String> {
@@ -1520,7 +1918,7 @@ pub async fn cleanup_finished_processes(db: State<'_, AgentDb>) -> Result<Vec<i6
 
     // Get all running processes
     let mut stmt = conn
-        .prepare("SELECT id, pid FROM agent_runs WHERE status = 'running' AND pid IS NOT NULL")
+        .prepare("SELECT id, pid FROM agent_runs WHERE status IN ('running', 'paused') AND pid IS NOT NULL")
         .map_err(|e| e.to_string())?;
 
     let running_processes = stmt
@@ -1535,29 +1933,7 @@ pub async fn cleanup_finished_processes(db: State<'_, AgentDb>) -> Result<Vec<i6
 
     for (run_id, pid) in running_processes {
         // Check if the process is still running
-        let is_running = if cfg!(target_os = "windows") {
-            // On Windows, use tasklist to check if process exists
-            match std::process::Command::new("tasklist")
-                .args(["/FI", &format!("PID eq {}", pid)])
-                .args(["/FO", "CSV"])
-                .output()
-            {
-                Ok(output) => {
-                    let output_str = String::from_utf8_lossy(&output.stdout);
-                    output_str.lines().count() > 1 // Header + process line if exists
-                }
-                Err(_) => false,
-            }
-        } else {
-            // On Unix-like systems, use kill -0 to check if process exists
-            match std::process::Command::new("kill")
-                .args(["-0", &pid.to_string()])
-                .output()
-            {
-                Ok(output) => output.status.success(),
-                Err(_) => false,
-            }
-        };
+        let is_running = crate::process::signals::is_pid_alive(pid as u32);
 
         if !is_running {
             // Process has finished, update status
@@ -1588,85 +1964,61 @@ pub async fn get_live_session_output(
     registry.0.get_live_output(run_id)
 }
 
+/// Read live output from a running process starting at a line offset
+///
+/// Returns `None` once the process is no longer tracked.
+#[tauri::command]
+pub async fn read_live_session_output(
+    registry: State<'_, crate::process::ProcessRegistryState>,
+    run_id: i64,
+    offset: u64,
+    limit: Option<usize>,
+) -> Result<Option<crate::process::OutputChunk>, String> {
+    registry.0.read_live_output(run_id, offset, limit)
+}
+
 /// Get real-time output for a running session by reading its JSONL file with live output fallback
+///
+/// The run's secret environment values are masked.
 #[tauri::command]
 pub async fn get_session_output(
+    app: AppHandle,
     db: State<'_, AgentDb>,
     registry: State<'_, crate::process::ProcessRegistryState>,
     run_id: i64,
 ) -> Result<String, String> {
     // Get the session information
     let run = get_agent_run(db, run_id).await?;
+    let env = run_env(&app, Some(run.agent_id), &run.project_path)?;
 
     // If no session ID yet, try to get live output from registry
     if run.session_id.is_empty() {
         let live_output = registry.0.get_live_output(run_id)?;
-        if !live_output.is_empty() {
-            return Ok(live_output);
-        }
-        return Ok(String::new());
+        return Ok(env.redact(&live_output).into_owned());
     }
 
-    // Get the Claude directory
-    let claude_dir = dirs::home_dir()
+    // Find the correct project directory by searching for the session file
+    let projects_dir = dirs::home_dir()
         .ok_or("Failed to get home directory")?
-        .join(".claude");
+        .join(".claude")
+        .join("projects");
 
-    // Find the correct project directory by searching for the session file
-    let projects_dir = claude_dir.join("projects");
-    
     // Check if projects directory exists
     if !projects_dir.exists() {
         log::error!("Projects directory not found at: {:?}", projects_dir);
         return Err("Projects directory not found".to_string());
     }
 
-    // Search for the session file in all project directories
-    let mut session_file_path = None;
-    log::info!("Searching for session file {} in all project directories", run.session_id);
-    
-    if let Ok(entries) = std::fs::read_dir(&projects_dir) {
-        for entry in entries.filter_map(Result::ok) {
-            let path = entry.path();
-            if path.is_dir() {
-                let dir_name = path.file_name().unwrap_or_default().to_string_lossy();
-                log::debug!("Checking project directory: {}", dir_name);
-                
-                let potential_session_file = path.join(format!("{}.jsonl", run.session_id));
-                if potential_session_file.exists() {
-                    log::info!("Found session file at: {:?}", potential_session_file);
-                    session_file_path = Some(potential_session_file);
-                    break;
-                } else {
-                    log::debug!("Session file not found in: {}", dir_name);
-                }
-            }
-        }
-    } else {
-        log::error!("Failed to read projects directory");
-    }
-
-    // If we found the session file, read it
-    if let Some(session_path) = session_file_path {
-        match tokio::fs::read_to_string(&session_path).await {
-            Ok(content) => Ok(content),
-            Err(e) => {
-                log::error!("Failed to read session file {}: {}", session_path.display(), e);
-                // Fallback to live output if file read fails
-                let live_output = registry.0.get_live_output(run_id)?;
-                Ok(live_output)
-            }
-        }
-    } else {
-        // If session file not found, try the old method as fallback
-        log::warn!("Session file not found for {}, trying legacy method", run.session_id);
-        match read_session_jsonl(&run.session_id, &run.project_path).await {
-            Ok(content) => Ok(content),
-            Err(_) => {
-                // Final fallback to live output
-                let live_output = registry.0.get_live_output(run_id)?;
-                Ok(live_output)
-            }
+    match read_redacted_session(&projects_dir, &run.session_id, &env).await {
+        Ok(content) => Ok(content),
+        Err(e) => {
+            // Try the old method, then live output, as fallbacks
+            log::warn!("{}, trying legacy method", e);
+            let output = match read_session_jsonl(&run.session_id, run.working_dir()).await {
+                Ok(content) => content,
+                Err(_) => registry.0.get_live_output(run_id)?,
+            };
+            Ok(env.redact(&output).into_owned())
         }
     }
 }
@@ -1687,7 +2039,8 @@ pub async fn stream_session_output(
     }
 
     let session_id = run.session_id.clone();
-    let project_path = run.project_path.clone();
+    let project_path = run.working_dir().to_string();
+    let env = run_env(&app, Some(run.agent_id), &run.project_path)?;
 
     // Spawn a task to monitor the file
     tokio::spawn(async move {
@@ -1711,6 +2064,7 @@ pub async fn stream_session_output(
                     if current_size > last_size {
                         // File has grown, read new content
                         if let Ok(content) = tokio::fs::read_to_string(&session_file).await {
+                            let content = env.redact(&content);
                             let _ = app
                                 .emit("session-output-update", &format!("{}:{}", run_id, content));
                         }
@@ -1736,7 +2090,7 @@ pub async fn stream_session_output(
                     rusqlite::params![run_id],
                     |row| row.get::<_, String>(0),
                 ) {
-                    if status != "running" {
+                    if status != "running" && status != "paused" {
                         debug!("Session {} is no longer running, stopping stream", run_id);
                         break;
                     }
@@ -1766,7 +2120,7 @@ pub async fn export_agent(db: State<'_, AgentDb>, id: i64) -> Result<String, Str
     // Fetch the agent
     let agent = conn
         .query_row(
-            "SELECT name, icon, system_prompt, default_task, model, hooks FROM agents WHERE id = ?1",
+            "SELECT name, icon, system_prompt, default_task, model, hooks, parameters FROM agents WHERE id = ?1",
             params![id],
             |row| {
                 Ok(serde_json::json!({
@@ -1775,7 +2129,8 @@ pub async fn export_agent(db: State<'_, AgentDb>, id: i64) -> Result<String, Str
                     "system_prompt": row.get::<_, String>(2)?,
                     "default_task": row.get::<_, Option<String>>(3)?,
                     "model": row.get::<_, String>(4)?,
-                    "hooks": row.get::<_, Option<String>>(5)?
+                    "hooks": row.get::<_, Option<String>>(5)?,
+                    "parameters": row.get::<_, Option<String>>(6)?
                 }))
             },
         )
@@ -1957,84 +2312,6 @@ pub async fn list_claude_installations(
     Ok(installations)
 }
 
-/// Helper function to create a tokio Command with proper environment variables
-/// This ensures commands like Claude can find Node.js and other dependencies
-fn create_command_with_env(program: &str) -> Command {
-    // Convert std::process::Command to tokio::process::Command
-    let _std_cmd = crate::claude_binary::create_command_with_env(program);
-
-    // Create a new tokio Command from the program path
-    let mut tokio_cmd = Command::new(program);
-
-    // Copy over all environment variables from the std::process::Command
-    // This is a workaround since we can't directly convert between the two types
-    for (key, value) in std::env::vars() {
-        if key == "PATH"
-            || key == "HOME"
-            || key == "USER"
-            || key == "SHELL"
-            || key == "LANG"
-            || key == "LC_ALL"
-            || key.starts_with("LC_")
-            || key == "NODE_PATH"
-            || key == "NVM_DIR"
-            || key == "NVM_BIN"
-            || key == "HOMEBREW_PREFIX"
-            || key == "HOMEBREW_CELLAR"
-        {
-            tokio_cmd.env(&key, &value);
-        }
-    }
-
-    // Add NVM support if the program is in an NVM directory
-    if program.contains("/.nvm/versions/node/") {
-        if let Some(node_bin_dir) = std::path::Path::new(program).parent() {
-            let current_path = std::env::var("PATH").unwrap_or_default();
-            let node_bin_str = node_bin_dir.to_string_lossy();
-            if !current_path.contains(&node_bin_str.as_ref()) {
-                let new_path = format!("{}:{}", node_bin_str, current_path);
-                tokio_cmd.env("PATH", new_path);
-            }
-        }
-    }
-
-    // Ensure PATH contains common Homebrew locations
-    if let Ok(existing_path) = std::env::var("PATH") {
-        let mut paths: Vec<&str> = existing_path.split(':').collect();
-        for p in ["/opt/homebrew/bin", "/usr/local/bin", "/usr/bin", "/bin"].iter() {
-            if !paths.contains(p) {
-                paths.push(p);
-            }
-        }
-        let joined = paths.join(":");
-        tokio_cmd.env("PATH", joined);
-    } else {
-        tokio_cmd.env("PATH", "/opt/homebrew/bin:/usr/local/bin:/usr/bin:/bin");
-    }
-
-    // BEGIN PATCH: Ensure bundled sidecar directory is in PATH when using the "claude-code" placeholder
-    if program == "claude-code" {
-        // Attempt to locate the sidecar binaries directory that Tauri uses during development
-        // At compile-time, CARGO_MANIFEST_DIR resolves to the absolute path of the src-tauri crate.
-        // The sidecar binaries live in <src-tauri>/binaries.
-        #[allow(clippy::redundant_clone)]
-        let sidecar_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("binaries");
-        if sidecar_dir.exists() {
-            if let Some(sidecar_dir_str) = sidecar_dir.to_str() {
-                let current_path = std::env::var("PATH").unwrap_or_default();
-                let separator = if cfg!(target_os = "windows") { ";" } else { ":" };
-                if !current_path.split(separator).any(|p| p == sidecar_dir_str) {
-                    let new_path = format!("{}{}{}", sidecar_dir_str, separator, current_path);
-                    tokio_cmd.env("PATH", new_path);
-                }
-            }
-        }
-    }
-    // END PATCH
-
-    tokio_cmd
-}
-
 /// Import an agent from JSON data
 #[tauri::command]
 pub async fn import_agent(db: State<'_, AgentDb>, json_data: String) -> Result<Agent, String> {
@@ -2069,43 +2346,32 @@ pub async fn import_agent(db: State<'_, AgentDb>, json_data: String) -> Result<A
         agent_data.name
     };
 
+    parse_parameters(agent_data.parameters.as_deref())?;
+
     // Create the agent
     conn.execute(
-        "INSERT INTO agents (name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks) VALUES (?1, ?2, ?3, ?4, ?5, 1, 1, 0, ?6)",
+        "INSERT INTO agents (name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, parameters) VALUES (?1, ?2, ?3, ?4, ?5, 1, 1, 0, ?6, ?7)",
         params![
             final_name,
             agent_data.icon,
             agent_data.system_prompt,
             agent_data.default_task,
             agent_data.model,
-            agent_data.hooks
+            agent_data.hooks,
+            agent_data.parameters
         ],
     )
     .map_err(|e| format!("Failed to create agent: {}", e))?;
 
     let id = conn.last_insert_rowid();
+    record_agent_version(&conn, id, Some("Imported"))?;
 
     // Fetch the created agent
     let agent = conn
         .query_row(
-            "SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents WHERE id = ?1",
+            &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
             params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    system_prompt: row.get(3)?,
-                    default_task: row.get(4)?,
-                    model: row.get(5)?,
-                    enable_file_read: row.get(6)?,
-                    enable_file_write: row.get(7)?,
-                    enable_network: row.get(8)?,
-                    hooks: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
+            Agent::from_row,
         )
         .map_err(|e| format!("Failed to fetch created agent: {}", e))?;
 
@@ -2258,65 +2524,147 @@ pub async fn import_agent_from_github(
 
 /// Load agent session history from JSONL file
 /// Similar to Claude Code's load_session_history, but searches across all project directories
+///
+/// Secret environment values of the run the session belongs to are masked.
 #[tauri::command]
 pub async fn load_agent_session_history(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
     session_id: String,
 ) -> Result<Vec<serde_json::Value>, String> {
     log::info!("Loading agent session history for session: {}", session_id);
 
-    let claude_dir = dirs::home_dir()
+    let projects_dir = dirs::home_dir()
         .ok_or("Failed to get home directory")?
-        .join(".claude");
+        .join(".claude")
+        .join("projects");
 
-    let projects_dir = claude_dir.join("projects");
-    
     if !projects_dir.exists() {
         log::error!("Projects directory not found at: {:?}", projects_dir);
         return Err("Projects directory not found".to_string());
     }
 
-    // Search for the session file in all project directories
-    let mut session_file_path = None;
-    log::info!("Searching for session file {} in all project directories", session_id);
-    
-    if let Ok(entries) = std::fs::read_dir(&projects_dir) {
-        for entry in entries.filter_map(Result::ok) {
-            let path = entry.path();
-            if path.is_dir() {
-                let dir_name = path.file_name().unwrap_or_default().to_string_lossy();
-                log::debug!("Checking project directory: {}", dir_name);
-                
-                let potential_session_file = path.join(format!("{}.jsonl", session_id));
-                if potential_session_file.exists() {
-                    log::info!("Found session file at: {:?}", potential_session_file);
-                    session_file_path = Some(potential_session_file);
-                    break;
-                } else {
-                    log::debug!("Session file not found in: {}", dir_name);
-                }
-            }
+    let run = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT agent_id, project_path FROM agent_runs WHERE session_id = ?1 ORDER BY id DESC LIMIT 1",
+            params![session_id],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+    };
+    let env = match run {
+        Some((agent_id, project_path)) => run_env(&app, Some(agent_id), &project_path)?,
+        None => RunEnv::default(),
+    };
+
+    let content = read_redacted_session(&projects_dir, &session_id, &env).await?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(file_read: bool, file_write: bool, network: bool, permissions: Option<&str>) -> Agent {
+        Agent {
+            id: Some(1),
+            name: "Reviewer".to_string(),
+            icon: "bot".to_string(),
+            system_prompt: String::new(),
+            default_task: None,
+            model: "sonnet".to_string(),
+            enable_file_read: file_read,
+            enable_file_write: file_write,
+            enable_network: network,
+            hooks: None,
+            resource_limits: None,
+            permissions: permissions.map(str::to_string),
+            parameters: None,
+            budget: None,
+            verification: None,
+            retry_policy: None,
+            created_at: String::new(),
+            updated_at: String::new(),
         }
-    } else {
-        log::error!("Failed to read projects directory");
     }
 
-    if let Some(session_path) = session_file_path {
-        let file = std::fs::File::open(&session_path)
-            .map_err(|e| format!("Failed to open session file: {}", e))?;
+    fn disallowed(args: &[String]) -> Vec<&str> {
+        args.iter()
+            .skip_while(|a| *a != "--disallowedTools")
+            .skip(1)
+            .take_while(|a| !a.starts_with("--"))
+            .map(String::as_str)
+            .collect()
+    }
 
-        let reader = BufReader::new(file);
-        let mut messages = Vec::new();
+    #[tokio::test]
+    async fn test_session_output_masks_secrets() {
+        let projects = tempfile::tempdir().unwrap();
+        let project_dir = projects.path().join("-tmp-repo");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        std::fs::write(
+            project_dir.join("abc.jsonl"),
+            "{\"type\":\"user\",\"text\":\"echo $NPM_TOKEN\"}\n{\"type\":\"result\",\"result\":\"npm_s3cret\"}\n",
+        )
+        .unwrap();
+        let mut env = RunEnv::default();
+        env.set("NPM_TOKEN".to_string(), "npm_s3cret".to_string(), true);
+
+        // What get_session_output returns for a run with a session file
+        let output = read_redacted_session(projects.path(), "abc", &env)
+            .await
+            .unwrap();
+        assert!(!output.contains("npm_s3cret"));
+        assert!(output.contains(r#""result":"[REDACTED]""#));
+        assert!(output.contains("echo $NPM_TOKEN"));
+        assert!(read_redacted_session(projects.path(), "missing", &env)
+            .await
+            .is_err());
+    }
 
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
-                    messages.push(json);
-                }
-            }
+    #[test]
+    fn test_read_only_agent_cannot_write() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE app_settings (key TEXT PRIMARY KEY, value TEXT NOT NULL)",
+            [],
+        )
+        .unwrap();
+
+        // Even an agent that asks to bypass permissions and pre-approves writes
+        let read_only = agent(
+            true,
+            false,
+            true,
+            Some(r#"{"mode":"bypassPermissions","allowed_tools":["Write","Bash(rm:*)","Read"]}"#),
+        );
+        let settings = agent_permissions(&conn, &read_only, "/tmp/project").unwrap();
+        let args = settings.cli_args(false).unwrap();
+        let denied = disallowed(&args);
+        for tool in ["Write", "Edit", "MultiEdit", "NotebookEdit", "Bash", "Task"] {
+            assert!(
+                denied.contains(&tool),
+                "{} should be disallowed in {:?}",
+                tool,
+                args
+            );
         }
-
-        Ok(messages)
-    } else {
-        Err(format!("Session file not found: {}", session_id))
+        assert!(!denied.contains(&"Read"));
+        assert_eq!(settings.allowed_tools, vec!["Read"]);
+
+        // Without network access Bash is denied too, since any command could reach it
+        let offline = agent_permissions(&conn, &agent(true, true, false, None), "/tmp/project")
+            .unwrap()
+            .cli_args(false)
+            .unwrap();
+        let denied = disallowed(&offline);
+        assert!(denied.contains(&"WebFetch"));
+        assert!(denied.contains(&"Bash"));
+        assert!(!denied.contains(&"Write"));
     }
 }
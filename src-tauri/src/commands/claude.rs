@@ -3,28 +3,26 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
-use std::process::Stdio;
 use std::sync::Arc;
 use std::time::SystemTime;
-use tauri::{AppHandle, Emitter, Manager};
-use tokio::process::{Child, Command};
-use tokio::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tokio::sync::oneshot;
 use tauri_plugin_shell::ShellExt;
-use tauri_plugin_shell::process::CommandEvent;
 use regex;
-
-/// Global state to track current Claude process
-pub struct ClaudeProcessState {
-    pub current_process: Arc<Mutex<Option<Child>>>,
-}
-
-impl Default for ClaudeProcessState {
-    fn default() -> Self {
-        Self {
-            current_process: Arc::new(Mutex::new(None)),
-        }
-    }
-}
+use crate::commands::agents::AgentDb;
+use crate::commands::env_vars::run_env;
+use crate::commands::permissions::load_project_permissions;
+use crate::process::events::{
+    line_events, SessionEvent, SessionEventBus, SessionEventBusState, SessionEventsPage,
+    SessionState, UsageTotals,
+};
+use crate::process::input::prompt_args;
+use crate::process::limits::LimitViolation;
+use crate::process::runner::{ClaudeRunner, RunKind, RunObserver, RunRequest};
+use crate::process::{ProcessRegistryState, ResourceLimits, RunEnv, SessionInput};
+
+/// How long to wait for Claude to report a session ID after spawning
+const SESSION_START_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
 
 /// Represents a project in the ~/.claude/projects directory
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -129,6 +127,15 @@ pub struct FileEntry {
     pub extension: Option<String>,
 }
 
+/// A session's transcript and how far its live events had got when it was read
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionHistory {
+    pub messages: Vec<serde_json::Value>,
+    /// Sequence number of the session's latest event before the transcript was read; a client
+    /// following the live session only needs the events after it
+    pub last_seq: u64,
+}
+
 /// Finds the full path to the claude binary
 /// This is necessary because macOS apps have a limited PATH environment
 fn find_claude_binary(app_handle: &AppHandle) -> Result<String, String> {
@@ -222,95 +229,6 @@ fn extract_first_user_message(jsonl_path: &PathBuf) -> (Option<String>, Option<S
     (None, None)
 }
 
-/// Helper function to create a tokio Command with proper environment variables
-/// This ensures commands like Claude can find Node.js and other dependencies
-fn create_command_with_env(program: &str) -> Command {
-    // Convert std::process::Command to tokio::process::Command
-    let _std_cmd = crate::claude_binary::create_command_with_env(program);
-
-    // Create a new tokio Command from the program path
-    let mut tokio_cmd = Command::new(program);
-
-    // Copy over all environment variables
-    for (key, value) in std::env::vars() {
-        if key == "PATH"
-            || key == "HOME"
-            || key == "USER"
-            || key == "SHELL"
-            || key == "LANG"
-            || key == "LC_ALL"
-            || key.starts_with("LC_")
-            || key == "NODE_PATH"
-            || key == "NVM_DIR"
-            || key == "NVM_BIN"
-            || key == "HOMEBREW_PREFIX"
-            || key == "HOMEBREW_CELLAR"
-        {
-            log::debug!("Inheriting env var: {}={}", key, value);
-            tokio_cmd.env(&key, &value);
-        }
-    }
-
-    // Add NVM support if the program is in an NVM directory
-    if program.contains("/.nvm/versions/node/") {
-        if let Some(node_bin_dir) = std::path::Path::new(program).parent() {
-            let current_path = std::env::var("PATH").unwrap_or_default();
-            let node_bin_str = node_bin_dir.to_string_lossy();
-            if !current_path.contains(&node_bin_str.as_ref()) {
-                let new_path = format!("{}:{}", node_bin_str, current_path);
-                tokio_cmd.env("PATH", new_path);
-            }
-        }
-    }
-
-    tokio_cmd
-}
-
-/// Determines whether to use sidecar or system binary execution
-fn should_use_sidecar(claude_path: &str) -> bool {
-    claude_path == "claude-code"
-}
-
-/// Creates a sidecar command with the given arguments
-fn create_sidecar_command(
-    app: &AppHandle,
-    args: Vec<String>,
-    project_path: &str,
-) -> Result<tauri_plugin_shell::process::Command, String> {
-    let mut sidecar_cmd = app
-        .shell()
-        .sidecar("claude-code")
-        .map_err(|e| format!("Failed to create sidecar command: {}", e))?;
-    
-    // Add all arguments
-    sidecar_cmd = sidecar_cmd.args(args);
-    
-    // Set working directory
-    sidecar_cmd = sidecar_cmd.current_dir(project_path);
-    
-    Ok(sidecar_cmd)
-}
-
-/// Creates a system binary command with the given arguments
-fn create_system_command(
-    claude_path: &str,
-    args: Vec<String>,
-    project_path: &str,
-) -> Command {
-    let mut cmd = create_command_with_env(claude_path);
-    
-    // Add all arguments
-    for arg in args {
-        cmd.arg(arg);
-    }
-    
-    cmd.current_dir(project_path)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
-    
-    cmd
-}
-
 /// Lists all projects in the ~/.claude/projects directory
 #[tauri::command]
 pub async fn list_projects() -> Result<Vec<Project>, String> {
@@ -883,12 +801,22 @@ pub async fn save_claude_md_file(file_path: String, content: String) -> Result<S
     Ok("File saved successfully".to_string())
 }
 
-/// Loads the JSONL history for a specific session
+/// Parses a session's JSONL with secret values masked, skipping lines that aren't JSON
+fn redacted_history(content: &str, env: &RunEnv) -> Vec<serde_json::Value> {
+    env.redact(content)
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Loads the JSONL history for a specific session, with the project's secret values masked
 #[tauri::command]
 pub async fn load_session_history(
+    app: AppHandle,
+    bus: tauri::State<'_, SessionEventBusState>,
     session_id: String,
     project_id: String,
-) -> Result<Vec<serde_json::Value>, String> {
+) -> Result<SessionHistory, String> {
     log::info!(
         "Loading session history for session: {} in project: {}",
         session_id,
@@ -905,97 +833,88 @@ pub async fn load_session_history(
         return Err(format!("Session file not found: {}", session_id));
     }
 
-    let file =
-        fs::File::open(&session_path).map_err(|e| format!("Failed to open session file: {}", e))?;
-
-    let reader = BufReader::new(file);
-    let mut messages = Vec::new();
+    // Taken first, so events written to the transcript while it is read are replayed rather
+    // than missed
+    let last_seq = bus.0.last_seq(&session_id);
+    let content =
+        fs::read(&session_path).map_err(|e| format!("Failed to open session file: {}", e))?;
 
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
-                messages.push(json);
-            }
-        }
-    }
+    let project_dir = claude_dir.join("projects").join(&project_id);
+    let project_path = get_project_path_from_sessions(&project_dir)
+        .unwrap_or_else(|_| decode_project_path(&project_id));
+    let env = run_env(&app, None, &project_path)?;
+    let messages = redacted_history(&String::from_utf8_lossy(&content), &env);
 
-    Ok(messages)
+    Ok(SessionHistory { messages, last_seq })
 }
 
 
 
 /// Execute a new interactive Claude Code session with streaming output
+///
+/// Returns the Claude session ID; output follows on `claude-output:{session_id}`. With
+/// `interactive`, stdin stays open for `send_session_input`.
 #[tauri::command]
 pub async fn execute_claude_code(
     app: AppHandle,
     project_path: String,
     prompt: String,
     model: String,
-) -> Result<(), String> {
+    resource_limits: Option<ResourceLimits>,
+    interactive: Option<bool>,
+) -> Result<String, String> {
     log::info!(
         "Starting new Claude Code session in: {} with model: {}",
         project_path,
         model
     );
 
-    let claude_path = find_claude_binary(&app)?;
-    
-    let args = vec![
-        "-p".to_string(),
-        prompt.clone(),
-        "--model".to_string(),
-        model.clone(),
-        "--output-format".to_string(),
-        "stream-json".to_string(),
-        "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
-    ];
-
-    if should_use_sidecar(&claude_path) {
-        spawn_claude_sidecar(app, args, prompt, model, project_path).await
-    } else {
-        let cmd = create_system_command(&claude_path, args, &project_path);
-        spawn_claude_process(app, cmd, prompt, model, project_path).await
-    }
+    launch_session(
+        app,
+        Vec::new(),
+        project_path,
+        prompt,
+        model,
+        resource_limits,
+        interactive.unwrap_or(false),
+    )
+    .await
 }
 
 /// Continue an existing Claude Code conversation with streaming output
+///
+/// Returns the Claude session ID; output follows on `claude-output:{session_id}`.
 #[tauri::command]
 pub async fn continue_claude_code(
     app: AppHandle,
     project_path: String,
     prompt: String,
     model: String,
-) -> Result<(), String> {
+    resource_limits: Option<ResourceLimits>,
+    interactive: Option<bool>,
+) -> Result<String, String> {
     log::info!(
         "Continuing Claude Code conversation in: {} with model: {}",
         project_path,
         model
     );
 
-    let claude_path = find_claude_binary(&app)?;
-    
-    let args = vec![
-        "-c".to_string(), // Continue flag
-        "-p".to_string(),
-        prompt.clone(),
-        "--model".to_string(),
-        model.clone(),
-        "--output-format".to_string(),
-        "stream-json".to_string(),
-        "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
-    ];
-
-    if should_use_sidecar(&claude_path) {
-        spawn_claude_sidecar(app, args, prompt, model, project_path).await
-    } else {
-        let cmd = create_system_command(&claude_path, args, &project_path);
-        spawn_claude_process(app, cmd, prompt, model, project_path).await
-    }
+    let leading_args = vec!["-c".to_string()]; // Continue flag
+    launch_session(
+        app,
+        leading_args,
+        project_path,
+        prompt,
+        model,
+        resource_limits,
+        interactive.unwrap_or(false),
+    )
+    .await
 }
 
 /// Resume an existing Claude Code session by ID with streaming output
+///
+/// Returns the Claude session ID; output follows on `claude-output:{session_id}`.
 #[tauri::command]
 pub async fn resume_claude_code(
     app: AppHandle,
@@ -1003,7 +922,9 @@ pub async fn resume_claude_code(
     session_id: String,
     prompt: String,
     model: String,
-) -> Result<(), String> {
+    resource_limits: Option<ResourceLimits>,
+    interactive: Option<bool>,
+) -> Result<String, String> {
     log::info!(
         "Resuming Claude Code session: {} in: {} with model: {}",
         session_id,
@@ -1011,157 +932,132 @@ pub async fn resume_claude_code(
         model
     );
 
-    let claude_path = find_claude_binary(&app)?;
-    
-    let args = vec![
-        "--resume".to_string(),
-        session_id.clone(),
-        "-p".to_string(),
-        prompt.clone(),
+    let leading_args = vec!["--resume".to_string(), session_id];
+    launch_session(
+        app,
+        leading_args,
+        project_path,
+        prompt,
+        model,
+        resource_limits,
+        interactive.unwrap_or(false),
+    )
+    .await
+}
+
+/// Builds the arguments shared by all session commands and spawns Claude
+async fn launch_session(
+    app: AppHandle,
+    leading_args: Vec<String>,
+    project_path: String,
+    prompt: String,
+    model: String,
+    resource_limits: Option<ResourceLimits>,
+    interactive: bool,
+) -> Result<String, String> {
+    let runner = ClaudeRunner::for_app(&app)?;
+    let permissions = {
+        let db = app.state::<AgentDb>();
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        load_project_permissions(&conn, &project_path)?
+    };
+
+    let mut args = leading_args;
+    args.extend(prompt_args(&prompt, interactive));
+    args.extend(permissions.cli_args(interactive)?);
+    args.extend([
         "--model".to_string(),
         model.clone(),
         "--output-format".to_string(),
         "stream-json".to_string(),
         "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
-    ];
+    ]);
 
-    if should_use_sidecar(&claude_path) {
-        spawn_claude_sidecar(app, args, prompt, model, project_path).await
-    } else {
-        let cmd = create_system_command(&claude_path, args, &project_path);
-        spawn_claude_process(app, cmd, prompt, model, project_path).await
-    }
+    let env = run_env(&app, None, &project_path)?;
+
+    let bus = app.state::<SessionEventBusState>().0.clone();
+    let (tracker, started_rx) = SessionTracker::new(bus);
+    let request = RunRequest {
+        kind: RunKind::Session,
+        args,
+        project_path,
+        task: prompt,
+        model,
+        resource_limits,
+        interactive,
+        startup_timeout: SESSION_START_TIMEOUT,
+        env,
+    };
+    runner.spawn(request, tracker).await?;
+
+    started_rx
+        .await
+        .unwrap_or_else(|_| Err("Claude exited before starting a session".to_string()))
 }
 
-/// Cancel the currently running Claude Code execution
+/// Cancel a running Claude Code session
 #[tauri::command]
 pub async fn cancel_claude_execution(
-    app: AppHandle,
-    session_id: Option<String>,
+    registry: tauri::State<'_, ProcessRegistryState>,
+    bus: tauri::State<'_, SessionEventBusState>,
+    session_id: String,
 ) -> Result<(), String> {
     log::info!(
-        "Cancelling Claude Code execution for session: {:?}",
+        "Cancelling Claude Code execution for session: {}",
         session_id
     );
 
-    let mut killed = false;
-    let mut attempted_methods = Vec::new();
-
-    // Method 1: Try to find and kill via ProcessRegistry using session ID
-    if let Some(sid) = &session_id {
-        let registry = app.state::<crate::process::ProcessRegistryState>();
-        match registry.0.get_claude_session_by_id(sid) {
-            Ok(Some(process_info)) => {
-                log::info!("Found process in registry for session {}: run_id={}, PID={}", 
-                    sid, process_info.run_id, process_info.pid);
-                match registry.0.kill_process(process_info.run_id).await {
-                    Ok(success) => {
-                        if success {
-                            log::info!("Successfully killed process via registry");
-                            killed = true;
-                        } else {
-                            log::warn!("Registry kill returned false");
-                        }
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to kill via registry: {}", e);
-                    }
-                }
-                attempted_methods.push("registry");
-            }
-            Ok(None) => {
-                log::warn!("Session {} not found in ProcessRegistry", sid);
-            }
-            Err(e) => {
-                log::error!("Error querying ProcessRegistry: {}", e);
-            }
-        }
-    }
+    // Report the cancellation first so the exit that follows isn't reported as a failure
+    bus.0.publish(
+        &session_id,
+        SessionEvent::StateChanged {
+            state: SessionState::Cancelled,
+            reason: None,
+        },
+    );
 
-    // Method 2: Try the legacy approach via ClaudeProcessState
-    if !killed {
-        let claude_state = app.state::<ClaudeProcessState>();
-        let mut current_process = claude_state.current_process.lock().await;
-
-        if let Some(mut child) = current_process.take() {
-            // Try to get the PID before killing
-            let pid = child.id();
-            log::info!("Attempting to kill Claude process via ClaudeProcessState with PID: {:?}", pid);
-
-            // Kill the process
-            match child.kill().await {
-                Ok(_) => {
-                    log::info!("Successfully killed Claude process via ClaudeProcessState");
-                    killed = true;
+    match registry.0.get_claude_session_by_id(&session_id)? {
+        Some(process_info) => {
+            log::info!(
+                "Found process in registry for session {}: run_id={}, PID={}",
+                session_id,
+                process_info.run_id,
+                process_info.pid
+            );
+            match registry.0.kill_process(process_info.run_id).await {
+                Ok(Some(report)) => {
+                    log::info!(
+                        "Cancelled session {}: {}",
+                        session_id,
+                        report.reason.description()
+                    );
                 }
-                Err(e) => {
-                    log::error!("Failed to kill Claude process via ClaudeProcessState: {}", e);
-                    
-                    // Method 3: If we have a PID, try system kill as last resort
-                    if let Some(pid) = pid {
-                        log::info!("Attempting system kill as last resort for PID: {}", pid);
-                        let kill_result = if cfg!(target_os = "windows") {
-                            std::process::Command::new("taskkill")
-                                .args(["/F", "/PID", &pid.to_string()])
-                                .output()
-                        } else {
-                            std::process::Command::new("kill")
-                                .args(["-KILL", &pid.to_string()])
-                                .output()
-                        };
-                        
-                        match kill_result {
-                            Ok(output) if output.status.success() => {
-                                log::info!("Successfully killed process via system command");
-                                killed = true;
-                            }
-                            Ok(output) => {
-                                let stderr = String::from_utf8_lossy(&output.stderr);
-                                log::error!("System kill failed: {}", stderr);
-                            }
-                            Err(e) => {
-                                log::error!("Failed to execute system kill command: {}", e);
-                            }
-                        }
-                    }
+                Ok(None) => {
+                    log::warn!("Session {} exited before it could be cancelled", session_id)
                 }
+                Err(e) => log::warn!("Failed to cancel session {}: {}", session_id, e),
             }
-            attempted_methods.push("claude_state");
-        } else {
-            log::warn!("No active Claude process in ClaudeProcessState");
         }
+        None => log::warn!("Session {} not found in ProcessRegistry", session_id),
     }
 
-    if !killed && attempted_methods.is_empty() {
-        log::warn!("No active Claude process found to cancel");
-    }
-
-    // Always emit cancellation events for UI consistency
-    if let Some(sid) = session_id {
-        let _ = app.emit(&format!("claude-cancelled:{}", sid), true);
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-        let _ = app.emit(&format!("claude-complete:{}", sid), false);
-    }
-    
-    // Also emit generic events for backward compatibility
-    let _ = app.emit("claude-cancelled", true);
-    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-    let _ = app.emit("claude-complete", false);
-    
-    if killed {
-        log::info!("Claude process cancellation completed successfully");
-    } else if !attempted_methods.is_empty() {
-        log::warn!("Claude process cancellation attempted but process may have already exited. Attempted methods: {:?}", attempted_methods);
-    }
-    
     Ok(())
 }
 
+/// Returns a session's buffered events after `after_seq`, for clients that detected a gap
+#[tauri::command]
+pub async fn get_session_events(
+    bus: tauri::State<'_, SessionEventBusState>,
+    session_id: String,
+    after_seq: u64,
+) -> Result<SessionEventsPage, String> {
+    Ok(bus.0.events_since(&session_id, after_seq))
+}
+
 /// Get all running Claude sessions
 #[tauri::command]
 pub async fn list_running_claude_sessions(
-    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    registry: tauri::State<'_, ProcessRegistryState>,
 ) -> Result<Vec<crate::process::ProcessInfo>, String> {
     registry.0.get_running_claude_sessions()
 }
@@ -1169,7 +1065,7 @@ pub async fn list_running_claude_sessions(
 /// Get live output from a Claude session
 #[tauri::command]
 pub async fn get_claude_session_output(
-    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    registry: tauri::State<'_, ProcessRegistryState>,
     session_id: String,
 ) -> Result<String, String> {
     // Find the process by session ID
@@ -1180,311 +1076,155 @@ pub async fn get_claude_session_output(
     }
 }
 
-/// Helper function to spawn Claude process and handle streaming
-async fn spawn_claude_process(app: AppHandle, mut cmd: Command, prompt: String, model: String, project_path: String) -> Result<(), String> {
-    use tokio::io::{AsyncBufReadExt, BufReader};
-    use std::sync::Mutex;
-
-    // Spawn the process
-    let mut child = cmd
-        .spawn()
-        .map_err(|e| format!("Failed to spawn Claude: {}", e))?;
-
-    // Get stdout and stderr
-    let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
-    let stderr = child.stderr.take().ok_or("Failed to get stderr")?;
+/// Send a follow-up message, an interrupt, a permission decision, or end-of-input to a session
+/// started in interactive mode
+///
+/// Interactive sessions are addressed by `session_id`, agent runs by `run_id`.
+#[tauri::command]
+pub async fn send_session_input(
+    registry: tauri::State<'_, ProcessRegistryState>,
+    session_id: Option<String>,
+    run_id: Option<i64>,
+    input: SessionInput,
+) -> Result<(), String> {
+    let run_id = match (session_id, run_id) {
+        (Some(session_id), _) => registry
+            .0
+            .get_claude_session_by_id(&session_id)?
+            .map(|info| info.run_id)
+            .ok_or_else(|| format!("Session {} is not running", session_id))?,
+        (None, Some(run_id)) => run_id,
+        (None, None) => return Err("A session ID or run ID is required".to_string()),
+    };
 
-    // Get the child PID for logging
-    let pid = child.id().unwrap_or(0);
-    log::info!(
-        "Spawned Claude process with PID: {:?}",
-        pid
-    );
+    log::info!("Sending {:?} to process {}", input, run_id);
+    registry.0.send_input(run_id, &input).await
+}
 
-    // Create readers first (before moving child)
-    let stdout_reader = BufReader::new(stdout);
-    let stderr_reader = BufReader::new(stderr);
+/// Publishes one interactive session's output as typed events on the session event bus
+///
+/// Events are keyed by session ID, so nothing is published before Claude reports it; the
+/// launching command resolves with that ID.
+struct SessionTracker {
+    bus: Arc<SessionEventBus>,
+    session_id: Option<String>,
+    started_tx: Option<oneshot::Sender<Result<String, String>>>,
+    /// Stderr seen before the session started, reported if it never does
+    early_stderr: Vec<String>,
+    usage: UsageTotals,
+}
 
-    // We'll extract the session ID from Claude's init message
-    let session_id_holder: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
-    let run_id_holder: Arc<Mutex<Option<i64>>> = Arc::new(Mutex::new(None));
+impl SessionTracker {
+    fn new(bus: Arc<SessionEventBus>) -> (Self, oneshot::Receiver<Result<String, String>>) {
+        let (started_tx, started_rx) = oneshot::channel();
+        let tracker = Self {
+            bus,
+            session_id: None,
+            started_tx: Some(started_tx),
+            early_stderr: Vec::new(),
+            usage: UsageTotals::default(),
+        };
+        (tracker, started_rx)
+    }
 
-    // Store the child process in the global state (for backward compatibility)
-    let claude_state = app.state::<ClaudeProcessState>();
-    {
-        let mut current_process = claude_state.current_process.lock().await;
-        // If there's already a process running, kill it first
-        if let Some(mut existing_child) = current_process.take() {
-            log::warn!("Killing existing Claude process before starting new one");
-            let _ = existing_child.kill().await;
+    fn report_start(&mut self, result: Result<String, String>) {
+        if let Some(tx) = self.started_tx.take() {
+            let _ = tx.send(result);
         }
-        *current_process = Some(child);
     }
 
-    // Spawn tasks to read stdout and stderr
-    let app_handle = app.clone();
-    let session_id_holder_clone = session_id_holder.clone();
-    let run_id_holder_clone = run_id_holder.clone();
-    let registry = app.state::<crate::process::ProcessRegistryState>();
-    let registry_clone = registry.0.clone();
-    let project_path_clone = project_path.clone();
-    let prompt_clone = prompt.clone();
-    let model_clone = model.clone();
-    let stdout_task = tokio::spawn(async move {
-        let mut lines = stdout_reader.lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            log::debug!("Claude stdout: {}", line);
-            
-            // Parse the line to check for init message with session ID
-            if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&line) {
-                if msg["type"] == "system" && msg["subtype"] == "init" {
-                    if let Some(claude_session_id) = msg["session_id"].as_str() {
-                        let mut session_id_guard = session_id_holder_clone.lock().unwrap();
-                        if session_id_guard.is_none() {
-                            *session_id_guard = Some(claude_session_id.to_string());
-                            log::info!("Extracted Claude session ID: {}", claude_session_id);
-                            
-                            // Now register with ProcessRegistry using Claude's session ID
-                            match registry_clone.register_claude_session(
-                                claude_session_id.to_string(),
-                                pid,
-                                project_path_clone.clone(),
-                                prompt_clone.clone(),
-                                model_clone.clone(),
-                            ) {
-                                Ok(run_id) => {
-                                    log::info!("Registered Claude session with run_id: {}", run_id);
-                                    let mut run_id_guard = run_id_holder_clone.lock().unwrap();
-                                    *run_id_guard = Some(run_id);
-                                }
-                                Err(e) => {
-                                    log::error!("Failed to register Claude session: {}", e);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            
-            // Store live output in registry if we have a run_id
-            if let Some(run_id) = *run_id_holder_clone.lock().unwrap() {
-                let _ = registry_clone.append_live_output(run_id, &line);
-            }
-            
-            // Emit the line to the frontend with session isolation if we have session ID
-            if let Some(ref session_id) = *session_id_holder_clone.lock().unwrap() {
-                let _ = app_handle.emit(&format!("claude-output:{}", session_id), &line);
-            }
-            // Also emit to the generic event for backward compatibility
-            let _ = app_handle.emit("claude-output", &line);
+    fn publish(&self, event: SessionEvent) {
+        if let Some(session_id) = &self.session_id {
+            self.bus.publish(session_id, event);
         }
-    });
+    }
+}
 
-    let app_handle_stderr = app.clone();
-    let session_id_holder_clone2 = session_id_holder.clone();
-    let stderr_task = tokio::spawn(async move {
-        let mut lines = stderr_reader.lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            log::error!("Claude stderr: {}", line);
-            // Emit error lines to the frontend with session isolation if we have session ID
-            if let Some(ref session_id) = *session_id_holder_clone2.lock().unwrap() {
-                let _ = app_handle_stderr.emit(&format!("claude-error:{}", session_id), &line);
-            }
-            // Also emit to the generic event for backward compatibility
-            let _ = app_handle_stderr.emit("claude-error", &line);
-        }
-    });
+impl RunObserver for SessionTracker {
+    fn on_session_started(&mut self, run_id: i64, session_id: &str) {
+        log::info!(
+            "Registered Claude session {} with run_id: {}",
+            session_id,
+            run_id
+        );
+        self.session_id = Some(session_id.to_string());
+        self.publish(SessionEvent::StateChanged {
+            state: SessionState::Running,
+            reason: None,
+        });
+        self.report_start(Ok(session_id.to_string()));
+    }
 
-    // Wait for the process to complete
-    let app_handle_wait = app.clone();
-    let claude_state_wait = claude_state.current_process.clone();
-    let session_id_holder_clone3 = session_id_holder.clone();
-    let run_id_holder_clone2 = run_id_holder.clone();
-    let registry_clone2 = registry.0.clone();
-    tokio::spawn(async move {
-        let _ = stdout_task.await;
-        let _ = stderr_task.await;
-
-        // Get the child from the state to wait on it
-        let mut current_process = claude_state_wait.lock().await;
-        if let Some(mut child) = current_process.take() {
-            match child.wait().await {
-                Ok(status) => {
-                    log::info!("Claude process exited with status: {}", status);
-                    // Add a small delay to ensure all messages are processed
-                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                    if let Some(ref session_id) = *session_id_holder_clone3.lock().unwrap() {
-                        let _ = app_handle_wait.emit(
-                            &format!("claude-complete:{}", session_id),
-                            status.success(),
-                        );
-                    }
-                    // Also emit to the generic event for backward compatibility
-                    let _ = app_handle_wait.emit("claude-complete", status.success());
-                }
-                Err(e) => {
-                    log::error!("Failed to wait for Claude process: {}", e);
-                    // Add a small delay to ensure all messages are processed
-                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                    if let Some(ref session_id) = *session_id_holder_clone3.lock().unwrap() {
-                        let _ = app_handle_wait
-                            .emit(&format!("claude-complete:{}", session_id), false);
-                    }
-                    // Also emit to the generic event for backward compatibility
-                    let _ = app_handle_wait.emit("claude-complete", false);
-                }
+    fn on_stdout(&mut self, line: &str) {
+        if self.session_id.is_none() {
+            log::warn!(
+                "Dropping Claude output received before session init: {}",
+                line
+            );
+            return;
+        }
+        let (events, parsed) = line_events(line);
+        if events.is_empty() {
+            log::debug!("Ignoring non-JSON Claude output: {}", line);
+        }
+        for event in events {
+            self.publish(event);
+        }
+        if let Some(parsed) = parsed {
+            if self.usage.update(&parsed.event) {
+                self.publish(self.usage.event());
             }
         }
+    }
 
-        // Unregister from ProcessRegistry if we have a run_id
-        if let Some(run_id) = *run_id_holder_clone2.lock().unwrap() {
-            let _ = registry_clone2.unregister_process(run_id);
+    fn on_stderr(&mut self, line: &str) {
+        match &self.session_id {
+            Some(_) => self.publish(SessionEvent::Error {
+                message: line.to_string(),
+            }),
+            None => self.early_stderr.push(line.to_string()),
         }
+    }
 
-        // Clear the process from state
-        *current_process = None;
-    });
-
-    Ok(())
-}
+    fn on_limit_exceeded(&mut self, violation: LimitViolation, limits: &ResourceLimits) {
+        let message = format!("Session stopped: {}", violation.describe(limits));
+        self.publish(SessionEvent::Error {
+            message: message.clone(),
+        });
+        self.publish(SessionEvent::StateChanged {
+            state: SessionState::Failed,
+            reason: Some(message),
+        });
+    }
 
-/// Helper function to spawn Claude sidecar process and handle streaming
-async fn spawn_claude_sidecar(
-    app: AppHandle,
-    args: Vec<String>,
-    prompt: String,
-    model: String,
-    project_path: String,
-) -> Result<(), String> {
-    use std::sync::Mutex;
+    fn on_startup_timeout(&mut self, _kill_reason: &str) {
+        self.report_start(Err(
+            "Timed out waiting for Claude to start a session".to_string()
+        ));
+    }
 
-    // Create the sidecar command
-    let sidecar_cmd = create_sidecar_command(&app, args, &project_path)?;
-    
-    // Spawn the sidecar process
-    let (mut rx, child) = sidecar_cmd
-        .spawn()
-        .map_err(|e| format!("Failed to spawn Claude sidecar: {}", e))?;
-
-    // Get the child PID for logging
-    let pid = child.pid();
-    log::info!("Spawned Claude sidecar process with PID: {:?}", pid);
-
-    // We'll extract the session ID from Claude's init message
-    let session_id_holder: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
-    let run_id_holder: Arc<Mutex<Option<i64>>> = Arc::new(Mutex::new(None));
-
-    // Register with ProcessRegistry
-    let registry = app.state::<crate::process::ProcessRegistryState>();
-    let registry_clone = registry.0.clone();
-    let project_path_clone = project_path.clone();
-    let prompt_clone = prompt.clone();
-    let model_clone = model.clone();
-
-    // Spawn task to read events from sidecar
-    let app_handle = app.clone();
-    let session_id_holder_clone = session_id_holder.clone();
-    let run_id_holder_clone = run_id_holder.clone();
-    
-    tauri::async_runtime::spawn(async move {
-        while let Some(event) = rx.recv().await {
-            match event {
-                CommandEvent::Stdout(line_bytes) => {
-                    let line = String::from_utf8_lossy(&line_bytes);
-                    let line_str = line.trim_end_matches('\n').trim_end_matches('\r');
-                    
-                    if !line_str.is_empty() {
-                        log::debug!("Claude sidecar stdout: {}", line_str);
-                        
-                        // Parse the line to check for init message with session ID
-                        if let Ok(msg) = serde_json::from_str::<serde_json::Value>(line_str) {
-                            if msg["type"] == "system" && msg["subtype"] == "init" {
-                                if let Some(claude_session_id) = msg["session_id"].as_str() {
-                                    let mut session_id_guard = session_id_holder_clone.lock().unwrap();
-                                    if session_id_guard.is_none() {
-                                        *session_id_guard = Some(claude_session_id.to_string());
-                                        log::info!("Extracted Claude session ID: {}", claude_session_id);
-                                        
-                                        // Register with ProcessRegistry using Claude's session ID
-                                        match registry_clone.register_claude_session(
-                                            claude_session_id.to_string(),
-                                            pid,
-                                            project_path_clone.clone(),
-                                            prompt_clone.clone(),
-                                            model_clone.clone(),
-                                        ) {
-                                            Ok(run_id) => {
-                                                log::info!("Registered Claude sidecar session with run_id: {}", run_id);
-                                                let mut run_id_guard = run_id_holder_clone.lock().unwrap();
-                                                *run_id_guard = Some(run_id);
-                                            }
-                                            Err(e) => {
-                                                log::error!("Failed to register Claude sidecar session: {}", e);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        
-                        // Store live output in registry if we have a run_id
-                        if let Some(run_id) = *run_id_holder_clone.lock().unwrap() {
-                            let _ = registry_clone.append_live_output(run_id, line_str);
-                        }
-                        
-                        // Emit the line to the frontend with session isolation if we have session ID
-                        if let Some(ref session_id) = *session_id_holder_clone.lock().unwrap() {
-                            let _ = app_handle.emit(&format!("claude-output:{}", session_id), line_str);
-                        }
-                        // Also emit to the generic event for backward compatibility
-                        let _ = app_handle.emit("claude-output", line_str);
-                    }
-                }
-                CommandEvent::Stderr(line_bytes) => {
-                    let line = String::from_utf8_lossy(&line_bytes);
-                    let line_str = line.trim_end_matches('\n').trim_end_matches('\r');
-                    
-                    if !line_str.is_empty() {
-                        log::error!("Claude sidecar stderr: {}", line_str);
-                        
-                        // Emit error lines to the frontend with session isolation if we have session ID
-                        if let Some(ref session_id) = *session_id_holder_clone.lock().unwrap() {
-                            let _ = app_handle.emit(&format!("claude-error:{}", session_id), line_str);
-                        }
-                        // Also emit to the generic event for backward compatibility
-                        let _ = app_handle.emit("claude-error", line_str);
-                    }
-                }
-                CommandEvent::Terminated(payload) => {
-                    log::info!("Claude sidecar process terminated with payload: {:?}", payload);
-                    
-                    // Add a small delay to ensure all messages are processed
-                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                    
-                    let success = payload.code.unwrap_or(-1) == 0;
-                    
-                    if let Some(ref session_id) = *session_id_holder_clone.lock().unwrap() {
-                        let _ = app_handle.emit(&format!("claude-complete:{}", session_id), success);
-                    }
-                    // Also emit to the generic event for backward compatibility
-                    let _ = app_handle.emit("claude-complete", success);
-                    
-                    // Unregister from ProcessRegistry if we have a run_id
-                    if let Some(run_id) = *run_id_holder_clone.lock().unwrap() {
-                        let _ = registry_clone.unregister_process(run_id);
-                    }
-                    
-                    break;
-                }
-                _ => {
-                    // Handle other event types if needed
-                    log::debug!("Claude sidecar event: {:?}", event);
-                }
-            }
+    fn on_exit(&mut self, exit_code: Option<i32>) {
+        if self.session_id.is_some() {
+            self.publish(SessionEvent::StateChanged {
+                state: if exit_code == Some(0) {
+                    SessionState::Completed
+                } else {
+                    SessionState::Failed
+                },
+                reason: None,
+            });
+        } else {
+            let message = if self.early_stderr.is_empty() {
+                "Claude exited before starting a session".to_string()
+            } else {
+                format!(
+                    "Claude exited before starting a session: {}",
+                    self.early_stderr.join("\n")
+                )
+            };
+            self.report_start(Err(message));
         }
-    });
-
-    Ok(())
+    }
 }
 
 /// Lists files and directories in a given path
@@ -1685,6 +1425,7 @@ fn search_files_recursive(
 #[tauri::command]
 pub async fn create_checkpoint(
     app: tauri::State<'_, crate::checkpoint::state::CheckpointState>,
+    bus: tauri::State<'_, SessionEventBusState>,
     session_id: String,
     project_id: String,
     project_path: String,
@@ -1706,39 +1447,25 @@ pub async fn create_checkpoint(
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
-    // Always load current session messages from the JSONL file
-    let session_path = get_claude_dir()
-        .map_err(|e| e.to_string())?
-        .join("projects")
-        .join(&project_id)
-        .join(format!("{}.jsonl", session_id));
-
-    if session_path.exists() {
-        let file = fs::File::open(&session_path)
-            .map_err(|e| format!("Failed to open session file: {}", e))?;
-        let reader = BufReader::new(file);
-
-        let mut line_count = 0;
-        for line in reader.lines() {
-            if let Some(index) = message_index {
-                if line_count > index {
-                    break;
-                }
-            }
-            if let Ok(line) = line {
-                manager
-                    .track_message(line)
-                    .await
-                    .map_err(|e| format!("Failed to track message: {}", e))?;
-            }
-            line_count += 1;
-        }
-    }
-
+    // Pick up any messages appended to the session JSONL file since the last sync
     manager
+        .sync_messages_from_session(message_index)
+        .await
+        .map_err(|e| format!("Failed to track messages: {}", e))?;
+
+    let result = manager
         .create_checkpoint(description, None)
         .await
-        .map_err(|e| format!("Failed to create checkpoint: {}", e))
+        .map_err(|e| format!("Failed to create checkpoint: {}", e))?;
+
+    bus.0.publish(
+        &session_id,
+        SessionEvent::CheckpointCreated {
+            checkpoint_id: result.checkpoint.id.clone(),
+            description: result.checkpoint.description.clone(),
+        },
+    );
+    Ok(result)
 }
 
 /// Restores a session to a specific checkpoint
@@ -1893,6 +1620,9 @@ pub async fn update_checkpoint_settings(
     project_path: String,
     auto_checkpoint_enabled: bool,
     checkpoint_strategy: String,
+    max_file_size: Option<u64>,
+    max_checkpoint_size: Option<u64>,
+    excluded_extensions: Option<Vec<String>>,
 ) -> Result<(), String> {
     use crate::checkpoint::CheckpointStrategy;
 
@@ -1916,8 +1646,28 @@ pub async fn update_checkpoint_settings(
         .await
         .map_err(|e| format!("Failed to get checkpoint manager: {}", e))?;
 
+    // Only touch the limits when the caller provided at least one of them
+    let limits = if max_file_size.is_some()
+        || max_checkpoint_size.is_some()
+        || excluded_extensions.is_some()
+    {
+        let mut limits = manager.get_timeline().await.limits;
+        if let Some(max_file_size) = max_file_size {
+            limits.max_file_size = max_file_size;
+        }
+        if let Some(max_checkpoint_size) = max_checkpoint_size {
+            limits.max_checkpoint_size = max_checkpoint_size;
+        }
+        if let Some(excluded_extensions) = excluded_extensions {
+            limits.excluded_extensions = excluded_extensions;
+        }
+        Some(limits)
+    } else {
+        None
+    };
+
     manager
-        .update_settings(auto_checkpoint_enabled, strategy)
+        .update_settings(auto_checkpoint_enabled, strategy, limits)
         .await
         .map_err(|e| format!("Failed to update settings: {}", e))
 }
@@ -2102,6 +1852,7 @@ pub async fn get_checkpoint_settings(
         "checkpoint_strategy": timeline.checkpoint_strategy,
         "total_checkpoints": timeline.total_checkpoints,
         "current_checkpoint_id": timeline.current_checkpoint_id,
+        "limits": timeline.limits,
     }))
 }
 
@@ -2321,3 +2072,20 @@ pub async fn validate_hook_command(command: String) -> Result<serde_json::Value,
         Err(e) => Err(format!("Failed to validate command: {}", e))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_history_masks_secrets() {
+        let mut env = RunEnv::default();
+        env.set("NPM_TOKEN".to_string(), "npm_s3cret".to_string(), true);
+        let content = "{\"type\":\"user\",\"text\":\"echo $NPM_TOKEN\"}\nnot json\n{\"type\":\"result\",\"result\":\"npm_s3cret\"}\n";
+
+        let history = redacted_history(content, &env);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0]["text"], "echo $NPM_TOKEN");
+        assert_eq!(history[1]["result"], "[REDACTED]");
+    }
+}
@@ -0,0 +1,323 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use regex::Regex;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use tauri::{AppHandle, Manager, State};
+
+use super::agents::AgentDb;
+use crate::process::env::RunEnv;
+
+/// File in the app data directory holding the key secret values are encrypted with
+const KEY_FILE: &str = "secrets.key";
+
+/// Length of the nonce stored in front of each encrypted value
+const NONCE_LEN: usize = 12;
+
+/// What a set of environment variables belongs to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EnvScope {
+    /// Set for every run of an agent
+    Agent { agent_id: i64 },
+    /// Set for every agent run and session in a project; agent variables win
+    Project { project_path: String },
+}
+
+impl EnvScope {
+    /// The `scope` and `target` columns of the scope's variables
+    fn columns(&self) -> (&'static str, String) {
+        match self {
+            EnvScope::Agent { agent_id } => ("agent", agent_id.to_string()),
+            EnvScope::Project { project_path } => ("project", project_path.clone()),
+        }
+    }
+}
+
+/// An environment variable as shown to the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvVar {
+    pub id: i64,
+    pub name: String,
+    /// None for secrets, whose values never leave the backend
+    pub value: Option<String>,
+    pub secret: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Encrypts secret values at rest with a key kept outside the database
+pub struct SecretKey(ChaCha20Poly1305);
+
+impl SecretKey {
+    /// Loads the key from `path`, creating it readable only by the user on first use
+    pub fn load_or_create(path: &Path) -> Result<Self, String> {
+        match std::fs::read(path) {
+            Ok(bytes) if bytes.len() == 32 => {
+                Ok(Self(ChaCha20Poly1305::new(Key::from_slice(&bytes))))
+            }
+            Ok(_) => Err(format!("Secret key file {} is corrupt", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+                let mut options = std::fs::OpenOptions::new();
+                options.write(true).create_new(true);
+                #[cfg(unix)]
+                std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+                match options.open(path) {
+                    Ok(mut file) => {
+                        file.write_all(&key)
+                            .map_err(|e| format!("Failed to write secret key: {}", e))?;
+                        Ok(Self(ChaCha20Poly1305::new(&key)))
+                    }
+                    // Created by someone else in the meantime
+                    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                        Self::load_or_create(path)
+                    }
+                    Err(e) => Err(format!("Failed to create secret key: {}", e)),
+                }
+            }
+            Err(e) => Err(format!("Failed to read secret key: {}", e)),
+        }
+    }
+
+    /// The app's key, kept in its data directory
+    pub fn for_app(app: &AppHandle) -> Result<Self, String> {
+        let dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+        Self::load_or_create(&dir.join(KEY_FILE))
+    }
+
+    /// Encrypts a value to the base64 of its nonce and ciphertext
+    pub fn encrypt(&self, value: &str) -> Result<String, String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, value.as_bytes())
+            .map_err(|_| "Failed to encrypt secret".to_string())?;
+        let mut stored = nonce.to_vec();
+        stored.extend(ciphertext);
+        Ok(STANDARD.encode(stored))
+    }
+
+    /// Decrypts a value stored by `encrypt`
+    pub fn decrypt(&self, stored: &str) -> Result<String, String> {
+        let bytes = STANDARD
+            .decode(stored)
+            .map_err(|e| format!("Invalid encrypted secret: {}", e))?;
+        if bytes.len() < NONCE_LEN {
+            return Err("Invalid encrypted secret: too short".to_string());
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let plaintext = self
+            .0
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Failed to decrypt secret; the key file may have changed".to_string())?;
+        String::from_utf8(plaintext).map_err(|e| e.to_string())
+    }
+}
+
+/// Creates the env_vars table
+pub fn init_env_vars(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS env_vars (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            scope TEXT NOT NULL,
+            target TEXT NOT NULL,
+            name TEXT NOT NULL,
+            value TEXT NOT NULL,
+            secret BOOLEAN NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (scope, target, name)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// The variables for a run of an agent in a project; agent variables override project ones
+pub(crate) fn resolve_env(
+    conn: &Connection,
+    key: &SecretKey,
+    agent_id: Option<i64>,
+    project_path: &str,
+) -> Result<RunEnv, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT name, value, secret FROM env_vars
+             WHERE (scope = 'project' AND target = ?1) OR (scope = 'agent' AND target = ?2)
+             ORDER BY scope = 'agent', name",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(
+            params![project_path, agent_id.map(|id| id.to_string())],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, bool>(2)?,
+                ))
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut env = RunEnv::default();
+    for (name, value, secret) in rows {
+        let value = if secret {
+            key.decrypt(&value)
+                .map_err(|e| format!("Failed to read secret {}: {}", name, e))?
+        } else {
+            value
+        };
+        env.set(name, value, secret);
+    }
+    Ok(env)
+}
+
+/// The variables for a run, read with the app's key
+pub(crate) fn run_env(
+    app: &AppHandle,
+    agent_id: Option<i64>,
+    project_path: &str,
+) -> Result<RunEnv, String> {
+    let key = SecretKey::for_app(app)?;
+    let db = app.state::<AgentDb>();
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    resolve_env(&conn, &key, agent_id, project_path)
+}
+
+/// Removes an agent's variables when the agent is deleted
+pub(crate) fn delete_agent_env_vars(conn: &Connection, agent_id: i64) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM env_vars WHERE scope = 'agent' AND target = ?1",
+        params![agent_id.to_string()],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// List the environment variables of an agent or project; secret values are left out
+#[tauri::command]
+pub async fn list_env_vars(db: State<'_, AgentDb>, scope: EnvScope) -> Result<Vec<EnvVar>, String> {
+    let (scope, target) = scope.columns();
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, name, value, secret, created_at, updated_at FROM env_vars
+             WHERE scope = ?1 AND target = ?2 ORDER BY name",
+        )
+        .map_err(|e| e.to_string())?;
+    let vars = stmt
+        .query_map(params![scope, target], |row| {
+            let secret: bool = row.get(3)?;
+            Ok(EnvVar {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                value: if secret { None } else { row.get(2)? },
+                secret,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(vars)
+}
+
+/// Set an environment variable of an agent or project; secret values are stored encrypted
+#[tauri::command]
+pub async fn set_env_var(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    scope: EnvScope,
+    name: String,
+    value: String,
+    secret: bool,
+) -> Result<(), String> {
+    let valid_name = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap();
+    if !valid_name.is_match(&name) {
+        return Err(format!("Invalid environment variable name '{}'", name));
+    }
+    let stored = if secret {
+        SecretKey::for_app(&app)?.encrypt(&value)?
+    } else {
+        value
+    };
+
+    let (scope, target) = scope.columns();
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO env_vars (scope, target, name, value, secret) VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(scope, target, name) DO UPDATE SET value = ?4, secret = ?5, updated_at = CURRENT_TIMESTAMP",
+        params![scope, target, name, stored, secret],
+    )
+    .map_err(|e| format!("Failed to save environment variable: {}", e))?;
+    Ok(())
+}
+
+/// Delete an environment variable
+#[tauri::command]
+pub async fn delete_env_var(db: State<'_, AgentDb>, id: i64) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM env_vars WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secrets_are_encrypted_and_agent_vars_win() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join(KEY_FILE);
+        let key = SecretKey::load_or_create(&key_path).unwrap();
+        let stored = key.encrypt("npm_s3cret").unwrap();
+        assert!(!stored.contains("npm_s3cret"));
+
+        // The same key is read back from the keyfile
+        let key = SecretKey::load_or_create(&key_path).unwrap();
+        assert_eq!(key.decrypt(&stored).unwrap(), "npm_s3cret");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&key_path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let conn = Connection::open_in_memory().unwrap();
+        init_env_vars(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO env_vars (scope, target, name, value, secret) VALUES
+                ('project', '/repo', 'REGISTRY', 'https://npm.example.com', 0),
+                ('project', '/repo', 'NPM_TOKEN', 'project-token', 0),
+                ('agent', '7', 'NPM_TOKEN', ?1, 1),
+                ('agent', '8', 'OTHER', 'x', 0)",
+            params![stored],
+        )
+        .unwrap();
+
+        let env = resolve_env(&conn, &key, Some(7), "/repo").unwrap();
+        assert_eq!(env.vars.len(), 2);
+        assert_eq!(env.vars["NPM_TOKEN"], "npm_s3cret");
+        assert_eq!(env.vars["REGISTRY"], "https://npm.example.com");
+        assert_eq!(env.secrets, vec!["npm_s3cret".to_string()]);
+
+        let env = resolve_env(&conn, &key, None, "/repo").unwrap();
+        assert_eq!(env.vars["NPM_TOKEN"], "project-token");
+        assert!(env.secrets.is_empty());
+
+        let other = SecretKey::load_or_create(&dir.path().join("other.key")).unwrap();
+        assert!(other.decrypt(&stored).is_err());
+    }
+}
@@ -4,3 +4,14 @@ pub mod mcp;
 pub mod usage;
 pub mod storage;
 pub mod slash_commands;
+pub mod queue;
+pub mod permissions;
+pub mod agent_versions;
+pub mod agent_parameters;
+pub mod workflows;
+pub mod schedules;
+pub mod triggers;
+pub mod worktrees;
+pub mod verification;
+pub mod retries;
+pub mod env_vars;
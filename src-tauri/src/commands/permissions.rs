@@ -0,0 +1,55 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::State;
+
+use super::agents::AgentDb;
+use crate::process::PermissionSettings;
+
+/// Key under which a project's permission settings are stored in `app_settings`
+fn settings_key(project_path: &str) -> String {
+    format!("permissions:{}", project_path)
+}
+
+/// Loads a project's permission settings, falling back to defaults
+pub fn load_project_permissions(
+    conn: &Connection,
+    project_path: &str,
+) -> Result<PermissionSettings, String> {
+    let value = conn
+        .query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![settings_key(project_path)],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    Ok(PermissionSettings::from_json(value.as_deref())?.unwrap_or_default())
+}
+
+/// Get the permission settings for a project
+#[tauri::command]
+pub async fn get_project_permissions(
+    db: State<'_, AgentDb>,
+    project_path: String,
+) -> Result<PermissionSettings, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_project_permissions(&conn, &project_path)
+}
+
+/// Update the permission settings for a project
+#[tauri::command]
+pub async fn set_project_permissions(
+    db: State<'_, AgentDb>,
+    project_path: String,
+    settings: PermissionSettings,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let value = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = ?2",
+        params![settings_key(&project_path), value],
+    )
+    .map_err(|e| format!("Failed to save permission settings: {}", e))?;
+    Ok(())
+}
@@ -0,0 +1,377 @@
+use log::{error, info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use super::agents::{start_agent_run, AgentDb, AgentRun};
+use super::worktrees::discard_unstarted_worktree;
+
+/// Key under which queue settings are stored in `app_settings`
+const QUEUE_SETTINGS_KEY: &str = "agent_queue_settings";
+
+/// How often the dispatcher re-checks the queue for free slots
+const DISPATCH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Order in which queued runs are started
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueOrdering {
+    /// Strictly in the order runs were queued (or reordered)
+    Fifo,
+    /// Highest priority first, queue order within the same priority
+    #[default]
+    Priority,
+}
+
+/// Concurrency limits for agent runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueSettings {
+    /// Maximum number of agent runs executing at once across all projects
+    pub max_concurrent_runs: u32,
+    /// Maximum number of agent runs executing at once in the same project
+    pub max_runs_per_project: u32,
+    #[serde(default)]
+    pub ordering: QueueOrdering,
+}
+
+impl Default for QueueSettings {
+    fn default() -> Self {
+        Self {
+            max_concurrent_runs: 4,
+            max_runs_per_project: 1,
+            ordering: QueueOrdering::default(),
+        }
+    }
+}
+
+/// Serializes queue dispatching so a slot is never handed out twice
+#[derive(Default)]
+pub struct RunQueueState(pub tokio::sync::Mutex<()>);
+
+/// A queued run as seen by the dispatcher
+#[derive(Debug, Clone)]
+struct QueuedRun {
+    id: i64,
+    project_path: String,
+    priority: i64,
+    queue_order: i64,
+    /// False while a retry waits out its backoff
+    due: bool,
+}
+
+/// Sorts queued runs into the order they will start
+fn order_queue(runs: &mut [QueuedRun], ordering: QueueOrdering) {
+    match ordering {
+        QueueOrdering::Fifo => runs.sort_by_key(|run| (run.queue_order, run.id)),
+        QueueOrdering::Priority => runs.sort_by_key(|run| (-run.priority, run.queue_order, run.id)),
+    }
+}
+
+/// Picks the next queued run that fits within the concurrency limits
+///
+/// `running` maps project paths to the number of runs currently executing there.
+fn next_runnable(
+    queued: &[QueuedRun],
+    running: &HashMap<String, u32>,
+    settings: &QueueSettings,
+) -> Option<i64> {
+    let total: u32 = running.values().sum();
+    if total >= settings.max_concurrent_runs {
+        return None;
+    }
+
+    let mut ordered = queued.to_vec();
+    order_queue(&mut ordered, settings.ordering);
+
+    // A project at its limit doesn't block runs for other projects
+    ordered
+        .into_iter()
+        .find(|run| {
+            run.due
+                && running.get(&run.project_path).copied().unwrap_or(0)
+                    < settings.max_runs_per_project
+        })
+        .map(|run| run.id)
+}
+
+/// Loads the queue settings, falling back to defaults
+fn load_settings(conn: &Connection) -> Result<QueueSettings, String> {
+    let value = conn
+        .query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![QUEUE_SETTINGS_KEY],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    Ok(value
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default())
+}
+
+/// Loads queued runs and per-project running counts
+///
+/// Paused runs keep their slot so that resuming them never exceeds the limits.
+fn load_queue_state(conn: &Connection) -> Result<(Vec<QueuedRun>, HashMap<String, u32>), String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, project_path, priority, COALESCE(queue_order, id), not_before IS NULL OR not_before <= datetime('now') FROM agent_runs WHERE status = 'queued'",
+        )
+        .map_err(|e| e.to_string())?;
+    let queued = stmt
+        .query_map([], |row| {
+            Ok(QueuedRun {
+                id: row.get(0)?,
+                project_path: row.get(1)?,
+                priority: row.get::<_, Option<i64>>(2)?.unwrap_or(0),
+                queue_order: row.get(3)?,
+                due: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT project_path, COUNT(*) FROM agent_runs WHERE status IN ('running', 'paused') GROUP BY project_path",
+        )
+        .map_err(|e| e.to_string())?;
+    let running = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<HashMap<_, _>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok((queued, running))
+}
+
+/// Starts queued runs while there are free slots
+pub async fn dispatch_queued_runs(app: &AppHandle) {
+    let queue = app.state::<RunQueueState>();
+    let _guard = queue.0.lock().await;
+
+    loop {
+        let next = {
+            let db = app.state::<AgentDb>();
+            let conn = match db.0.lock() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    error!("Failed to lock agents database: {}", e);
+                    return;
+                }
+            };
+            load_settings(&conn).and_then(|settings| {
+                let (queued, running) = load_queue_state(&conn)?;
+                Ok(next_runnable(&queued, &running, &settings))
+            })
+        };
+
+        let run_id = match next {
+            Ok(Some(run_id)) => run_id,
+            Ok(None) => return,
+            Err(e) => {
+                error!("Failed to read run queue: {}", e);
+                return;
+            }
+        };
+
+        info!("Starting queued agent run {}", run_id);
+        if let Err(e) = start_agent_run(app, run_id).await {
+            warn!("Failed to start queued run {}: {}", run_id, e);
+            let recorded = {
+                let db = app.state::<AgentDb>();
+                let conn = db.0.lock().map_err(|e| e.to_string());
+                conn.and_then(|conn| {
+                    conn.execute(
+                        "UPDATE agent_runs SET status = 'failed', status_reason = ?2, completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'queued'",
+                        params![run_id, format!("Failed to start: {}", e)],
+                    )
+                    .map_err(|e| e.to_string())
+                })
+            };
+            // The run would still be picked next; leave it for the next pass
+            if let Err(e) = recorded {
+                error!("Failed to mark queued run {} as failed: {}", run_id, e);
+                return;
+            }
+            let _ = app.emit(&format!("agent-complete:{}", run_id), false);
+        }
+    }
+}
+
+/// Periodically starts queued runs as running ones finish
+pub fn spawn_queue_dispatcher(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            dispatch_queued_runs(&app).await;
+            tokio::time::sleep(DISPATCH_INTERVAL).await;
+        }
+    });
+}
+
+/// List queued agent runs in the order they will start
+#[tauri::command]
+pub async fn list_queued_runs(db: State<'_, AgentDb>) -> Result<Vec<AgentRun>, String> {
+    let ordered = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let settings = load_settings(&conn)?;
+        let (mut queued, _) = load_queue_state(&conn)?;
+        order_queue(&mut queued, settings.ordering);
+        queued
+    };
+
+    let mut runs = Vec::with_capacity(ordered.len());
+    for queued in ordered {
+        runs.push(super::agents::get_agent_run(db.clone(), queued.id).await?);
+    }
+    Ok(runs)
+}
+
+/// Get the agent run queue settings
+#[tauri::command]
+pub async fn get_queue_settings(db: State<'_, AgentDb>) -> Result<QueueSettings, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_settings(&conn)
+}
+
+/// Update the agent run queue settings
+#[tauri::command]
+pub async fn update_queue_settings(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    settings: QueueSettings,
+) -> Result<(), String> {
+    if settings.max_concurrent_runs == 0 || settings.max_runs_per_project == 0 {
+        return Err("Concurrency limits must be at least 1".to_string());
+    }
+
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let value = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = ?2",
+            params![QUEUE_SETTINGS_KEY, value],
+        )
+        .map_err(|e| format!("Failed to save queue settings: {}", e))?;
+    }
+
+    // Raised limits may free up slots right away
+    dispatch_queued_runs(&app).await;
+    Ok(())
+}
+
+/// Change the priority of a queued run
+#[tauri::command]
+pub async fn set_queued_run_priority(
+    db: State<'_, AgentDb>,
+    run_id: i64,
+    priority: i64,
+) -> Result<bool, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let updated = conn
+        .execute(
+            "UPDATE agent_runs SET priority = ?2 WHERE id = ?1 AND status = 'queued'",
+            params![run_id, priority],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(updated > 0)
+}
+
+/// Reorder queued runs; the given runs move to the front of the queue in the given order
+#[tauri::command]
+pub async fn reorder_queued_runs(db: State<'_, AgentDb>, run_ids: Vec<i64>) -> Result<(), String> {
+    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let first: i64 = tx
+        .query_row(
+            "SELECT COALESCE(MIN(queue_order), 0) FROM agent_runs WHERE status = 'queued'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let start = first - run_ids.len() as i64;
+    for (index, run_id) in run_ids.iter().enumerate() {
+        tx.execute(
+            "UPDATE agent_runs SET queue_order = ?2 WHERE id = ?1 AND status = 'queued'",
+            params![run_id, start + index as i64],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Cancel a run that hasn't started yet
+#[tauri::command]
+pub async fn cancel_queued_run(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    run_id: i64,
+) -> Result<bool, String> {
+    let updated = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE agent_runs SET status = 'cancelled', status_reason = 'Cancelled while queued', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'queued'",
+            params![run_id],
+        )
+        .map_err(|e| e.to_string())?
+    };
+
+    if updated > 0 {
+        if let Err(e) = discard_unstarted_worktree(db, run_id).await {
+            warn!(
+                "Failed to discard worktree of cancelled run {}: {}",
+                run_id, e
+            );
+        }
+        let _ = app.emit(&format!("agent-cancelled:{}", run_id), true);
+    }
+    Ok(updated > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(id: i64, project: &str, priority: i64) -> QueuedRun {
+        QueuedRun {
+            id,
+            project_path: project.to_string(),
+            priority,
+            queue_order: id,
+            due: true,
+        }
+    }
+
+    #[test]
+    fn test_next_runnable_respects_limits_and_ordering() {
+        let queue = vec![queued(1, "/a", 0), queued(2, "/b", 0), queued(3, "/b", 5)];
+        let mut settings = QueueSettings::default();
+        let mut running = HashMap::new();
+
+        assert_eq!(next_runnable(&queue, &running, &settings), Some(3));
+
+        settings.ordering = QueueOrdering::Fifo;
+        assert_eq!(next_runnable(&queue, &running, &settings), Some(1));
+
+        // Project /a is busy, so the next run for another project goes first
+        running.insert("/a".to_string(), 1);
+        assert_eq!(next_runnable(&queue, &running, &settings), Some(2));
+
+        // A retry waiting out its backoff is passed over
+        let mut waiting = queue.clone();
+        waiting[1].due = false;
+        assert_eq!(next_runnable(&waiting, &running, &settings), Some(3));
+
+        settings.max_concurrent_runs = 1;
+        assert_eq!(next_runnable(&queue, &running, &settings), None);
+    }
+}
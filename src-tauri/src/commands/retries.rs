@@ -0,0 +1,359 @@
+use log::info;
+use regex::{Regex, RegexBuilder};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Task sent to a retry that continues the failed attempt's Claude session
+pub const RESUME_PROMPT: &str =
+    "The previous attempt was interrupted by an error. Continue the task from where you left off.";
+
+/// Output of API rate limits and overload errors
+const RATE_LIMIT_PATTERN: &str = r"rate[ _-]?limit|too many requests|overloaded|\b(429|529)\b";
+
+/// Output of requests that never reached the API
+const NETWORK_PATTERN: &str = r"ECONNRESET|ECONNREFUSED|ETIMEDOUT|ENOTFOUND|EAI_AGAIN|socket hang up|fetch failed|network error|connection (error|reset|refused)";
+
+/// Output of a CLI that crashed rather than exiting with an error
+const CRASH_PATTERN: &str =
+    r"panicked|segmentation fault|fatal error|uncaught exception|core dumped";
+
+/// Kinds of failure a retry policy can retry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    /// The API rejected requests because of rate limits or overload
+    RateLimit,
+    /// The API couldn't be reached
+    Network,
+    /// The CLI was killed by a signal or crashed
+    Crash,
+    /// The CLI printed nothing within the startup timeout
+    StartupTimeout,
+    /// Any other non-zero exit
+    Other,
+}
+
+/// How an attempt failed
+#[derive(Debug, Clone, Default)]
+pub struct RunFailure {
+    /// None when the process was killed by a signal
+    pub exit_code: Option<i32>,
+    /// The end of the run's stderr and any error result Claude reported
+    pub output: String,
+    pub startup_timeout: bool,
+}
+
+impl RunFailure {
+    /// The kind of failure, judged from the output first and the exit code second
+    pub fn class(&self) -> FailureClass {
+        if matches(RATE_LIMIT_PATTERN, &self.output) {
+            FailureClass::RateLimit
+        } else if matches(NETWORK_PATTERN, &self.output) {
+            FailureClass::Network
+        } else if self.startup_timeout {
+            FailureClass::StartupTimeout
+        } else if self.exit_code.is_none_or(|code| code >= 128)
+            || matches(CRASH_PATTERN, &self.output)
+        {
+            FailureClass::Crash
+        } else {
+            FailureClass::Other
+        }
+    }
+
+    /// Status reason recorded on the failed run
+    pub fn describe(&self) -> String {
+        match self.exit_code {
+            Some(code) => format!("Claude exited with status {}", code),
+            None => "Claude was terminated by a signal".to_string(),
+        }
+    }
+}
+
+fn matches(pattern: &str, text: &str) -> bool {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .is_ok_and(|regex| regex.is_match(text))
+}
+
+/// When and how often an agent's failed runs are started again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first one included
+    pub max_attempts: u32,
+    /// Delay before the first retry in seconds
+    pub initial_delay_secs: u64,
+    /// Factor the delay grows by with each further retry
+    pub backoff_multiplier: f64,
+    /// Longest delay between attempts in seconds
+    pub max_delay_secs: u64,
+    /// Failure classes that are retried
+    pub retry_on: Vec<FailureClass>,
+    /// Exit codes that are retried whatever their class
+    pub exit_codes: Vec<i32>,
+    /// Regexes matched against the failure's output; a match is retried whatever its class
+    pub stderr_patterns: Vec<String>,
+    /// Continue the failed attempt's Claude session with `--resume` instead of starting over
+    pub resume_session: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay_secs: 30,
+            backoff_multiplier: 2.0,
+            max_delay_secs: 900,
+            retry_on: vec![
+                FailureClass::RateLimit,
+                FailureClass::Network,
+                FailureClass::Crash,
+                FailureClass::StartupTimeout,
+            ],
+            exit_codes: Vec::new(),
+            stderr_patterns: Vec::new(),
+            resume_session: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Parses a retry policy stored as a JSON string, ignoring empty values
+    pub fn from_json(json: Option<&str>) -> Result<Option<Self>, String> {
+        let policy: Self = match json.map(str::trim) {
+            None | Some("") => return Ok(None),
+            Some(json) => {
+                serde_json::from_str(json).map_err(|e| format!("Invalid retry policy: {}", e))?
+            }
+        };
+        if policy.max_attempts == 0 {
+            return Err("Invalid retry policy: max_attempts must be at least 1".to_string());
+        }
+        if !policy.backoff_multiplier.is_finite() || policy.backoff_multiplier < 1.0 {
+            return Err(format!(
+                "Invalid retry policy: backoff_multiplier must be at least 1, got {}",
+                policy.backoff_multiplier
+            ));
+        }
+        for pattern in &policy.stderr_patterns {
+            Regex::new(pattern)
+                .map_err(|e| format!("Invalid stderr pattern '{}': {}", pattern, e))?;
+        }
+        Ok(Some(policy))
+    }
+
+    /// Whether the policy retries `failure`
+    pub fn retries(&self, failure: &RunFailure) -> bool {
+        self.retry_on.contains(&failure.class())
+            || failure
+                .exit_code
+                .is_some_and(|code| self.exit_codes.contains(&code))
+            || self.stderr_patterns.iter().any(|pattern| {
+                Regex::new(pattern).is_ok_and(|regex| regex.is_match(&failure.output))
+            })
+    }
+
+    /// How long to wait before retry number `retry`, counting from 1
+    pub fn delay(&self, retry: u32) -> Duration {
+        let secs = self.initial_delay_secs as f64
+            * self
+                .backoff_multiplier
+                .powi(retry.saturating_sub(1).min(i32::MAX as u32) as i32);
+        Duration::from_secs_f64(secs.min(self.max_delay_secs as f64))
+    }
+}
+
+/// Queues the next attempt of a failed run if its agent's retry policy allows one
+///
+/// The attempt waits out the backoff in the queue, takes over the run's worktree and is
+/// linked to the first attempt through `retry_of`. Returns the new run's ID.
+pub(crate) fn schedule_retry(
+    conn: &Connection,
+    run_id: i64,
+    failure: &RunFailure,
+) -> Result<Option<i64>, String> {
+    let Some((agent_id, agent_version, attempt, session_id)) = conn
+        .query_row(
+            "SELECT agent_id, agent_version, attempt, session_id FROM agent_runs WHERE id = ?1 AND status = 'failed'",
+            params![run_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<i64>>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+    else {
+        return Ok(None);
+    };
+
+    // Retry with the policy of the agent version the run used
+    let json: Option<String> = match agent_version {
+        Some(version) => conn.query_row(
+            "SELECT retry_policy FROM agent_versions WHERE agent_id = ?1 AND version = ?2",
+            params![agent_id, version],
+            |row| row.get(0),
+        ),
+        None => conn.query_row(
+            "SELECT retry_policy FROM agents WHERE id = ?1",
+            params![agent_id],
+            |row| row.get(0),
+        ),
+    }
+    .optional()
+    .map_err(|e| e.to_string())?
+    .flatten();
+    let Some(policy) = RetryPolicy::from_json(json.as_deref())? else {
+        return Ok(None);
+    };
+    if attempt >= i64::from(policy.max_attempts) || !policy.retries(failure) {
+        return Ok(None);
+    }
+
+    let delay = policy.delay(attempt as u32);
+    let resume_session = (policy.resume_session && !session_id.is_empty()).then_some(session_id);
+    conn.execute(
+        "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, priority, interactive, agent_version, parameters, trigger_cause, worktree_path, worktree_branch, worktree_base, worktree_state, budget, retry_of, attempt, not_before, resume_session, queue_order)
+         SELECT agent_id, agent_name, agent_icon, task, model, project_path, '', 'queued', priority, interactive, agent_version, parameters, trigger_cause, worktree_path, worktree_branch, worktree_base, worktree_state, budget, COALESCE(retry_of, id), attempt + 1, datetime('now', ?2), ?3, (SELECT COALESCE(MAX(queue_order), 0) + 1 FROM agent_runs)
+         FROM agent_runs WHERE id = ?1",
+        params![run_id, format!("+{} seconds", delay.as_secs()), resume_session],
+    )
+    .map_err(|e| e.to_string())?;
+    let retry_id = conn.last_insert_rowid();
+    conn.execute(
+        "UPDATE agent_runs SET worktree_state = 'retried' WHERE id = ?1 AND worktree_state = 'active'",
+        params![run_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    info!(
+        "Retrying agent run {} as run {} (attempt {} of {}) in {}s: {:?}",
+        run_id,
+        retry_id,
+        attempt + 1,
+        policy.max_attempts,
+        delay.as_secs(),
+        failure.class()
+    );
+    Ok(Some(retry_id))
+}
+
+/// The latest attempt of a run; the run itself unless it was retried
+pub(crate) fn latest_attempt(conn: &Connection, run_id: i64) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT id FROM agent_runs WHERE id = ?1 OR retry_of = ?1 ORDER BY attempt DESC, id DESC LIMIT 1",
+        params![run_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map(|id| id.unwrap_or(run_id))
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(exit_code: Option<i32>, output: &str) -> RunFailure {
+        RunFailure {
+            exit_code,
+            output: output.to_string(),
+            startup_timeout: false,
+        }
+    }
+
+    #[test]
+    fn test_failures_are_classified_and_backed_off() {
+        assert_eq!(
+            failure(Some(1), "API Error: 529 {\"type\":\"overloaded_error\"}").class(),
+            FailureClass::RateLimit
+        );
+        assert_eq!(
+            failure(Some(1), "Error: read ECONNRESET").class(),
+            FailureClass::Network
+        );
+        assert_eq!(failure(None, "").class(), FailureClass::Crash);
+        assert_eq!(
+            failure(Some(1), "Invalid model name").class(),
+            FailureClass::Other
+        );
+
+        let policy = RetryPolicy::from_json(Some(
+            r#"{"max_attempts": 5, "initial_delay_secs": 10, "max_delay_secs": 60, "exit_codes": [3]}"#,
+        ))
+        .unwrap()
+        .unwrap();
+        assert!(policy.retries(&failure(None, "")));
+        assert!(policy.retries(&failure(Some(3), "")));
+        assert!(!policy.retries(&failure(Some(1), "Invalid model name")));
+        assert_eq!(policy.delay(1), Duration::from_secs(10));
+        assert_eq!(policy.delay(3), Duration::from_secs(40));
+        assert_eq!(policy.delay(4), Duration::from_secs(60));
+
+        assert!(RetryPolicy::from_json(Some(r#"{"max_attempts": 0}"#)).is_err());
+        assert!(RetryPolicy::from_json(Some(r#"{"stderr_patterns": ["("]}"#)).is_err());
+    }
+
+    #[test]
+    fn test_failed_run_is_retried_until_attempts_run_out() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE agents (id INTEGER PRIMARY KEY, retry_policy TEXT);
+             CREATE TABLE agent_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, agent_id INTEGER NOT NULL, agent_name TEXT, agent_icon TEXT,
+                task TEXT, model TEXT, project_path TEXT, session_id TEXT NOT NULL DEFAULT '', status TEXT,
+                priority INTEGER NOT NULL DEFAULT 0, interactive INTEGER NOT NULL DEFAULT 0, agent_version INTEGER,
+                parameters TEXT, trigger_cause TEXT, worktree_path TEXT, worktree_branch TEXT, worktree_base TEXT,
+                worktree_state TEXT, budget TEXT, retry_of INTEGER, attempt INTEGER NOT NULL DEFAULT 1,
+                not_before TEXT, resume_session TEXT, queue_order INTEGER
+             );
+             INSERT INTO agents (id, retry_policy) VALUES (1, '{\"max_attempts\": 2, \"resume_session\": true}');
+             INSERT INTO agent_runs (agent_id, task, session_id, status, worktree_path, worktree_state)
+                VALUES (1, 'Fix the build', 'session-1', 'failed', '/tmp/wt', 'active');",
+        )
+        .unwrap();
+
+        let crash = failure(None, "");
+        assert_eq!(
+            schedule_retry(&conn, 1, &failure(Some(1), "Invalid model name")).unwrap(),
+            None
+        );
+        let retry = schedule_retry(&conn, 1, &crash).unwrap().unwrap();
+        let (status, task, retry_of, attempt, resume, worktree_state): (
+            String,
+            String,
+            i64,
+            i64,
+            Option<String>,
+            Option<String>,
+        ) = conn
+            .query_row(
+                "SELECT status, task, retry_of, attempt, resume_session, worktree_state FROM agent_runs WHERE id = ?1",
+                params![retry],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            (status.as_str(), task.as_str(), retry_of, attempt),
+            ("queued", "Fix the build", 1, 2)
+        );
+        assert_eq!(resume.as_deref(), Some("session-1"));
+        assert_eq!(worktree_state.as_deref(), Some("active"));
+        assert_eq!(latest_attempt(&conn, 1).unwrap(), retry);
+
+        // The second attempt is the last one the policy allows
+        conn.execute(
+            "UPDATE agent_runs SET status = 'failed' WHERE id = ?1",
+            params![retry],
+        )
+        .unwrap();
+        assert_eq!(schedule_retry(&conn, retry, &crash).unwrap(), None);
+    }
+}
@@ -0,0 +1,763 @@
+use chrono::{DateTime, Datelike, Local, NaiveTime, SecondsFormat, TimeZone, Timelike, Utc};
+use log::{error, info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use super::agent_parameters::{parse_parameters, resolve_parameters};
+use super::agents::{get_agent, queue_agent_run, AgentDb, QueueRunRequest};
+
+/// How often the scheduler looks for due schedules
+const SCHEDULER_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// A run that comes due more than this long before the scheduler sees it was missed,
+/// usually because the app wasn't running
+const MISSED_AFTER: chrono::Duration = chrono::Duration::minutes(5);
+
+/// What to do about runs that were due while the app wasn't running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MissedRunPolicy {
+    /// Wait for the next scheduled time
+    #[default]
+    Skip,
+    /// Run once as soon as possible, however many runs were missed
+    CatchUp,
+}
+
+impl MissedRunPolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MissedRunPolicy::Skip => "skip",
+            MissedRunPolicy::CatchUp => "catch_up",
+        }
+    }
+
+    fn parse(policy: &str) -> Self {
+        match policy {
+            "catch_up" => MissedRunPolicy::CatchUp,
+            _ => MissedRunPolicy::Skip,
+        }
+    }
+}
+
+/// A parsed five-field cron expression: minute, hour, day of month, month, day of week
+///
+/// Fields accept `*`, numbers, ranges (`1-5`), steps (`*/15`, `0-30/10`) and lists of
+/// those. `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are also accepted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Whether the day-of-month and day-of-week fields start with `*`, as in `*` or `*/2`;
+    /// when both are restricted a day matching either one matches
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let expanded = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "Invalid cron expression '{}': expected 5 fields (minute hour day month weekday)",
+                expression
+            ));
+        }
+
+        let field = |index: usize, name: &str, min: u32, max: u32| {
+            parse_cron_field(fields[index], min, max)
+                .map_err(|e| format!("Invalid {} field '{}': {}", name, fields[index], e))
+        };
+        // Sunday may be written as 0 or 7
+        let mut days_of_week = field(4, "weekday", 0, 7)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            minutes: field(0, "minute", 0, 59)?,
+            hours: field(1, "hour", 0, 23)?,
+            days_of_month: field(2, "day", 1, 31)?,
+            months: field(3, "month", 1, 12)?,
+            days_of_week,
+            any_day_of_month: fields[2].starts_with('*'),
+            any_day_of_week: fields[4].starts_with('*'),
+        })
+    }
+
+    fn matches_date(&self, date: chrono::NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day_of_month = self.days_of_month & (1 << date.day()) != 0;
+        let day_of_week = self.days_of_week & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+
+    /// The first matching minute after `after`, in its time zone
+    ///
+    /// Local times skipped by a DST change never match; repeated ones match once.
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = after.timezone();
+        let local = after.naive_local();
+        let start = local.with_second(0)?.with_nanosecond(0)? + chrono::Duration::minutes(1);
+
+        let mut date = start.date();
+        // Four years and a bit covers expressions that only match on February 29
+        for _ in 0..(366 * 5) {
+            if self.matches_date(date) {
+                let from = if date == start.date() {
+                    start.time()
+                } else {
+                    NaiveTime::MIN
+                };
+                for hour in from.hour()..24 {
+                    if self.hours & (1 << hour) == 0 {
+                        continue;
+                    }
+                    let first_minute = if hour == from.hour() {
+                        from.minute()
+                    } else {
+                        0
+                    };
+                    for minute in first_minute..60 {
+                        if self.minutes & (1 << minute) == 0 {
+                            continue;
+                        }
+                        let candidate = date.and_hms_opt(hour, minute, 0)?;
+                        if let Some(time) = tz.from_local_datetime(&candidate).earliest() {
+                            return Some(time);
+                        }
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+}
+
+/// Parses one cron field into a bit set of the values it matches
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let number = |text: &str| {
+        text.parse::<u32>()
+            .ok()
+            .filter(|n| (min..=max).contains(n))
+            .ok_or_else(|| format!("'{}' is not a number from {} to {}", text, min, max))
+    };
+
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("invalid step '{}'", step))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (number(start)?, number(end)?),
+                // A single value with a step runs from that value to the end of the range
+                None if step > 1 => (number(range)?, max),
+                None => {
+                    let value = number(range)?;
+                    (value, value)
+                }
+            },
+        };
+        if start > end {
+            return Err(format!("range {}-{} is backwards", start, end));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+/// Whether a run that came due at `due_at` should start now or be skipped as missed
+fn should_run(due_at: DateTime<Utc>, now: DateTime<Utc>, policy: MissedRunPolicy) -> bool {
+    now - due_at <= MISSED_AFTER || policy == MissedRunPolicy::CatchUp
+}
+
+/// The next time a schedule runs, stored in UTC
+fn next_run_at(cron: &CronSchedule, after: DateTime<Utc>) -> Option<String> {
+    cron.next_after(&after.with_timezone(&Local)).map(|time| {
+        time.with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Secs, true)
+    })
+}
+
+/// The next run time of a schedule being saved or enabled; expressions that can never match,
+/// such as February 30th, are refused
+fn first_run_at(cron: &CronSchedule, expression: &str) -> Result<String, String> {
+    next_run_at(cron, Utc::now()).ok_or_else(|| {
+        format!(
+            "Cron expression '{}' never matches a date",
+            expression.trim()
+        )
+    })
+}
+
+/// Runs an agent in a project on a cron schedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentSchedule {
+    pub id: Option<i64>,
+    pub agent_id: i64,
+    pub project_path: String,
+    pub cron: String,         // Evaluated in the local time zone
+    pub task: Option<String>, // Falls back to the agent's default task
+    pub model: Option<String>,
+    pub parameters: Option<String>, // JSON object of parameter values
+    pub missed_run_policy: MissedRunPolicy,
+    pub enabled: bool,
+    pub next_run_at: Option<String>,
+    pub last_run_at: Option<String>,
+    pub last_run_id: Option<i64>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Columns selected when loading an `AgentSchedule`, in the order `AgentSchedule::from_row` expects
+const SCHEDULE_COLUMNS: &str = "id, agent_id, project_path, cron, task, model, parameters, missed_run_policy, enabled, next_run_at, last_run_at, last_run_id, last_error, created_at, updated_at";
+
+impl AgentSchedule {
+    /// Builds an `AgentSchedule` from a row selected with `SCHEDULE_COLUMNS`
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: Some(row.get(0)?),
+            agent_id: row.get(1)?,
+            project_path: row.get(2)?,
+            cron: row.get(3)?,
+            task: row.get(4)?,
+            model: row.get(5)?,
+            parameters: row.get(6)?,
+            missed_run_policy: MissedRunPolicy::parse(&row.get::<_, String>(7)?),
+            enabled: row.get(8)?,
+            next_run_at: row.get(9)?,
+            last_run_at: row.get(10)?,
+            last_run_id: row.get(11)?,
+            last_error: row.get(12)?,
+            created_at: row.get(13)?,
+            updated_at: row.get(14)?,
+        })
+    }
+
+    fn parameter_values(&self) -> Result<HashMap<String, Value>, String> {
+        match self.parameters.as_deref() {
+            None | Some("") => Ok(HashMap::new()),
+            Some(json) => serde_json::from_str(json)
+                .map_err(|e| format!("Invalid schedule parameters: {}", e)),
+        }
+    }
+}
+
+/// Creates the `agent_schedules` table
+pub fn init_schedules(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agent_schedules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id INTEGER NOT NULL,
+            project_path TEXT NOT NULL,
+            cron TEXT NOT NULL,
+            task TEXT,
+            model TEXT,
+            parameters TEXT,
+            missed_run_policy TEXT NOT NULL DEFAULT 'skip',
+            enabled BOOLEAN NOT NULL DEFAULT 1,
+            next_run_at TEXT,
+            last_run_at TEXT,
+            last_run_id INTEGER,
+            last_error TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn load_schedule(conn: &Connection, id: i64) -> Result<AgentSchedule, String> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM agent_schedules WHERE id = ?1",
+            SCHEDULE_COLUMNS
+        ),
+        params![id],
+        AgentSchedule::from_row,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Schedule {} not found", id))
+}
+
+/// Checks that a schedule can run: the cron expression parses and matches some date, there
+/// is a task and the parameter values suit the agent
+async fn check_schedule(
+    db: &State<'_, AgentDb>,
+    agent_id: i64,
+    project_path: &str,
+    expression: &str,
+    task: Option<&str>,
+    parameters: &HashMap<String, Value>,
+) -> Result<CronSchedule, String> {
+    let cron = CronSchedule::parse(expression)?;
+    first_run_at(&cron, expression)?;
+    let agent = get_agent(db.clone(), agent_id).await?;
+    let has_task = task
+        .or(agent.default_task.as_deref())
+        .is_some_and(|task| !task.trim().is_empty());
+    if !has_task {
+        return Err(format!(
+            "Agent '{}' has no default task; give the schedule a task",
+            agent.name
+        ));
+    }
+    resolve_parameters(
+        &parse_parameters(agent.parameters.as_deref())?,
+        parameters,
+        project_path,
+    )?;
+    Ok(cron)
+}
+
+/// Queues a run for a schedule
+async fn launch_schedule(app: &AppHandle, schedule: &AgentSchedule) -> Result<i64, String> {
+    let agent = get_agent(app.state::<AgentDb>(), schedule.agent_id).await?;
+    let task = schedule
+        .task
+        .clone()
+        .or(agent.default_task)
+        .ok_or_else(|| format!("Agent '{}' has no default task", agent.name))?;
+    queue_agent_run(
+        app,
+        QueueRunRequest {
+            agent_id: schedule.agent_id,
+            project_path: schedule.project_path.clone(),
+            task,
+            model: schedule.model.clone(),
+            priority: None,
+            interactive: false,
+            parameters: schedule.parameter_values()?,
+            trigger_cause: Some(format!(
+                "Schedule {} ({})",
+                schedule.id.unwrap_or_default(),
+                schedule.cron
+            )),
+            isolated: false,
+            budget: None,
+        },
+    )
+    .await
+}
+
+/// Starts runs for schedules that are due and works out when they run next
+pub async fn run_due_schedules(app: &AppHandle) {
+    let now = Utc::now();
+    let schedules = {
+        let db = app.state::<AgentDb>();
+        let conn = match db.0.lock() {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("Failed to lock agents database: {}", e);
+                return;
+            }
+        };
+        match enabled_schedules(&conn) {
+            Ok(schedules) => schedules,
+            Err(e) => {
+                error!("Failed to load agent schedules: {}", e);
+                return;
+            }
+        }
+    };
+
+    for schedule in schedules {
+        let Some(id) = schedule.id else {
+            continue;
+        };
+        let due_at = schedule
+            .next_run_at
+            .as_deref()
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.with_timezone(&Utc));
+        if due_at.is_some_and(|due_at| due_at > now) {
+            continue;
+        }
+
+        let cron = match CronSchedule::parse(&schedule.cron) {
+            Ok(cron) => cron,
+            Err(e) => {
+                warn!("Schedule {} has an invalid cron expression: {}", id, e);
+                continue;
+            }
+        };
+        // Only schedules saved before such expressions were refused get here; they are
+        // disabled rather than rescheduled on every tick
+        let Some(next) = next_run_at(&cron, now) else {
+            warn!("Schedule {} never matches a date; disabling it", id);
+            disable_schedule(app, id, "The cron expression never matches a date");
+            continue;
+        };
+
+        // Schedules without a next run time (e.g. just re-enabled) only get one
+        let Some(due_at) = due_at else {
+            save_next_run(app, id, &next);
+            continue;
+        };
+
+        if !should_run(due_at, now, schedule.missed_run_policy) {
+            info!(
+                "Skipping run of schedule {} missed at {}",
+                id,
+                due_at.to_rfc3339()
+            );
+            save_next_run(app, id, &next);
+            continue;
+        }
+
+        info!(
+            "Schedule {} is due; queueing agent {}",
+            id, schedule.agent_id
+        );
+        let launched = launch_schedule(app, &schedule).await;
+        if let Err(e) = &launched {
+            warn!("Schedule {} failed to start a run: {}", id, e);
+        }
+        let db = app.state::<AgentDb>();
+        if let Ok(conn) = db.0.lock() {
+            let (run_id, error) = match &launched {
+                Ok(run_id) => (Some(*run_id), None),
+                Err(e) => (None, Some(e.clone())),
+            };
+            if let Err(e) = conn.execute(
+                "UPDATE agent_schedules SET next_run_at = ?2, last_run_at = ?3, last_run_id = COALESCE(?4, last_run_id), last_error = ?5 WHERE id = ?1",
+                params![id, next, now.to_rfc3339_opts(SecondsFormat::Secs, true), run_id, error],
+            ) {
+                error!("Failed to update schedule {}: {}", id, e);
+            }
+        }
+        let _ = app.emit(&format!("schedule-run:{}", id), launched.is_ok());
+    }
+}
+
+fn enabled_schedules(conn: &Connection) -> rusqlite::Result<Vec<AgentSchedule>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM agent_schedules WHERE enabled = 1",
+        SCHEDULE_COLUMNS
+    ))?;
+    let schedules = stmt
+        .query_map([], AgentSchedule::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(schedules)
+}
+
+fn save_next_run(app: &AppHandle, id: i64, next: &str) {
+    let db = app.state::<AgentDb>();
+    if let Ok(conn) = db.0.lock() {
+        if let Err(e) = conn.execute(
+            "UPDATE agent_schedules SET next_run_at = ?2 WHERE id = ?1",
+            params![id, next],
+        ) {
+            error!("Failed to update schedule {}: {}", id, e);
+        }
+    }
+}
+
+fn disable_schedule(app: &AppHandle, id: i64, error: &str) {
+    let db = app.state::<AgentDb>();
+    if let Ok(conn) = db.0.lock() {
+        if let Err(e) = conn.execute(
+            "UPDATE agent_schedules SET enabled = 0, next_run_at = NULL, last_error = ?2 WHERE id = ?1",
+            params![id, error],
+        ) {
+            error!("Failed to update schedule {}: {}", id, e);
+        }
+    }
+}
+
+/// Periodically starts runs for due schedules while the app is running
+pub fn spawn_scheduler(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            run_due_schedules(&app).await;
+            tokio::time::sleep(SCHEDULER_INTERVAL).await;
+        }
+    });
+}
+
+/// List schedules, optionally for one agent
+#[tauri::command]
+pub async fn list_schedules(
+    db: State<'_, AgentDb>,
+    agent_id: Option<i64>,
+) -> Result<Vec<AgentSchedule>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM agent_schedules WHERE ?1 IS NULL OR agent_id = ?1 ORDER BY created_at DESC, id DESC",
+            SCHEDULE_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let schedules = stmt
+        .query_map(params![agent_id], AgentSchedule::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(schedules)
+}
+
+/// Create a schedule for an agent in a project
+#[tauri::command]
+pub async fn create_schedule(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+    project_path: String,
+    cron: String,
+    task: Option<String>,
+    model: Option<String>,
+    parameters: Option<HashMap<String, Value>>,
+    missed_run_policy: Option<MissedRunPolicy>,
+) -> Result<AgentSchedule, String> {
+    let parameters = parameters.unwrap_or_default();
+    let parsed = check_schedule(
+        &db,
+        agent_id,
+        &project_path,
+        &cron,
+        task.as_deref(),
+        &parameters,
+    )
+    .await?;
+    let parameters_json = serde_json::to_string(&parameters).map_err(|e| e.to_string())?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO agent_schedules (agent_id, project_path, cron, task, model, parameters, missed_run_policy, next_run_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            agent_id,
+            project_path,
+            cron.trim(),
+            task,
+            model,
+            parameters_json,
+            missed_run_policy.unwrap_or_default().as_str(),
+            next_run_at(&parsed, Utc::now())
+        ],
+    )
+    .map_err(|e| format!("Failed to create schedule: {}", e))?;
+    load_schedule(&conn, conn.last_insert_rowid())
+}
+
+/// Update a schedule; its next run time is worked out again from now
+#[tauri::command]
+pub async fn update_schedule(
+    db: State<'_, AgentDb>,
+    id: i64,
+    project_path: String,
+    cron: String,
+    task: Option<String>,
+    model: Option<String>,
+    parameters: Option<HashMap<String, Value>>,
+    missed_run_policy: Option<MissedRunPolicy>,
+) -> Result<AgentSchedule, String> {
+    let agent_id = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        load_schedule(&conn, id)?.agent_id
+    };
+    let parameters = parameters.unwrap_or_default();
+    let parsed = check_schedule(
+        &db,
+        agent_id,
+        &project_path,
+        &cron,
+        task.as_deref(),
+        &parameters,
+    )
+    .await?;
+    let parameters_json = serde_json::to_string(&parameters).map_err(|e| e.to_string())?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE agent_schedules SET project_path = ?2, cron = ?3, task = ?4, model = ?5, parameters = ?6,
+            missed_run_policy = ?7, next_run_at = CASE WHEN enabled THEN ?8 ELSE NULL END, updated_at = CURRENT_TIMESTAMP
+         WHERE id = ?1",
+        params![
+            id,
+            project_path,
+            cron.trim(),
+            task,
+            model,
+            parameters_json,
+            missed_run_policy.unwrap_or_default().as_str(),
+            next_run_at(&parsed, Utc::now())
+        ],
+    )
+    .map_err(|e| format!("Failed to update schedule: {}", e))?;
+    load_schedule(&conn, id)
+}
+
+/// Enable or disable a schedule
+///
+/// Runs that fall due while a schedule is disabled are not caught up when it is enabled again.
+#[tauri::command]
+pub async fn set_schedule_enabled(
+    db: State<'_, AgentDb>,
+    id: i64,
+    enabled: bool,
+) -> Result<AgentSchedule, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let schedule = load_schedule(&conn, id)?;
+    let next = if enabled {
+        Some(first_run_at(
+            &CronSchedule::parse(&schedule.cron)?,
+            &schedule.cron,
+        )?)
+    } else {
+        None
+    };
+    conn.execute(
+        "UPDATE agent_schedules SET enabled = ?2, next_run_at = ?3, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        params![id, enabled, next],
+    )
+    .map_err(|e| format!("Failed to update schedule: {}", e))?;
+    load_schedule(&conn, id)
+}
+
+/// Delete a schedule
+#[tauri::command]
+pub async fn delete_schedule(db: State<'_, AgentDb>, id: i64) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM agent_schedules WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// The next times a cron expression matches, for previewing a schedule
+#[tauri::command]
+pub async fn preview_schedule(cron: String, count: Option<usize>) -> Result<Vec<String>, String> {
+    let cron = CronSchedule::parse(&cron)?;
+    let mut times = Vec::new();
+    let mut after = Local::now();
+    while times.len() < count.unwrap_or(5).min(100) {
+        let Some(next) = cron.next_after(&after) else {
+            break;
+        };
+        times.push(
+            next.with_timezone(&Utc)
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+        );
+        after = next;
+    }
+    Ok(times)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn next(cron: &str, after: &str) -> String {
+        CronSchedule::parse(cron)
+            .unwrap()
+            .next_after(&at(after))
+            .unwrap()
+            .to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        // 2026-10-18 is a Sunday
+        assert_eq!(
+            next("*/15 * * * *", "2026-10-18T10:07:30Z"),
+            "2026-10-18T10:15:00Z"
+        );
+        assert_eq!(
+            next("0 9 * * 1-5", "2026-10-18T10:00:00Z"),
+            "2026-10-19T09:00:00Z"
+        );
+        assert_eq!(
+            next("30 2 * * 7", "2026-10-18T02:30:00Z"),
+            "2026-10-25T02:30:00Z"
+        );
+        assert_eq!(
+            next("@monthly", "2026-10-18T10:00:00Z"),
+            "2026-11-01T00:00:00Z"
+        );
+        assert_eq!(
+            next("0 0 29 2 *", "2026-10-18T10:00:00Z"),
+            "2028-02-29T00:00:00Z"
+        );
+        // With both day fields restricted, either one matches
+        assert_eq!(
+            next("0 0 1 * 3", "2026-10-18T10:00:00Z"),
+            "2026-10-21T00:00:00Z"
+        );
+
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("5-1 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+
+        // A stepped `*` leaves the field unrestricted, so both day fields must match
+        assert_eq!(
+            next("0 0 */10 * 1", "2026-10-18T10:00:00Z"),
+            "2026-12-21T00:00:00Z"
+        );
+        // Parses, but there is no February 30th
+        assert!(CronSchedule::parse("0 0 30 2 *")
+            .unwrap()
+            .next_after(&at("2026-10-18T10:00:00Z"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_missed_runs_follow_policy() {
+        let now = at("2026-10-18T10:00:20Z");
+        assert!(should_run(
+            at("2026-10-18T10:00:00Z"),
+            now,
+            MissedRunPolicy::Skip
+        ));
+        assert!(!should_run(
+            at("2026-10-18T06:00:00Z"),
+            now,
+            MissedRunPolicy::Skip
+        ));
+        assert!(should_run(
+            at("2026-10-18T06:00:00Z"),
+            now,
+            MissedRunPolicy::CatchUp
+        ));
+    }
+}
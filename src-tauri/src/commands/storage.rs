@@ -447,6 +447,18 @@ pub async fn storage_reset_database(app: AppHandle) -> Result<(), String> {
             .map_err(|e| format!("Failed to drop agent_runs table: {}", e))?;
         conn.execute("DROP TABLE IF EXISTS agents", [])
             .map_err(|e| format!("Failed to drop agents table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS agent_versions", [])
+            .map_err(|e| format!("Failed to drop agent_versions table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS workflow_run_steps", [])
+            .map_err(|e| format!("Failed to drop workflow_run_steps table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS workflow_runs", [])
+            .map_err(|e| format!("Failed to drop workflow_runs table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS workflows", [])
+            .map_err(|e| format!("Failed to drop workflows table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS agent_schedules", [])
+            .map_err(|e| format!("Failed to drop agent_schedules table: {}", e))?;
+        conn.execute("DROP TABLE IF EXISTS agent_triggers", [])
+            .map_err(|e| format!("Failed to drop agent_triggers table: {}", e))?;
         conn.execute("DROP TABLE IF EXISTS app_settings", [])
             .map_err(|e| format!("Failed to drop app_settings table: {}", e))?;
         
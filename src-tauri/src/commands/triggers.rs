@@ -0,0 +1,814 @@
+use glob::{MatchOptions, Pattern};
+use log::{error, info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use super::agent_parameters::{parse_parameters, resolve_parameters};
+use super::agents::{get_agent, queue_agent_run, AgentDb, QueueRunRequest};
+use super::retries::latest_attempt;
+
+/// How often projects are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Directories never scanned for file changes unless a pattern starts inside them
+const IGNORED_DIRS: &[&str] = &[".git", "node_modules", "target"];
+
+/// Stop scanning a project after this many matching files
+const MAX_WATCHED_FILES: usize = 50_000;
+
+/// Most changed paths listed in a run's trigger cause
+const MAX_LISTED_CHANGES: usize = 5;
+
+/// What a trigger reacts to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TriggerEvent {
+    /// Files matching any of the glob patterns changed; patterns are relative to the project
+    FileChange { patterns: Vec<String> },
+    /// A new commit landed on a branch, or on the checked-out branch when none is given
+    GitCommit {
+        #[serde(default)]
+        branch: Option<String>,
+    },
+}
+
+impl TriggerEvent {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            TriggerEvent::FileChange { patterns } => {
+                if patterns.is_empty() {
+                    return Err("A file change trigger needs at least one pattern".to_string());
+                }
+                compile_patterns(patterns).map(|_| ())
+            }
+            TriggerEvent::GitCommit { branch } => match branch.as_deref() {
+                Some(branch) if branch.trim().is_empty() || branch.contains("..") => {
+                    Err(format!("Invalid branch name '{}'", branch))
+                }
+                _ => Ok(()),
+            },
+        }
+    }
+}
+
+/// Runs an agent when something happens in a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentTrigger {
+    pub id: Option<i64>,
+    pub agent_id: i64,
+    pub project_path: String,
+    pub event: TriggerEvent,
+    pub task: Option<String>, // Falls back to the agent's default task
+    pub model: Option<String>,
+    pub parameters: Option<String>, // JSON object of parameter values
+    pub debounce_ms: u64,           // Quiet time after the last change before the run starts
+    pub cooldown_secs: u64,         // Least time between two runs of the trigger
+    pub enabled: bool,
+    pub last_fired_at: Option<String>,
+    pub last_run_id: Option<i64>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Columns selected when loading an `AgentTrigger`, in the order `AgentTrigger::from_row` expects
+const TRIGGER_COLUMNS: &str = "id, agent_id, project_path, event, task, model, parameters, debounce_ms, cooldown_secs, enabled, last_fired_at, last_run_id, last_error, created_at, updated_at";
+
+impl AgentTrigger {
+    /// Builds an `AgentTrigger` from a row selected with `TRIGGER_COLUMNS`
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let event: String = row.get(3)?;
+        Ok(Self {
+            id: Some(row.get(0)?),
+            agent_id: row.get(1)?,
+            project_path: row.get(2)?,
+            event: serde_json::from_str(&event).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    3,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })?,
+            task: row.get(4)?,
+            model: row.get(5)?,
+            parameters: row.get(6)?,
+            debounce_ms: row.get::<_, i64>(7)?.max(0) as u64,
+            cooldown_secs: row.get::<_, i64>(8)?.max(0) as u64,
+            enabled: row.get(9)?,
+            last_fired_at: row.get(10)?,
+            last_run_id: row.get(11)?,
+            last_error: row.get(12)?,
+            created_at: row.get(13)?,
+            updated_at: row.get(14)?,
+        })
+    }
+
+    fn parameter_values(&self) -> Result<HashMap<String, Value>, String> {
+        match self.parameters.as_deref() {
+            None | Some("") => Ok(HashMap::new()),
+            Some(json) => {
+                serde_json::from_str(json).map_err(|e| format!("Invalid trigger parameters: {}", e))
+            }
+        }
+    }
+}
+
+/// Creates the `agent_triggers` table
+pub fn init_triggers(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agent_triggers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id INTEGER NOT NULL,
+            project_path TEXT NOT NULL,
+            event TEXT NOT NULL,
+            task TEXT,
+            model TEXT,
+            parameters TEXT,
+            debounce_ms INTEGER NOT NULL DEFAULT 2000,
+            cooldown_secs INTEGER NOT NULL DEFAULT 60,
+            enabled BOOLEAN NOT NULL DEFAULT 1,
+            last_fired_at TEXT,
+            last_run_id INTEGER,
+            last_error TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Pattern>, String> {
+    patterns
+        .iter()
+        .map(|p| Pattern::new(p).map_err(|e| format!("Invalid pattern '{}': {}", p, e)))
+        .collect()
+}
+
+/// The leading directories of a pattern that contain no wildcards; only they are scanned
+fn literal_prefix(pattern: &str) -> PathBuf {
+    pattern
+        .split('/')
+        .take_while(|part| !part.contains(['*', '?', '[', '{']))
+        .collect()
+}
+
+/// Size and modification time of each file matching the patterns, by relative path
+fn scan_files(project: &Path, patterns: &[String]) -> BTreeMap<String, (u64, Option<SystemTime>)> {
+    let Ok(compiled) = compile_patterns(patterns) else {
+        return BTreeMap::new();
+    };
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+
+    let mut files = BTreeMap::new();
+    let mut roots: Vec<PathBuf> = patterns.iter().map(|p| literal_prefix(p)).collect();
+    roots.sort();
+    roots.dedup();
+    // A root inside another root would only be scanned twice
+    let roots: Vec<&PathBuf> = roots
+        .iter()
+        .filter(|root| {
+            !roots
+                .iter()
+                .any(|other| other != *root && root.starts_with(other))
+        })
+        .collect();
+
+    for root in roots {
+        let walker = walkdir::WalkDir::new(project.join(root))
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.depth() == 0
+                    || !entry.file_type().is_dir()
+                    || !IGNORED_DIRS.contains(&entry.file_name().to_string_lossy().as_ref())
+            });
+        for entry in walker.filter_map(Result::ok) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(relative) = entry.path().strip_prefix(project) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            if !compiled.iter().any(|p| p.matches_with(&relative, options)) {
+                continue;
+            }
+            let metadata = entry.metadata().ok();
+            files.insert(
+                relative,
+                (
+                    metadata.as_ref().map_or(0, |m| m.len()),
+                    metadata.and_then(|m| m.modified().ok()),
+                ),
+            );
+            if files.len() >= MAX_WATCHED_FILES {
+                return files;
+            }
+        }
+    }
+    files
+}
+
+/// The git directory of a project, following the `.git` file of linked worktrees
+fn git_dir(project: &Path) -> Option<PathBuf> {
+    let dot_git = project.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let content = std::fs::read_to_string(&dot_git).ok()?;
+    let target = content.strip_prefix("gitdir:")?.trim();
+    Some(project.join(target))
+}
+
+/// The branch a ref points at and its commit: `branch`, or the checked-out branch
+///
+/// A detached HEAD is reported as the branch `HEAD`.
+fn resolve_git_ref(git_dir: &Path, branch: Option<&str>) -> Option<(String, String)> {
+    // Linked worktrees keep their own HEAD but share refs with the main repository
+    let common_dir = std::fs::read_to_string(git_dir.join("commondir"))
+        .map(|dir| git_dir.join(dir.trim()))
+        .unwrap_or_else(|_| git_dir.to_path_buf());
+
+    let branch = match branch {
+        Some(branch) => branch.to_string(),
+        None => {
+            let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+            match head.trim().strip_prefix("ref: refs/heads/") {
+                Some(branch) => branch.to_string(),
+                None => return Some(("HEAD".to_string(), head.trim().to_string())),
+            }
+        }
+    };
+
+    let ref_name = format!("refs/heads/{}", branch);
+    if let Ok(sha) = std::fs::read_to_string(common_dir.join(&ref_name)) {
+        return Some((branch, sha.trim().to_string()));
+    }
+    let packed = std::fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+    packed
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .find(|(_, name)| *name == ref_name)
+        .map(|(sha, _)| (branch, sha.to_string()))
+}
+
+/// What a trigger last saw of its project
+#[derive(Debug, Clone, PartialEq)]
+enum Snapshot {
+    Files(BTreeMap<String, (u64, Option<SystemTime>)>),
+    Git(Option<(String, String)>),
+}
+
+impl Snapshot {
+    fn take(project: &Path, event: &TriggerEvent) -> Self {
+        match event {
+            TriggerEvent::FileChange { patterns } => Snapshot::Files(scan_files(project, patterns)),
+            TriggerEvent::GitCommit { branch } => Snapshot::Git(
+                git_dir(project).and_then(|dir| resolve_git_ref(&dir, branch.as_deref())),
+            ),
+        }
+    }
+
+    /// Descriptions of what changed since `previous`; empty when nothing a trigger reacts to did
+    fn changes_since(&self, previous: &Snapshot) -> Vec<String> {
+        match (previous, self) {
+            (Snapshot::Files(before), Snapshot::Files(after)) => {
+                let removed = before.keys().filter(|path| !after.contains_key(*path));
+                let changed = after
+                    .iter()
+                    .filter(|(path, stat)| before.get(*path) != Some(stat))
+                    .map(|(path, _)| path);
+                changed.chain(removed).cloned().collect()
+            }
+            // Checking out another branch is not a commit
+            (Snapshot::Git(Some((before_branch, before))), Snapshot::Git(Some((branch, sha))))
+                if before_branch == branch && before != sha =>
+            {
+                vec![format!("commit {} on {}", short_sha(sha), branch)]
+            }
+            (Snapshot::Git(None), Snapshot::Git(Some((branch, sha)))) => {
+                vec![format!("commit {} on {}", short_sha(sha), branch)]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn short_sha(sha: &str) -> &str {
+    &sha[..sha.len().min(8)]
+}
+
+/// Debounce and cooldown bookkeeping for one trigger
+#[derive(Debug)]
+struct TriggerWatch {
+    /// The trigger's `updated_at` when watching started; edits start over from a new snapshot
+    updated_at: String,
+    snapshot: Snapshot,
+    pending: BTreeSet<String>,
+    last_change: Option<Instant>,
+    last_fired: Option<Instant>,
+}
+
+impl TriggerWatch {
+    fn new(updated_at: String, snapshot: Snapshot) -> Self {
+        Self {
+            updated_at,
+            snapshot,
+            pending: BTreeSet::new(),
+            last_change: None,
+            last_fired: None,
+        }
+    }
+
+    /// Records a new snapshot; changes are dropped while the trigger's last run is still going
+    fn observe(&mut self, snapshot: Snapshot, run_active: bool, now: Instant) {
+        let changes = snapshot.changes_since(&self.snapshot);
+        self.snapshot = snapshot;
+        if run_active {
+            self.pending.clear();
+            self.last_change = None;
+        } else if !changes.is_empty() {
+            self.pending.extend(changes);
+            self.last_change = Some(now);
+        }
+    }
+
+    /// Whether changes have settled for the debounce time and the cooldown has passed
+    fn ready(&self, now: Instant, debounce: Duration, cooldown: Duration) -> bool {
+        let settled = self
+            .last_change
+            .is_some_and(|changed| now.duration_since(changed) >= debounce);
+        let cooled = self
+            .last_fired
+            .is_none_or(|fired| now.duration_since(fired) >= cooldown);
+        !self.pending.is_empty() && settled && cooled
+    }
+
+    /// Takes the pending changes as a run's trigger cause
+    fn fire(&mut self, event: &TriggerEvent, now: Instant) -> String {
+        let changes = std::mem::take(&mut self.pending);
+        self.last_change = None;
+        self.last_fired = Some(now);
+        match event {
+            TriggerEvent::FileChange { .. } => {
+                let listed: Vec<&str> = changes
+                    .iter()
+                    .take(MAX_LISTED_CHANGES)
+                    .map(String::as_str)
+                    .collect();
+                let more = changes.len().saturating_sub(MAX_LISTED_CHANGES);
+                if more > 0 {
+                    format!("Files changed: {} (+{} more)", listed.join(", "), more)
+                } else {
+                    format!("Files changed: {}", listed.join(", "))
+                }
+            }
+            TriggerEvent::GitCommit { .. } => {
+                let latest = changes.iter().last().cloned().unwrap_or_default();
+                format!("Git {}", latest)
+            }
+        }
+    }
+}
+
+fn enabled_triggers(conn: &Connection) -> rusqlite::Result<Vec<AgentTrigger>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM agent_triggers WHERE enabled = 1",
+        TRIGGER_COLUMNS
+    ))?;
+    let triggers = stmt
+        .query_map([], AgentTrigger::from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(triggers)
+}
+
+/// Whether a trigger's last run, or its retry, is still queued, running or being verified
+fn run_active(conn: &Connection, run_id: Option<i64>) -> bool {
+    let Some(run_id) = run_id else {
+        return false;
+    };
+    conn.query_row(
+        "SELECT status FROM agent_runs WHERE id = ?1",
+        params![latest_attempt(conn, run_id).unwrap_or(run_id)],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .ok()
+    .flatten()
+    .is_some_and(|status| {
+        matches!(
+            status.as_str(),
+            "pending" | "queued" | "running" | "paused" | "verifying"
+        )
+    })
+}
+
+/// Queues a run for a trigger
+async fn launch_trigger(
+    app: &AppHandle,
+    trigger: &AgentTrigger,
+    cause: String,
+) -> Result<i64, String> {
+    let agent = get_agent(app.state::<AgentDb>(), trigger.agent_id).await?;
+    let task = trigger
+        .task
+        .clone()
+        .or(agent.default_task)
+        .ok_or_else(|| format!("Agent '{}' has no default task", agent.name))?;
+    queue_agent_run(
+        app,
+        QueueRunRequest {
+            agent_id: trigger.agent_id,
+            project_path: trigger.project_path.clone(),
+            task,
+            model: trigger.model.clone(),
+            priority: None,
+            interactive: false,
+            parameters: trigger.parameter_values()?,
+            trigger_cause: Some(cause),
+            isolated: false,
+            budget: None,
+        },
+    )
+    .await
+}
+
+/// Checks every enabled trigger once, starting runs for those whose changes have settled
+async fn poll_triggers(app: &AppHandle, watches: &mut HashMap<i64, TriggerWatch>) {
+    let triggers = {
+        let db = app.state::<AgentDb>();
+        let conn = match db.0.lock() {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("Failed to lock agents database: {}", e);
+                return;
+            }
+        };
+        match enabled_triggers(&conn) {
+            Ok(triggers) => triggers
+                .into_iter()
+                .map(|trigger| {
+                    let active = run_active(&conn, trigger.last_run_id);
+                    (trigger, active)
+                })
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                error!("Failed to load agent triggers: {}", e);
+                return;
+            }
+        }
+    };
+
+    // Forget triggers that were deleted or disabled
+    watches.retain(|id, _| triggers.iter().any(|(t, _)| t.id == Some(*id)));
+
+    for (trigger, active) in triggers {
+        let Some(id) = trigger.id else {
+            continue;
+        };
+        let project = PathBuf::from(&trigger.project_path);
+        let event = trigger.event.clone();
+        let snapshot =
+            match tauri::async_runtime::spawn_blocking(move || Snapshot::take(&project, &event))
+                .await
+            {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    warn!("Failed to check trigger {}: {}", id, e);
+                    continue;
+                }
+            };
+
+        let now = Instant::now();
+        let watch = match watches.get_mut(&id) {
+            Some(watch) if watch.updated_at == trigger.updated_at => watch,
+            // New or edited triggers start from what is there now
+            _ => {
+                watches.insert(id, TriggerWatch::new(trigger.updated_at.clone(), snapshot));
+                continue;
+            }
+        };
+        watch.observe(snapshot, active, now);
+        let debounce = Duration::from_millis(trigger.debounce_ms);
+        let cooldown = Duration::from_secs(trigger.cooldown_secs);
+        if !watch.ready(now, debounce, cooldown) {
+            continue;
+        }
+
+        let cause = watch.fire(&trigger.event, now);
+        info!("Trigger {} fired: {}", id, cause);
+        let launched = launch_trigger(app, &trigger, cause).await;
+        if let Err(e) = &launched {
+            warn!("Trigger {} failed to start a run: {}", id, e);
+        }
+        let db = app.state::<AgentDb>();
+        if let Ok(conn) = db.0.lock() {
+            let (run_id, error) = match &launched {
+                Ok(run_id) => (Some(*run_id), None),
+                Err(e) => (None, Some(e.clone())),
+            };
+            if let Err(e) = conn.execute(
+                "UPDATE agent_triggers SET last_fired_at = ?2, last_run_id = COALESCE(?3, last_run_id), last_error = ?4 WHERE id = ?1",
+                params![id, chrono::Utc::now().to_rfc3339(), run_id, error],
+            ) {
+                error!("Failed to update trigger {}: {}", id, e);
+            }
+        }
+        let _ = app.emit(&format!("trigger-fired:{}", id), launched.is_ok());
+    }
+}
+
+/// Watches the projects of enabled triggers while the app is running
+pub fn spawn_trigger_watcher(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut watches = HashMap::new();
+        loop {
+            poll_triggers(&app, &mut watches).await;
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+fn load_trigger(conn: &Connection, id: i64) -> Result<AgentTrigger, String> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM agent_triggers WHERE id = ?1",
+            TRIGGER_COLUMNS
+        ),
+        params![id],
+        AgentTrigger::from_row,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Trigger {} not found", id))
+}
+
+/// Checks that a trigger can run: the event is valid, there is a task and the parameter
+/// values suit the agent
+async fn check_trigger(
+    db: &State<'_, AgentDb>,
+    agent_id: i64,
+    project_path: &str,
+    event: &TriggerEvent,
+    task: Option<&str>,
+    parameters: &HashMap<String, Value>,
+) -> Result<(), String> {
+    event.validate()?;
+    let agent = get_agent(db.clone(), agent_id).await?;
+    let has_task = task
+        .or(agent.default_task.as_deref())
+        .is_some_and(|task| !task.trim().is_empty());
+    if !has_task {
+        return Err(format!(
+            "Agent '{}' has no default task; give the trigger a task",
+            agent.name
+        ));
+    }
+    resolve_parameters(
+        &parse_parameters(agent.parameters.as_deref())?,
+        parameters,
+        project_path,
+    )?;
+    Ok(())
+}
+
+/// List triggers, optionally for one agent
+#[tauri::command]
+pub async fn list_triggers(
+    db: State<'_, AgentDb>,
+    agent_id: Option<i64>,
+) -> Result<Vec<AgentTrigger>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM agent_triggers WHERE ?1 IS NULL OR agent_id = ?1 ORDER BY created_at DESC, id DESC",
+            TRIGGER_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let triggers = stmt
+        .query_map(params![agent_id], AgentTrigger::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(triggers)
+}
+
+/// Create a trigger that runs an agent on file changes or commits in a project
+#[tauri::command]
+pub async fn create_trigger(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+    project_path: String,
+    event: TriggerEvent,
+    task: Option<String>,
+    model: Option<String>,
+    parameters: Option<HashMap<String, Value>>,
+    debounce_ms: Option<u64>,
+    cooldown_secs: Option<u64>,
+) -> Result<AgentTrigger, String> {
+    let parameters = parameters.unwrap_or_default();
+    check_trigger(
+        &db,
+        agent_id,
+        &project_path,
+        &event,
+        task.as_deref(),
+        &parameters,
+    )
+    .await?;
+    let event_json = serde_json::to_string(&event).map_err(|e| e.to_string())?;
+    let parameters_json = serde_json::to_string(&parameters).map_err(|e| e.to_string())?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO agent_triggers (agent_id, project_path, event, task, model, parameters, debounce_ms, cooldown_secs)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            agent_id,
+            project_path,
+            event_json,
+            task,
+            model,
+            parameters_json,
+            debounce_ms.unwrap_or(2000) as i64,
+            cooldown_secs.unwrap_or(60) as i64
+        ],
+    )
+    .map_err(|e| format!("Failed to create trigger: {}", e))?;
+    load_trigger(&conn, conn.last_insert_rowid())
+}
+
+/// Update a trigger; it starts watching again from the project's current state
+#[tauri::command]
+pub async fn update_trigger(
+    db: State<'_, AgentDb>,
+    id: i64,
+    project_path: String,
+    event: TriggerEvent,
+    task: Option<String>,
+    model: Option<String>,
+    parameters: Option<HashMap<String, Value>>,
+    debounce_ms: Option<u64>,
+    cooldown_secs: Option<u64>,
+) -> Result<AgentTrigger, String> {
+    let agent_id = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        load_trigger(&conn, id)?.agent_id
+    };
+    let parameters = parameters.unwrap_or_default();
+    check_trigger(
+        &db,
+        agent_id,
+        &project_path,
+        &event,
+        task.as_deref(),
+        &parameters,
+    )
+    .await?;
+    let event_json = serde_json::to_string(&event).map_err(|e| e.to_string())?;
+    let parameters_json = serde_json::to_string(&parameters).map_err(|e| e.to_string())?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE agent_triggers SET project_path = ?2, event = ?3, task = ?4, model = ?5, parameters = ?6,
+            debounce_ms = ?7, cooldown_secs = ?8, updated_at = CURRENT_TIMESTAMP
+         WHERE id = ?1",
+        params![
+            id,
+            project_path,
+            event_json,
+            task,
+            model,
+            parameters_json,
+            debounce_ms.unwrap_or(2000) as i64,
+            cooldown_secs.unwrap_or(60) as i64
+        ],
+    )
+    .map_err(|e| format!("Failed to update trigger: {}", e))?;
+    load_trigger(&conn, id)
+}
+
+/// Enable or disable a trigger
+#[tauri::command]
+pub async fn set_trigger_enabled(
+    db: State<'_, AgentDb>,
+    id: i64,
+    enabled: bool,
+) -> Result<AgentTrigger, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE agent_triggers SET enabled = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+        params![id, enabled],
+    )
+    .map_err(|e| format!("Failed to update trigger: {}", e))?;
+    load_trigger(&conn, id)
+}
+
+/// Delete a trigger
+#[tauri::command]
+pub async fn delete_trigger(db: State<'_, AgentDb>, id: i64) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM agent_triggers WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_changes_are_debounced_and_cooled_down() {
+        let project = tempfile::tempdir().unwrap();
+        let root = project.path();
+        std::fs::create_dir_all(root.join("src/bin")).unwrap();
+        std::fs::create_dir_all(root.join("target/debug")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "").unwrap();
+        std::fs::write(root.join("README.md"), "").unwrap();
+
+        let event = TriggerEvent::FileChange {
+            patterns: vec!["src/**/*.rs".to_string()],
+        };
+        let start = Instant::now();
+        let mut watch = TriggerWatch::new(String::new(), Snapshot::take(root, &event));
+
+        // Only matching files outside ignored directories count
+        std::fs::write(root.join("README.md"), "changed").unwrap();
+        std::fs::write(root.join("target/debug/out.rs"), "").unwrap();
+        watch.observe(Snapshot::take(root, &event), false, start);
+        assert!(watch.pending.is_empty());
+
+        std::fs::write(root.join("src/bin/main.rs"), "fn main() {}").unwrap();
+        std::fs::remove_file(root.join("src/lib.rs")).unwrap();
+        watch.observe(Snapshot::take(root, &event), false, start);
+
+        let debounce = Duration::from_secs(2);
+        let cooldown = Duration::from_secs(60);
+        assert!(!watch.ready(start + Duration::from_secs(1), debounce, cooldown));
+        let fired_at = start + Duration::from_secs(2);
+        assert!(watch.ready(fired_at, debounce, cooldown));
+        assert_eq!(
+            watch.fire(&event, fired_at),
+            "Files changed: src/bin/main.rs, src/lib.rs"
+        );
+
+        // Changes made while the triggered run is going don't trigger it again
+        std::fs::write(root.join("src/bin/main.rs"), "fn main() { run() }").unwrap();
+        watch.observe(Snapshot::take(root, &event), true, fired_at);
+        assert!(watch.pending.is_empty());
+
+        std::fs::write(root.join("src/new.rs"), "").unwrap();
+        watch.observe(Snapshot::take(root, &event), false, fired_at);
+        assert!(!watch.ready(fired_at + Duration::from_secs(30), debounce, cooldown));
+        assert!(watch.ready(fired_at + Duration::from_secs(60), debounce, cooldown));
+    }
+
+    #[test]
+    fn test_git_commits_are_detected_per_branch() {
+        let project = tempfile::tempdir().unwrap();
+        let git = project.path().join(".git");
+        std::fs::create_dir_all(git.join("refs/heads")).unwrap();
+        std::fs::write(git.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        std::fs::write(git.join("refs/heads/main"), "1111111111\n").unwrap();
+        std::fs::write(
+            git.join("packed-refs"),
+            "# pack-refs\n2222222222 refs/heads/release\n",
+        )
+        .unwrap();
+
+        let on_head = TriggerEvent::GitCommit { branch: None };
+        let on_release = TriggerEvent::GitCommit {
+            branch: Some("release".to_string()),
+        };
+        let head = Snapshot::take(project.path(), &on_head);
+        let release = Snapshot::take(project.path(), &on_release);
+        assert_eq!(
+            release,
+            Snapshot::Git(Some(("release".to_string(), "2222222222".to_string())))
+        );
+
+        std::fs::write(git.join("refs/heads/main"), "3333333333\n").unwrap();
+        assert_eq!(
+            Snapshot::take(project.path(), &on_head).changes_since(&head),
+            vec!["commit 33333333 on main".to_string()]
+        );
+        assert!(Snapshot::take(project.path(), &on_release)
+            .changes_since(&release)
+            .is_empty());
+
+        // Switching branches is not a commit
+        std::fs::write(git.join("HEAD"), "ref: refs/heads/release\n").unwrap();
+        assert!(Snapshot::take(project.path(), &on_head)
+            .changes_since(&head)
+            .is_empty());
+    }
+}
@@ -0,0 +1,418 @@
+use log::{error, info, warn};
+use regex::Regex;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::agents::{get_agent_run, read_session_jsonl, AgentDb, AgentRun};
+use super::env_vars::run_env;
+use super::workflows::final_result;
+use crate::process::signals::terminate_process_tree;
+use crate::process::{KillPolicy, ProcessRegistryState, RunEnv};
+
+/// Time limit for each verification command unless the agent sets one
+const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Most output kept from each verification command; the end is kept
+const MAX_OUTPUT_BYTES: usize = 16 * 1024;
+
+/// Checks a run has to pass after it finishes to count as succeeded
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentVerification {
+    /// Shell commands run in the run's project, in order; each must exit with status 0
+    #[serde(default)]
+    pub commands: Vec<String>,
+    /// Regex the agent's final result message must match
+    #[serde(default)]
+    pub result_pattern: Option<String>,
+    /// Time limit for each command in seconds
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+impl AgentVerification {
+    /// Parses verification settings stored as a JSON string, ignoring empty values
+    pub fn from_json(json: Option<&str>) -> Result<Option<Self>, String> {
+        let verification: Self = match json.map(str::trim) {
+            None | Some("") => return Ok(None),
+            Some(json) => serde_json::from_str(json)
+                .map_err(|e| format!("Invalid verification settings: {}", e))?,
+        };
+        if let Some(pattern) = &verification.result_pattern {
+            Regex::new(pattern)
+                .map_err(|e| format!("Invalid result pattern '{}': {}", pattern, e))?;
+        }
+        if verification.commands.iter().any(|c| c.trim().is_empty()) {
+            return Err("Verification commands can't be empty".to_string());
+        }
+        Ok(Some(verification))
+    }
+
+    /// Whether there is nothing to check
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty() && self.result_pattern.is_none()
+    }
+}
+
+/// The outcome of one verification check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerificationCheck {
+    /// The command that was run, or the result pattern
+    pub check: String,
+    pub passed: bool,
+    /// None for the result pattern and for commands that timed out or didn't start
+    pub exit_code: Option<i32>,
+    pub output: String,
+}
+
+impl VerificationCheck {
+    /// Why the check failed, for the run's status reason
+    fn failure(&self) -> String {
+        match self.exit_code {
+            Some(code) => format!("`{}` exited with status {}", self.check, code),
+            None => format!(
+                "{}: {}",
+                self.check,
+                self.output.lines().next_back().unwrap_or("")
+            ),
+        }
+    }
+}
+
+/// The verdict of a run's verification, stored on the run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerificationReport {
+    pub passed: bool,
+    pub checks: Vec<VerificationCheck>,
+}
+
+/// Runs the checks for a finished run in `dir`, with the run's environment
+///
+/// The result pattern is checked first; commands stop at the first that fails. Commands that
+/// time out are killed along with everything they spawned, following `kill_policy`.
+pub async fn run_verification(
+    verification: &AgentVerification,
+    dir: &Path,
+    result: Option<&str>,
+    env: &RunEnv,
+    kill_policy: &KillPolicy,
+) -> VerificationReport {
+    let mut checks = Vec::new();
+    if let Some(pattern) = &verification.result_pattern {
+        checks.push(check_result(pattern, result));
+    }
+    let timeout = verification
+        .timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_COMMAND_TIMEOUT);
+    for command in &verification.commands {
+        if checks.iter().any(|check| !check.passed) {
+            break;
+        }
+        checks.push(run_command(command, dir, timeout, env, kill_policy).await);
+    }
+    VerificationReport {
+        passed: checks.iter().all(|check| check.passed),
+        checks,
+    }
+}
+
+fn check_result(pattern: &str, result: Option<&str>) -> VerificationCheck {
+    let check = format!("Result matches /{}/", pattern);
+    let (passed, output) = match (Regex::new(pattern), result) {
+        (Err(e), _) => (false, format!("Invalid pattern: {}", e)),
+        (Ok(_), None) => (false, "The run has no final result message".to_string()),
+        (Ok(regex), Some(result)) if regex.is_match(result) => (true, String::new()),
+        (Ok(_), Some(_)) => (false, "The final result message doesn't match".to_string()),
+    };
+    VerificationCheck {
+        check,
+        passed,
+        exit_code: None,
+        output,
+    }
+}
+
+async fn run_command(
+    command: &str,
+    dir: &Path,
+    timeout: Duration,
+    env: &RunEnv,
+    kill_policy: &KillPolicy,
+) -> VerificationCheck {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = tokio::process::Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    cmd.current_dir(dir)
+        .envs(&env.vars)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    // Its own process group, so a timeout also stops test runners and other workers it spawned
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    let (exit_code, output) = match cmd.spawn() {
+        Err(e) => (None, format!("Failed to run command: {}", e)),
+        Ok(mut child) => {
+            let stdout = child.stdout.take();
+            let stderr = child.stderr.take();
+            let finished = tokio::time::timeout(timeout, async {
+                let (status, stdout, stderr) =
+                    tokio::join!(child.wait(), read_all(stdout), read_all(stderr));
+                status.map(|status| (status, stdout, stderr))
+            })
+            .await;
+            match finished {
+                Ok(Ok((status, stdout, stderr))) => {
+                    let mut text = String::from_utf8_lossy(&stdout).to_string();
+                    text.push_str(&String::from_utf8_lossy(&stderr));
+                    (status.code(), tail(&env.redact(&text)))
+                }
+                Ok(Err(e)) => (None, format!("Failed to run command: {}", e)),
+                Err(_) => {
+                    if let Some(pid) = child.id() {
+                        terminate_process_tree(pid, kill_policy, || {
+                            matches!(child.try_wait(), Ok(Some(_)))
+                        })
+                        .await;
+                    }
+                    (None, format!("Timed out after {}s", timeout.as_secs()))
+                }
+            }
+        }
+    };
+    VerificationCheck {
+        check: command.to_string(),
+        passed: exit_code == Some(0),
+        exit_code,
+        output,
+    }
+}
+
+/// Everything written to a pipe until it closes
+async fn read_all<R: AsyncRead + Unpin>(pipe: Option<R>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    if let Some(mut pipe) = pipe {
+        let _ = pipe.read_to_end(&mut bytes).await;
+    }
+    bytes
+}
+
+/// The last `MAX_OUTPUT_BYTES` of `text`
+fn tail(text: &str) -> String {
+    let mut start = text.len().saturating_sub(MAX_OUTPUT_BYTES);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    text[start..].to_string()
+}
+
+/// The verification settings of the agent version a run used
+pub(crate) fn run_verification_settings(
+    conn: &Connection,
+    run: &AgentRun,
+) -> Result<Option<AgentVerification>, String> {
+    let json: Option<String> = match run.agent_version {
+        Some(version) => conn.query_row(
+            "SELECT verification FROM agent_versions WHERE agent_id = ?1 AND version = ?2",
+            params![run.agent_id, version],
+            |row| row.get(0),
+        ),
+        None => conn.query_row(
+            "SELECT verification FROM agents WHERE id = ?1",
+            params![run.agent_id],
+            |row| row.get(0),
+        ),
+    }
+    .optional()
+    .map_err(|e| e.to_string())?
+    .flatten();
+    Ok(AgentVerification::from_json(json.as_deref())?.filter(|v| !v.is_empty()))
+}
+
+/// Verifies a run left `verifying` when its process exited, marking it `succeeded` or
+/// `verification_failed`
+pub(crate) async fn verify_agent_run(app: AppHandle, run_id: i64) {
+    if let Err(e) = try_verify_agent_run(&app, run_id).await {
+        error!("Failed to verify agent run {}: {}", run_id, e);
+        let db = app.state::<AgentDb>();
+        if let Ok(conn) = db.0.lock() {
+            let _ = conn.execute(
+                "UPDATE agent_runs SET status = 'verification_failed', status_reason = ?2 WHERE id = ?1 AND status = 'verifying'",
+                params![run_id, format!("Verification couldn't run: {}", e)],
+            );
+        }
+        let _ = app.emit(&format!("agent-verified:{}", run_id), false);
+    }
+}
+
+async fn try_verify_agent_run(app: &AppHandle, run_id: i64) -> Result<(), String> {
+    let db = app.state::<AgentDb>();
+    let run = get_agent_run(db.clone(), run_id).await?;
+    if run.status != "verifying" {
+        return Ok(());
+    }
+    let verification = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        run_verification_settings(&conn, &run)?
+    };
+    let env = run_env(app, Some(run.agent_id), &run.project_path)?;
+    let kill_policy = app.state::<ProcessRegistryState>().0.kill_policy()?;
+
+    let report = match verification {
+        Some(verification) => {
+            info!("Verifying agent run {}", run_id);
+            let result = read_session_jsonl(&run.session_id, run.working_dir())
+                .await
+                .ok()
+                .and_then(|jsonl| final_result(&jsonl))
+                .and_then(|result| result.result);
+            run_verification(
+                &verification,
+                Path::new(run.working_dir()),
+                result.as_deref(),
+                &env,
+                &kill_policy,
+            )
+            .await
+        }
+        // The agent's settings went away; nothing to check
+        None => VerificationReport {
+            passed: true,
+            checks: Vec::new(),
+        },
+    };
+
+    let (status, reason) = if report.passed {
+        ("succeeded", None)
+    } else {
+        let reason = report
+            .checks
+            .iter()
+            .find(|c| !c.passed)
+            .map(|c| c.failure());
+        warn!(
+            "Agent run {} failed verification: {}",
+            run_id,
+            reason.as_deref().unwrap_or_default()
+        );
+        ("verification_failed", reason)
+    };
+    let report_json = serde_json::to_string(&report).map_err(|e| e.to_string())?;
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE agent_runs SET status = ?2, status_reason = COALESCE(?3, status_reason), verification = ?4 WHERE id = ?1 AND status = 'verifying'",
+            params![run_id, status, reason, report_json],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    let _ = app.emit(&format!("agent-verified:{}", run_id), report.passed);
+    Ok(())
+}
+
+/// Starts verification again for runs whose verification was cut short by the app closing
+pub(crate) fn resume_verifications(app: &AppHandle) -> Result<(), String> {
+    let run_ids = {
+        let db = app.state::<AgentDb>();
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare("SELECT id FROM agent_runs WHERE status = 'verifying'")
+            .map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        ids
+    };
+    for run_id in run_ids {
+        info!("Resuming verification of agent run {}", run_id);
+        tauri::async_runtime::spawn(verify_agent_run(app.clone(), run_id));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_verification_checks_result_then_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let verification = AgentVerification::from_json(Some(
+            r#"{"commands": ["echo checked", "exit 3", "echo never"], "result_pattern": "(?i)all tests pass"}"#,
+        ))
+        .unwrap()
+        .unwrap();
+
+        let (env, policy) = (RunEnv::default(), KillPolicy::default());
+        let report = run_verification(
+            &verification,
+            dir.path(),
+            Some("Done. All tests pass."),
+            &env,
+            &policy,
+        )
+        .await;
+        assert!(!report.passed);
+        assert_eq!(report.checks.len(), 3);
+        assert!(report.checks[0].passed);
+        assert_eq!(report.checks[1].exit_code, Some(0));
+        assert_eq!(report.checks[1].output.trim(), "checked");
+        assert_eq!(report.checks[2].exit_code, Some(3));
+        assert_eq!(report.checks[2].failure(), "`exit 3` exited with status 3");
+
+        // A result that doesn't match fails before any command runs
+        let report =
+            run_verification(&verification, dir.path(), Some("Gave up"), &env, &policy).await;
+        assert_eq!(report.checks.len(), 1);
+        assert!(!report.passed);
+
+        assert!(AgentVerification::from_json(Some(r#"{"result_pattern": "("}"#)).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_verification_commands_get_run_env_and_timeouts_kill_the_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut env = RunEnv::default();
+        env.set("NPM_TOKEN".to_string(), "npm_s3cret".to_string(), true);
+        let policy = KillPolicy {
+            interrupt_grace_ms: 100,
+            terminate_grace_ms: 100,
+        };
+        let verification = AgentVerification {
+            commands: vec![
+                "echo token=$NPM_TOKEN".to_string(),
+                "sleep 30 & echo $! > worker.pid; wait".to_string(),
+            ],
+            result_pattern: None,
+            timeout_secs: Some(1),
+        };
+
+        let report = run_verification(&verification, dir.path(), None, &env, &policy).await;
+        assert_eq!(report.checks[0].output.trim(), "token=[REDACTED]");
+        assert_eq!(report.checks[1].exit_code, None);
+        assert_eq!(report.checks[1].output, "Timed out after 1s");
+
+        let worker: u32 = std::fs::read_to_string(dir.path().join("worker.pid"))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        assert!(!crate::process::signals::is_pid_alive(worker));
+    }
+}
@@ -0,0 +1,1084 @@
+use log::{error, info, warn};
+use regex::Regex;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use super::agent_parameters::substitute;
+use super::agents::{
+    kill_agent_session, queue_agent_run, read_session_jsonl, AgentDb, QueueRunRequest,
+};
+use super::queue::cancel_queued_run;
+use super::retries::latest_attempt;
+use crate::process::stream::{ResultEvent, StreamEvent, StreamLine};
+
+/// How often running workflows are checked for finished steps
+const DRIVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// When a step runs, based on how the steps it depends on finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StepCondition {
+    /// Every dependency completed successfully
+    #[default]
+    OnSuccess,
+    /// At least one dependency failed
+    OnFailure,
+    /// Every dependency finished, however it went
+    Always,
+}
+
+/// One agent run within a workflow
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowStep {
+    /// Name other steps use to depend on this step and to refer to its output
+    pub id: String,
+    pub agent_id: i64,
+    /// Task for the agent; `{{steps.<id>.result}}`, `{{steps.<id>.status}}`,
+    /// `{{steps.<id>.output}}` and `{{steps.<id>.output.<field>}}` are filled in from
+    /// steps this one depends on
+    pub task: String,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub condition: StepCondition,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Values for the agent's parameters; strings may use the same placeholders as the task
+    #[serde(default)]
+    pub parameters: HashMap<String, Value>,
+}
+
+/// A named DAG of agent steps
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workflow {
+    pub id: Option<i64>,
+    pub name: String,
+    pub description: Option<String>,
+    pub steps: Vec<WorkflowStep>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Columns selected when loading a `Workflow`, in the order `Workflow::from_row` expects
+const WORKFLOW_COLUMNS: &str = "id, name, description, steps, created_at, updated_at";
+
+impl Workflow {
+    /// Builds a `Workflow` from a row selected with `WORKFLOW_COLUMNS`
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: Some(row.get(0)?),
+            name: row.get(1)?,
+            description: row.get(2)?,
+            steps: parse_steps_column(row, 3)?,
+            created_at: row.get(4)?,
+            updated_at: row.get(5)?,
+        })
+    }
+}
+
+fn parse_steps_column(row: &rusqlite::Row, idx: usize) -> rusqlite::Result<Vec<WorkflowStep>> {
+    let json: String = row.get(idx)?;
+    serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Pending,
+    /// The step's agent run is queued or running
+    Running,
+    Completed,
+    Failed,
+    /// The step's condition wasn't met
+    Skipped,
+    /// The workflow was cancelled before the step finished
+    Cancelled,
+}
+
+impl StepStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            StepStatus::Pending => "pending",
+            StepStatus::Running => "running",
+            StepStatus::Completed => "completed",
+            StepStatus::Failed => "failed",
+            StepStatus::Skipped => "skipped",
+            StepStatus::Cancelled => "cancelled",
+        }
+    }
+
+    fn parse(status: &str) -> Self {
+        match status {
+            "running" => StepStatus::Running,
+            "completed" => StepStatus::Completed,
+            "failed" => StepStatus::Failed,
+            "skipped" => StepStatus::Skipped,
+            "cancelled" => StepStatus::Cancelled,
+            _ => StepStatus::Pending,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        !matches!(self, StepStatus::Pending | StepStatus::Running)
+    }
+}
+
+/// Progress of one step within a workflow run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowStepRun {
+    pub step_id: String,
+    pub status: StepStatus,
+    pub agent_run_id: Option<i64>,
+    pub result: Option<String>, // Final result text of the agent run
+    pub status_reason: Option<String>,
+}
+
+/// One execution of a workflow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowRun {
+    pub id: i64,
+    pub workflow_id: i64,
+    pub workflow_name: String,
+    pub project_path: String,
+    pub status: String, // 'running', 'completed', 'failed' or 'cancelled'
+    pub status_reason: Option<String>,
+    pub steps: Vec<WorkflowStep>, // The workflow's steps when the run started
+    pub step_runs: Vec<WorkflowStepRun>,
+    pub created_at: String,
+    pub completed_at: Option<String>,
+}
+
+/// Serializes workflow advancement so a step is never started twice
+#[derive(Default)]
+pub struct WorkflowDriverState(pub tokio::sync::Mutex<()>);
+
+/// Creates the workflow tables
+pub fn init_workflows(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS workflows (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            description TEXT,
+            steps TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS workflow_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            workflow_id INTEGER NOT NULL,
+            workflow_name TEXT NOT NULL,
+            project_path TEXT NOT NULL,
+            steps TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'running',
+            status_reason TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            completed_at TEXT,
+            FOREIGN KEY (workflow_id) REFERENCES workflows(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // One row per step, linking it to the agent run that executed it
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS workflow_run_steps (
+            workflow_run_id INTEGER NOT NULL,
+            step_id TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'pending',
+            agent_run_id INTEGER,
+            result TEXT,
+            status_reason TEXT,
+            PRIMARY KEY (workflow_run_id, step_id),
+            FOREIGN KEY (workflow_run_id) REFERENCES workflow_runs(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Matches placeholders that refer to another step's output, capturing the step ID
+fn step_reference_regex() -> Regex {
+    Regex::new(r"\{\{steps\.([A-Za-z0-9_-]+)\.").expect("valid regex")
+}
+
+/// Checks that steps form a DAG and only refer to the output of steps they depend on
+fn validate_steps(steps: &[WorkflowStep]) -> Result<(), String> {
+    if steps.is_empty() {
+        return Err("A workflow needs at least one step".to_string());
+    }
+
+    let mut by_id = HashMap::new();
+    for step in steps {
+        let valid_id = !step.id.is_empty()
+            && step
+                .id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_id {
+            return Err(format!(
+                "Invalid step ID '{}': use letters, digits, '-' and '_'",
+                step.id
+            ));
+        }
+        if by_id.insert(step.id.as_str(), step).is_some() {
+            return Err(format!("Step '{}' is declared twice", step.id));
+        }
+    }
+
+    for step in steps {
+        if let Some(unknown) = step
+            .depends_on
+            .iter()
+            .find(|d| !by_id.contains_key(d.as_str()))
+        {
+            return Err(format!(
+                "Step '{}' depends on unknown step '{}'",
+                step.id, unknown
+            ));
+        }
+        if step.condition == StepCondition::OnFailure && step.depends_on.is_empty() {
+            return Err(format!(
+                "Step '{}' runs on failure but doesn't depend on any step",
+                step.id
+            ));
+        }
+    }
+
+    // Depth-first search; a step seen again while still on the stack closes a cycle
+    fn visit<'a>(
+        id: &'a str,
+        by_id: &HashMap<&'a str, &'a WorkflowStep>,
+        on_stack: &mut HashSet<&'a str>,
+        done: &mut HashSet<&'a str>,
+    ) -> Result<(), String> {
+        if done.contains(id) {
+            return Ok(());
+        }
+        if !on_stack.insert(id) {
+            return Err(format!("Steps form a cycle through '{}'", id));
+        }
+        let step: &'a WorkflowStep = by_id[id];
+        for dep in &step.depends_on {
+            visit(dep, by_id, on_stack, done)?;
+        }
+        on_stack.remove(id);
+        done.insert(id);
+        Ok(())
+    }
+    let mut done = HashSet::new();
+    for step in steps {
+        visit(&step.id, &by_id, &mut HashSet::new(), &mut done)?;
+    }
+
+    let references = step_reference_regex();
+    for step in steps {
+        let upstream = ancestors(&step.id, &by_id);
+        let templates = std::iter::once(step.task.as_str())
+            .chain(step.parameters.values().filter_map(Value::as_str));
+        for template in templates {
+            for capture in references.captures_iter(template) {
+                let referenced = &capture[1];
+                if !upstream.contains(referenced) {
+                    return Err(format!(
+                        "Step '{}' refers to the output of '{}', which it doesn't depend on",
+                        step.id, referenced
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Steps that finish before the given step, directly or indirectly
+fn ancestors<'a>(id: &str, by_id: &HashMap<&'a str, &'a WorkflowStep>) -> HashSet<&'a str> {
+    let mut found = HashSet::new();
+    let mut pending: Vec<&str> = by_id[id].depends_on.iter().map(String::as_str).collect();
+    while let Some(dep) = pending.pop() {
+        if let Some((&key, step)) = by_id.get_key_value(dep) {
+            if found.insert(key) {
+                pending.extend(step.depends_on.iter().map(String::as_str));
+            }
+        }
+    }
+    found
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepAction {
+    Start,
+    Skip,
+}
+
+/// What to do with a pending step, or `None` while its dependencies are still going
+fn next_action(step: &WorkflowStep, statuses: &HashMap<String, StepStatus>) -> Option<StepAction> {
+    let deps: Vec<StepStatus> = step
+        .depends_on
+        .iter()
+        .map(|dep| statuses.get(dep).copied().unwrap_or(StepStatus::Pending))
+        .collect();
+    if deps.iter().any(|status| !status.is_finished()) {
+        return None;
+    }
+
+    let start = match step.condition {
+        StepCondition::OnSuccess => deps.iter().all(|s| *s == StepStatus::Completed),
+        StepCondition::OnFailure => deps.contains(&StepStatus::Failed),
+        StepCondition::Always => true,
+    };
+    Some(if start {
+        StepAction::Start
+    } else {
+        StepAction::Skip
+    })
+}
+
+/// The final status of a workflow run once every step has finished
+///
+/// A failed step fails the workflow unless an `on_failure` step ran to handle it.
+fn workflow_outcome(
+    steps: &[WorkflowStep],
+    statuses: &HashMap<String, StepStatus>,
+) -> Option<(&'static str, Option<String>)> {
+    let status = |id: &str| statuses.get(id).copied().unwrap_or(StepStatus::Pending);
+    if steps.iter().any(|step| !status(&step.id).is_finished()) {
+        return None;
+    }
+
+    let unhandled = steps.iter().find(|failed| {
+        status(&failed.id) == StepStatus::Failed
+            && !steps.iter().any(|handler| {
+                handler.condition == StepCondition::OnFailure
+                    && handler.depends_on.contains(&failed.id)
+                    && matches!(
+                        status(&handler.id),
+                        StepStatus::Completed | StepStatus::Failed
+                    )
+            })
+    });
+    Some(match unhandled {
+        Some(step) => ("failed", Some(format!("Step '{}' failed", step.id))),
+        None => ("completed", None),
+    })
+}
+
+/// The `result` line a run ended with
+pub(crate) fn final_result(jsonl: &str) -> Option<ResultEvent> {
+    jsonl
+        .lines()
+        .rev()
+        .filter_map(StreamLine::parse)
+        .find_map(|line| match line.event {
+            StreamEvent::Result(result) => Some(result),
+            _ => None,
+        })
+}
+
+/// JSON in a step's result: the whole text, or else its last ```json block
+fn structured_output(result: &str) -> Option<Value> {
+    let trimmed = result.trim();
+    if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        if let Ok(value) = serde_json::from_str(trimmed) {
+            return Some(value);
+        }
+    }
+
+    let block = Regex::new(r"(?s)```json\s*\n(.*?)```").expect("valid regex");
+    block
+        .captures_iter(result)
+        .last()
+        .and_then(|capture| serde_json::from_str(capture[1].trim()).ok())
+}
+
+/// Placeholder values for the output of finished steps, keyed as `steps.<id>.<field>`
+fn step_values(step_runs: &[WorkflowStepRun]) -> BTreeMap<String, Value> {
+    let mut values = BTreeMap::new();
+    for step in step_runs.iter().filter(|s| s.status.is_finished()) {
+        let key = |field: &str| format!("steps.{}.{}", step.step_id, field);
+        values.insert(key("status"), Value::from(step.status.as_str()));
+        let Some(result) = &step.result else {
+            continue;
+        };
+        values.insert(key("result"), Value::from(result.as_str()));
+        if let Some(output) = structured_output(result) {
+            if let Value::Object(fields) = &output {
+                for (field, value) in fields {
+                    values.insert(key(&format!("output.{}", field)), value.clone());
+                }
+            }
+            values.insert(key("output"), output);
+        }
+    }
+    values
+}
+
+/// Loads a workflow run with the progress of its steps
+fn load_workflow_run(conn: &Connection, id: i64) -> Result<WorkflowRun, String> {
+    let mut run = conn
+        .query_row(
+            "SELECT id, workflow_id, workflow_name, project_path, steps, status, status_reason, created_at, completed_at
+             FROM workflow_runs WHERE id = ?1",
+            params![id],
+            |row| {
+                Ok(WorkflowRun {
+                    id: row.get(0)?,
+                    workflow_id: row.get(1)?,
+                    workflow_name: row.get(2)?,
+                    project_path: row.get(3)?,
+                    steps: parse_steps_column(row, 4)?,
+                    status: row.get(5)?,
+                    status_reason: row.get(6)?,
+                    created_at: row.get(7)?,
+                    completed_at: row.get(8)?,
+                    step_runs: Vec::new(),
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Workflow run {} not found", id))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT step_id, status, agent_run_id, result, status_reason FROM workflow_run_steps
+             WHERE workflow_run_id = ?1 ORDER BY rowid",
+        )
+        .map_err(|e| e.to_string())?;
+    run.step_runs = stmt
+        .query_map(params![id], |row| {
+            Ok(WorkflowStepRun {
+                step_id: row.get(0)?,
+                status: StepStatus::parse(&row.get::<_, String>(1)?),
+                agent_run_id: row.get(2)?,
+                result: row.get(3)?,
+                status_reason: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(run)
+}
+
+/// Writes a step's progress back to `workflow_run_steps`
+fn save_step_run(
+    conn: &Connection,
+    workflow_run_id: i64,
+    step: &WorkflowStepRun,
+) -> Result<(), String> {
+    conn.execute(
+        "UPDATE workflow_run_steps SET status = ?3, agent_run_id = ?4, result = ?5, status_reason = ?6
+         WHERE workflow_run_id = ?1 AND step_id = ?2",
+        params![
+            workflow_run_id,
+            step.step_id,
+            step.status.as_str(),
+            step.agent_run_id,
+            step.result,
+            step.status_reason
+        ],
+    )
+    .map_err(|e| format!("Failed to update workflow step: {}", e))?;
+    Ok(())
+}
+
+fn check_agents_exist(conn: &Connection, steps: &[WorkflowStep]) -> Result<(), String> {
+    for step in steps {
+        let exists: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM agents WHERE id = ?1)",
+                params![step.agent_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !exists {
+            return Err(format!(
+                "Step '{}' uses unknown agent {}",
+                step.id, step.agent_id
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Records the outcome of steps whose agent runs have finished
+async fn collect_finished_steps(app: &AppHandle, run: &mut WorkflowRun) -> Result<(), String> {
+    let db = app.state::<AgentDb>();
+    for step in run
+        .step_runs
+        .iter_mut()
+        .filter(|s| s.status == StepStatus::Running)
+    {
+        let Some(agent_run_id) = step.agent_run_id else {
+            continue;
+        };
+        let agent_run = {
+            let conn = db.0.lock().map_err(|e| e.to_string())?;
+            // A failed run that was retried is judged by its latest attempt
+            let agent_run_id = latest_attempt(&conn, agent_run_id)?;
+            conn.query_row(
+                "SELECT status, status_reason, session_id, COALESCE(worktree_path, project_path) FROM agent_runs WHERE id = ?1",
+                params![agent_run_id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+        };
+
+        let Some((status, status_reason, session_id, project_path)) = agent_run else {
+            step.status = StepStatus::Failed;
+            step.status_reason = Some(format!("Agent run {} no longer exists", agent_run_id));
+            continue;
+        };
+        if matches!(
+            status.as_str(),
+            "pending" | "queued" | "running" | "paused" | "verifying"
+        ) {
+            continue;
+        }
+
+        let result = read_session_jsonl(&session_id, &project_path)
+            .await
+            .ok()
+            .and_then(|jsonl| final_result(&jsonl));
+        step.result = result.as_ref().and_then(|r| r.result.clone());
+        match (status.as_str(), result) {
+            ("completed" | "succeeded", Some(result)) if result.is_error => {
+                step.status = StepStatus::Failed;
+                step.status_reason = Some(format!("Agent reported an error ({})", result.subtype));
+            }
+            ("completed" | "succeeded", _) => step.status = StepStatus::Completed,
+            (other, _) => {
+                step.status = StepStatus::Failed;
+                step.status_reason =
+                    Some(status_reason.unwrap_or_else(|| format!("Agent run {}", other)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Moves a workflow run forward: records finished steps, starts or skips steps whose
+/// dependencies are done, and completes the run once no step is left
+async fn advance_workflow_run(app: &AppHandle, id: i64) -> Result<(), String> {
+    let db = app.state::<AgentDb>();
+    let mut run = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        load_workflow_run(&conn, id)?
+    };
+    let before: Vec<StepStatus> = run.step_runs.iter().map(|s| s.status).collect();
+
+    collect_finished_steps(app, &mut run).await?;
+
+    // Skipping a step can make the steps after it ready, so repeat until nothing changes
+    loop {
+        let statuses: HashMap<String, StepStatus> = run
+            .step_runs
+            .iter()
+            .map(|s| (s.step_id.clone(), s.status))
+            .collect();
+        let ready = run.steps.iter().find_map(|step| {
+            let index = run.step_runs.iter().position(|s| s.step_id == step.id)?;
+            if run.step_runs[index].status != StepStatus::Pending {
+                return None;
+            }
+            next_action(step, &statuses).map(|action| (index, step.clone(), action))
+        });
+        let Some((index, step, action)) = ready else {
+            break;
+        };
+
+        if action == StepAction::Skip {
+            run.step_runs[index].status = StepStatus::Skipped;
+            continue;
+        }
+
+        let values = step_values(&run.step_runs);
+        let parameters = step
+            .parameters
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::String(text) => Value::String(substitute(text, &values)),
+                    other => other.clone(),
+                };
+                (name.clone(), value)
+            })
+            .collect();
+        info!("Workflow run {} is starting step '{}'", id, step.id);
+        let started = queue_agent_run(
+            app,
+            QueueRunRequest {
+                agent_id: step.agent_id,
+                project_path: run.project_path.clone(),
+                task: substitute(&step.task, &values),
+                model: step.model.clone(),
+                priority: None,
+                interactive: false,
+                parameters,
+                trigger_cause: Some(format!(
+                    "Workflow '{}' (run {}), step '{}'",
+                    run.workflow_name, id, step.id
+                )),
+                isolated: false,
+                budget: None,
+            },
+        )
+        .await;
+
+        let step_run = &mut run.step_runs[index];
+        match started {
+            Ok(agent_run_id) => {
+                step_run.status = StepStatus::Running;
+                step_run.agent_run_id = Some(agent_run_id);
+            }
+            Err(e) => {
+                warn!(
+                    "Workflow run {} failed to start step '{}': {}",
+                    id, step.id, e
+                );
+                step_run.status = StepStatus::Failed;
+                step_run.status_reason = Some(format!("Failed to start: {}", e));
+            }
+        }
+    }
+
+    let statuses: HashMap<String, StepStatus> = run
+        .step_runs
+        .iter()
+        .map(|s| (s.step_id.clone(), s.status))
+        .collect();
+    let outcome = workflow_outcome(&run.steps, &statuses);
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        for (step, previous) in run.step_runs.iter().zip(before) {
+            if step.status != previous {
+                save_step_run(&conn, id, step)?;
+            }
+        }
+        if let Some((status, reason)) = &outcome {
+            conn.execute(
+                "UPDATE workflow_runs SET status = ?2, status_reason = ?3, completed_at = CURRENT_TIMESTAMP
+                 WHERE id = ?1 AND status = 'running'",
+                params![id, status, reason],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let _ = app.emit(&format!("workflow-progress:{}", id), &run.step_runs);
+    if let Some((status, _)) = outcome {
+        info!("Workflow run {} finished as {}", id, status);
+        let _ = app.emit(&format!("workflow-complete:{}", id), status == "completed");
+    }
+    Ok(())
+}
+
+fn running_workflow_run_ids(conn: &Connection) -> rusqlite::Result<Vec<i64>> {
+    let mut stmt = conn.prepare("SELECT id FROM workflow_runs WHERE status = 'running'")?;
+    let ids = stmt
+        .query_map([], |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ids)
+}
+
+/// Advances every running workflow
+pub async fn advance_workflow_runs(app: &AppHandle) {
+    let driver = app.state::<WorkflowDriverState>();
+    let _guard = driver.0.lock().await;
+
+    let ids = {
+        let db = app.state::<AgentDb>();
+        let conn = match db.0.lock() {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("Failed to lock agents database: {}", e);
+                return;
+            }
+        };
+        running_workflow_run_ids(&conn)
+    };
+
+    match ids {
+        Ok(ids) => {
+            for id in ids {
+                if let Err(e) = advance_workflow_run(app, id).await {
+                    error!("Failed to advance workflow run {}: {}", id, e);
+                }
+            }
+        }
+        Err(e) => error!("Failed to list running workflows: {}", e),
+    }
+}
+
+/// Periodically advances running workflows as their agent runs finish
+pub fn spawn_workflow_driver(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            advance_workflow_runs(&app).await;
+            tokio::time::sleep(DRIVE_INTERVAL).await;
+        }
+    });
+}
+
+/// List all workflows
+#[tauri::command]
+pub async fn list_workflows(db: State<'_, AgentDb>) -> Result<Vec<Workflow>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM workflows ORDER BY created_at DESC",
+            WORKFLOW_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let workflows = stmt
+        .query_map([], Workflow::from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(workflows)
+}
+
+/// Get a single workflow by ID
+#[tauri::command]
+pub async fn get_workflow(db: State<'_, AgentDb>, id: i64) -> Result<Workflow, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.query_row(
+        &format!("SELECT {} FROM workflows WHERE id = ?1", WORKFLOW_COLUMNS),
+        params![id],
+        Workflow::from_row,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Workflow {} not found", id))
+}
+
+/// Create a new workflow
+#[tauri::command]
+pub async fn create_workflow(
+    db: State<'_, AgentDb>,
+    name: String,
+    description: Option<String>,
+    steps: Vec<WorkflowStep>,
+) -> Result<Workflow, String> {
+    validate_steps(&steps)?;
+    let id = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        check_agents_exist(&conn, &steps)?;
+        let steps_json = serde_json::to_string(&steps).map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO workflows (name, description, steps) VALUES (?1, ?2, ?3)",
+            params![name, description, steps_json],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.last_insert_rowid()
+    };
+    get_workflow(db, id).await
+}
+
+/// Update a workflow; runs already started keep the steps they started with
+#[tauri::command]
+pub async fn update_workflow(
+    db: State<'_, AgentDb>,
+    id: i64,
+    name: String,
+    description: Option<String>,
+    steps: Vec<WorkflowStep>,
+) -> Result<Workflow, String> {
+    validate_steps(&steps)?;
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        check_agents_exist(&conn, &steps)?;
+        let steps_json = serde_json::to_string(&steps).map_err(|e| e.to_string())?;
+        let updated = conn
+            .execute(
+                "UPDATE workflows SET name = ?2, description = ?3, steps = ?4, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+                params![id, name, description, steps_json],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Err(format!("Workflow {} not found", id));
+        }
+    }
+    get_workflow(db, id).await
+}
+
+/// Delete a workflow and its run history
+#[tauri::command]
+pub async fn delete_workflow(db: State<'_, AgentDb>, id: i64) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM workflow_run_steps WHERE workflow_run_id IN (SELECT id FROM workflow_runs WHERE workflow_id = ?1)",
+        params![id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM workflow_runs WHERE workflow_id = ?1",
+        params![id],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM workflows WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Start a workflow in a project; steps without dependencies are queued right away
+#[tauri::command]
+pub async fn run_workflow(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    workflow_id: i64,
+    project_path: String,
+) -> Result<i64, String> {
+    let workflow = get_workflow(db.clone(), workflow_id).await?;
+    let run_id = {
+        let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let steps_json = serde_json::to_string(&workflow.steps).map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT INTO workflow_runs (workflow_id, workflow_name, project_path, steps) VALUES (?1, ?2, ?3, ?4)",
+            params![workflow_id, workflow.name, project_path, steps_json],
+        )
+        .map_err(|e| e.to_string())?;
+        let run_id = tx.last_insert_rowid();
+        for step in &workflow.steps {
+            tx.execute(
+                "INSERT INTO workflow_run_steps (workflow_run_id, step_id) VALUES (?1, ?2)",
+                params![run_id, step.id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        run_id
+    };
+    info!("Started workflow '{}' as run {}", workflow.name, run_id);
+
+    advance_workflow_runs(&app).await;
+    Ok(run_id)
+}
+
+/// List workflow runs, newest first, optionally for one workflow
+#[tauri::command]
+pub async fn list_workflow_runs(
+    db: State<'_, AgentDb>,
+    workflow_id: Option<i64>,
+) -> Result<Vec<WorkflowRun>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id FROM workflow_runs WHERE ?1 IS NULL OR workflow_id = ?1 ORDER BY created_at DESC, id DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let ids = stmt
+        .query_map(params![workflow_id], |row| row.get::<_, i64>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    ids.into_iter()
+        .map(|id| load_workflow_run(&conn, id))
+        .collect()
+}
+
+/// Get a workflow run with the progress of each step
+#[tauri::command]
+pub async fn get_workflow_run(db: State<'_, AgentDb>, id: i64) -> Result<WorkflowRun, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    load_workflow_run(&conn, id)
+}
+
+/// Cancel a running workflow, stopping the agent runs of its unfinished steps
+#[tauri::command]
+pub async fn cancel_workflow_run(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    registry: State<'_, crate::process::ProcessRegistryState>,
+    id: i64,
+) -> Result<bool, String> {
+    let driver = app.state::<WorkflowDriverState>();
+    let _guard = driver.0.lock().await;
+
+    let agent_run_ids = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let updated = conn
+            .execute(
+                "UPDATE workflow_runs SET status = 'cancelled', status_reason = 'Cancelled by user', completed_at = CURRENT_TIMESTAMP
+                 WHERE id = ?1 AND status = 'running'",
+                params![id],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Ok(false);
+        }
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT agent_run_id FROM workflow_run_steps
+                 WHERE workflow_run_id = ?1 AND status = 'running' AND agent_run_id IS NOT NULL",
+            )
+            .map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map(params![id], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE workflow_run_steps SET status = 'cancelled' WHERE workflow_run_id = ?1 AND status IN ('pending', 'running')",
+            params![id],
+        )
+        .map_err(|e| e.to_string())?;
+        // Stop the attempt in progress of runs that were retried
+        ids.into_iter()
+            .map(|run_id| latest_attempt(&conn, run_id))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    for agent_run_id in agent_run_ids {
+        if cancel_queued_run(app.clone(), db.clone(), agent_run_id).await? {
+            continue;
+        }
+        if let Err(e) = kill_agent_session(
+            app.clone(),
+            db.clone(),
+            registry.clone(),
+            agent_run_id,
+            None,
+            None,
+        )
+        .await
+        {
+            warn!(
+                "Failed to stop agent run {} of workflow run {}: {}",
+                agent_run_id, id, e
+            );
+        }
+    }
+
+    let _ = app.emit(&format!("workflow-complete:{}", id), false);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn step(id: &str, depends_on: &[&str], condition: StepCondition, task: &str) -> WorkflowStep {
+        WorkflowStep {
+            id: id.to_string(),
+            agent_id: 1,
+            task: task.to_string(),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            condition,
+            model: None,
+            parameters: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_validate_steps_rejects_bad_graphs() {
+        use StepCondition::*;
+
+        let pipeline = vec![
+            step("tests", &[], OnSuccess, "Write tests"),
+            step("scan", &["tests"], OnSuccess, "Scan {{steps.tests.result}}"),
+            step(
+                "commit",
+                &["scan"],
+                OnSuccess,
+                "Commit ({{steps.tests.output.files}})",
+            ),
+        ];
+        assert!(validate_steps(&pipeline).is_ok());
+
+        let mut cycle = pipeline.clone();
+        cycle[0].depends_on = vec!["commit".to_string()];
+        assert!(validate_steps(&cycle).unwrap_err().contains("cycle"));
+
+        let mut unknown = pipeline.clone();
+        unknown[1].depends_on = vec!["lint".to_string()];
+        assert!(validate_steps(&unknown)
+            .unwrap_err()
+            .contains("unknown step 'lint'"));
+
+        // The scan can't see output from the commit step, which runs after it
+        let mut reference = pipeline.clone();
+        reference[1].task = "Scan {{steps.commit.result}}".to_string();
+        assert!(validate_steps(&reference)
+            .unwrap_err()
+            .contains("doesn't depend on"));
+
+        assert!(validate_steps(&[step("fix", &[], OnFailure, "Fix it")]).is_err());
+        assert!(validate_steps(&[]).is_err());
+    }
+
+    #[test]
+    fn test_steps_run_by_condition_and_pass_output() {
+        use StepCondition::*;
+        use StepStatus::*;
+
+        let steps = vec![
+            step("tests", &[], OnSuccess, ""),
+            step("scan", &["tests"], OnSuccess, ""),
+            step("fix", &["tests"], OnFailure, ""),
+            step("report", &["scan", "fix"], Always, ""),
+        ];
+        let mut statuses: HashMap<String, StepStatus> =
+            steps.iter().map(|s| (s.id.clone(), Pending)).collect();
+
+        assert_eq!(next_action(&steps[0], &statuses), Some(StepAction::Start));
+        assert_eq!(next_action(&steps[1], &statuses), None);
+
+        statuses.insert("tests".to_string(), Failed);
+        assert_eq!(next_action(&steps[1], &statuses), Some(StepAction::Skip));
+        assert_eq!(next_action(&steps[2], &statuses), Some(StepAction::Start));
+        assert_eq!(next_action(&steps[3], &statuses), None);
+
+        statuses.insert("scan".to_string(), Skipped);
+        statuses.insert("fix".to_string(), Completed);
+        assert_eq!(next_action(&steps[3], &statuses), Some(StepAction::Start));
+        assert_eq!(workflow_outcome(&steps, &statuses), None);
+
+        // The failed tests were handled by the fix step
+        statuses.insert("report".to_string(), Completed);
+        assert_eq!(
+            workflow_outcome(&steps, &statuses),
+            Some(("completed", None))
+        );
+        statuses.insert("fix".to_string(), Skipped);
+        assert_eq!(workflow_outcome(&steps, &statuses).unwrap().0, "failed");
+
+        let runs = vec![WorkflowStepRun {
+            step_id: "tests".to_string(),
+            status: Completed,
+            agent_run_id: Some(7),
+            result: Some(
+                "Added tests.\n```json\n{\"files\": [\"a.rs\"], \"count\": 3}\n```".to_string(),
+            ),
+            status_reason: None,
+        }];
+        let values = step_values(&runs);
+        assert_eq!(values["steps.tests.output.count"], json!(3));
+        assert_eq!(
+            substitute(
+                "Scan {{steps.tests.output.files}} ({{steps.tests.status}})",
+                &values
+            ),
+            r#"Scan ["a.rs"] (completed)"#
+        );
+    }
+}
@@ -0,0 +1,615 @@
+use log::{info, warn};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::State;
+
+use super::agents::{get_agent_run, AgentDb, AgentRun};
+
+/// What to do with an isolated run's changes once it has finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorktreeAction {
+    /// Merge the run's branch into the project's checked-out branch
+    Merge,
+    /// Apply the run's commits on top of the project's checked-out branch
+    CherryPick,
+    /// Throw the changes away
+    Discard,
+}
+
+impl WorktreeAction {
+    /// The `worktree_state` of a run once the action is done
+    fn state(self) -> &'static str {
+        match self {
+            WorktreeAction::Merge => "merged",
+            WorktreeAction::CherryPick => "cherry_picked",
+            WorktreeAction::Discard => "discarded",
+        }
+    }
+}
+
+/// Where an isolated run works: a git worktree on its own branch, or a plain copy of the
+/// project when it isn't a git repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Worktree {
+    /// The directory the agent runs in; inside the worktree when the project is a
+    /// subdirectory of its repository
+    pub path: PathBuf,
+    /// The worktree's branch; None for copies
+    pub branch: Option<String>,
+    /// The commit the branch started from; None for copies
+    pub base: Option<String>,
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// A lowercase name usable in branch and directory names
+fn slug(name: &str) -> String {
+    let slug = name
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "agent".to_string()
+    } else {
+        slug
+    }
+}
+
+/// Creates a worktree for a run of `agent_name` under `worktrees_dir`
+///
+/// Git projects get a `git worktree` on a new `agents/...` branch from the checked-out
+/// commit; uncommitted changes in the project are not carried over. Other projects are copied,
+/// with a manifest of the copied files' hashes next to the copy.
+pub fn create_worktree(
+    project_path: &Path,
+    worktrees_dir: &Path,
+    agent_name: &str,
+) -> Result<Worktree, String> {
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let name = format!("{}-{}", slug(agent_name), &id[..8]);
+    let dir = worktrees_dir.join(&name);
+    std::fs::create_dir_all(worktrees_dir)
+        .map_err(|e| format!("Failed to create worktrees directory: {}", e))?;
+
+    let Ok(toplevel) = git(project_path, &["rev-parse", "--show-toplevel"]) else {
+        let manifest = copy_dir(project_path, &dir)?;
+        write_manifest(&dir, &manifest)?;
+        info!("Copied {:?} to {:?} for an isolated run", project_path, dir);
+        return Ok(Worktree {
+            path: dir,
+            branch: None,
+            base: None,
+        });
+    };
+
+    let prefix = git(project_path, &["rev-parse", "--show-prefix"])?;
+    let base = git(project_path, &["rev-parse", "HEAD"])
+        .map_err(|_| "The project's repository has no commits yet".to_string())?;
+    let branch = format!("agents/{}", name);
+    git(
+        Path::new(&toplevel),
+        &[
+            "worktree",
+            "add",
+            "-b",
+            &branch,
+            &dir.to_string_lossy(),
+            &base,
+        ],
+    )?;
+    info!("Created worktree {:?} on branch {}", dir, branch);
+
+    Ok(Worktree {
+        path: dir.join(prefix),
+        branch: Some(branch),
+        base: Some(base),
+    })
+}
+
+/// Copies a directory tree, keeping symlinks as links, and returns the hash of every file
+/// copied by its relative path
+fn copy_dir(from: &Path, to: &Path) -> Result<BTreeMap<String, String>, String> {
+    let mut manifest = BTreeMap::new();
+    for entry in walkdir::WalkDir::new(from) {
+        let entry = entry.map_err(|e| format!("Failed to copy project: {}", e))?;
+        let relative = entry.path().strip_prefix(from).map_err(|e| e.to_string())?;
+        let target = to.join(relative);
+        let file_type = entry.file_type();
+        let copied = if file_type.is_dir() {
+            std::fs::create_dir_all(&target)
+        } else if file_type.is_symlink() {
+            copy_symlink(entry.path(), &target)
+        } else {
+            std::fs::copy(entry.path(), &target).and_then(|_| {
+                manifest.insert(relative_name(relative), hash_file(&target)?);
+                Ok(())
+            })
+        };
+        copied.map_err(|e| format!("Failed to copy {:?}: {}", entry.path(), e))?;
+    }
+    Ok(manifest)
+}
+
+fn relative_name(relative: &Path) -> String {
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(std::fs::read(path)?);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// The hash of a file, or None when it doesn't exist
+fn current_hash(path: &Path) -> Result<Option<String>, String> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    hash_file(path)
+        .map(Some)
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))
+}
+
+/// Where the manifest of a copy is kept; outside the copy so the agent doesn't see it
+fn manifest_path(copy: &Path) -> PathBuf {
+    let name = copy.file_name().unwrap_or_default().to_string_lossy();
+    copy.with_file_name(format!("{}.manifest.json", name))
+}
+
+fn write_manifest(copy: &Path, manifest: &BTreeMap<String, String>) -> Result<(), String> {
+    let json = serde_json::to_string(manifest).map_err(|e| e.to_string())?;
+    std::fs::write(manifest_path(copy), json)
+        .map_err(|e| format!("Failed to write copy manifest: {}", e))
+}
+
+/// The hashes of the project's files when the copy was made
+///
+/// Copies made before manifests were kept are compared against the project as it is now.
+fn read_manifest(project_path: &Path, copy: &Path) -> Result<BTreeMap<String, String>, String> {
+    match std::fs::read_to_string(manifest_path(copy)) {
+        Ok(json) => {
+            serde_json::from_str(&json).map_err(|e| format!("Invalid copy manifest: {}", e))
+        }
+        Err(_) => {
+            let mut manifest = BTreeMap::new();
+            for entry in walkdir::WalkDir::new(copy) {
+                let entry = entry.map_err(|e| format!("Failed to read copy: {}", e))?;
+                let relative = entry.path().strip_prefix(copy).map_err(|e| e.to_string())?;
+                if let Some(hash) = current_hash(&project_path.join(relative))? {
+                    manifest.insert(relative_name(relative), hash);
+                }
+            }
+            Ok(manifest)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::copy(from, to).map(|_| ())
+}
+
+/// Files the run changed, relative to the root of its worktree or copy
+///
+/// For copies these are the files the agent added, changed or deleted since the copy was made.
+pub fn worktree_changes(project_path: &Path, worktree: &Worktree) -> Result<Vec<String>, String> {
+    let Some(base) = &worktree.base else {
+        return Ok(changed_copies(project_path, &worktree.path)?
+            .into_iter()
+            .map(|change| change.path)
+            .collect());
+    };
+    let root = PathBuf::from(git(&worktree.path, &["rev-parse", "--show-toplevel"])?);
+    let mut changes: Vec<String> = git(&root, &["diff", "--name-only", base, "HEAD"])?
+        .lines()
+        .map(str::to_string)
+        .collect();
+    // Uncommitted changes, including untracked files
+    for line in git(&root, &["status", "--porcelain", "--untracked-files=all"])?.lines() {
+        let path = line.get(3..).unwrap_or_default();
+        let path = path.rsplit(" -> ").next().unwrap_or(path);
+        changes.push(path.trim_matches('"').to_string());
+    }
+    changes.sort();
+    changes.dedup();
+    Ok(changes)
+}
+
+/// A file the agent changed in a copy
+struct CopyChange {
+    path: String,
+    /// The file's hash when the copy was made; None if the agent added it
+    snapshot: Option<String>,
+    /// The file's hash in the copy now; None if the agent deleted it
+    hash: Option<String>,
+}
+
+fn changed_copies(project_path: &Path, copy: &Path) -> Result<Vec<CopyChange>, String> {
+    let mut snapshots = read_manifest(project_path, copy)?;
+    let mut changes = Vec::new();
+    for entry in walkdir::WalkDir::new(copy) {
+        let entry = entry.map_err(|e| format!("Failed to read copy: {}", e))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(copy).map_err(|e| e.to_string())?;
+        let path = relative_name(relative);
+        let snapshot = snapshots.remove(&path);
+        let hash = current_hash(entry.path())?;
+        if hash != snapshot {
+            changes.push(CopyChange {
+                path,
+                snapshot,
+                hash,
+            });
+        }
+    }
+    // Files left in the manifest are gone from the copy
+    changes.extend(snapshots.into_iter().map(|(path, snapshot)| CopyChange {
+        path,
+        snapshot: Some(snapshot),
+        hash: None,
+    }));
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changes)
+}
+
+/// Applies the agent's changes in a copy to the project
+///
+/// Nothing is applied if the developer has since changed any of the same files in the project;
+/// the conflicting files are listed in the error instead.
+fn merge_copy(project_path: &Path, copy: &Path) -> Result<(), String> {
+    let mut changes = Vec::new();
+    let mut conflicts = Vec::new();
+    for change in changed_copies(project_path, copy)? {
+        let current = current_hash(&project_path.join(&change.path))?;
+        if current == change.hash {
+            continue;
+        }
+        if current == change.snapshot {
+            changes.push(change);
+        } else {
+            conflicts.push(change.path);
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(format!(
+            "These files also changed in the project since the copy was made: {}",
+            conflicts.join(", ")
+        ));
+    }
+
+    for change in changes {
+        let target = project_path.join(&change.path);
+        if change.hash.is_none() {
+            std::fs::remove_file(&target)
+                .map_err(|e| format!("Failed to remove {}: {}", change.path, e))?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::copy(copy.join(&change.path), &target)
+            .map_err(|e| format!("Failed to copy back {}: {}", change.path, e))?;
+    }
+    Ok(())
+}
+
+/// Brings a finished run's changes back into the project, or throws them away, then removes
+/// the worktree and its branch
+///
+/// Uncommitted changes in a git worktree are committed first with `message`. A merge or
+/// cherry-pick that doesn't apply cleanly is aborted and the worktree is kept, as is a copy
+/// whose changed files were also changed in the project.
+pub fn finish_worktree(
+    project_path: &Path,
+    worktree: &Worktree,
+    action: WorktreeAction,
+    message: &str,
+) -> Result<(), String> {
+    let (Some(branch), Some(base)) = (&worktree.branch, &worktree.base) else {
+        match action {
+            WorktreeAction::Merge => merge_copy(project_path, &worktree.path)?,
+            WorktreeAction::CherryPick => {
+                return Err(
+                    "Cherry-picking needs a git project; merge or discard the copy instead"
+                        .to_string(),
+                )
+            }
+            WorktreeAction::Discard => {}
+        }
+        let _ = std::fs::remove_file(manifest_path(&worktree.path));
+        return std::fs::remove_dir_all(&worktree.path)
+            .map_err(|e| format!("Failed to remove copy: {}", e));
+    };
+
+    let toplevel = PathBuf::from(git(project_path, &["rev-parse", "--show-toplevel"])?);
+    let root = git(&worktree.path, &["rev-parse", "--show-toplevel"])?;
+    if action != WorktreeAction::Discard {
+        git(Path::new(&root), &["add", "-A"])?;
+        if !git(Path::new(&root), &["status", "--porcelain"])?.is_empty() {
+            git(Path::new(&root), &["commit", "-m", message])?;
+        }
+    }
+
+    match action {
+        WorktreeAction::Merge => {
+            if let Err(e) = git(&toplevel, &["merge", "--no-ff", "--no-edit", branch]) {
+                let _ = git(&toplevel, &["merge", "--abort"]);
+                return Err(e);
+            }
+        }
+        WorktreeAction::CherryPick => {
+            let range = format!("{}..{}", base, branch);
+            let commits = git(&toplevel, &["rev-list", "--count", &range])?;
+            if commits != "0" {
+                if let Err(e) = git(&toplevel, &["cherry-pick", &range]) {
+                    let _ = git(&toplevel, &["cherry-pick", "--abort"]);
+                    return Err(e);
+                }
+            }
+        }
+        WorktreeAction::Discard => {}
+    }
+
+    git(&toplevel, &["worktree", "remove", "--force", &root])?;
+    if let Err(e) = git(&toplevel, &["branch", "-D", branch]) {
+        warn!("Failed to delete branch {}: {}", branch, e);
+    }
+    Ok(())
+}
+
+impl AgentRun {
+    /// The worktree of an isolated run that hasn't been merged or discarded yet
+    fn active_worktree(&self) -> Result<Worktree, String> {
+        let path = self
+            .worktree_path
+            .as_ref()
+            .ok_or_else(|| "The run didn't run in a worktree".to_string())?;
+        if self.worktree_state.as_deref() != Some("active") {
+            return Err(format!(
+                "The run's worktree was already {}",
+                self.worktree_state
+                    .as_deref()
+                    .unwrap_or("removed")
+                    .replace('_', "-")
+            ));
+        }
+        Ok(Worktree {
+            path: PathBuf::from(path),
+            branch: self.worktree_branch.clone(),
+            base: self.worktree_base.clone(),
+        })
+    }
+}
+
+/// List the files an isolated run changed in its worktree
+#[tauri::command]
+pub async fn list_run_worktree_changes(
+    db: State<'_, AgentDb>,
+    run_id: i64,
+) -> Result<Vec<String>, String> {
+    let run = get_agent_run(db, run_id).await?;
+    let worktree = run.active_worktree()?;
+    worktree_changes(Path::new(&run.project_path), &worktree)
+}
+
+/// Merge, cherry-pick or discard a finished isolated run's changes and remove its worktree
+#[tauri::command]
+pub async fn finish_run_worktree(
+    db: State<'_, AgentDb>,
+    run_id: i64,
+    action: WorktreeAction,
+) -> Result<AgentRun, String> {
+    let run = get_agent_run(db.clone(), run_id).await?;
+    let worktree = run.active_worktree()?;
+    if matches!(
+        run.status.as_str(),
+        "pending" | "queued" | "running" | "paused" | "verifying"
+    ) {
+        return Err("The run hasn't finished yet".to_string());
+    }
+
+    let message = format!("Agent run {}: {}", run_id, run.agent_name);
+    let project_path = PathBuf::from(&run.project_path);
+    tauri::async_runtime::spawn_blocking(move || {
+        finish_worktree(&project_path, &worktree, action, &message)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    info!("Finished worktree of run {}: {}", run_id, action.state());
+
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE agent_runs SET worktree_state = ?2 WHERE id = ?1",
+            params![run_id, action.state()],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    get_agent_run(db, run_id).await
+}
+
+/// Removes the worktree of a run cancelled before it started
+///
+/// A first attempt's worktree has nothing in it yet. A retry's holds the work of the attempts
+/// before it, so it is kept for the user to merge or discard.
+pub(crate) async fn discard_unstarted_worktree(
+    db: State<'_, AgentDb>,
+    run_id: i64,
+) -> Result<(), String> {
+    let run = get_agent_run(db.clone(), run_id).await?;
+    if run.retry_of.is_some() {
+        return Ok(());
+    }
+    let Ok(worktree) = run.active_worktree() else {
+        return Ok(());
+    };
+
+    let project_path = PathBuf::from(&run.project_path);
+    tauri::async_runtime::spawn_blocking(move || {
+        finish_worktree(&project_path, &worktree, WorktreeAction::Discard, "")
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    info!("Discarded worktree of cancelled run {}", run_id);
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE agent_runs SET worktree_state = ?2 WHERE id = ?1",
+        params![run_id, WorktreeAction::Discard.state()],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        git(path, &["init", "-q", "-b", "main"]).unwrap();
+        git(path, &["config", "user.name", "Test"]).unwrap();
+        git(path, &["config", "user.email", "test@example.com"]).unwrap();
+        std::fs::create_dir_all(path.join("app")).unwrap();
+        std::fs::write(path.join("app/lib.rs"), "fn one() {}\n").unwrap();
+        git(path, &["add", "-A"]).unwrap();
+        git(path, &["commit", "-q", "-m", "init"]).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_git_worktree_is_merged_back() {
+        let repo = repo();
+        let worktrees = tempfile::tempdir().unwrap();
+        let project = repo.path().join("app");
+
+        let worktree = create_worktree(&project, worktrees.path(), "Unit Tests Bot").unwrap();
+        assert!(worktree
+            .branch
+            .as_deref()
+            .unwrap()
+            .starts_with("agents/unit-tests-bot-"));
+        assert!(worktree.path.ends_with("app"));
+
+        std::fs::write(worktree.path.join("lib.rs"), "fn two() {}\n").unwrap();
+        std::fs::write(worktree.path.join("new.rs"), "").unwrap();
+        assert_eq!(
+            worktree_changes(&project, &worktree).unwrap(),
+            vec!["app/lib.rs".to_string(), "app/new.rs".to_string()]
+        );
+        // The developer's checkout is untouched until the run is merged
+        assert_eq!(
+            std::fs::read_to_string(project.join("lib.rs")).unwrap(),
+            "fn one() {}\n"
+        );
+
+        finish_worktree(&project, &worktree, WorktreeAction::Merge, "Agent run 1").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(project.join("lib.rs")).unwrap(),
+            "fn two() {}\n"
+        );
+        assert!(project.join("new.rs").exists());
+        assert!(!worktree.path.exists());
+        assert!(git(repo.path(), &["branch", "--list", "agents/*"])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_copy_is_used_outside_git() {
+        let project = tempfile::tempdir().unwrap();
+        let worktrees = tempfile::tempdir().unwrap();
+        std::fs::write(project.path().join("notes.txt"), "draft").unwrap();
+
+        let worktree = create_worktree(project.path(), worktrees.path(), "Writer").unwrap();
+        assert_eq!(worktree.branch, None);
+        std::fs::write(worktree.path.join("notes.txt"), "final").unwrap();
+        assert_eq!(
+            worktree_changes(project.path(), &worktree).unwrap(),
+            vec!["notes.txt".to_string()]
+        );
+        assert!(
+            finish_worktree(project.path(), &worktree, WorktreeAction::CherryPick, "").is_err()
+        );
+
+        finish_worktree(project.path(), &worktree, WorktreeAction::Merge, "").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(project.path().join("notes.txt")).unwrap(),
+            "final"
+        );
+        assert!(!worktree.path.exists());
+    }
+
+    #[test]
+    fn test_copy_merge_keeps_later_project_edits() {
+        let project = tempfile::tempdir().unwrap();
+        let worktrees = tempfile::tempdir().unwrap();
+        std::fs::write(project.path().join("notes.txt"), "draft").unwrap();
+        std::fs::write(project.path().join("todo.txt"), "one").unwrap();
+
+        let worktree = create_worktree(project.path(), worktrees.path(), "Writer").unwrap();
+        // The developer keeps working on a file the agent doesn't touch
+        std::fs::write(project.path().join("todo.txt"), "one\ntwo").unwrap();
+        std::fs::write(worktree.path.join("notes.txt"), "final").unwrap();
+        assert_eq!(
+            worktree_changes(project.path(), &worktree).unwrap(),
+            vec!["notes.txt".to_string()]
+        );
+        finish_worktree(project.path(), &worktree, WorktreeAction::Merge, "").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(project.path().join("notes.txt")).unwrap(),
+            "final"
+        );
+        assert_eq!(
+            std::fs::read_to_string(project.path().join("todo.txt")).unwrap(),
+            "one\ntwo"
+        );
+
+        // Both sides changing the same file is a conflict and nothing is copied back
+        let worktree = create_worktree(project.path(), worktrees.path(), "Writer").unwrap();
+        std::fs::write(worktree.path.join("notes.txt"), "agent").unwrap();
+        std::fs::write(worktree.path.join("todo.txt"), "done").unwrap();
+        std::fs::write(project.path().join("todo.txt"), "one\ntwo\nthree").unwrap();
+        let err =
+            finish_worktree(project.path(), &worktree, WorktreeAction::Merge, "").unwrap_err();
+        assert!(err.contains("todo.txt"));
+        assert_eq!(
+            std::fs::read_to_string(project.path().join("notes.txt")).unwrap(),
+            "final"
+        );
+        assert!(worktree.path.exists());
+    }
+}
@@ -10,24 +10,25 @@ use checkpoint::state::CheckpointState;
 use commands::agents::{
     cleanup_finished_processes, create_agent, delete_agent, execute_agent, export_agent,
     export_agent_to_file, fetch_github_agent_content, fetch_github_agents, get_agent,
-    get_agent_run, get_agent_run_with_real_time_metrics, get_claude_binary_path,
+    get_agent_run, get_agent_run_with_real_time_metrics, get_claude_binary_path, get_kill_policy,
     get_live_session_output, get_session_output, get_session_status, import_agent,
     import_agent_from_file, import_agent_from_github, init_database, kill_agent_session,
     list_agent_runs, list_agent_runs_with_metrics, list_agents, list_claude_installations,
-    list_running_sessions, load_agent_session_history, set_claude_binary_path, stream_session_output, update_agent, AgentDb,
+    list_running_sessions, load_agent_session_history, pause_agent_session,
+    read_live_session_output, reconcile_agent_runs, resume_agent_session, set_claude_binary_path,
+    set_kill_policy, stream_session_output, update_agent, AgentDb,
 };
 use commands::claude::{
     cancel_claude_execution, check_auto_checkpoint, check_claude_version, cleanup_old_checkpoints,
     clear_checkpoint_manager, continue_claude_code, create_checkpoint, execute_claude_code,
     find_claude_md_files, fork_from_checkpoint, get_checkpoint_diff, get_checkpoint_settings,
-    get_checkpoint_state_stats, get_claude_session_output, get_claude_settings, get_project_sessions,
-    get_recently_modified_files, get_session_timeline, get_system_prompt, list_checkpoints,
-    list_directory_contents, list_projects, list_running_claude_sessions, load_session_history,
-    open_new_session, read_claude_md_file, restore_checkpoint, resume_claude_code,
-    save_claude_md_file, save_claude_settings, save_system_prompt, search_files,
-    track_checkpoint_message, track_session_messages, update_checkpoint_settings,
-    get_hooks_config, update_hooks_config, validate_hook_command,
-    ClaudeProcessState,
+    get_checkpoint_state_stats, get_claude_session_output, get_claude_settings, get_hooks_config,
+    get_project_sessions, get_recently_modified_files, get_session_events, get_session_timeline,
+    get_system_prompt, list_checkpoints, list_directory_contents, list_projects,
+    list_running_claude_sessions, load_session_history, open_new_session, read_claude_md_file,
+    restore_checkpoint, resume_claude_code, save_claude_md_file, save_claude_settings,
+    save_system_prompt, search_files, send_session_input, track_checkpoint_message,
+    track_session_messages, update_checkpoint_settings, update_hooks_config, validate_hook_command,
 };
 use commands::mcp::{
     mcp_add, mcp_add_from_claude_desktop, mcp_add_json, mcp_get, mcp_get_server_status, mcp_list,
@@ -42,8 +43,32 @@ use commands::storage::{
     storage_list_tables, storage_read_table, storage_update_row, storage_delete_row,
     storage_insert_row, storage_execute_sql, storage_reset_database,
 };
+use commands::queue::{
+    cancel_queued_run, get_queue_settings, list_queued_runs, reorder_queued_runs,
+    set_queued_run_priority, spawn_queue_dispatcher, update_queue_settings, RunQueueState,
+};
+use commands::agent_versions::{
+    diff_agent_versions, get_agent_version, list_agent_versions, rollback_agent,
+};
+use commands::permissions::{get_project_permissions, set_project_permissions};
+use commands::schedules::{
+    create_schedule, delete_schedule, list_schedules, preview_schedule, set_schedule_enabled,
+    spawn_scheduler, update_schedule,
+};
+use commands::triggers::{
+    create_trigger, delete_trigger, list_triggers, set_trigger_enabled, spawn_trigger_watcher,
+    update_trigger,
+};
+use commands::workflows::{
+    cancel_workflow_run, create_workflow, delete_workflow, get_workflow, get_workflow_run,
+    list_workflow_runs, list_workflows, run_workflow, spawn_workflow_driver, update_workflow,
+    WorkflowDriverState,
+};
+use commands::worktrees::{finish_run_worktree, list_run_worktree_changes};
+use commands::env_vars::{delete_env_var, list_env_vars, set_env_var};
+use process::events::{SessionEventBus, SessionEventBusState};
 use process::ProcessRegistryState;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use tauri::Manager;
 
 fn main() {
@@ -83,8 +108,30 @@ fn main() {
             // Initialize process registry
             app.manage(ProcessRegistryState::default());
 
-            // Initialize Claude process state
-            app.manage(ClaudeProcessState::default());
+            // Initialize the session event bus
+            app.manage(SessionEventBusState(Arc::new(SessionEventBus::new(Some(
+                app.handle().clone(),
+            )))));
+
+            // Initialize the agent run queue
+            app.manage(RunQueueState::default());
+
+            // Initialize the workflow driver
+            app.manage(WorkflowDriverState::default());
+
+            // Re-attach to agent runs that outlived the previous app instance, then start
+            // dispatching queued runs into the freed slots, advancing workflows, running
+            // schedules and watching trigger projects
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = reconcile_agent_runs(app_handle.clone()).await {
+                    log::error!("Failed to reconcile agent runs: {}", e);
+                }
+                spawn_queue_dispatcher(app_handle.clone());
+                spawn_workflow_driver(app_handle.clone());
+                spawn_scheduler(app_handle.clone());
+                spawn_trigger_watcher(app_handle);
+            });
 
             Ok(())
         })
@@ -106,6 +153,8 @@ fn main() {
             continue_claude_code,
             resume_claude_code,
             cancel_claude_execution,
+            get_session_events,
+            send_session_input,
             list_running_claude_sessions,
             get_claude_session_output,
             list_directory_contents,
@@ -144,10 +193,52 @@ fn main() {
             get_agent_run_with_real_time_metrics,
             list_running_sessions,
             kill_agent_session,
+            get_kill_policy,
+            set_kill_policy,
+            pause_agent_session,
+            resume_agent_session,
+            list_queued_runs,
+            get_queue_settings,
+            update_queue_settings,
+            get_project_permissions,
+            list_agent_versions,
+            get_agent_version,
+            diff_agent_versions,
+            rollback_agent,
+            set_project_permissions,
+            set_queued_run_priority,
+            reorder_queued_runs,
+            cancel_queued_run,
+            list_workflows,
+            get_workflow,
+            create_workflow,
+            update_workflow,
+            delete_workflow,
+            run_workflow,
+            list_workflow_runs,
+            get_workflow_run,
+            cancel_workflow_run,
+            list_schedules,
+            create_schedule,
+            update_schedule,
+            set_schedule_enabled,
+            delete_schedule,
+            preview_schedule,
+            list_triggers,
+            create_trigger,
+            update_trigger,
+            set_trigger_enabled,
+            delete_trigger,
+            list_run_worktree_changes,
+            finish_run_worktree,
+            list_env_vars,
+            set_env_var,
+            delete_env_var,
             get_session_status,
             cleanup_finished_processes,
             get_session_output,
             get_live_session_output,
+            read_live_session_output,
             stream_session_output,
             load_agent_session_history,
             get_claude_binary_path,
@@ -0,0 +1,265 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::stream::{StreamEvent, StreamLine, Usage};
+
+// Claude 4 pricing constants (per million tokens)
+const OPUS_4_INPUT_PRICE: f64 = 15.0;
+const OPUS_4_OUTPUT_PRICE: f64 = 75.0;
+const OPUS_4_CACHE_WRITE_PRICE: f64 = 18.75;
+const OPUS_4_CACHE_READ_PRICE: f64 = 1.50;
+
+const SONNET_4_INPUT_PRICE: f64 = 3.0;
+const SONNET_4_OUTPUT_PRICE: f64 = 15.0;
+const SONNET_4_CACHE_WRITE_PRICE: f64 = 3.75;
+const SONNET_4_CACHE_READ_PRICE: f64 = 0.30;
+
+/// Per-million-token prices of a model: input, output, cache write and cache read
+///
+/// The CLI's `opus` and `sonnet` aliases are priced as the Claude 4 models they stand for.
+fn model_prices(model: &str) -> Option<(f64, f64, f64, f64)> {
+    if model == "opus" || model.contains("opus-4") {
+        Some((
+            OPUS_4_INPUT_PRICE,
+            OPUS_4_OUTPUT_PRICE,
+            OPUS_4_CACHE_WRITE_PRICE,
+            OPUS_4_CACHE_READ_PRICE,
+        ))
+    } else if model == "sonnet" || model.contains("sonnet-4") {
+        Some((
+            SONNET_4_INPUT_PRICE,
+            SONNET_4_OUTPUT_PRICE,
+            SONNET_4_CACHE_WRITE_PRICE,
+            SONNET_4_CACHE_READ_PRICE,
+        ))
+    } else {
+        None
+    }
+}
+
+/// Whether the cost of a model's usage can be estimated, so a cost budget can be enforced
+pub fn has_known_pricing(model: &str) -> bool {
+    model_prices(model).is_some()
+}
+
+/// Estimated cost in USD of the tokens in `usage`, or 0 for models without known pricing
+pub fn estimate_cost_usd(model: &str, usage: &Usage) -> f64 {
+    // Return 0 for unknown models to avoid incorrect cost estimations.
+    let (input_price, output_price, cache_write_price, cache_read_price) =
+        model_prices(model).unwrap_or_default();
+
+    (usage.input_tokens as f64 * input_price
+        + usage.output_tokens as f64 * output_price
+        + usage.cache_creation_input_tokens as f64 * cache_write_price
+        + usage.cache_read_input_tokens as f64 * cache_read_price)
+        / 1_000_000.0
+}
+
+/// Spending limits for an agent run, checked against the usage Claude reports while it runs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunBudget {
+    /// Maximum cost in USD, estimated from token usage
+    #[serde(default)]
+    pub max_cost_usd: Option<f64>,
+    /// Maximum input plus output tokens
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    /// Maximum number of assistant turns
+    #[serde(default)]
+    pub max_turns: Option<u64>,
+}
+
+impl RunBudget {
+    /// Parses a budget stored as a JSON string, ignoring empty values
+    pub fn from_json(json: Option<&str>) -> Result<Option<Self>, String> {
+        let budget: Option<Self> = match json.map(str::trim) {
+            None | Some("") => None,
+            Some(json) => {
+                Some(serde_json::from_str(json).map_err(|e| format!("Invalid budget: {}", e))?)
+            }
+        };
+        if let Some(cost) = budget.as_ref().and_then(|b| b.max_cost_usd) {
+            if !cost.is_finite() || cost < 0.0 {
+                return Err(format!(
+                    "Invalid budget: max_cost_usd must be positive, got {}",
+                    cost
+                ));
+            }
+        }
+        Ok(budget)
+    }
+
+    /// Whether any limit is set
+    pub fn is_empty(&self) -> bool {
+        self.max_cost_usd.is_none() && self.max_tokens.is_none() && self.max_turns.is_none()
+    }
+
+    /// This budget, with the limits it leaves unset taken from `fallback`
+    pub fn or(self, fallback: &RunBudget) -> RunBudget {
+        RunBudget {
+            max_cost_usd: self.max_cost_usd.or(fallback.max_cost_usd),
+            max_tokens: self.max_tokens.or(fallback.max_tokens),
+            max_turns: self.max_turns.or(fallback.max_turns),
+        }
+    }
+
+    /// The first limit `usage` has reached, if any
+    pub fn check(&self, usage: &BudgetUsage) -> Option<BudgetViolation> {
+        if self.max_cost_usd.is_some_and(|max| usage.cost_usd >= max) {
+            return Some(BudgetViolation::Cost);
+        }
+        if self.max_tokens.is_some_and(|max| usage.tokens >= max) {
+            return Some(BudgetViolation::Tokens);
+        }
+        if self.max_turns.is_some_and(|max| usage.turns >= max) {
+            return Some(BudgetViolation::Turns);
+        }
+        None
+    }
+}
+
+/// What a run has used so far
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BudgetUsage {
+    pub cost_usd: f64,
+    pub tokens: u64,
+    pub turns: u64,
+}
+
+/// A budget limit that a run reached
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetViolation {
+    Cost,
+    Tokens,
+    Turns,
+}
+
+impl BudgetViolation {
+    /// Human readable reason stored alongside the `budget_exceeded` status
+    pub fn describe(&self, budget: &RunBudget, usage: &BudgetUsage) -> String {
+        match self {
+            BudgetViolation::Cost => format!(
+                "Exceeded cost budget of ${:.2} (${:.2} spent)",
+                budget.max_cost_usd.unwrap_or_default(),
+                usage.cost_usd
+            ),
+            BudgetViolation::Tokens => format!(
+                "Exceeded token budget of {} ({} used)",
+                budget.max_tokens.unwrap_or_default(),
+                usage.tokens
+            ),
+            BudgetViolation::Turns => format!(
+                "Exceeded turn budget of {}",
+                budget.max_turns.unwrap_or_default()
+            ),
+        }
+    }
+}
+
+/// Adds up a run's usage from its stream-json output
+///
+/// Claude repeats a message's usage on every line of the message, so usage is kept per
+/// message ID and the latest report wins.
+#[derive(Debug, Default)]
+pub struct BudgetTracker {
+    messages: HashMap<String, BudgetUsage>,
+    /// Usage of messages without an ID, which can't be repeated
+    unidentified: BudgetUsage,
+    /// Whether a message from a model without known pricing was already logged
+    warned_unpriced: bool,
+}
+
+impl BudgetTracker {
+    /// Records the usage reported by an `assistant` line
+    pub fn observe(&mut self, line: &StreamLine) {
+        let StreamEvent::Assistant(event) = &line.event else {
+            return;
+        };
+        let usage = event.message.usage.clone().unwrap_or_default();
+        let model = event.message.model.as_deref().unwrap_or_default();
+        let cost_usd = event.cost_usd.unwrap_or_else(|| {
+            if !self.warned_unpriced && !has_known_pricing(model) {
+                warn!(
+                    "No pricing known for model {:?}; its usage counts as $0",
+                    model
+                );
+                self.warned_unpriced = true;
+            }
+            estimate_cost_usd(model, &usage)
+        });
+        let message = BudgetUsage {
+            cost_usd,
+            tokens: usage.total_tokens(),
+            // Messages of sub-agents don't count as turns of the run
+            turns: u64::from(event.parent_tool_use_id.is_none()),
+        };
+        match &event.message.id {
+            Some(id) => {
+                self.messages.insert(id.clone(), message);
+            }
+            None => {
+                self.unidentified.cost_usd += message.cost_usd;
+                self.unidentified.tokens += message.tokens;
+                self.unidentified.turns += message.turns;
+            }
+        }
+    }
+
+    /// The run's usage so far
+    pub fn usage(&self) -> BudgetUsage {
+        self.messages
+            .values()
+            .fold(self.unidentified.clone(), |total, message| BudgetUsage {
+                cost_usd: total.cost_usd + message.cost_usd,
+                tokens: total.tokens + message.tokens,
+                turns: total.turns + message.turns,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_tracks_stream_output() {
+        let mut tracker = BudgetTracker::default();
+        for line in include_str!("testdata/stream_output.jsonl").lines() {
+            if let Some(line) = StreamLine::parse(line) {
+                tracker.observe(&line);
+            }
+        }
+        // The first message is reported twice; only its latest usage counts
+        let usage = tracker.usage();
+        assert_eq!(usage.turns, 4);
+        assert_eq!(usage.tokens, 389);
+        assert!(usage.cost_usd > 0.0);
+
+        let budget = RunBudget::from_json(Some(r#"{"max_turns": 1}"#))
+            .unwrap()
+            .unwrap()
+            .or(&RunBudget {
+                max_tokens: Some(usage.tokens + 1),
+                max_turns: Some(100),
+                ..Default::default()
+            });
+        assert_eq!(budget.max_tokens, Some(usage.tokens + 1));
+        assert_eq!(budget.check(&usage), Some(BudgetViolation::Turns));
+        assert_eq!(
+            RunBudget {
+                max_cost_usd: Some(usage.cost_usd / 2.0),
+                ..Default::default()
+            }
+            .check(&usage),
+            Some(BudgetViolation::Cost)
+        );
+        assert_eq!(RunBudget::default().check(&usage), None);
+        assert!(RunBudget::from_json(Some(r#"{"max_cost_usd": -1}"#)).is_err());
+
+        assert!(has_known_pricing("sonnet"));
+        assert!(has_known_pricing("claude-opus-4-20250514"));
+        assert!(!has_known_pricing("claude-3-5-haiku-20241022"));
+    }
+}
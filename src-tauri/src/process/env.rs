@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// Shown in place of a secret value
+const REDACTED: &str = "[REDACTED]";
+
+/// Secret values shorter than this aren't redacted; replacing them would mangle unrelated output
+const MIN_REDACTED_LEN: usize = 4;
+
+/// Environment variables set for a run, on top of those inherited from the app
+#[derive(Debug, Clone, Default)]
+pub struct RunEnv {
+    pub vars: BTreeMap<String, String>,
+    /// Values of secret variables, kept out of everything the run prints
+    pub secrets: Vec<String>,
+}
+
+impl RunEnv {
+    /// Adds a variable, replacing an earlier one with the same name
+    pub fn set(&mut self, name: String, value: String, secret: bool) {
+        if secret && value.len() >= MIN_REDACTED_LEN {
+            self.secrets.push(value.clone());
+        }
+        self.vars.insert(name, value);
+    }
+
+    /// `line` with every secret value replaced, as printed and as escaped inside JSON strings
+    pub fn redact<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let mut line = Cow::Borrowed(line);
+        for secret in &self.secrets {
+            let escaped = serde_json::to_string(secret).unwrap_or_default();
+            let escaped = escaped.trim_matches('"');
+            for form in [secret.as_str(), escaped] {
+                if line.contains(form) {
+                    line = Cow::Owned(line.replace(form, REDACTED));
+                }
+            }
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secrets_are_redacted_from_output() {
+        let mut env = RunEnv::default();
+        env.set("NPM_TOKEN".to_string(), "npm_s3cr\"et".to_string(), true);
+        env.set(
+            "REGISTRY".to_string(),
+            "https://npm.example.com".to_string(),
+            false,
+        );
+        env.set("PIN".to_string(), "42".to_string(), true);
+
+        assert_eq!(env.vars.len(), 3);
+        assert_eq!(env.redact("token=npm_s3cr\"et"), "token=[REDACTED]");
+        // Claude's stream-json output escapes the value
+        assert_eq!(
+            env.redact(r#"{"text":"npm_s3cr\"et at https://npm.example.com"}"#),
+            r#"{"text":"[REDACTED] at https://npm.example.com"}"#
+        );
+        assert!(matches!(env.redact("nothing to hide"), Cow::Borrowed(_)));
+        assert_eq!(env.redact("answer 42"), "answer 42");
+    }
+}
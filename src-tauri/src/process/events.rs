@@ -0,0 +1,385 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter};
+
+use super::stream::{ContentBlock, StreamEvent, StreamLine, Usage};
+
+/// Events kept per session so clients that missed some can resync
+const REPLAY_BUFFER_SIZE: usize = 1000;
+
+/// Sessions whose events are kept; the least recently active are forgotten first
+const MAX_TRACKED_SESSIONS: usize = 64;
+
+/// Lifecycle state of an interactive session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionState {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// A typed event published for an interactive session
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SessionEvent {
+    /// A line of Claude's stream-json output, as parsed JSON
+    MessageAdded {
+        message: Value,
+    },
+    ToolStarted {
+        tool_use_id: String,
+        name: String,
+        input: Value,
+    },
+    ToolFinished {
+        tool_use_id: String,
+        is_error: bool,
+    },
+    /// Claude is waiting for the user to allow or deny a tool call
+    PermissionRequested {
+        request_id: String,
+        tool_name: String,
+        input: Value,
+    },
+    /// Running totals for the session so far
+    UsageUpdated {
+        usage: Usage,
+        cost_usd: Option<f64>,
+    },
+    CheckpointCreated {
+        checkpoint_id: String,
+        description: Option<String>,
+    },
+    StateChanged {
+        state: SessionState,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// A line Claude wrote to stderr, or a message about why the session stopped
+    Error {
+        message: String,
+    },
+}
+
+/// An event together with its session and its position in the session's stream
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionEnvelope {
+    pub session_id: String,
+    /// Starts at 1 and increases by one per event, so a jump means events were missed
+    pub seq: u64,
+    pub event: SessionEvent,
+}
+
+/// Events returned to a client catching up after a gap
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionEventsPage {
+    pub events: Vec<SessionEnvelope>,
+    /// True when older events were dropped from the buffer and the client must reload
+    /// the session transcript instead
+    pub truncated: bool,
+}
+
+#[derive(Default)]
+struct SessionLog {
+    last_seq: u64,
+    state: Option<SessionState>,
+    recent: VecDeque<SessionEnvelope>,
+    last_active: u64,
+}
+
+/// Assigns sequence numbers to session events, keeps recent ones and emits them
+/// on `session-event:{session_id}`
+pub struct SessionEventBus {
+    app: Option<AppHandle>,
+    sessions: Mutex<HashMap<String, SessionLog>>,
+    clock: AtomicU64,
+}
+
+impl SessionEventBus {
+    pub fn new(app: Option<AppHandle>) -> Self {
+        Self {
+            app,
+            sessions: Mutex::new(HashMap::new()),
+            clock: AtomicU64::new(0),
+        }
+    }
+
+    /// Records an event and emits it to the frontend, returning its sequence number
+    ///
+    /// Only the first of several end states is published, so a cancelled session isn't
+    /// later reported as failed when its process exits.
+    pub fn publish(&self, session_id: &str, event: SessionEvent) -> Option<u64> {
+        let envelope = self.record(session_id, event)?;
+        if let Some(app) = &self.app {
+            let _ = app.emit(&format!("session-event:{}", session_id), &envelope);
+        }
+        Some(envelope.seq)
+    }
+
+    fn record(&self, session_id: &str, event: SessionEvent) -> Option<SessionEnvelope> {
+        let tick = self.clock.fetch_add(1, Ordering::Relaxed) + 1;
+
+        let mut sessions = self.sessions.lock().unwrap();
+        if !sessions.contains_key(session_id) && sessions.len() >= MAX_TRACKED_SESSIONS {
+            if let Some(oldest) = sessions
+                .iter()
+                .min_by_key(|(_, log)| log.last_active)
+                .map(|(id, _)| id.clone())
+            {
+                sessions.remove(&oldest);
+            }
+        }
+
+        let log = sessions.entry(session_id.to_string()).or_default();
+        if let SessionEvent::StateChanged { state, .. } = &event {
+            let ended = matches!(log.state, Some(s) if s != SessionState::Running);
+            if ended && *state != SessionState::Running {
+                return None;
+            }
+            log.state = Some(*state);
+        }
+        log.last_seq += 1;
+        log.last_active = tick;
+        let envelope = SessionEnvelope {
+            session_id: session_id.to_string(),
+            seq: log.last_seq,
+            event,
+        };
+        if log.recent.len() == REPLAY_BUFFER_SIZE {
+            log.recent.pop_front();
+        }
+        log.recent.push_back(envelope.clone());
+        Some(envelope)
+    }
+
+    /// The sequence number of a session's latest event, or 0 before its first
+    pub fn last_seq(&self, session_id: &str) -> u64 {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(session_id).map_or(0, |log| log.last_seq)
+    }
+
+    /// Events with a sequence number greater than `after_seq`
+    pub fn events_since(&self, session_id: &str, after_seq: u64) -> SessionEventsPage {
+        let sessions = self.sessions.lock().unwrap();
+        let Some(log) = sessions.get(session_id) else {
+            return SessionEventsPage {
+                events: Vec::new(),
+                truncated: false,
+            };
+        };
+
+        let oldest = log
+            .recent
+            .front()
+            .map(|e| e.seq)
+            .unwrap_or(log.last_seq + 1);
+        SessionEventsPage {
+            events: log
+                .recent
+                .iter()
+                .filter(|e| e.seq > after_seq)
+                .cloned()
+                .collect(),
+            truncated: oldest > after_seq + 1,
+        }
+    }
+}
+
+/// Shared event bus managed as Tauri state
+pub struct SessionEventBusState(pub Arc<SessionEventBus>);
+
+/// Running token and cost totals for a session
+///
+/// Claude repeats a message's usage on every content block it streams, so usage is
+/// counted once per message ID; the final `result` line replaces the totals outright.
+#[derive(Debug, Default)]
+pub struct UsageTotals {
+    usage: Usage,
+    cost_usd: Option<f64>,
+    last_message: Option<(String, Usage)>,
+}
+
+impl UsageTotals {
+    /// Folds a line into the totals, returning whether they changed
+    pub fn update(&mut self, event: &StreamEvent) -> bool {
+        match event {
+            StreamEvent::Result(result) => {
+                if let Some(usage) = &result.usage {
+                    self.usage = usage.clone();
+                }
+                self.cost_usd = result.total_cost_usd.or(self.cost_usd);
+                self.last_message = None;
+                true
+            }
+            StreamEvent::Assistant(message) => {
+                let Some(usage) = &message.message.usage else {
+                    return false;
+                };
+                let id = message.message.id.clone().unwrap_or_default();
+                if let Some((last_id, last_usage)) = &self.last_message {
+                    if *last_id == id && !id.is_empty() {
+                        if last_usage == usage {
+                            return false;
+                        }
+                        subtract(&mut self.usage, last_usage);
+                    }
+                }
+                add(&mut self.usage, usage);
+                self.last_message = Some((id, usage.clone()));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn event(&self) -> SessionEvent {
+        SessionEvent::UsageUpdated {
+            usage: self.usage.clone(),
+            cost_usd: self.cost_usd,
+        }
+    }
+}
+
+fn add(total: &mut Usage, usage: &Usage) {
+    total.input_tokens += usage.input_tokens;
+    total.output_tokens += usage.output_tokens;
+    total.cache_creation_input_tokens += usage.cache_creation_input_tokens;
+    total.cache_read_input_tokens += usage.cache_read_input_tokens;
+}
+
+fn subtract(total: &mut Usage, usage: &Usage) {
+    total.input_tokens = total.input_tokens.saturating_sub(usage.input_tokens);
+    total.output_tokens = total.output_tokens.saturating_sub(usage.output_tokens);
+    total.cache_creation_input_tokens = total
+        .cache_creation_input_tokens
+        .saturating_sub(usage.cache_creation_input_tokens);
+    total.cache_read_input_tokens = total
+        .cache_read_input_tokens
+        .saturating_sub(usage.cache_read_input_tokens);
+}
+
+/// The message and tool events described by one line of stream-json output
+pub fn line_events(raw: &str) -> (Vec<SessionEvent>, Option<StreamLine>) {
+    let Ok(message) = serde_json::from_str::<Value>(raw) else {
+        return (Vec::new(), None);
+    };
+    let line: Option<StreamLine> = serde_json::from_value(message.clone()).ok();
+
+    // Control requests are part of the protocol, not the conversation
+    if let Some((request_id, tool_name, input)) =
+        line.as_ref().and_then(|l| l.event.permission_request())
+    {
+        let event = SessionEvent::PermissionRequested {
+            request_id: request_id.to_string(),
+            tool_name: tool_name.to_string(),
+            input: input.clone(),
+        };
+        return (vec![event], line);
+    }
+
+    let mut events = vec![SessionEvent::MessageAdded { message }];
+    if let Some(msg) = line.as_ref().and_then(|l| l.event.message()) {
+        for block in &msg.content {
+            match block {
+                ContentBlock::ToolUse { id, name, input } => {
+                    events.push(SessionEvent::ToolStarted {
+                        tool_use_id: id.clone(),
+                        name: name.clone(),
+                        input: input.clone(),
+                    })
+                }
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    is_error,
+                    ..
+                } => events.push(SessionEvent::ToolFinished {
+                    tool_use_id: tool_use_id.clone(),
+                    is_error: *is_error,
+                }),
+                _ => {}
+            }
+        }
+    }
+    (events, line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_numbers_are_per_session_and_replayable() {
+        let bus = SessionEventBus::new(None);
+        let error = |m: &str| SessionEvent::Error {
+            message: m.to_string(),
+        };
+
+        let state = |state| SessionEvent::StateChanged {
+            state,
+            reason: None,
+        };
+
+        assert_eq!(bus.publish("a", error("one")), Some(1));
+        assert_eq!(bus.publish("b", error("other")), Some(1));
+        assert_eq!(bus.publish("a", state(SessionState::Cancelled)), Some(2));
+        assert_eq!(bus.publish("a", state(SessionState::Failed)), None);
+        assert_eq!(bus.publish("a", error("three")), Some(3));
+        assert_eq!(bus.last_seq("a"), 3);
+        assert_eq!(bus.last_seq("c"), 0);
+
+        let page = bus.events_since("a", 1);
+        assert!(!page.truncated);
+        assert_eq!(
+            page.events.iter().map(|e| e.seq).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+
+        for i in 0..REPLAY_BUFFER_SIZE {
+            bus.publish("a", error(&i.to_string()));
+        }
+        assert!(bus.events_since("a", 1).truncated);
+        assert!(!bus.events_since("a", 4).truncated);
+    }
+
+    #[test]
+    fn test_stream_output_events_and_usage_totals() {
+        let mut totals = UsageTotals::default();
+        let mut kinds = Vec::new();
+        for raw in include_str!("testdata/stream_output.jsonl").lines() {
+            let (events, line) = line_events(raw);
+            for event in events {
+                kinds.push(match event {
+                    SessionEvent::ToolStarted { name, .. } => format!("start {}", name),
+                    SessionEvent::ToolFinished { is_error, .. } => format!("finish {}", is_error),
+                    SessionEvent::PermissionRequested { tool_name, .. } => {
+                        format!("approve {}", tool_name)
+                    }
+                    _ => continue,
+                });
+            }
+            if let Some(line) = line {
+                totals.update(&line.event);
+            }
+        }
+
+        assert_eq!(
+            kinds,
+            vec![
+                "start Read",
+                "finish false",
+                "start Edit",
+                "finish false",
+                "start Bash",
+                "approve Bash",
+                "finish true"
+            ]
+        );
+        assert_eq!(totals.usage.total_tokens(), 22 + 367);
+        assert_eq!(totals.cost_usd, Some(0.0421));
+    }
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Input pushed into a session launched with `--input-format stream-json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionInput {
+    /// A follow-up user message
+    Message { text: String },
+    /// Interrupt the turn in progress without ending the session
+    Interrupt,
+    /// Close stdin so the session ends once the current turn completes
+    Close,
+    /// Answer a tool permission request from a run that requires approval
+    PermissionResponse {
+        request_id: String,
+        allow: bool,
+        /// The tool input to run with; Claude requires it when allowing
+        #[serde(default)]
+        input: Option<Value>,
+        /// Shown to Claude when denying
+        #[serde(default)]
+        message: Option<String>,
+    },
+}
+
+impl SessionInput {
+    /// Encodes the input as a stream-json line, or `None` for `Close`
+    pub fn to_stream_json(&self) -> Option<String> {
+        match self {
+            SessionInput::Message { text } => Some(user_message_line(text)),
+            SessionInput::Interrupt => Some(
+                json!({
+                    "type": "control_request",
+                    "request_id": uuid::Uuid::new_v4().to_string(),
+                    "request": { "subtype": "interrupt" },
+                })
+                .to_string(),
+            ),
+            SessionInput::Close => None,
+            SessionInput::PermissionResponse {
+                request_id,
+                allow,
+                input,
+                message,
+            } => {
+                let decision = if *allow {
+                    json!({ "behavior": "allow", "updatedInput": input.clone().unwrap_or(json!({})) })
+                } else {
+                    json!({
+                        "behavior": "deny",
+                        "message": message.as_deref().unwrap_or("The user denied this tool use"),
+                    })
+                };
+                Some(
+                    json!({
+                        "type": "control_response",
+                        "response": {
+                            "subtype": "success",
+                            "request_id": request_id,
+                            "response": decision,
+                        },
+                    })
+                    .to_string(),
+                )
+            }
+        }
+    }
+}
+
+/// Encodes a user message as a stream-json input line
+pub fn user_message_line(text: &str) -> String {
+    json!({
+        "type": "user",
+        "message": {
+            "role": "user",
+            "content": [{ "type": "text", "text": text }],
+        },
+    })
+    .to_string()
+}
+
+/// Builds the prompt arguments for a Claude invocation
+///
+/// Interactive runs read the prompt, and any follow-ups, from stdin instead of the command line.
+pub fn prompt_args(prompt: &str, interactive: bool) -> Vec<String> {
+    if interactive {
+        vec![
+            "-p".to_string(),
+            "--input-format".to_string(),
+            "stream-json".to_string(),
+        ]
+    } else {
+        vec!["-p".to_string(), prompt.to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_input_encoding() {
+        let input: SessionInput =
+            serde_json::from_str(r#"{"type":"message","text":"next step"}"#).unwrap();
+        let line: serde_json::Value =
+            serde_json::from_str(&input.to_stream_json().unwrap()).unwrap();
+        assert_eq!(line["type"], "user");
+        assert_eq!(line["message"]["content"][0]["text"], "next step");
+
+        let line: serde_json::Value =
+            serde_json::from_str(&SessionInput::Interrupt.to_stream_json().unwrap()).unwrap();
+        assert_eq!(line["type"], "control_request");
+        assert_eq!(line["request"]["subtype"], "interrupt");
+
+        assert!(SessionInput::Close.to_stream_json().is_none());
+
+        let input: SessionInput = serde_json::from_str(
+            r#"{"type":"permission_response","request_id":"req-1","allow":false}"#,
+        )
+        .unwrap();
+        let line: serde_json::Value =
+            serde_json::from_str(&input.to_stream_json().unwrap()).unwrap();
+        assert_eq!(line["type"], "control_response");
+        assert_eq!(line["response"]["request_id"], "req-1");
+        assert_eq!(line["response"]["response"]["behavior"], "deny");
+        assert_eq!(prompt_args("hi", false), vec!["-p", "hi"]);
+    }
+}
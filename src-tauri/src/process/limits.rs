@@ -0,0 +1,446 @@
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::registry::ProcessRegistry;
+
+/// How often the resource monitor samples a process tree
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Limits applied to a spawned Claude process and everything it starts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// Maximum wall-clock run time in seconds
+    #[serde(default)]
+    pub max_wall_time_secs: Option<u64>,
+    /// Maximum CPU time in seconds, summed over the process tree
+    #[serde(default)]
+    pub max_cpu_time_secs: Option<u64>,
+    /// Maximum resident memory in megabytes, summed over the process tree
+    #[serde(default)]
+    pub max_memory_mb: Option<u64>,
+    /// Maximum number of live processes in the tree, including the root
+    #[serde(default)]
+    pub max_processes: Option<u32>,
+}
+
+impl ResourceLimits {
+    /// Parses limits stored as a JSON string, ignoring empty values
+    pub fn from_json(json: Option<&str>) -> Result<Option<Self>, String> {
+        match json.map(str::trim) {
+            None | Some("") => Ok(None),
+            Some(json) => serde_json::from_str(json)
+                .map(Some)
+                .map_err(|e| format!("Invalid resource limits: {}", e)),
+        }
+    }
+
+    /// Whether any limit is set
+    pub fn is_empty(&self) -> bool {
+        self.max_wall_time_secs.is_none()
+            && self.max_cpu_time_secs.is_none()
+            && self.max_memory_mb.is_none()
+            && self.max_processes.is_none()
+    }
+}
+
+/// Latest resource sample for a process tree
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// CPU time consumed by the live processes in the tree, in seconds
+    pub cpu_time_secs: f64,
+    /// CPU usage since the previous sample, in percent of one core
+    pub cpu_percent: f64,
+    /// Resident memory of the tree in bytes
+    pub rss_bytes: u64,
+    /// Number of live processes in the tree
+    pub process_count: u32,
+    pub sampled_at: Option<DateTime<Utc>>,
+}
+
+/// A limit that a process tree exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitViolation {
+    WallTime,
+    CpuTime,
+    Memory,
+    Processes,
+}
+
+impl LimitViolation {
+    /// The `agent_runs.status` recorded for a run killed for this violation
+    pub fn run_status(&self) -> &'static str {
+        match self {
+            LimitViolation::WallTime | LimitViolation::CpuTime => "timed_out",
+            LimitViolation::Memory => "oom_killed",
+            LimitViolation::Processes => "limit_exceeded",
+        }
+    }
+
+    /// Human readable reason stored alongside the status
+    pub fn describe(&self, limits: &ResourceLimits) -> String {
+        match self {
+            LimitViolation::WallTime => format!(
+                "Exceeded wall-clock limit of {}s",
+                limits.max_wall_time_secs.unwrap_or_default()
+            ),
+            LimitViolation::CpuTime => format!(
+                "Exceeded CPU time limit of {}s",
+                limits.max_cpu_time_secs.unwrap_or_default()
+            ),
+            LimitViolation::Memory => format!(
+                "Exceeded memory limit of {} MB",
+                limits.max_memory_mb.unwrap_or_default()
+            ),
+            LimitViolation::Processes => format!(
+                "Exceeded process limit of {}",
+                limits.max_processes.unwrap_or_default()
+            ),
+        }
+    }
+}
+
+/// Checks a sample against the limits
+pub fn check_limits(
+    limits: &ResourceLimits,
+    usage: &ResourceUsage,
+    elapsed: Duration,
+    oom_killed: bool,
+) -> Option<LimitViolation> {
+    if oom_killed {
+        return Some(LimitViolation::Memory);
+    }
+    if let Some(max) = limits.max_wall_time_secs {
+        if elapsed.as_secs() >= max {
+            return Some(LimitViolation::WallTime);
+        }
+    }
+    if let Some(max) = limits.max_cpu_time_secs {
+        if usage.cpu_time_secs >= max as f64 {
+            return Some(LimitViolation::CpuTime);
+        }
+    }
+    if let Some(max) = limits.max_memory_mb {
+        if usage.rss_bytes >= max * 1024 * 1024 {
+            return Some(LimitViolation::Memory);
+        }
+    }
+    if let Some(max) = limits.max_processes {
+        if usage.process_count > max {
+            return Some(LimitViolation::Processes);
+        }
+    }
+    None
+}
+
+/// Samples CPU time, RSS and process count for a process and its descendants
+#[cfg(unix)]
+pub fn sample_process_tree(root: u32) -> Option<ResourceUsage> {
+    let output = std::process::Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid=,rss=,cputime="])
+        .output()
+        .ok()?;
+
+    let rows: Vec<(u32, u32, u64, f64)> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let pid = parts.next()?.parse().ok()?;
+            let ppid = parts.next()?.parse().ok()?;
+            let rss_kb = parts.next()?.parse().ok()?;
+            let cpu = parse_cpu_time(parts.next()?)?;
+            Some((pid, ppid, rss_kb, cpu))
+        })
+        .collect();
+
+    let mut tree = vec![root];
+    let mut index = 0;
+    while index < tree.len() {
+        let parent = tree[index];
+        tree.extend(
+            rows.iter()
+                .filter(|(pid, ppid, _, _)| *ppid == parent && !tree.contains(pid))
+                .map(|(pid, _, _, _)| *pid)
+                .collect::<Vec<_>>(),
+        );
+        index += 1;
+    }
+
+    let mut usage = ResourceUsage {
+        sampled_at: Some(Utc::now()),
+        ..Default::default()
+    };
+    for (pid, _, rss_kb, cpu) in &rows {
+        if tree.contains(pid) {
+            usage.rss_bytes += rss_kb * 1024;
+            usage.cpu_time_secs += cpu;
+            usage.process_count += 1;
+        }
+    }
+
+    if usage.process_count == 0 {
+        None
+    } else {
+        Some(usage)
+    }
+}
+
+/// Samples CPU time, RSS and process count for a process and its descendants
+#[cfg(not(unix))]
+pub fn sample_process_tree(_root: u32) -> Option<ResourceUsage> {
+    None
+}
+
+/// Parses `ps` cputime output: `[dd-]hh:mm:ss` on Linux, `m:ss.ss` on macOS
+fn parse_cpu_time(value: &str) -> Option<f64> {
+    let (days, clock) = match value.split_once('-') {
+        Some((days, clock)) => (days.parse::<f64>().ok()?, clock),
+        None => (0.0, value),
+    };
+
+    let mut seconds = 0.0;
+    for part in clock.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(days * 86_400.0 + seconds)
+}
+
+/// Applies kernel-enforced limits to a command before it is spawned
+///
+/// RLIMIT_CPU is set with some slack so the monitor normally reports the violation first.
+/// Memory is not limited through rlimits: Node reserves far more address space than it uses,
+/// so RLIMIT_AS would break the CLI. Memory and process counts are enforced through cgroups
+/// or sampling instead.
+#[cfg(unix)]
+pub fn apply_rlimits(cmd: &mut tokio::process::Command, limits: &ResourceLimits) {
+    let Some(cpu_secs) = limits.max_cpu_time_secs else {
+        return;
+    };
+    let soft = cpu_secs + 10;
+
+    unsafe {
+        cmd.pre_exec(move || {
+            let limit = libc::rlimit {
+                rlim_cur: soft as libc::rlim_t,
+                rlim_max: (soft + 5) as libc::rlim_t,
+            };
+            if libc::setrlimit(libc::RLIMIT_CPU, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Applies kernel-enforced limits to a command before it is spawned
+#[cfg(not(unix))]
+pub fn apply_rlimits(_cmd: &mut tokio::process::Command, _limits: &ResourceLimits) {}
+
+/// A cgroup v2 created for a single run, removed again when dropped
+pub struct CgroupScope {
+    path: PathBuf,
+}
+
+impl CgroupScope {
+    /// Creates a child cgroup of the app's own cgroup with memory and pids limits
+    ///
+    /// Returns `None` when cgroups v2 is unavailable or the controllers aren't delegated to us.
+    pub fn create(name: &str, limits: &ResourceLimits) -> Option<Self> {
+        if limits.max_memory_mb.is_none() && limits.max_processes.is_none() {
+            return None;
+        }
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+
+        let own = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+        let relative = own.lines().find_map(|line| line.strip_prefix("0::"))?;
+        let parent = PathBuf::from("/sys/fs/cgroup").join(relative.trim_start_matches('/'));
+        let path = parent.join(format!("claudia-{}", name));
+
+        std::fs::create_dir(&path).ok()?;
+        let scope = Self { path };
+
+        // Controllers that aren't enabled for our subtree have no interface files
+        if let Some(mb) = limits.max_memory_mb {
+            let bytes = (mb * 1024 * 1024).to_string();
+            if std::fs::write(scope.path.join("memory.max"), bytes).is_err() {
+                return None;
+            }
+            let _ = std::fs::write(scope.path.join("memory.swap.max"), "0");
+        }
+        if let Some(max) = limits.max_processes {
+            if std::fs::write(scope.path.join("pids.max"), max.to_string()).is_err() {
+                return None;
+            }
+        }
+
+        Some(scope)
+    }
+
+    /// Moves a process into the cgroup; its future children inherit it
+    pub fn add_process(&self, pid: u32) -> bool {
+        std::fs::write(self.path.join("cgroup.procs"), pid.to_string()).is_ok()
+    }
+
+    /// Whether the kernel OOM killer fired inside this cgroup
+    pub fn oom_killed(&self) -> bool {
+        std::fs::read_to_string(self.path.join("memory.events"))
+            .ok()
+            .and_then(|events| {
+                events.lines().find_map(|line| {
+                    line.strip_prefix("oom_kill ")
+                        .and_then(|count| count.trim().parse::<u64>().ok())
+                })
+            })
+            .is_some_and(|count| count > 0)
+    }
+}
+
+impl Drop for CgroupScope {
+    fn drop(&mut self) {
+        // Only succeeds once every process in the cgroup has exited
+        let _ = std::fs::remove_dir(&self.path);
+    }
+}
+
+/// Samples a process tree until it exits, killing it if it exceeds a limit
+///
+/// Usage is recorded on the run's `ProcessInfo` every sample. `on_violation` runs before the
+/// kill so callers can record the outcome before the process's own exit handling sees it.
+pub async fn monitor_resources<F>(
+    registry: Arc<ProcessRegistry>,
+    run_id: i64,
+    pid: u32,
+    limits: Option<ResourceLimits>,
+    cgroup: Option<CgroupScope>,
+    on_violation: F,
+) -> Option<LimitViolation>
+where
+    F: FnOnce(LimitViolation, &ResourceLimits),
+{
+    let limits = limits.unwrap_or_default();
+    let started = Instant::now();
+    let mut previous: Option<(Instant, f64)> = None;
+
+    loop {
+        tokio::time::sleep(SAMPLE_INTERVAL).await;
+
+        let oom_killed = cgroup.as_ref().is_some_and(|cgroup| cgroup.oom_killed());
+        let sample = sample_process_tree(pid);
+        if sample.is_none() && !oom_killed {
+            // The tree has exited on its own
+            return None;
+        }
+
+        let now = Instant::now();
+        let mut usage = sample.unwrap_or_default();
+        if let Some((at, cpu)) = previous {
+            let wall = now.duration_since(at).as_secs_f64();
+            if wall > 0.0 {
+                usage.cpu_percent = ((usage.cpu_time_secs - cpu) / wall * 100.0).max(0.0);
+            }
+        }
+        previous = Some((now, usage.cpu_time_secs));
+        let _ = registry.update_resource_usage(run_id, usage.clone());
+
+        // Time spent paused doesn't count towards the wall-clock limit
+        let elapsed = started
+            .elapsed()
+            .saturating_sub(registry.paused_duration(run_id));
+
+        if let Some(violation) = check_limits(&limits, &usage, elapsed, oom_killed) {
+            warn!(
+                "Process {} (PID {}): {}",
+                run_id,
+                pid,
+                violation.describe(&limits)
+            );
+            on_violation(violation, &limits);
+
+            let report = match registry.kill_process(run_id).await {
+                Ok(Some(report)) => Ok(report),
+                // Not tracked by the registry (e.g. sidecar runs), kill by PID instead
+                Ok(None) => registry.kill_process_by_pid(run_id, pid).await,
+                Err(e) => Err(e),
+            };
+            match report {
+                Ok(report) => info!(
+                    "Killed process {} for exceeding limits: {}",
+                    run_id,
+                    report.reason.description()
+                ),
+                Err(e) => warn!("Failed to kill process {}: {}", run_id, e),
+            }
+            return Some(violation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_limits_maps_to_violations() {
+        let limits = ResourceLimits {
+            max_wall_time_secs: Some(60),
+            max_cpu_time_secs: Some(10),
+            max_memory_mb: Some(100),
+            max_processes: Some(4),
+        };
+        let usage = ResourceUsage {
+            cpu_time_secs: 1.0,
+            rss_bytes: 10 * 1024 * 1024,
+            process_count: 2,
+            ..Default::default()
+        };
+        let secs = Duration::from_secs;
+
+        assert_eq!(check_limits(&limits, &usage, secs(1), false), None);
+        assert_eq!(
+            check_limits(&limits, &usage, secs(61), false),
+            Some(LimitViolation::WallTime)
+        );
+        assert_eq!(
+            check_limits(&limits, &usage, secs(1), true),
+            Some(LimitViolation::Memory)
+        );
+
+        let heavy = ResourceUsage {
+            rss_bytes: 200 * 1024 * 1024,
+            ..usage.clone()
+        };
+        let memory = check_limits(&limits, &heavy, secs(1), false);
+        assert_eq!(memory, Some(LimitViolation::Memory));
+        assert_eq!(memory.unwrap().run_status(), "oom_killed");
+
+        let forky = ResourceUsage {
+            process_count: 5,
+            ..usage
+        };
+        assert_eq!(
+            check_limits(&limits, &forky, secs(1), false),
+            Some(LimitViolation::Processes)
+        );
+    }
+
+    #[test]
+    fn test_parse_cpu_time_formats() {
+        assert_eq!(parse_cpu_time("00:01:05"), Some(65.0));
+        assert_eq!(parse_cpu_time("1-00:00:00"), Some(86_400.0));
+        assert_eq!(parse_cpu_time("0:02.50"), Some(2.5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sample_includes_own_process() {
+        let usage = sample_process_tree(std::process::id()).unwrap();
+        assert!(usage.process_count >= 1);
+        assert!(usage.rss_bytes > 0);
+    }
+}
@@ -1,3 +1,21 @@
+pub mod budget;
+pub mod env;
+pub mod events;
+pub mod input;
+pub mod limits;
+pub mod output;
+pub mod permissions;
+pub mod probe;
 pub mod registry;
+pub mod runner;
+pub mod signals;
+pub mod stream;
 
+pub use budget::RunBudget;
+pub use env::RunEnv;
+pub use input::SessionInput;
+pub use limits::ResourceLimits;
+pub use output::OutputChunk;
+pub use permissions::PermissionSettings;
 pub use registry::*;
+pub use signals::KillPolicy;
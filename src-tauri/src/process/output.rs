@@ -0,0 +1,303 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// Maximum number of lines kept in memory per process
+const DEFAULT_MAX_LINES: usize = 10_000;
+/// Maximum number of bytes kept in memory per process
+const DEFAULT_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// A range of output lines returned by `OutputBuffer::read_from`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputChunk {
+    /// Line index of the first entry in `lines`
+    pub start_line: u64,
+    pub lines: Vec<String>,
+    /// Offset to pass on the next call to continue reading
+    pub next_offset: u64,
+    /// Total number of lines produced by the process so far
+    pub total_lines: u64,
+}
+
+/// Lines evicted from memory, appended to a file with a byte offset per line
+struct SpillFile {
+    path: PathBuf,
+    file: File,
+    line_offsets: Vec<u64>,
+    len: u64,
+}
+
+impl SpillFile {
+    fn create(path: PathBuf) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .open(&path)?;
+        Ok(Self {
+            path,
+            file,
+            line_offsets: Vec::new(),
+            len: 0,
+        })
+    }
+
+    fn append(&mut self, line: &str) -> std::io::Result<()> {
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.write_all(line.as_bytes())?;
+        self.file.write_all(b"\n")?;
+        self.line_offsets.push(self.len);
+        self.len += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn read_lines(&mut self, from: usize, limit: usize) -> std::io::Result<Vec<String>> {
+        let Some(&start) = self.line_offsets.get(from) else {
+            return Ok(Vec::new());
+        };
+        self.file.seek(SeekFrom::Start(start))?;
+
+        let mut reader = BufReader::new(&self.file);
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        while lines.len() < limit && from + lines.len() < self.line_offsets.len() {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            lines.push(line.trim_end_matches('\n').to_string());
+        }
+        Ok(lines)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Bounded, line-indexed buffer for a process's live output
+///
+/// The most recent lines are kept in memory; older lines spill to a temporary file so that
+/// readers can still fetch them by line offset.
+pub struct OutputBuffer {
+    lines: VecDeque<String>,
+    /// Line index of `lines[0]`
+    first_line: u64,
+    bytes: usize,
+    max_lines: usize,
+    max_bytes: usize,
+    spill_path: Option<PathBuf>,
+    spill: Option<SpillFile>,
+}
+
+impl OutputBuffer {
+    /// Creates a buffer that spills evicted lines to `spill_path`
+    pub fn new(spill_path: Option<PathBuf>) -> Self {
+        Self::with_limits(spill_path, DEFAULT_MAX_LINES, DEFAULT_MAX_BYTES)
+    }
+
+    /// Creates a buffer with explicit in-memory limits
+    pub fn with_limits(spill_path: Option<PathBuf>, max_lines: usize, max_bytes: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            first_line: 0,
+            bytes: 0,
+            max_lines: max_lines.max(1),
+            max_bytes,
+            spill_path,
+            spill: None,
+        }
+    }
+
+    /// Default location of the spill file for a run
+    pub fn spill_path_for(run_id: i64) -> PathBuf {
+        std::env::temp_dir().join("claudia-output").join(format!(
+            "{}-{}.log",
+            std::process::id(),
+            run_id
+        ))
+    }
+
+    /// Total number of lines appended so far, including spilled ones
+    pub fn total_lines(&self) -> u64 {
+        self.first_line + self.lines.len() as u64
+    }
+
+    /// Appends a line, evicting the oldest lines once the buffer is over its limits
+    pub fn push_line(&mut self, line: &str) {
+        self.bytes += line.len();
+        self.lines.push_back(line.to_string());
+
+        while self.lines.len() > 1
+            && (self.lines.len() > self.max_lines || self.bytes > self.max_bytes)
+        {
+            let Some(evicted) = self.lines.pop_front() else {
+                break;
+            };
+            self.bytes -= evicted.len();
+            self.first_line += 1;
+            self.spill_line(&evicted);
+        }
+    }
+
+    fn spill_line(&mut self, line: &str) {
+        if self.spill.is_none() {
+            let Some(path) = self.spill_path.take() else {
+                return;
+            };
+            match SpillFile::create(path) {
+                Ok(spill) => self.spill = Some(spill),
+                Err(e) => {
+                    log::warn!("Failed to create output spill file: {}", e);
+                    return;
+                }
+            }
+        }
+
+        if let Some(spill) = self.spill.as_mut() {
+            if let Err(e) = spill.append(line) {
+                // The file no longer lines up with the in-memory window; stop spilling and
+                // treat every evicted line as skipped
+                log::warn!("Failed to spill output line to disk: {}", e);
+                self.spill = None;
+            }
+        }
+    }
+
+    /// Reads up to `limit` lines starting at line `offset`
+    ///
+    /// Lines that were evicted without a spill file are skipped, so `start_line` may be
+    /// greater than `offset`.
+    pub fn read_from(&mut self, offset: u64, limit: Option<usize>) -> OutputChunk {
+        let total_lines = self.total_lines();
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut lines = Vec::new();
+
+        let spilled = self
+            .spill
+            .as_ref()
+            .map(|spill| spill.line_offsets.len() as u64)
+            .unwrap_or(0);
+        // Spilled lines always end right where the in-memory window begins
+        let spill_start = self.first_line - spilled;
+        let mut start_line = offset.max(spill_start).min(total_lines);
+
+        if start_line < self.first_line {
+            if let Some(spill) = self.spill.as_mut() {
+                let from = (start_line - spill_start) as usize;
+                match spill.read_lines(from, limit) {
+                    Ok(read) => lines = read,
+                    Err(e) => {
+                        log::warn!("Failed to read spilled output: {}", e);
+                        start_line = self.first_line;
+                    }
+                }
+            }
+        }
+
+        let memory_from = (start_line + lines.len() as u64).max(self.first_line);
+        let skip = (memory_from - self.first_line) as usize;
+        let remaining = limit.saturating_sub(lines.len());
+        lines.extend(self.lines.iter().skip(skip).take(remaining).cloned());
+
+        OutputChunk {
+            start_line,
+            next_offset: start_line + lines.len() as u64,
+            lines,
+            total_lines,
+        }
+    }
+
+    /// Returns the in-memory window as newline-terminated text
+    pub fn recent_text(&self) -> String {
+        let mut text = String::with_capacity(self.bytes + self.lines.len());
+        for line in &self.lines {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_buffer_spills_and_reads_from_offset() {
+        let temp_dir = TempDir::new().unwrap();
+        let spill_path = temp_dir.path().join("output.log");
+        let mut buffer = OutputBuffer::with_limits(Some(spill_path.clone()), 3, usize::MAX);
+
+        for i in 0..10 {
+            buffer.push_line(&format!("line {}", i));
+        }
+
+        assert_eq!(buffer.total_lines(), 10);
+        assert_eq!(buffer.recent_text(), "line 7\nline 8\nline 9\n");
+        assert!(spill_path.exists());
+
+        // A read spanning the spill file and the in-memory window
+        let chunk = buffer.read_from(5, Some(4));
+        assert_eq!(chunk.start_line, 5);
+        assert_eq!(chunk.lines, vec!["line 5", "line 6", "line 7", "line 8"]);
+        assert_eq!(chunk.next_offset, 9);
+
+        // Incremental delta from the end
+        let chunk = buffer.read_from(chunk.next_offset, None);
+        assert_eq!(chunk.lines, vec!["line 9"]);
+        assert_eq!(chunk.next_offset, 10);
+        assert!(buffer.read_from(10, None).lines.is_empty());
+
+        drop(buffer);
+        assert!(!spill_path.exists());
+    }
+
+    #[test]
+    fn test_buffer_without_spill_skips_evicted_lines() {
+        let mut buffer = OutputBuffer::with_limits(None, 2, usize::MAX);
+        for i in 0..5 {
+            buffer.push_line(&format!("line {}", i));
+        }
+
+        let chunk = buffer.read_from(0, None);
+        assert_eq!(chunk.start_line, 3);
+        assert_eq!(chunk.lines, vec!["line 3", "line 4"]);
+        assert_eq!(chunk.total_lines, 5);
+    }
+
+    #[test]
+    fn test_buffer_stops_spilling_after_failed_write() {
+        let temp_dir = TempDir::new().unwrap();
+        let spill_path = temp_dir.path().join("output.log");
+        let mut buffer = OutputBuffer::with_limits(Some(spill_path.clone()), 2, usize::MAX);
+        for i in 0..3 {
+            buffer.push_line(&format!("line {}", i));
+        }
+        assert_eq!(
+            buffer.read_from(0, None).lines,
+            vec!["line 0", "line 1", "line 2"]
+        );
+
+        // Writes through a read-only handle fail
+        buffer.spill.as_mut().unwrap().file = File::open(&spill_path).unwrap();
+        for i in 3..5 {
+            buffer.push_line(&format!("line {}", i));
+        }
+
+        assert!(buffer.spill.is_none());
+        assert!(!spill_path.exists());
+        let chunk = buffer.read_from(0, None);
+        assert_eq!(chunk.start_line, 3);
+        assert_eq!(chunk.lines, vec!["line 3", "line 4"]);
+    }
+}
@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+
+/// Claude's `--permission-mode` choices, from least to most permissive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PermissionMode {
+    /// Read-only planning; no edits or commands
+    Plan,
+    /// Ask before every tool that isn't explicitly allowed
+    Default,
+    /// Accept file edits without asking
+    AcceptEdits,
+    /// Never ask; the behaviour of runs that predate permission settings
+    #[default]
+    BypassPermissions,
+}
+
+impl PermissionMode {
+    fn as_arg(self) -> &'static str {
+        match self {
+            PermissionMode::Plan => "plan",
+            PermissionMode::Default => "default",
+            PermissionMode::AcceptEdits => "acceptEdits",
+            PermissionMode::BypassPermissions => "bypassPermissions",
+        }
+    }
+}
+
+/// Tools that read files
+const FILE_READ_TOOLS: &[&str] = &["Read", "Glob", "Grep", "LS", "NotebookRead"];
+
+/// Tools that create or modify files
+const FILE_WRITE_TOOLS: &[&str] = &["Write", "Edit", "MultiEdit", "NotebookEdit"];
+
+/// Tools that reach the network
+const NETWORK_TOOLS: &[&str] = &["WebFetch", "WebSearch"];
+
+/// What an agent may touch, from its `enable_*` flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub file_read: bool,
+    pub file_write: bool,
+    pub network: bool,
+}
+
+/// Tool permissions for a project or an agent, turned into Claude CLI flags
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PermissionSettings {
+    #[serde(default)]
+    pub mode: PermissionMode,
+    /// Tools, or tool patterns such as `Bash(git diff:*)`, used without asking
+    #[serde(default)]
+    pub allowed_tools: Vec<String>,
+    /// Tools Claude may not use at all
+    #[serde(default)]
+    pub disallowed_tools: Vec<String>,
+    /// Forward permission prompts to the app for the user to approve or deny
+    #[serde(default)]
+    pub require_approval: bool,
+}
+
+impl PermissionSettings {
+    /// Parses settings stored as a JSON string, ignoring empty values
+    pub fn from_json(json: Option<&str>) -> Result<Option<Self>, String> {
+        match json.map(str::trim) {
+            None | Some("") => Ok(None),
+            Some(json) => serde_json::from_str(json)
+                .map(Some)
+                .map_err(|e| format!("Invalid permission settings: {}", e)),
+        }
+    }
+
+    /// Restricts these (agent) settings to what the project's settings allow
+    ///
+    /// The stricter mode wins, disallowed tools accumulate, and when the project lists
+    /// allowed tools the agent can only narrow that list.
+    pub fn within(&self, project: &PermissionSettings) -> PermissionSettings {
+        let mut disallowed_tools = project.disallowed_tools.clone();
+        for tool in &self.disallowed_tools {
+            if !disallowed_tools.contains(tool) {
+                disallowed_tools.push(tool.clone());
+            }
+        }
+
+        let allowed_tools = if self.allowed_tools.is_empty() {
+            project.allowed_tools.clone()
+        } else if project.allowed_tools.is_empty() {
+            self.allowed_tools.clone()
+        } else {
+            self.allowed_tools
+                .iter()
+                .filter(|tool| project.allowed_tools.contains(tool))
+                .cloned()
+                .collect()
+        };
+
+        PermissionSettings {
+            mode: self.mode.min(project.mode),
+            allowed_tools: allowed_tools
+                .into_iter()
+                .filter(|tool| !disallowed_tools.contains(tool))
+                .collect(),
+            disallowed_tools,
+            require_approval: self.require_approval || project.require_approval,
+        }
+    }
+
+    /// Denies the tools an agent's capabilities rule out, whatever else the settings allow
+    ///
+    /// Bash can read, write and reach anything, so it goes with any restriction, as does
+    /// Task, whose sub-agents would otherwise get a fresh set of tools. Claude itself needs
+    /// the network to reach its API, so the process isn't isolated from it: MCP tools and
+    /// hooks configured for the project can still reach the network without it.
+    pub fn restricted_to(mut self, capabilities: Capabilities) -> PermissionSettings {
+        let mut denied: Vec<String> = Vec::new();
+        if !capabilities.file_read {
+            denied.extend(FILE_READ_TOOLS.iter().map(|t| t.to_string()));
+        }
+        if !capabilities.file_write {
+            denied.extend(FILE_WRITE_TOOLS.iter().map(|t| t.to_string()));
+        }
+        if !capabilities.network {
+            denied.extend(NETWORK_TOOLS.iter().map(|t| t.to_string()));
+        }
+        if !capabilities.file_read || !capabilities.file_write || !capabilities.network {
+            denied.extend(["Bash".to_string(), "Task".to_string()]);
+        }
+
+        for tool in denied {
+            if !self.disallowed_tools.contains(&tool) {
+                self.disallowed_tools.push(tool);
+            }
+        }
+        // An allowed entry such as `Bash(rm:*)` is covered by denying `Bash`
+        let disallowed = &self.disallowed_tools;
+        self.allowed_tools.retain(|allowed| {
+            let tool = allowed.split('(').next().unwrap_or(allowed);
+            !disallowed.iter().any(|d| d == allowed || d == tool)
+        });
+        self
+    }
+
+    /// The Claude CLI flags for these settings
+    ///
+    /// Approval prompts are exchanged over stream-json stdin, so they need an interactive run.
+    pub fn cli_args(&self, interactive: bool) -> Result<Vec<String>, String> {
+        if self.require_approval && !interactive {
+            return Err("Approving tool use requires an interactive run".to_string());
+        }
+
+        // Bypassing permissions would mean there is never anything to approve
+        let mode = if self.require_approval && self.mode == PermissionMode::BypassPermissions {
+            PermissionMode::Default
+        } else {
+            self.mode
+        };
+
+        let mut args = vec!["--permission-mode".to_string(), mode.as_arg().to_string()];
+        if !self.allowed_tools.is_empty() {
+            args.push("--allowedTools".to_string());
+            args.extend(self.allowed_tools.iter().cloned());
+        }
+        if !self.disallowed_tools.is_empty() {
+            args.push("--disallowedTools".to_string());
+            args.extend(self.disallowed_tools.iter().cloned());
+        }
+        if self.require_approval {
+            args.extend(["--permission-prompt-tool".to_string(), "stdio".to_string()]);
+        }
+        Ok(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_settings_are_limited_by_project() {
+        let project = PermissionSettings {
+            mode: PermissionMode::AcceptEdits,
+            allowed_tools: vec!["Read".to_string(), "Edit".to_string()],
+            disallowed_tools: vec!["WebFetch".to_string()],
+            require_approval: false,
+        };
+        let agent = PermissionSettings {
+            mode: PermissionMode::BypassPermissions,
+            allowed_tools: vec!["Edit".to_string(), "Bash".to_string()],
+            disallowed_tools: vec!["Edit".to_string()],
+            require_approval: true,
+        };
+
+        let merged = agent.within(&project);
+        assert_eq!(merged.mode, PermissionMode::AcceptEdits);
+        assert!(merged.allowed_tools.is_empty());
+        assert_eq!(merged.disallowed_tools, vec!["WebFetch", "Edit"]);
+        assert!(merged.require_approval);
+
+        assert!(merged.cli_args(false).is_err());
+        assert_eq!(
+            merged.cli_args(true).unwrap(),
+            vec![
+                "--permission-mode",
+                "acceptEdits",
+                "--disallowedTools",
+                "WebFetch",
+                "Edit",
+                "--permission-prompt-tool",
+                "stdio"
+            ]
+        );
+        assert_eq!(
+            PermissionSettings::default().cli_args(false).unwrap(),
+            vec!["--permission-mode", "bypassPermissions"]
+        );
+    }
+
+    #[test]
+    fn test_no_network_denies_all_shell_commands() {
+        let agent = PermissionSettings {
+            allowed_tools: vec![
+                "Bash(python -c:*)".to_string(),
+                "Bash(git fetch:*)".to_string(),
+                "Read".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let restricted = agent.restricted_to(Capabilities {
+            file_read: true,
+            file_write: true,
+            network: false,
+        });
+        assert_eq!(restricted.allowed_tools, vec!["Read"]);
+        assert_eq!(
+            restricted.disallowed_tools,
+            vec!["WebFetch", "WebSearch", "Bash", "Task"]
+        );
+    }
+}
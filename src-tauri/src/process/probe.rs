@@ -0,0 +1,133 @@
+use chrono::{DateTime, Utc};
+
+use super::signals::is_pid_alive;
+
+/// How far a probed start time may drift from the recorded one and still count as the same process
+const START_TIME_TOLERANCE_SECS: i64 = 5;
+
+/// Result of checking whether a recorded PID still belongs to the run that spawned it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunProbe {
+    /// The PID is alive and its start time matches the recorded one
+    Alive,
+    /// No process with this PID exists
+    Dead,
+    /// A process with this PID exists but started at a different time
+    PidReused,
+}
+
+/// Checks whether `pid` is still the process that was recorded as starting at `recorded_start`
+///
+/// When the platform cannot report a start time, a live PID is trusted.
+pub fn probe_run(pid: u32, recorded_start: Option<&DateTime<Utc>>) -> RunProbe {
+    if pid == 0 || !is_pid_alive(pid) {
+        return RunProbe::Dead;
+    }
+
+    match (recorded_start, process_start_time(pid)) {
+        (Some(recorded), Some(actual)) => {
+            if (actual - *recorded).num_seconds().abs() <= START_TIME_TOLERANCE_SECS {
+                RunProbe::Alive
+            } else {
+                RunProbe::PidReused
+            }
+        }
+        _ => RunProbe::Alive,
+    }
+}
+
+/// Returns when the process with the given PID was started
+#[cfg(target_os = "linux")]
+pub fn process_start_time(pid: u32) -> Option<DateTime<Utc>> {
+    // Field 22 of /proc/<pid>/stat is the start time in clock ticks since boot.
+    // The command name may contain spaces, so parse from the last ')'.
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let after_comm = &stat[stat.rfind(')')? + 1..];
+    let start_ticks: u64 = after_comm.split_whitespace().nth(19)?.parse().ok()?;
+
+    let boot_time: i64 = std::fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_sec <= 0 {
+        return None;
+    }
+
+    let start_ms = boot_time * 1000 + (start_ticks as i64 * 1000) / ticks_per_sec as i64;
+    DateTime::from_timestamp_millis(start_ms)
+}
+
+/// Returns when the process with the given PID was started
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn process_start_time(pid: u32) -> Option<DateTime<Utc>> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "etime=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let elapsed = parse_etime(String::from_utf8_lossy(&output.stdout).trim())?;
+    Some(Utc::now() - chrono::Duration::seconds(elapsed))
+}
+
+/// Returns when the process with the given PID was started
+#[cfg(not(unix))]
+pub fn process_start_time(_pid: u32) -> Option<DateTime<Utc>> {
+    None
+}
+
+/// Parses the `[[dd-]hh:]mm:ss` elapsed time format printed by `ps -o etime`
+#[allow(dead_code)]
+fn parse_etime(etime: &str) -> Option<i64> {
+    let (days, clock) = match etime.split_once('-') {
+        Some((days, clock)) => (days.parse::<i64>().ok()?, clock),
+        None => (0, etime),
+    };
+
+    let mut seconds = 0i64;
+    for part in clock.split(':') {
+        seconds = seconds * 60 + part.parse::<i64>().ok()?;
+    }
+    Some(days * 86_400 + seconds)
+}
+
+/// Parses a recorded `process_started_at` value
+pub fn parse_recorded_start(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_probe_distinguishes_live_dead_and_reused_pids() {
+        let pid = std::process::id();
+        let started = process_start_time(pid).unwrap_or_else(Utc::now);
+        assert_eq!(probe_run(pid, Some(&started)), RunProbe::Alive);
+
+        if process_start_time(pid).is_some() {
+            let long_ago = started - Duration::days(1);
+            assert_eq!(probe_run(pid, Some(&long_ago)), RunProbe::PidReused);
+        }
+
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead_pid = child.id();
+        child.wait().unwrap();
+        assert_eq!(probe_run(dead_pid, None), RunProbe::Dead);
+    }
+
+    #[test]
+    fn test_parse_etime() {
+        assert_eq!(parse_etime("05:07"), Some(307));
+        assert_eq!(parse_etime("01:00:00"), Some(3600));
+        assert_eq!(parse_etime("2-00:00:01"), Some(172_801));
+        assert_eq!(parse_etime("garbage"), None);
+    }
+}
@@ -2,7 +2,16 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use tokio::process::Child;
+use tokio::io::AsyncWriteExt;
+use tokio::process::ChildStdin;
+
+use super::input::SessionInput;
+use super::limits::{ResourceLimits, ResourceUsage};
+use super::output::{OutputBuffer, OutputChunk};
+use super::signals::{
+    is_pid_alive, resume_process_tree, suspend_process_tree, terminate_process_tree, KillPolicy,
+    KillReport,
+};
 
 /// Type of process being tracked
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,20 +35,43 @@ pub struct ProcessInfo {
     pub project_path: String,
     pub task: String,
     pub model: String,
+    /// Limits enforced on this process tree, if any
+    #[serde(default)]
+    pub resource_limits: Option<ResourceLimits>,
+    /// Latest CPU/memory sample for this process tree
+    #[serde(default)]
+    pub resource_usage: Option<ResourceUsage>,
+    /// When the process tree was paused, if it is currently paused
+    #[serde(default)]
+    pub paused_at: Option<DateTime<Utc>>,
+    /// Total time spent paused before the current pause, in milliseconds
+    #[serde(default)]
+    pub paused_ms: u64,
 }
 
 /// Information about a running process with handle
+///
+/// The task that spawned the process owns its child handle, so processes are killed by PID.
 #[allow(dead_code)]
 pub struct ProcessHandle {
     pub info: ProcessInfo,
-    pub child: Arc<Mutex<Option<Child>>>,
-    pub live_output: Arc<Mutex<String>>,
+    pub live_output: Arc<Mutex<OutputBuffer>>,
+    /// Stdin of sessions started in interactive mode
+    pub stdin: Arc<tokio::sync::Mutex<Option<ChildStdin>>>,
+}
+
+/// Creates the live output buffer for a run, spilling to a per-run temp file
+fn new_live_output(run_id: i64) -> Arc<Mutex<OutputBuffer>> {
+    Arc::new(Mutex::new(OutputBuffer::new(Some(
+        OutputBuffer::spill_path_for(run_id),
+    ))))
 }
 
 /// Registry for tracking active agent processes
 pub struct ProcessRegistry {
     processes: Arc<Mutex<HashMap<i64, ProcessHandle>>>, // run_id -> ProcessHandle
     next_id: Arc<Mutex<i64>>, // Auto-incrementing ID for non-agent processes
+    kill_policy: Arc<Mutex<KillPolicy>>,
 }
 
 impl ProcessRegistry {
@@ -47,6 +79,7 @@ impl ProcessRegistry {
         Self {
             processes: Arc::new(Mutex::new(HashMap::new())),
             next_id: Arc::new(Mutex::new(1000000)), // Start at high number to avoid conflicts
+            kill_policy: Arc::new(Mutex::new(KillPolicy::default())),
         }
     }
 
@@ -59,6 +92,8 @@ impl ProcessRegistry {
     }
 
     /// Register a new running agent process
+    ///
+    /// `stdin` is kept for runs started in interactive mode.
     pub fn register_process(
         &self,
         run_id: i64,
@@ -68,7 +103,7 @@ impl ProcessRegistry {
         project_path: String,
         task: String,
         model: String,
-        child: Child,
+        stdin: Option<ChildStdin>,
     ) -> Result<(), String> {
         let process_info = ProcessInfo {
             run_id,
@@ -78,12 +113,49 @@ impl ProcessRegistry {
             project_path,
             task,
             model,
+            resource_limits: None,
+            resource_usage: None,
+            paused_at: None,
+            paused_ms: 0,
         };
 
-        self.register_process_internal(run_id, process_info, child)
+        self.register_process_internal(run_id, process_info, stdin)
     }
 
-    /// Register a new Claude session (without child process - handled separately)
+    /// Re-attach to an agent run that outlived a previous app instance
+    ///
+    /// Nothing in this app instance waits on the process, so it is only tracked by PID.
+    pub fn register_reattached_run(
+        &self,
+        run_id: i64,
+        agent_id: i64,
+        agent_name: String,
+        pid: u32,
+        started_at: DateTime<Utc>,
+        project_path: String,
+        task: String,
+        model: String,
+    ) -> Result<(), String> {
+        let process_info = ProcessInfo {
+            run_id,
+            process_type: ProcessType::AgentRun { agent_id, agent_name },
+            pid,
+            started_at,
+            project_path,
+            task,
+            model,
+            resource_limits: None,
+            resource_usage: None,
+            paused_at: None,
+            paused_ms: 0,
+        };
+
+        self.register_process_internal(run_id, process_info, None)
+    }
+
+    /// Register a new interactive Claude session under its session ID
+    ///
+    /// `stdin` is kept for sessions started in interactive mode.
     pub fn register_claude_session(
         &self,
         session_id: String,
@@ -91,6 +163,7 @@ impl ProcessRegistry {
         project_path: String,
         task: String,
         model: String,
+        stdin: Option<ChildStdin>,
     ) -> Result<i64, String> {
         let run_id = self.generate_id()?;
         
@@ -102,18 +175,13 @@ impl ProcessRegistry {
             project_path,
             task,
             model,
+            resource_limits: None,
+            resource_usage: None,
+            paused_at: None,
+            paused_ms: 0,
         };
 
-        // Register without child - Claude sessions use ClaudeProcessState for process management
-        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
-        
-        let process_handle = ProcessHandle {
-            info: process_info,
-            child: Arc::new(Mutex::new(None)), // No child handle for Claude sessions
-            live_output: Arc::new(Mutex::new(String::new())),
-        };
-
-        processes.insert(run_id, process_handle);
+        self.register_process_internal(run_id, process_info, stdin)?;
         Ok(run_id)
     }
 
@@ -122,14 +190,14 @@ impl ProcessRegistry {
         &self,
         run_id: i64,
         process_info: ProcessInfo,
-        child: Child,
+        stdin: Option<ChildStdin>,
     ) -> Result<(), String> {
         let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
 
         let process_handle = ProcessHandle {
             info: process_info,
-            child: Arc::new(Mutex::new(Some(child))),
-            live_output: Arc::new(Mutex::new(String::new())),
+            live_output: new_live_output(run_id),
+            stdin: Arc::new(tokio::sync::Mutex::new(stdin)),
         };
 
         processes.insert(run_id, process_handle);
@@ -203,18 +271,43 @@ impl ProcessRegistry {
         Ok(processes.get(&run_id).map(|handle| handle.info.clone()))
     }
 
-    /// Kill a running process with proper cleanup
-    pub async fn kill_process(&self, run_id: i64) -> Result<bool, String> {
-        use log::{error, info, warn};
+    /// Get the grace periods used when killing processes
+    pub fn kill_policy(&self) -> Result<KillPolicy, String> {
+        let policy = self.kill_policy.lock().map_err(|e| e.to_string())?;
+        Ok(policy.clone())
+    }
+
+    /// Set the grace periods used when killing processes
+    pub fn set_kill_policy(&self, policy: KillPolicy) -> Result<(), String> {
+        let mut current = self.kill_policy.lock().map_err(|e| e.to_string())?;
+        *current = policy;
+        Ok(())
+    }
+
+    /// Kill a running process and its descendants using the registry's kill policy
+    ///
+    /// Returns `None` if the process is not tracked by the registry.
+    pub async fn kill_process(&self, run_id: i64) -> Result<Option<KillReport>, String> {
+        let policy = self.kill_policy()?;
+        self.kill_process_with_policy(run_id, &policy).await
+    }
+
+    /// Kill a running process and its descendants, escalating SIGINT -> SIGTERM -> SIGKILL
+    pub async fn kill_process_with_policy(
+        &self,
+        run_id: i64,
+        policy: &KillPolicy,
+    ) -> Result<Option<KillReport>, String> {
+        use log::{info, warn};
 
         // First check if the process exists and get its PID
-        let (pid, child_arc) = {
+        let pid = {
             let processes = self.processes.lock().map_err(|e| e.to_string())?;
             if let Some(handle) = processes.get(&run_id) {
-                (handle.info.pid, handle.child.clone())
+                handle.info.pid
             } else {
                 warn!("Process {} not found in registry", run_id);
-                return Ok(false); // Process not found
+                return Ok(None); // Process not found
             }
         };
 
@@ -223,208 +316,163 @@ impl ProcessRegistry {
             run_id, pid
         );
 
-        // Send kill signal to the process
-        let kill_sent = {
-            let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
-            if let Some(child) = child_guard.as_mut() {
-                match child.start_kill() {
-                    Ok(_) => {
-                        info!("Successfully sent kill signal to process {}", run_id);
-                        true
-                    }
-                    Err(e) => {
-                        error!("Failed to send kill signal to process {}: {}", run_id, e);
-                        // Don't return error here, try fallback method
-                        false
-                    }
-                }
-            } else {
-                warn!("No child handle available for process {} (PID: {}), attempting system kill", run_id, pid);
-                false // Process handle not available, try fallback
-            }
-        };
-
-        // If direct kill didn't work, try system command as fallback
-        if !kill_sent {
-            info!("Attempting fallback kill for process {} (PID: {})", run_id, pid);
-            match self.kill_process_by_pid(run_id, pid) {
-                Ok(true) => return Ok(true),
-                Ok(false) => warn!("Fallback kill also failed for process {} (PID: {})", run_id, pid),
-                Err(e) => error!("Error during fallback kill: {}", e),
-            }
-            // Continue with the rest of the cleanup even if fallback failed
-        }
-
-        // Wait for the process to exit (with timeout)
-        let wait_result = tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
-            loop {
-                // Check if process has exited
-                let status = {
-                    let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
-                    if let Some(child) = child_guard.as_mut() {
-                        match child.try_wait() {
-                            Ok(Some(status)) => {
-                                info!("Process {} exited with status: {:?}", run_id, status);
-                                *child_guard = None; // Clear the child handle
-                                Some(Ok::<(), String>(()))
-                            }
-                            Ok(None) => {
-                                // Still running
-                                None
-                            }
-                            Err(e) => {
-                                error!("Error checking process status: {}", e);
-                                Some(Err(e.to_string()))
-                            }
-                        }
-                    } else {
-                        // Process already gone
-                        Some(Ok(()))
-                    }
-                };
-
-                match status {
-                    Some(result) => return result,
-                    None => {
-                        // Still running, wait a bit
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                    }
-                }
-            }
-        })
-        .await;
+        let started = std::time::Instant::now();
+        // The spawning task reaps the child once it exits
+        let reason = terminate_process_tree(pid, policy, || false).await;
 
-        match wait_result {
-            Ok(Ok(_)) => {
-                info!("Process {} exited gracefully", run_id);
-            }
-            Ok(Err(e)) => {
-                error!("Error waiting for process {}: {}", run_id, e);
-            }
-            Err(_) => {
-                warn!("Process {} didn't exit within 5 seconds after kill", run_id);
-                // Force clear the handle
-                if let Ok(mut child_guard) = child_arc.lock() {
-                    *child_guard = None;
-                }
-                // One more attempt with system kill
-                let _ = self.kill_process_by_pid(run_id, pid);
-            }
-        }
+        info!("Process {} {}", run_id, reason.description());
 
         // Remove from registry after killing
         self.unregister_process(run_id)?;
 
-        Ok(true)
+        Ok(Some(KillReport {
+            run_id,
+            pid,
+            reason,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        }))
     }
 
-    /// Kill a process by PID using system commands (fallback method)
-    pub fn kill_process_by_pid(&self, run_id: i64, pid: u32) -> Result<bool, String> {
-        use log::{error, info, warn};
+    /// Kill a process tree by PID when it is not tracked by the registry (fallback method)
+    pub async fn kill_process_by_pid(&self, run_id: i64, pid: u32) -> Result<KillReport, String> {
+        use log::info;
 
         info!("Attempting to kill process {} by PID {}", run_id, pid);
 
-        let kill_result = if cfg!(target_os = "windows") {
-            std::process::Command::new("taskkill")
-                .args(["/F", "/PID", &pid.to_string()])
-                .output()
-        } else {
-            // First try SIGTERM
-            let term_result = std::process::Command::new("kill")
-                .args(["-TERM", &pid.to_string()])
-                .output();
-
-            match &term_result {
-                Ok(output) if output.status.success() => {
-                    info!("Sent SIGTERM to PID {}", pid);
-                    // Give it 2 seconds to exit gracefully
-                    std::thread::sleep(std::time::Duration::from_secs(2));
-
-                    // Check if still running
-                    let check_result = std::process::Command::new("kill")
-                        .args(["-0", &pid.to_string()])
-                        .output();
-
-                    if let Ok(output) = check_result {
-                        if output.status.success() {
-                            // Still running, send SIGKILL
-                            warn!(
-                                "Process {} still running after SIGTERM, sending SIGKILL",
-                                pid
-                            );
-                            std::process::Command::new("kill")
-                                .args(["-KILL", &pid.to_string()])
-                                .output()
-                        } else {
-                            term_result
-                        }
-                    } else {
-                        term_result
-                    }
-                }
-                _ => {
-                    // SIGTERM failed, try SIGKILL directly
-                    warn!("SIGTERM failed for PID {}, trying SIGKILL", pid);
-                    std::process::Command::new("kill")
-                        .args(["-KILL", &pid.to_string()])
-                        .output()
-                }
-            }
-        };
+        let policy = self.kill_policy()?;
+        let started = std::time::Instant::now();
+        let reason = terminate_process_tree(pid, &policy, || false).await;
+        info!("Process {} (PID {}) {}", run_id, pid, reason.description());
 
-        match kill_result {
-            Ok(output) => {
-                if output.status.success() {
-                    info!("Successfully killed process with PID {}", pid);
-                    // Remove from registry
-                    self.unregister_process(run_id)?;
-                    Ok(true)
-                } else {
-                    let error_msg = String::from_utf8_lossy(&output.stderr);
-                    warn!("Failed to kill PID {}: {}", pid, error_msg);
-                    Ok(false)
-                }
-            }
-            Err(e) => {
-                error!("Failed to execute kill command for PID {}: {}", pid, e);
-                Err(format!("Failed to execute kill command: {}", e))
-            }
-        }
+        self.unregister_process(run_id)?;
+
+        Ok(KillReport {
+            run_id,
+            pid,
+            reason,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        })
     }
 
-    /// Check if a process is still running by trying to get its status
+    /// Check if a process is still running
     #[allow(dead_code)]
     pub async fn is_process_running(&self, run_id: i64) -> Result<bool, String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
+        Ok(processes
+            .get(&run_id)
+            .is_some_and(|handle| is_pid_alive(handle.info.pid)))
+    }
 
-        if let Some(handle) = processes.get(&run_id) {
-            let child_arc = handle.child.clone();
-            drop(processes); // Release the lock before async operation
-
-            let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
-            if let Some(ref mut child) = child_guard.as_mut() {
-                match child.try_wait() {
-                    Ok(Some(_)) => {
-                        // Process has exited
-                        *child_guard = None;
-                        Ok(false)
-                    }
-                    Ok(None) => {
-                        // Process is still running
-                        Ok(true)
-                    }
-                    Err(_) => {
-                        // Error checking status, assume not running
-                        *child_guard = None;
-                        Ok(false)
-                    }
-                }
-            } else {
-                Ok(false) // No child handle
+    /// Pause a running process tree with SIGSTOP
+    ///
+    /// Returns false if the process is not tracked or is already paused.
+    pub fn pause_process(&self, run_id: i64) -> Result<bool, String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        let Some(handle) = processes.get_mut(&run_id) else {
+            return Ok(false);
+        };
+        if handle.info.paused_at.is_some() {
+            return Ok(false);
+        }
+
+        suspend_process_tree(handle.info.pid)?;
+        handle.info.paused_at = Some(Utc::now());
+        Ok(true)
+    }
+
+    /// Resume a paused process tree with SIGCONT
+    ///
+    /// Returns false if the process is not tracked or is not paused.
+    pub fn resume_process(&self, run_id: i64) -> Result<bool, String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        let Some(handle) = processes.get_mut(&run_id) else {
+            return Ok(false);
+        };
+        let Some(paused_at) = handle.info.paused_at else {
+            return Ok(false);
+        };
+
+        resume_process_tree(handle.info.pid)?;
+        let paused_for = (Utc::now() - paused_at).num_milliseconds().max(0) as u64;
+        handle.info.paused_ms += paused_for;
+        handle.info.paused_at = None;
+        Ok(true)
+    }
+
+    /// Whether a process is currently paused
+    pub fn is_paused(&self, run_id: i64) -> bool {
+        self.processes
+            .lock()
+            .ok()
+            .and_then(|processes| processes.get(&run_id).map(|h| h.info.paused_at.is_some()))
+            .unwrap_or(false)
+    }
+
+    /// Total time a process has spent paused, including any pause in progress
+    pub fn paused_duration(&self, run_id: i64) -> std::time::Duration {
+        let Ok(processes) = self.processes.lock() else {
+            return std::time::Duration::ZERO;
+        };
+        let Some(handle) = processes.get(&run_id) else {
+            return std::time::Duration::ZERO;
+        };
+        let current = handle
+            .info
+            .paused_at
+            .map(|at| (Utc::now() - at).num_milliseconds().max(0) as u64)
+            .unwrap_or(0);
+        std::time::Duration::from_millis(handle.info.paused_ms + current)
+    }
+
+    /// Write input to an interactive session's stdin
+    pub async fn send_input(&self, run_id: i64, input: &SessionInput) -> Result<(), String> {
+        let slot = {
+            let processes = self.processes.lock().map_err(|e| e.to_string())?;
+            match processes.get(&run_id) {
+                Some(handle) => handle.stdin.clone(),
+                None => return Err(format!("Process {} not found in registry", run_id)),
             }
-        } else {
-            Ok(false) // Process not found in registry
+        };
+
+        let mut stdin = slot.lock().await;
+        let Some(line) = input.to_stream_json() else {
+            // Dropping stdin sends EOF; Claude exits after the current turn
+            *stdin = None;
+            return Ok(());
+        };
+        let writer = stdin
+            .as_mut()
+            .ok_or("This session does not accept input; start it in interactive mode")?;
+        writer
+            .write_all(format!("{}\n", line).as_bytes())
+            .await
+            .map_err(|e| format!("Failed to write to session stdin: {}", e))?;
+        writer
+            .flush()
+            .await
+            .map_err(|e| format!("Failed to write to session stdin: {}", e))
+    }
+
+    /// Record the limits enforced on a process
+    pub fn set_resource_limits(
+        &self,
+        run_id: i64,
+        limits: Option<ResourceLimits>,
+    ) -> Result<(), String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get_mut(&run_id) {
+            handle.info.resource_limits = limits;
+        }
+        Ok(())
+    }
+
+    /// Record the latest resource sample for a process
+    pub fn update_resource_usage(&self, run_id: i64, usage: ResourceUsage) -> Result<(), String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get_mut(&run_id) {
+            handle.info.resource_usage = Some(usage);
         }
+        Ok(())
     }
 
     /// Append to live output for a process
@@ -432,23 +480,43 @@ impl ProcessRegistry {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
         if let Some(handle) = processes.get(&run_id) {
             let mut live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
-            live_output.push_str(output);
-            live_output.push('\n');
+            live_output.push_line(output);
         }
         Ok(())
     }
 
-    /// Get live output for a process
+    /// Get the most recent live output for a process
+    ///
+    /// Only the in-memory window is returned; use `read_live_output` to page through everything.
     pub fn get_live_output(&self, run_id: i64) -> Result<String, String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
         if let Some(handle) = processes.get(&run_id) {
             let live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
-            Ok(live_output.clone())
+            Ok(live_output.recent_text())
         } else {
             Ok(String::new())
         }
     }
 
+    /// Read live output for a process starting at a line offset
+    pub fn read_live_output(
+        &self,
+        run_id: i64,
+        offset: u64,
+        limit: Option<usize>,
+    ) -> Result<Option<OutputChunk>, String> {
+        let live_output = {
+            let processes = self.processes.lock().map_err(|e| e.to_string())?;
+            match processes.get(&run_id) {
+                Some(handle) => handle.live_output.clone(),
+                None => return Ok(None),
+            }
+        };
+
+        let mut live_output = live_output.lock().map_err(|e| e.to_string())?;
+        Ok(Some(live_output.read_from(offset, limit)))
+    }
+
     /// Cleanup finished processes
     #[allow(dead_code)]
     pub async fn cleanup_finished_processes(&self) -> Result<Vec<i64>, String> {
@@ -0,0 +1,676 @@
+use log::{debug, error, info, warn};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_shell::process::CommandEvent;
+use tauri_plugin_shell::ShellExt;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::{ChildStdin, Command};
+use tokio::sync::mpsc;
+
+use super::input::user_message_line;
+use super::limits::{apply_rlimits, monitor_resources, CgroupScope, LimitViolation};
+use super::registry::{ProcessRegistry, ProcessRegistryState};
+use super::signals::terminate_process_tree;
+use super::stream::StreamLine;
+use super::{ResourceLimits, RunEnv};
+
+/// Where the Claude CLI is run from
+#[derive(Debug, Clone)]
+pub enum ClaudeBinary {
+    /// The `claude-code` sidecar bundled with the app
+    Sidecar,
+    /// A Claude installation at the given path
+    System(String),
+}
+
+impl ClaudeBinary {
+    /// Resolves the Claude installation configured for the app
+    pub fn resolve(app: &AppHandle) -> Result<Self, String> {
+        let path = crate::claude_binary::find_claude_binary(app)?;
+        Ok(if path == "claude-code" {
+            ClaudeBinary::Sidecar
+        } else {
+            ClaudeBinary::System(path)
+        })
+    }
+}
+
+/// What a Claude process is being run for
+#[derive(Debug, Clone)]
+pub enum RunKind {
+    /// An agent run, registered under its `agent_runs` ID as soon as it spawns
+    Agent {
+        run_id: i64,
+        agent_id: i64,
+        agent_name: String,
+    },
+    /// An interactive session, registered under Claude's session ID once it reports one
+    Session,
+}
+
+/// Everything needed to start a Claude process
+#[derive(Debug, Clone)]
+pub struct RunRequest {
+    pub kind: RunKind,
+    pub args: Vec<String>,
+    pub project_path: String,
+    pub task: String,
+    pub model: String,
+    pub resource_limits: Option<ResourceLimits>,
+    /// Keep stdin open and send `task` over it as the first stream-json message
+    pub interactive: bool,
+    /// Kill the process if it prints nothing for this long; paused time doesn't count
+    pub startup_timeout: Duration,
+    /// Variables set for the process; its secret values are redacted from every output line
+    pub env: RunEnv,
+}
+
+/// Receives the lifecycle of a run started by `ClaudeRunner`
+///
+/// Callbacks are made from the runner's tasks, one at a time; `on_exit` is always last.
+pub trait RunObserver: Send + 'static {
+    /// The process was spawned; called before any output is delivered
+    fn on_spawned(&mut self, _pid: u32) {}
+    /// Claude reported its session ID; `run_id` is the registry ID the run is tracked under
+    fn on_session_started(&mut self, _run_id: i64, _session_id: &str) {}
+    fn on_stdout(&mut self, line: &str);
+    fn on_stderr(&mut self, line: &str);
+    /// The process tree exceeded a resource limit and is about to be killed
+    fn on_limit_exceeded(&mut self, _violation: LimitViolation, _limits: &ResourceLimits) {}
+    /// Nothing was printed within the startup timeout and the process tree was killed
+    fn on_startup_timeout(&mut self, _kill_reason: &str) {}
+    /// The process exited; `exit_code` is None when it was killed by a signal
+    fn on_exit(&mut self, exit_code: Option<i32>);
+}
+
+/// Output of a spawned process, normalised across sidecar and system binaries
+enum RunEvent {
+    Stdout(String),
+    Stderr(String),
+    Exited(Option<i32>),
+}
+
+/// Locks the observer, recovering from a callback that panicked
+fn lock<O>(observer: &Mutex<O>) -> MutexGuard<'_, O> {
+    observer.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Creates a tokio Command for a system Claude binary with the app's environment handling
+fn system_command(
+    program: &str,
+    args: &[String],
+    project_path: &str,
+    interactive: bool,
+    env: &RunEnv,
+) -> Command {
+    let mut cmd = Command::from(crate::claude_binary::create_command_with_env(program));
+    cmd.args(args)
+        .envs(&env.vars)
+        .current_dir(project_path)
+        .stdin(if interactive {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    // Run in its own process group so kills reach every descendant
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    cmd
+}
+
+/// Forwards each non-empty line of a pipe as an event
+async fn forward_lines<R>(
+    reader: R,
+    tx: mpsc::UnboundedSender<RunEvent>,
+    wrap: fn(String) -> RunEvent,
+) where
+    R: AsyncRead + Unpin,
+{
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let line = line.trim_end_matches('\r');
+        if !line.is_empty() && tx.send(wrap(line.to_string())).is_err() {
+            break;
+        }
+    }
+}
+
+/// Starts Claude processes and follows them until they exit
+///
+/// Handles binary resolution, environment, spawning through the sidecar or a system
+/// installation, session ID detection, registry bookkeeping, resource limits and the
+/// startup timeout. Callers only react to the lifecycle through a `RunObserver`.
+pub struct ClaudeRunner {
+    binary: ClaudeBinary,
+    registry: Arc<ProcessRegistry>,
+    /// Needed to spawn the sidecar
+    app: Option<AppHandle>,
+}
+
+impl ClaudeRunner {
+    pub fn new(
+        binary: ClaudeBinary,
+        registry: Arc<ProcessRegistry>,
+        app: Option<AppHandle>,
+    ) -> Self {
+        Self {
+            binary,
+            registry,
+            app,
+        }
+    }
+
+    /// Runner for the Claude installation configured for the app
+    pub fn for_app(app: &AppHandle) -> Result<Self, String> {
+        let binary = ClaudeBinary::resolve(app)?;
+        let registry = app.state::<ProcessRegistryState>().0.clone();
+        Ok(Self::new(binary, registry, Some(app.clone())))
+    }
+
+    /// Spawns Claude for a request and returns its PID
+    ///
+    /// The run is followed in the background; `observer` sees everything that happens to it.
+    pub async fn spawn<O: RunObserver>(
+        &self,
+        request: RunRequest,
+        observer: O,
+    ) -> Result<u32, String> {
+        let (pid, stdin, events) = match &self.binary {
+            ClaudeBinary::Sidecar => {
+                if request.interactive {
+                    return Err("Interactive runs require a system Claude installation".to_string());
+                }
+                self.spawn_sidecar(&request)?
+            }
+            ClaudeBinary::System(path) => self.spawn_system(path, &request)?,
+        };
+        info!("Spawned Claude process with PID {}", pid);
+
+        // Interactive runs read the prompt from stdin, which then stays open for follow-ups
+        let stdin = match stdin {
+            Some(mut stdin) => {
+                let line = format!("{}\n", user_message_line(&request.task));
+                if let Err(e) = stdin.write_all(line.as_bytes()).await {
+                    let error = format!("Failed to send prompt to Claude: {}", e);
+                    return Err(self.abandon(pid, error).await);
+                }
+                Some(stdin)
+            }
+            None => None,
+        };
+
+        let cgroup_name = match &request.kind {
+            RunKind::Agent { run_id, .. } => format!("run-{}", run_id),
+            RunKind::Session => format!("session-{}", pid),
+        };
+        let cgroup = request
+            .resource_limits
+            .as_ref()
+            .and_then(|limits| CgroupScope::create(&cgroup_name, limits))
+            .filter(|cgroup| cgroup.add_process(pid));
+        if cgroup.is_some() {
+            info!(
+                "Enforcing memory/process limits for PID {} through a cgroup",
+                pid
+            );
+        }
+
+        let mut observer = observer;
+        observer.on_spawned(pid);
+        let observer = Arc::new(Mutex::new(observer));
+
+        // Agent runs are tracked from the start, sessions once Claude reports their ID
+        let (registered, stdin, cgroup) = match &request.kind {
+            RunKind::Agent {
+                run_id,
+                agent_id,
+                agent_name,
+            } => {
+                if let Err(e) = self.registry.register_process(
+                    *run_id,
+                    *agent_id,
+                    agent_name.clone(),
+                    pid,
+                    request.project_path.clone(),
+                    request.task.clone(),
+                    request.model.clone(),
+                    stdin,
+                ) {
+                    return Err(self.abandon(pid, e).await);
+                }
+                start_monitor(
+                    &self.registry,
+                    *run_id,
+                    pid,
+                    &request,
+                    cgroup,
+                    observer.clone(),
+                );
+                (Some(*run_id), None, None)
+            }
+            RunKind::Session => (None, stdin, cgroup),
+        };
+
+        let first_output = Arc::new(AtomicBool::new(false));
+        let exited = Arc::new(AtomicBool::new(false));
+        let watchdog = spawn_startup_watchdog(
+            self.registry.clone(),
+            pid,
+            registered,
+            request.startup_timeout,
+            first_output.clone(),
+            exited.clone(),
+            observer.clone(),
+        );
+
+        let registry = self.registry.clone();
+        tokio::spawn(async move {
+            let mut events = events;
+            let mut run_id = registered;
+            let mut stdin = stdin;
+            let mut cgroup = cgroup;
+            let mut session_id: Option<String> = None;
+            let mut exit_code = None;
+
+            while let Some(event) = events.recv().await {
+                match event {
+                    RunEvent::Stdout(line) => {
+                        let line = request.env.redact(&line);
+                        first_output.store(true, Ordering::Relaxed);
+                        debug!("Claude stdout: {}", line);
+
+                        if session_id.is_none() {
+                            if let Some(sid) = StreamLine::parse(&line)
+                                .and_then(|l| l.init_session_id().map(str::to_string))
+                            {
+                                info!("Claude reported session ID {}", sid);
+                                if run_id.is_none() {
+                                    match registry.register_claude_session(
+                                        sid.clone(),
+                                        pid,
+                                        request.project_path.clone(),
+                                        request.task.clone(),
+                                        request.model.clone(),
+                                        stdin.take(),
+                                    ) {
+                                        Ok(id) => {
+                                            run_id = Some(id);
+                                            start_monitor(
+                                                &registry,
+                                                id,
+                                                pid,
+                                                &request,
+                                                cgroup.take(),
+                                                observer.clone(),
+                                            );
+                                        }
+                                        Err(e) => {
+                                            error!("Failed to register Claude session: {}", e)
+                                        }
+                                    }
+                                }
+                                if let Some(id) = run_id {
+                                    lock(&observer).on_session_started(id, &sid);
+                                }
+                                session_id = Some(sid);
+                            }
+                        }
+
+                        if let Some(id) = run_id {
+                            let _ = registry.append_live_output(id, &line);
+                        }
+                        lock(&observer).on_stdout(&line);
+                    }
+                    RunEvent::Stderr(line) => {
+                        let line = request.env.redact(&line);
+                        warn!("Claude stderr: {}", line);
+                        lock(&observer).on_stderr(&line);
+                    }
+                    RunEvent::Exited(code) => {
+                        exit_code = code;
+                        break;
+                    }
+                }
+            }
+            exited.store(true, Ordering::Relaxed);
+            let _ = watchdog.await;
+
+            // Give listeners a moment to process the last messages
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            lock(&observer).on_exit(exit_code);
+            if let Some(id) = run_id {
+                let _ = registry.unregister_process(id);
+            }
+        });
+
+        Ok(pid)
+    }
+
+    /// Kills a process that was spawned but couldn't be followed, and returns `error`
+    async fn abandon(&self, pid: u32, error: String) -> String {
+        warn!("Killing Claude process {}: {}", pid, error);
+        let policy = self.registry.kill_policy().unwrap_or_default();
+        terminate_process_tree(pid, &policy, || false).await;
+        error
+    }
+
+    fn spawn_system(
+        &self,
+        path: &str,
+        request: &RunRequest,
+    ) -> Result<(u32, Option<ChildStdin>, mpsc::UnboundedReceiver<RunEvent>), String> {
+        let mut cmd = system_command(
+            path,
+            &request.args,
+            &request.project_path,
+            request.interactive,
+            &request.env,
+        );
+        if let Some(limits) = &request.resource_limits {
+            apply_rlimits(&mut cmd, limits);
+        }
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| format!("Failed to spawn Claude: {}", e))?;
+        let pid = child.id().unwrap_or(0);
+        let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
+        let stderr = child.stderr.take().ok_or("Failed to get stderr")?;
+        let stdin = child.stdin.take();
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let stdout_task = tokio::spawn(forward_lines(stdout, tx.clone(), RunEvent::Stdout));
+        let stderr_task = tokio::spawn(forward_lines(stderr, tx.clone(), RunEvent::Stderr));
+
+        // This task owns the child handle and reaps it
+        tokio::spawn(async move {
+            let _ = stdout_task.await;
+            let _ = stderr_task.await;
+            let exit_code = match child.wait().await {
+                Ok(status) => {
+                    info!("Claude process {} exited with status: {}", pid, status);
+                    status.code()
+                }
+                Err(e) => {
+                    error!("Failed to wait for Claude process {}: {}", pid, e);
+                    None
+                }
+            };
+            let _ = tx.send(RunEvent::Exited(exit_code));
+        });
+
+        Ok((pid, stdin, rx))
+    }
+
+    fn spawn_sidecar(
+        &self,
+        request: &RunRequest,
+    ) -> Result<(u32, Option<ChildStdin>, mpsc::UnboundedReceiver<RunEvent>), String> {
+        let app = self
+            .app
+            .as_ref()
+            .ok_or("The bundled Claude sidecar is not available")?;
+        let sidecar_cmd = app
+            .shell()
+            .sidecar("claude-code")
+            .map_err(|e| format!("Failed to create sidecar command: {}", e))?
+            .args(&request.args)
+            .envs(request.env.vars.clone())
+            .current_dir(&request.project_path);
+
+        let (mut sidecar_rx, child) = sidecar_cmd
+            .spawn()
+            .map_err(|e| format!("Failed to spawn Claude sidecar: {}", e))?;
+        let pid = child.pid();
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            // Keep the child handle alive for as long as the sidecar runs
+            let _child = child;
+            let line = |bytes: &[u8]| {
+                String::from_utf8_lossy(bytes)
+                    .trim_end_matches(['\n', '\r'])
+                    .to_string()
+            };
+            while let Some(event) = sidecar_rx.recv().await {
+                let event = match event {
+                    CommandEvent::Stdout(bytes) => RunEvent::Stdout(line(&bytes)),
+                    CommandEvent::Stderr(bytes) => RunEvent::Stderr(line(&bytes)),
+                    CommandEvent::Terminated(payload) => {
+                        info!(
+                            "Claude sidecar {} terminated with payload: {:?}",
+                            pid, payload
+                        );
+                        let _ = tx.send(RunEvent::Exited(payload.code));
+                        break;
+                    }
+                    CommandEvent::Error(e) => {
+                        error!("Claude sidecar {} error: {}", pid, e);
+                        continue;
+                    }
+                    other => {
+                        debug!("Claude sidecar event: {:?}", other);
+                        continue;
+                    }
+                };
+                if matches!(&event, RunEvent::Stdout(l) | RunEvent::Stderr(l) if l.is_empty()) {
+                    continue;
+                }
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok((pid, None, rx))
+    }
+}
+
+/// Samples the process tree, killing it if it exceeds the request's limits
+fn start_monitor<O: RunObserver>(
+    registry: &Arc<ProcessRegistry>,
+    run_id: i64,
+    pid: u32,
+    request: &RunRequest,
+    cgroup: Option<CgroupScope>,
+    observer: Arc<Mutex<O>>,
+) {
+    let limits = request.resource_limits.clone();
+    let _ = registry.set_resource_limits(run_id, limits.clone());
+
+    let registry = registry.clone();
+    tokio::spawn(async move {
+        monitor_resources(
+            registry,
+            run_id,
+            pid,
+            limits,
+            cgroup,
+            move |violation, limits| {
+                lock(&observer).on_limit_exceeded(violation, limits);
+            },
+        )
+        .await;
+    });
+}
+
+/// Kills the process tree if it prints nothing before the startup timeout
+fn spawn_startup_watchdog<O: RunObserver>(
+    registry: Arc<ProcessRegistry>,
+    pid: u32,
+    run_id: Option<i64>,
+    timeout: Duration,
+    first_output: Arc<AtomicBool>,
+    exited: Arc<AtomicBool>,
+    observer: Arc<Mutex<O>>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let tick = Duration::from_millis(100);
+        let mut waited = Duration::ZERO;
+        while waited < timeout {
+            tokio::time::sleep(tick).await;
+            if first_output.load(Ordering::Relaxed) || exited.load(Ordering::Relaxed) {
+                return;
+            }
+            // Time spent paused doesn't count towards the timeout
+            if !run_id.is_some_and(|id| registry.is_paused(id)) {
+                waited += tick;
+            }
+        }
+
+        warn!(
+            "No output from Claude process {} after {}s; it may be waiting for input, \
+             unauthenticated, or unable to reach the network",
+            pid,
+            timeout.as_secs()
+        );
+        let reason = match run_id {
+            Some(id) => match registry.kill_process(id).await {
+                Ok(Some(report)) => report.reason.description(),
+                Ok(None) => "process was no longer registered",
+                Err(e) => {
+                    warn!("Failed to kill process {}: {}", id, e);
+                    "kill failed"
+                }
+            },
+            None => match registry.kill_policy() {
+                Ok(policy) => terminate_process_tree(pid, &policy, || false)
+                    .await
+                    .description(),
+                Err(e) => {
+                    warn!("Failed to kill process {}: {}", pid, e);
+                    "kill failed"
+                }
+            },
+        };
+        lock(&observer).on_startup_timeout(reason);
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use tokio::sync::oneshot;
+
+    /// Records every callback as a line of text
+    struct Recorder {
+        events: Arc<Mutex<Vec<String>>>,
+        done: Option<oneshot::Sender<()>>,
+    }
+
+    impl RunObserver for Recorder {
+        fn on_session_started(&mut self, _run_id: i64, session_id: &str) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("session {}", session_id));
+        }
+        fn on_stdout(&mut self, line: &str) {
+            self.events.lock().unwrap().push(format!("stdout {}", line));
+        }
+        fn on_stderr(&mut self, line: &str) {
+            self.events.lock().unwrap().push(format!("stderr {}", line));
+        }
+        fn on_startup_timeout(&mut self, _kill_reason: &str) {
+            self.events.lock().unwrap().push("timeout".to_string());
+        }
+        fn on_exit(&mut self, exit_code: Option<i32>) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("exit {:?}", exit_code));
+            if let Some(done) = self.done.take() {
+                let _ = done.send(());
+            }
+        }
+    }
+
+    /// Runs a shell script standing in for the Claude CLI and returns the recorded callbacks
+    async fn run_fake_claude(
+        script: &str,
+        interactive: bool,
+        startup_timeout: Duration,
+    ) -> (Vec<String>, Arc<ProcessRegistry>) {
+        let dir = TempDir::new().unwrap();
+        let script_path = dir.path().join("claude.sh");
+        std::fs::write(&script_path, script).unwrap();
+
+        let registry = Arc::new(ProcessRegistry::new());
+        let runner = ClaudeRunner::new(
+            ClaudeBinary::System("/bin/sh".to_string()),
+            registry.clone(),
+            None,
+        );
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let (done_tx, done_rx) = oneshot::channel();
+        let request = RunRequest {
+            kind: RunKind::Session,
+            args: vec![script_path.to_string_lossy().to_string()],
+            project_path: dir.path().to_string_lossy().to_string(),
+            task: "hello".to_string(),
+            model: "sonnet".to_string(),
+            resource_limits: None,
+            interactive,
+            startup_timeout,
+            env: RunEnv::default(),
+        };
+
+        runner
+            .spawn(
+                request,
+                Recorder {
+                    events: events.clone(),
+                    done: Some(done_tx),
+                },
+            )
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(10), done_rx)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let events = events.lock().unwrap().clone();
+        (events, registry)
+    }
+
+    #[tokio::test]
+    async fn test_runner_follows_fake_claude_session() {
+        let script = r#"read -r prompt
+echo '{"type":"system","subtype":"init","session_id":"fake-session"}'
+echo "$prompt"
+echo 'warning: fake' >&2
+"#;
+        let (events, registry) = run_fake_claude(script, true, Duration::from_secs(10)).await;
+
+        assert_eq!(events[0], "session fake-session");
+        assert!(events[1].starts_with("stdout {\"type\":\"system\""));
+        assert!(events.contains(&"stderr warning: fake".to_string()));
+        assert_eq!(events.last().unwrap(), "exit Some(0)");
+
+        // The prompt arrived on stdin as a stream-json user message
+        let echoed = events
+            .iter()
+            .filter_map(|e| e.strip_prefix("stdout "))
+            .nth(1)
+            .unwrap();
+        let echoed: serde_json::Value = serde_json::from_str(echoed).unwrap();
+        assert_eq!(echoed["message"]["content"][0]["text"], "hello");
+
+        assert!(registry.get_running_claude_sessions().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_runner_kills_silent_process_after_startup_timeout() {
+        let (events, _) = run_fake_claude("sleep 30\n", false, Duration::from_millis(300)).await;
+        assert_eq!(events, vec!["timeout", "exit None"]);
+    }
+}
@@ -0,0 +1,335 @@
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Grace periods used when escalating termination signals
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KillPolicy {
+    /// How long to wait after SIGINT before sending SIGTERM
+    pub interrupt_grace_ms: u64,
+    /// How long to wait after SIGTERM before sending SIGKILL
+    pub terminate_grace_ms: u64,
+}
+
+impl Default for KillPolicy {
+    fn default() -> Self {
+        Self {
+            interrupt_grace_ms: 2000,
+            terminate_grace_ms: 3000,
+        }
+    }
+}
+
+/// How a process ended after a termination request
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitReason {
+    /// The process was already gone when termination was requested
+    AlreadyExited,
+    /// The process exited after SIGINT
+    Interrupted,
+    /// The process exited after SIGTERM
+    Terminated,
+    /// The process had to be killed with SIGKILL
+    Killed,
+    /// The process could not be confirmed as exited
+    Unknown,
+}
+
+impl ExitReason {
+    /// Human readable description stored alongside run status
+    pub fn description(&self) -> &'static str {
+        match self {
+            ExitReason::AlreadyExited => "process had already exited",
+            ExitReason::Interrupted => "exited after SIGINT",
+            ExitReason::Terminated => "exited after SIGTERM",
+            ExitReason::Killed => "killed with SIGKILL",
+            ExitReason::Unknown => "exit could not be confirmed",
+        }
+    }
+}
+
+/// Outcome of terminating a process and its descendants
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KillReport {
+    pub run_id: i64,
+    pub pid: u32,
+    pub reason: ExitReason,
+    pub elapsed_ms: u64,
+}
+
+/// The set of processes a termination signal is delivered to
+#[cfg(unix)]
+enum SignalTarget {
+    /// A whole process group, used when the process leads its own group
+    Group(i32),
+    /// The process and the descendants known at the time termination started
+    Pids(Vec<i32>),
+}
+
+#[cfg(unix)]
+impl SignalTarget {
+    fn resolve(pid: u32) -> Self {
+        let pid = pid as i32;
+        // Runs are spawned as process group leaders, so the group covers every grandchild
+        if unsafe { libc::getpgid(pid) } == pid {
+            return SignalTarget::Group(pid);
+        }
+
+        let mut pids = vec![pid];
+        pids.extend(descendant_pids(pid as u32).into_iter().map(|p| p as i32));
+        SignalTarget::Pids(pids)
+    }
+
+    fn send(&self, signal: i32) {
+        match self {
+            SignalTarget::Group(pgid) => {
+                if unsafe { libc::killpg(*pgid, signal) } != 0 {
+                    debug!(
+                        "killpg({}, {}) failed: {}",
+                        pgid,
+                        signal,
+                        std::io::Error::last_os_error()
+                    );
+                }
+            }
+            SignalTarget::Pids(pids) => {
+                for pid in pids {
+                    unsafe {
+                        libc::kill(*pid, signal);
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        match self {
+            SignalTarget::Group(pgid) => unsafe { libc::killpg(*pgid, 0) == 0 },
+            SignalTarget::Pids(pids) => pids.iter().any(|pid| unsafe { libc::kill(*pid, 0) == 0 }),
+        }
+    }
+}
+
+/// Lists all descendants of a process using `ps`
+#[cfg(unix)]
+pub fn descendant_pids(root: u32) -> Vec<u32> {
+    let output = match std::process::Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid="])
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Failed to list processes: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let pairs: Vec<(u32, u32)> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let pid = parts.next()?.parse().ok()?;
+            let ppid = parts.next()?.parse().ok()?;
+            Some((pid, ppid))
+        })
+        .collect();
+
+    let mut descendants = Vec::new();
+    let mut frontier = vec![root];
+    while let Some(parent) = frontier.pop() {
+        for (pid, ppid) in &pairs {
+            if *ppid == parent && !descendants.contains(pid) {
+                descendants.push(*pid);
+                frontier.push(*pid);
+            }
+        }
+    }
+    descendants
+}
+
+/// Checks whether a process with the given PID exists
+pub fn is_pid_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        unsafe { libc::kill(pid as i32, 0) == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).lines().count() > 1)
+            .unwrap_or(false)
+    }
+}
+
+/// Stops a process and everything it spawned with SIGSTOP
+pub fn suspend_process_tree(pid: u32) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        SignalTarget::resolve(pid).send(libc::SIGSTOP);
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        Err("Pausing processes is not supported on this platform".to_string())
+    }
+}
+
+/// Continues a process tree stopped by `suspend_process_tree`
+pub fn resume_process_tree(pid: u32) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        SignalTarget::resolve(pid).send(libc::SIGCONT);
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        Err("Resuming processes is not supported on this platform".to_string())
+    }
+}
+
+/// Terminates a process and everything it spawned, escalating SIGINT -> SIGTERM -> SIGKILL
+///
+/// `reap` is polled while waiting so that callers owning the child handle can collect
+/// its exit status; it returns true once the direct child has exited.
+pub async fn terminate_process_tree<F>(pid: u32, policy: &KillPolicy, mut reap: F) -> ExitReason
+where
+    F: FnMut() -> bool,
+{
+    #[cfg(unix)]
+    {
+        let target = SignalTarget::resolve(pid);
+        reap();
+        if !target.is_alive() {
+            return ExitReason::AlreadyExited;
+        }
+
+        let steps = [
+            (
+                libc::SIGINT,
+                policy.interrupt_grace_ms,
+                ExitReason::Interrupted,
+            ),
+            (
+                libc::SIGTERM,
+                policy.terminate_grace_ms,
+                ExitReason::Terminated,
+            ),
+            (libc::SIGKILL, 2000, ExitReason::Killed),
+        ];
+
+        for (signal, grace_ms, reason) in steps {
+            debug!("Sending signal {} to process tree of PID {}", signal, pid);
+            target.send(signal);
+            // A paused tree only acts on SIGINT/SIGTERM once it is continued
+            target.send(libc::SIGCONT);
+
+            let deadline = Instant::now() + Duration::from_millis(grace_ms);
+            while Instant::now() < deadline {
+                reap();
+                if !target.is_alive() {
+                    return reason;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        }
+
+        warn!("Process tree of PID {} survived SIGKILL", pid);
+        ExitReason::Unknown
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = policy;
+        reap();
+        if !is_pid_alive(pid) {
+            return ExitReason::AlreadyExited;
+        }
+
+        // Windows has no signal escalation; taskkill /T takes the whole tree down
+        let _ = std::process::Command::new("taskkill")
+            .args(["/F", "/T", "/PID", &pid.to_string()])
+            .output();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if reap() || !is_pid_alive(pid) {
+                return ExitReason::Killed;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        ExitReason::Unknown
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::CommandExt;
+
+    fn process_state(pid: u32) -> String {
+        let output = std::process::Command::new("ps")
+            .args(["-o", "stat=", "-p", &pid.to_string()])
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    #[test]
+    fn test_suspend_and_resume_process_group() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+
+        suspend_process_tree(pid).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(process_state(pid).starts_with('T'));
+
+        resume_process_tree(pid).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!process_state(pid).starts_with('T'));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_terminate_escalates_to_sigkill_for_whole_group() {
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "trap '' INT TERM; sleep 60 & wait"])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+
+        let mut grandchildren = Vec::new();
+        for _ in 0..50 {
+            grandchildren = descendant_pids(pid);
+            if !grandchildren.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!grandchildren.is_empty());
+
+        let policy = KillPolicy {
+            interrupt_grace_ms: 200,
+            terminate_grace_ms: 200,
+        };
+        let reason =
+            terminate_process_tree(pid, &policy, || matches!(child.try_wait(), Ok(Some(_)))).await;
+
+        assert_eq!(reason, ExitReason::Killed);
+        assert!(matches!(child.try_wait(), Ok(Some(_))));
+        for grandchild in grandchildren {
+            assert!(!is_pid_alive(grandchild));
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+/// One line of Claude's stream-json output or of a session transcript
+///
+/// Both formats share the same message shapes; transcripts add bookkeeping fields such as
+/// `uuid` and `timestamp`, which are ignored unless listed here.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamLine {
+    #[serde(flatten)]
+    pub event: StreamEvent,
+    /// `session_id` in stream-json output, `sessionId` in transcripts
+    #[serde(default, alias = "sessionId")]
+    pub session_id: Option<String>,
+    /// RFC 3339 timestamp, present in transcripts
+    #[serde(default)]
+    pub timestamp: Option<String>,
+}
+
+/// The payload of a stream-json line, keyed by its `type`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    System(SystemEvent),
+    Assistant(MessageEvent),
+    User(MessageEvent),
+    Result(ResultEvent),
+    /// A request from Claude that the app must answer on stdin
+    ControlRequest(ControlRequestEvent),
+    /// A line type this version doesn't know about, such as transcript summaries
+    #[serde(other)]
+    Unknown,
+}
+
+/// `system` lines; `init` is the first line of every run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemEvent {
+    #[serde(default)]
+    pub subtype: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub tools: Vec<String>,
+}
+
+/// `control_request` lines, sent in runs started with `--permission-prompt-tool stdio`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlRequestEvent {
+    pub request_id: String,
+    pub request: ControlRequest,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "subtype", rename_all = "snake_case")]
+pub enum ControlRequest {
+    /// Claude asks whether it may run a tool
+    CanUseTool {
+        tool_name: String,
+        #[serde(default)]
+        input: Value,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+/// `assistant` and `user` lines
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageEvent {
+    pub message: Message,
+    /// Set on messages produced inside a sub-agent's tool call
+    #[serde(default)]
+    pub parent_tool_use_id: Option<String>,
+    /// Per-message cost recorded by older Claude versions
+    #[serde(default, rename = "costUSD")]
+    pub cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Plain string content is normalised to a single text block
+    #[serde(default, deserialize_with = "deserialize_content")]
+    pub content: Vec<ContentBlock>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+/// A block of message content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text {
+        text: String,
+    },
+    Thinking {
+        #[serde(default)]
+        thinking: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        #[serde(default)]
+        input: Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        /// A string or an array of content blocks, depending on the tool
+        #[serde(default)]
+        content: Value,
+        #[serde(default)]
+        is_error: bool,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+/// Token usage of a message or a whole run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+}
+
+impl Usage {
+    /// Input plus output tokens
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    /// All tokens including cache writes and reads
+    pub fn total_with_cache(&self) -> u64 {
+        self.total_tokens() + self.cache_creation_input_tokens + self.cache_read_input_tokens
+    }
+}
+
+/// The final `result` line of a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultEvent {
+    #[serde(default)]
+    pub subtype: String,
+    #[serde(default)]
+    pub is_error: bool,
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    #[serde(default)]
+    pub num_turns: Option<u64>,
+    #[serde(default)]
+    pub result: Option<String>,
+    #[serde(default, alias = "cost_usd")]
+    pub total_cost_usd: Option<f64>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+fn deserialize_content<'de, D>(deserializer: D) -> Result<Vec<ContentBlock>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Content {
+        Text(String),
+        Blocks(Vec<ContentBlock>),
+    }
+
+    Ok(match Option::<Content>::deserialize(deserializer)? {
+        Some(Content::Text(text)) => vec![ContentBlock::Text { text }],
+        Some(Content::Blocks(blocks)) => blocks,
+        None => Vec::new(),
+    })
+}
+
+impl StreamLine {
+    /// Parses a line, returning `None` for blank lines and anything that isn't a JSON object
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        serde_json::from_str(line).ok()
+    }
+
+    /// The session ID if this is the `system`/`init` line that starts a run
+    pub fn init_session_id(&self) -> Option<&str> {
+        match &self.event {
+            StreamEvent::System(system) if system.subtype == "init" => self.session_id.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl StreamEvent {
+    /// The message of an `assistant` or `user` line
+    pub fn message(&self) -> Option<&Message> {
+        match self {
+            StreamEvent::Assistant(event) | StreamEvent::User(event) => Some(&event.message),
+            _ => None,
+        }
+    }
+
+    /// Token usage reported by this line, from the message or the final result
+    pub fn usage(&self) -> Option<&Usage> {
+        match self {
+            StreamEvent::Result(result) => result.usage.as_ref(),
+            _ => self.message().and_then(|message| message.usage.as_ref()),
+        }
+    }
+
+    /// Cost reported by this line, from the final result or an older per-message cost
+    pub fn cost_usd(&self) -> Option<f64> {
+        match self {
+            StreamEvent::Result(result) => result.total_cost_usd,
+            StreamEvent::Assistant(event) | StreamEvent::User(event) => event.cost_usd,
+            _ => None,
+        }
+    }
+
+    /// The model named by this line, if any
+    pub fn model(&self) -> Option<&str> {
+        match self {
+            StreamEvent::System(system) => system.model.as_deref(),
+            _ => self.message().and_then(|message| message.model.as_deref()),
+        }
+    }
+
+    /// Tool calls made in this line as `(name, input)` pairs
+    pub fn tool_uses(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.message()
+            .into_iter()
+            .flat_map(|message| &message.content)
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse { name, input, .. } => Some((name.as_str(), input)),
+                _ => None,
+            })
+    }
+
+    /// The request ID, tool name and input of a tool permission request
+    pub fn permission_request(&self) -> Option<(&str, &str, &Value)> {
+        match self {
+            StreamEvent::ControlRequest(ControlRequestEvent {
+                request_id,
+                request: ControlRequest::CanUseTool { tool_name, input },
+            }) => Some((request_id.as_str(), tool_name.as_str(), input)),
+            _ => None,
+        }
+    }
+
+    /// The first text block of a user prompt; tool results are not prompts
+    pub fn user_prompt(&self) -> Option<&str> {
+        match self {
+            StreamEvent::User(event) => {
+                event.message.content.iter().find_map(|block| match block {
+                    ContentBlock::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders a parsed line as one line of the golden file
+    fn describe(line: &StreamLine) -> String {
+        let usage = |usage: Option<&Usage>| match usage {
+            Some(u) => format!(
+                " usage={}/{}/{}/{}",
+                u.input_tokens,
+                u.output_tokens,
+                u.cache_creation_input_tokens,
+                u.cache_read_input_tokens
+            ),
+            None => String::new(),
+        };
+        let blocks = |message: &Message| {
+            message
+                .content
+                .iter()
+                .map(|block| match block {
+                    ContentBlock::Text { text } => format!("text({})", text.chars().count()),
+                    ContentBlock::Thinking { .. } => "thinking".to_string(),
+                    ContentBlock::ToolUse { id, name, .. } => format!("tool_use({} {})", name, id),
+                    ContentBlock::ToolResult {
+                        tool_use_id,
+                        is_error,
+                        ..
+                    } => format!(
+                        "tool_result({} {})",
+                        tool_use_id,
+                        if *is_error { "error" } else { "ok" }
+                    ),
+                    ContentBlock::Unknown => "unknown".to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        let session = line.session_id.as_deref().unwrap_or("-");
+        match &line.event {
+            StreamEvent::System(system) => format!(
+                "system/{} session={} model={} tools={}",
+                system.subtype,
+                session,
+                system.model.as_deref().unwrap_or("-"),
+                system.tools.len()
+            ),
+            StreamEvent::Assistant(event) | StreamEvent::User(event) => format!(
+                "{} session={} model={} [{}]{}",
+                if matches!(line.event, StreamEvent::User(_)) {
+                    "user"
+                } else {
+                    "assistant"
+                },
+                session,
+                event.message.model.as_deref().unwrap_or("-"),
+                blocks(&event.message),
+                usage(event.message.usage.as_ref())
+            ),
+            StreamEvent::Result(result) => format!(
+                "result/{} session={} turns={} cost={}{}",
+                result.subtype,
+                session,
+                result.num_turns.unwrap_or(0),
+                result.total_cost_usd.unwrap_or(0.0),
+                usage(result.usage.as_ref())
+            ),
+            StreamEvent::ControlRequest(event) => match &event.request {
+                ControlRequest::CanUseTool { tool_name, .. } => {
+                    format!(
+                        "control_request/can_use_tool {} {}",
+                        event.request_id, tool_name
+                    )
+                }
+                ControlRequest::Unknown => format!("control_request/unknown {}", event.request_id),
+            },
+            StreamEvent::Unknown => "unknown".to_string(),
+        }
+    }
+
+    fn assert_golden(fixture: &str, golden: &str) {
+        let actual: Vec<String> = fixture
+            .lines()
+            .map(|line| {
+                StreamLine::parse(line)
+                    .map(|l| describe(&l))
+                    .unwrap_or_else(|| "invalid".to_string())
+            })
+            .collect();
+        let expected: Vec<&str> = golden.lines().collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_stream_json_output_golden() {
+        assert_golden(
+            include_str!("testdata/stream_output.jsonl"),
+            include_str!("testdata/stream_output.golden"),
+        );
+    }
+
+    #[test]
+    fn test_session_transcript_golden() {
+        let fixture = include_str!("testdata/session_transcript.jsonl");
+        assert_golden(fixture, include_str!("testdata/session_transcript.golden"));
+
+        let lines: Vec<StreamLine> = fixture.lines().filter_map(StreamLine::parse).collect();
+        assert_eq!(
+            lines[0].event.user_prompt(),
+            Some("Fix the failing test in src/lib.rs")
+        );
+        assert_eq!(
+            lines[0].timestamp.as_deref(),
+            Some("2025-07-14T10:00:00.000Z")
+        );
+        let tools: Vec<&str> = lines
+            .iter()
+            .flat_map(|l| l.event.tool_uses().map(|(name, _)| name))
+            .collect();
+        assert_eq!(tools, vec!["Read", "Edit"]);
+    }
+}
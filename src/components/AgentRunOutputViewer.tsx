@@ -11,14 +11,15 @@ import {
   Clock,
   Hash,
   DollarSign,
-  StopCircle
+  StopCircle,
+  GitBranch
 } from 'lucide-react';
 import { Button } from '@/components/ui/button';
 import { Card, CardContent, CardHeader, CardTitle } from '@/components/ui/card';
 import { Badge } from '@/components/ui/badge';
 import { Toast, ToastContainer } from '@/components/ui/toast';
 import { Popover } from '@/components/ui/popover';
-import { api, type AgentRunWithMetrics } from '@/lib/api';
+import { api, type AgentRunWithMetrics, type WorktreeAction } from '@/lib/api';
 import { useOutputCache } from '@/lib/outputCache';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { StreamMessage } from './StreamMessage';
@@ -67,6 +68,9 @@ export function AgentRunOutputViewer({
   const [toast, setToast] = useState<{ message: string; type: "success" | "error" } | null>(null);
   const [copyPopoverOpen, setCopyPopoverOpen] = useState(false);
   const [hasUserScrolled, setHasUserScrolled] = useState(false);
+  const [worktreePopoverOpen, setWorktreePopoverOpen] = useState(false);
+  const [worktreeChanges, setWorktreeChanges] = useState<string[] | null>(null);
+  const [finishingWorktree, setFinishingWorktree] = useState(false);
   
   // Track whether we're in the initial load phase
   const isInitialLoadRef = useRef(true);
@@ -441,6 +445,41 @@ export function AgentRunOutputViewer({
     }
   };
 
+  // An isolated run's changes wait in its worktree until they are merged or discarded
+  const hasPendingWorktree = run?.worktree_state === 'active' &&
+    !['pending', 'queued', 'running', 'paused', 'verifying'].includes(run.status);
+
+  const handleWorktreePopoverChange = async (open: boolean) => {
+    setWorktreePopoverOpen(open);
+    if (!open || !run?.id) return;
+    try {
+      setWorktreeChanges(await api.listRunWorktreeChanges(run.id));
+    } catch (err) {
+      console.error('[AgentRunOutputViewer] Failed to list worktree changes:', err);
+      setWorktreeChanges([]);
+    }
+  };
+
+  const handleFinishWorktree = async (action: WorktreeAction) => {
+    if (!run?.id) return;
+    setFinishingWorktree(true);
+    try {
+      const updated = await api.finishRunWorktree(run.id, action);
+      setRun({ ...run, worktree_state: updated.worktree_state });
+      setWorktreePopoverOpen(false);
+      const done = { merge: 'merged', cherry_pick: 'cherry-picked', discard: 'discarded' }[action];
+      setToast({ message: `Run changes ${done}`, type: 'success' });
+    } catch (err) {
+      console.error('[AgentRunOutputViewer] Failed to finish worktree:', err);
+      setToast({
+        message: `Failed to apply run changes: ${err instanceof Error ? err.message : err}`,
+        type: 'error'
+      });
+    } finally {
+      setFinishingWorktree(false);
+    }
+  };
+
   const handleScroll = (e: React.UIEvent<HTMLDivElement>) => {
     const target = e.currentTarget;
     const { scrollTop, scrollHeight, clientHeight } = target;
@@ -591,6 +630,74 @@ export function AgentRunOutputViewer({
                 </div>
               </div>
               <div className="flex items-center gap-1">
+                {hasPendingWorktree && (
+                  <Popover
+                    trigger={
+                      <Button
+                        variant="ghost"
+                        size="sm"
+                        className="h-8 px-2"
+                        title="Merge or discard the run's changes"
+                      >
+                        <GitBranch className="h-4 w-4 mr-1" />
+                        Changes
+                        <ChevronDown className="h-3 w-3 ml-1" />
+                      </Button>
+                    }
+                    content={
+                      <div className="w-64 p-1">
+                        <div className="px-2 py-1.5 text-xs text-muted-foreground">
+                          {worktreeChanges === null
+                            ? 'Loading changes...'
+                            : worktreeChanges.length === 0
+                              ? 'No changed files'
+                              : `${worktreeChanges.length} changed file${worktreeChanges.length === 1 ? '' : 's'}`}
+                        </div>
+                        {worktreeChanges && worktreeChanges.length > 0 && (
+                          <div className="max-h-40 overflow-y-auto px-2 pb-1">
+                            {worktreeChanges.map(path => (
+                              <p key={path} className="text-xs font-mono truncate" title={path}>
+                                {path}
+                              </p>
+                            ))}
+                          </div>
+                        )}
+                        <Button
+                          variant="ghost"
+                          size="sm"
+                          className="w-full justify-start"
+                          onClick={() => handleFinishWorktree('merge')}
+                          disabled={finishingWorktree}
+                        >
+                          Merge into project
+                        </Button>
+                        {run.worktree_branch && (
+                          <Button
+                            variant="ghost"
+                            size="sm"
+                            className="w-full justify-start"
+                            onClick={() => handleFinishWorktree('cherry_pick')}
+                            disabled={finishingWorktree}
+                          >
+                            Cherry-pick commits
+                          </Button>
+                        )}
+                        <Button
+                          variant="ghost"
+                          size="sm"
+                          className="w-full justify-start text-destructive hover:text-destructive"
+                          onClick={() => handleFinishWorktree('discard')}
+                          disabled={finishingWorktree}
+                        >
+                          Discard changes
+                        </Button>
+                      </div>
+                    }
+                    open={worktreePopoverOpen}
+                    onOpenChange={handleWorktreePopoverChange}
+                    align="end"
+                  />
+                )}
                 <Popover
                   trigger={
                     <Button
@@ -17,10 +17,11 @@ import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
 import { Label } from "@/components/ui/label";
 import { Popover } from "@/components/ui/popover";
-import { api, type Session } from "@/lib/api";
+import { api, type PermissionRequest, type Session } from "@/lib/api";
 import { cn } from "@/lib/utils";
 import { open } from "@tauri-apps/plugin-dialog";
-import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import { type UnlistenFn } from "@tauri-apps/api/event";
+import { listenToSessionEvents } from "@/lib/sessionEvents";
 import { StreamMessage } from "./StreamMessage";
 import { FloatingPromptInput, type FloatingPromptInputRef } from "./FloatingPromptInput";
 import { ErrorBoundary } from "./ErrorBoundary";
@@ -79,6 +80,7 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
   const [messages, setMessages] = useState<ClaudeStreamMessage[]>([]);
   const [isLoading, setIsLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  const [pendingPermissions, setPendingPermissions] = useState<PermissionRequest[]>([]);
   const [rawJsonlOutput, setRawJsonlOutput] = useState<string[]>([]);
   const [copyPopoverOpen, setCopyPopoverOpen] = useState(false);
   const [isFirstPrompt, setIsFirstPrompt] = useState(!session);
@@ -113,6 +115,8 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
   const queuedPromptsRef = useRef<Array<{ id: string; prompt: string; model: "sonnet" | "opus" }>>([]);
   const isMountedRef = useRef(true);
   const isListeningRef = useRef(false);
+  // Sequence number of the latest session event covered by the loaded history
+  const historySeqRef = useRef(0);
 
   // Keep ref in sync with state
   useEffect(() => {
@@ -267,7 +271,8 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
       setIsLoading(true);
       setError(null);
       
-      const history = await api.loadSessionHistory(session.id, session.project_id);
+      const { messages: history, last_seq } = await api.loadSessionHistory(session.id, session.project_id);
+      historySeqRef.current = last_seq;
       
       // Convert history to messages format
       const loadedMessages: ClaudeStreamMessage[] = history.map(entry => ({
@@ -337,40 +342,35 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
     // Mark as listening
     isListeningRef.current = true;
     
-    // Set up session-specific listeners
-    const outputUnlisten = await listen<string>(`claude-output:${sessionId}`, async (event) => {
-      try {
-        console.log('[ClaudeCodeSession] Received claude-output on reconnect:', event.payload);
-        
-        if (!isMountedRef.current) return;
-        
-        // Store raw JSONL
-        setRawJsonlOutput(prev => [...prev, event.payload]);
-        
-        // Parse and display
-        const message = JSON.parse(event.payload) as ClaudeStreamMessage;
-        setMessages(prev => [...prev, message]);
-      } catch (err) {
-        console.error("Failed to parse message:", err, event.payload);
-      }
-    });
-
-    const errorUnlisten = await listen<string>(`claude-error:${sessionId}`, (event) => {
-      console.error("Claude error:", event.payload);
-      if (isMountedRef.current) {
-        setError(event.payload);
+    // Set up session-specific listeners, skipping events already in the loaded history
+    const sessionUnlisten = await listenToSessionEvents(sessionId, (event) => {
+      if (!isMountedRef.current) return;
+
+      switch (event.kind) {
+        case 'message_added':
+          console.log('[ClaudeCodeSession] Received message on reconnect:', event.message);
+          // Store raw JSONL
+          setRawJsonlOutput(prev => [...prev, JSON.stringify(event.message)]);
+          setMessages(prev => [...prev, event.message as ClaudeStreamMessage]);
+          break;
+        case 'permission_requested':
+          setPendingPermissions(prev => [...prev, event]);
+          break;
+        case 'error':
+          console.error("Claude error:", event.message);
+          setError(event.message);
+          break;
+        case 'state_changed':
+          if (event.state !== 'running') {
+            console.log('[ClaudeCodeSession] Session ended on reconnect:', event.state);
+            setIsLoading(false);
+            hasActiveSessionRef.current = false;
+          }
+          break;
       }
-    });
+    }, undefined, historySeqRef.current);
 
-    const completeUnlisten = await listen<boolean>(`claude-complete:${sessionId}`, async (event) => {
-      console.log('[ClaudeCodeSession] Received claude-complete on reconnect:', event.payload);
-      if (isMountedRef.current) {
-        setIsLoading(false);
-        hasActiveSessionRef.current = false;
-      }
-    });
-
-    unlistenRefs.current = [outputUnlisten, errorUnlisten, completeUnlisten];
+    unlistenRefs.current = [sessionUnlisten];
     
     // Mark as loading to show the session is active
     if (isMountedRef.current) {
@@ -439,90 +439,59 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
         // --------------------------------------------------------------------
         // 1️⃣  Event Listener Setup Strategy
         // --------------------------------------------------------------------
-        // Claude Code may emit a *new* session_id even when we pass --resume. If
-        // we listen only on the old session-scoped channel we will miss the
-        // stream until the user navigates away & back. To avoid this we:
-        //   • Always start with GENERIC listeners (no suffix) so we catch the
-        //     very first "system:init" message regardless of the session id.
-        //   • Once that init message provides the *actual* session_id, we
-        //     dynamically switch to session-scoped listeners and stop the
-        //     generic ones to prevent duplicate handling.
+        // Typed events are only published on session-scoped channels so that
+        // several sessions can stream at once. Claude Code may pick a *new* session_id
+        // even when we pass --resume, so the backend resolves the command with
+        // the actual session_id once Claude has initialised, and we attach the
+        // scoped listeners then.
         // --------------------------------------------------------------------
 
-        console.log('[ClaudeCodeSession] Setting up generic event listeners first');
-
-        let currentSessionId: string | null = claudeSessionId || effectiveSession?.id || null;
-
-        // Helper to attach session-specific listeners **once we are sure**
+        // Helper to attach session-specific listeners once the session id is known
         const attachSessionSpecificListeners = async (sid: string) => {
           console.log('[ClaudeCodeSession] Attaching session-specific listeners for', sid);
 
-          const specificOutputUnlisten = await listen<string>(`claude-output:${sid}`, (evt) => {
-            handleStreamMessage(evt.payload);
-          });
-
-          const specificErrorUnlisten = await listen<string>(`claude-error:${sid}`, (evt) => {
-            console.error('Claude error (scoped):', evt.payload);
-            setError(evt.payload);
-          });
-
-          const specificCompleteUnlisten = await listen<boolean>(`claude-complete:${sid}`, (evt) => {
-            console.log('[ClaudeCodeSession] Received claude-complete (scoped):', evt.payload);
-            processComplete(evt.payload);
+          const specificUnlisten = await listenToSessionEvents(sid, (event) => {
+            switch (event.kind) {
+              case 'message_added':
+                handleStreamMessage(event.message);
+                break;
+              case 'permission_requested':
+                setPendingPermissions((prev) => [...prev, event]);
+                break;
+              case 'error':
+                console.error('Claude error (scoped):', event.message);
+                setError(event.message);
+                break;
+              case 'checkpoint_created':
+                setTimelineVersion((v) => v + 1);
+                break;
+              case 'state_changed':
+                if (event.state !== 'running') {
+                  console.log('[ClaudeCodeSession] Session ended (scoped):', event.state);
+                  processComplete(event.state === 'completed');
+                }
+                break;
+            }
           });
 
-          // Replace existing unlisten refs with these new ones (after cleaning up)
           unlistenRefs.current.forEach((u) => u());
-          unlistenRefs.current = [specificOutputUnlisten, specificErrorUnlisten, specificCompleteUnlisten];
+          unlistenRefs.current = [specificUnlisten];
         };
 
-        // Generic listeners (catch-all)
-        const genericOutputUnlisten = await listen<string>('claude-output', async (event) => {
-          handleStreamMessage(event.payload);
-
-          // Attempt to extract session_id on the fly (for the very first init)
-          try {
-            const msg = JSON.parse(event.payload) as ClaudeStreamMessage;
-            if (msg.type === 'system' && msg.subtype === 'init' && msg.session_id) {
-              if (!currentSessionId || currentSessionId !== msg.session_id) {
-                console.log('[ClaudeCodeSession] Detected new session_id from generic listener:', msg.session_id);
-                currentSessionId = msg.session_id;
-                setClaudeSessionId(msg.session_id);
-
-                // If we haven't extracted session info before, do it now
-                if (!extractedSessionInfo) {
-                  const projectId = projectPath.replace(/[^a-zA-Z0-9]/g, '-');
-                  setExtractedSessionInfo({ sessionId: msg.session_id, projectId });
-                }
-
-                // Switch to session-specific listeners
-                await attachSessionSpecificListeners(msg.session_id);
-              }
-            }
-          } catch {
-            /* ignore parse errors */
-          }
-        });
-
-        // Helper to process any JSONL stream message string
-        function handleStreamMessage(payload: string) {
-          try {
-            // Don't process if component unmounted
-            if (!isMountedRef.current) return;
-            
-            // Store raw JSONL
-            setRawJsonlOutput((prev) => [...prev, payload]);
+        // Helper to process a stream message published by the backend
+        function handleStreamMessage(message: ClaudeStreamMessage) {
+          // Don't process if component unmounted
+          if (!isMountedRef.current) return;
 
-            const message = JSON.parse(payload) as ClaudeStreamMessage;
-            setMessages((prev) => [...prev, message]);
-          } catch (err) {
-            console.error('Failed to parse message:', err, payload);
-          }
+          // Store raw JSONL
+          setRawJsonlOutput((prev) => [...prev, JSON.stringify(message)]);
+          setMessages((prev) => [...prev, message]);
         }
 
-        // Helper to handle completion events (both generic and scoped)
+        // Helper to handle completion events
         const processComplete = async (success: boolean) => {
           setIsLoading(false);
+          setPendingPermissions([]);
           hasActiveSessionRef.current = false;
           isListeningRef.current = false; // Reset listening state
 
@@ -561,24 +530,11 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
           }
         };
 
-        const genericErrorUnlisten = await listen<string>('claude-error', (evt) => {
-          console.error('Claude error:', evt.payload);
-          setError(evt.payload);
-        });
-
-        const genericCompleteUnlisten = await listen<boolean>('claude-complete', (evt) => {
-          console.log('[ClaudeCodeSession] Received claude-complete (generic):', evt.payload);
-          processComplete(evt.payload);
-        });
-
-        // Store the generic unlisteners for now; they may be replaced later.
-        unlistenRefs.current = [genericOutputUnlisten, genericErrorUnlisten, genericCompleteUnlisten];
-
         // --------------------------------------------------------------------
         // 2️⃣  Auto-checkpoint logic moved after listener setup (unchanged)
         // --------------------------------------------------------------------
 
-        // Add the user message immediately to the UI (after setting up listeners)
+        // Add the user message immediately to the UI
         const userMessage: ClaudeStreamMessage = {
           type: "user",
           message: {
@@ -593,13 +549,28 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
         setMessages(prev => [...prev, userMessage]);
 
         // Execute the appropriate command
+        let sid: string;
         if (effectiveSession && !isFirstPrompt) {
           console.log('[ClaudeCodeSession] Resuming session:', effectiveSession.id);
-          await api.resumeClaudeCode(projectPath, effectiveSession.id, prompt, model);
+          sid = await api.resumeClaudeCode(projectPath, effectiveSession.id, prompt, model);
         } else {
           console.log('[ClaudeCodeSession] Starting new session');
           setIsFirstPrompt(false);
-          await api.executeClaudeCode(projectPath, prompt, model);
+          sid = await api.executeClaudeCode(projectPath, prompt, model);
+        }
+
+        setClaudeSessionId(sid);
+        if (!extractedSessionInfo) {
+          const projectId = projectPath.replace(/[^a-zA-Z0-9]/g, '-');
+          setExtractedSessionInfo({ sessionId: sid, projectId });
+        }
+        await attachSessionSpecificListeners(sid);
+
+        // The init message was emitted before we could subscribe; replay it from the buffer
+        const buffered = await api.getClaudeSessionOutput(sid);
+        const initLine = buffered.split('\n')[0];
+        if (initLine) {
+          handleStreamMessage(initLine);
         }
       }
     } catch (err) {
@@ -747,6 +718,20 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
     }
   };
 
+  const handlePermissionDecision = async (request: PermissionRequest, allow: boolean) => {
+    setPendingPermissions(prev => prev.filter(r => r.request_id !== request.request_id));
+    if (!claudeSessionId) return;
+    try {
+      await api.sendSessionInput(
+        { sessionId: claudeSessionId },
+        { type: 'permission_response', request_id: request.request_id, allow, input: request.input }
+      );
+    } catch (err) {
+      console.error("Failed to answer permission request:", err);
+      setError(`Failed to answer permission request: ${err instanceof Error ? err.message : err}`);
+    }
+  };
+
   const handleFork = (checkpointId: string) => {
     setForkCheckpointId(checkpointId);
     setForkSessionName(`Fork-${new Date().toISOString().slice(0, 10)}`);
@@ -888,6 +873,29 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
         </motion.div>
       )}
 
+      {/* Tool calls waiting for approval */}
+      {pendingPermissions.map((request) => (
+        <motion.div
+          key={request.request_id}
+          initial={{ opacity: 0 }}
+          animate={{ opacity: 1 }}
+          className="rounded-lg border border-yellow-500/50 bg-yellow-500/10 p-4 text-sm mb-4 w-full max-w-5xl mx-auto"
+        >
+          <div className="font-medium mb-2">Claude wants to use {request.tool_name}</div>
+          <pre className="text-xs whitespace-pre-wrap break-all mb-3 text-muted-foreground">
+            {JSON.stringify(request.input, null, 2)}
+          </pre>
+          <div className="flex gap-2">
+            <Button size="sm" onClick={() => handlePermissionDecision(request, true)}>
+              Allow
+            </Button>
+            <Button size="sm" variant="outline" onClick={() => handlePermissionDecision(request, false)}>
+              Deny
+            </Button>
+          </div>
+        </motion.div>
+      ))}
+
       {/* Error indicator */}
       {error && (
         <motion.div
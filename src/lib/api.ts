@@ -15,6 +15,90 @@ export interface ProcessInfo {
   project_path: string;
   task: string;
   model: string;
+  resource_limits?: ResourceLimits | null;
+  resource_usage?: ResourceUsage | null;
+  paused_at?: string | null;
+  paused_ms?: number;
+}
+
+/**
+ * Limits enforced on a spawned Claude process tree
+ */
+export interface ResourceLimits {
+  max_wall_time_secs?: number | null;
+  max_cpu_time_secs?: number | null;
+  max_memory_mb?: number | null;
+  max_processes?: number | null;
+}
+
+/**
+ * Spending limits for an agent run, enforced live from the usage Claude reports
+ */
+export interface RunBudget {
+  /** Maximum cost in USD, estimated from token usage */
+  max_cost_usd?: number | null;
+  /** Maximum input plus output tokens */
+  max_tokens?: number | null;
+  /** Maximum number of assistant turns */
+  max_turns?: number | null;
+}
+
+/**
+ * Checks a run has to pass after it finishes to be marked succeeded instead of verification_failed
+ */
+export interface AgentVerification {
+  /** Shell commands run in the run's project, in order; each must exit with status 0 */
+  commands?: string[];
+  /** Regex the agent's final result message must match */
+  result_pattern?: string | null;
+  /** Time limit for each command in seconds (default 600) */
+  timeout_secs?: number | null;
+}
+
+/**
+ * The verdict of a run's verification
+ */
+export interface VerificationReport {
+  passed: boolean;
+  checks: { check: string; passed: boolean; exit_code: number | null; output: string }[];
+}
+
+/**
+ * Kinds of failure a retry policy can retry
+ */
+export type FailureClass = 'rate_limit' | 'network' | 'crash' | 'startup_timeout' | 'other';
+
+/**
+ * When and how often an agent's failed runs are started again; unset fields use the defaults
+ */
+export interface RetryPolicy {
+  /** Attempts in total, the first one included (default 3) */
+  max_attempts?: number;
+  /** Delay before the first retry in seconds (default 30) */
+  initial_delay_secs?: number;
+  /** Factor the delay grows by with each further retry (default 2) */
+  backoff_multiplier?: number;
+  /** Longest delay between attempts in seconds (default 900) */
+  max_delay_secs?: number;
+  /** Failure classes that are retried (default all but 'other') */
+  retry_on?: FailureClass[];
+  /** Exit codes that are retried whatever their class */
+  exit_codes?: number[];
+  /** Regexes matched against the failure's stderr; a match is retried whatever its class */
+  stderr_patterns?: string[];
+  /** Continue the failed attempt's Claude session with --resume instead of starting over */
+  resume_session?: boolean;
+}
+
+/**
+ * Latest CPU/memory sample for a running process tree
+ */
+export interface ResourceUsage {
+  cpu_time_secs: number;
+  cpu_percent: number;
+  rss_bytes: number;
+  process_count: number;
+  sampled_at?: string | null;
 }
 
 /**
@@ -118,10 +202,66 @@ export interface Agent {
   default_task?: string;
   model: string;
   hooks?: string; // JSON string of HooksConfiguration
+  resource_limits?: string; // JSON string of ResourceLimits
+  permissions?: string; // JSON string of PermissionSettings
+  parameters?: string; // JSON string of AgentParameter[]
+  budget?: string; // JSON string of RunBudget
+  verification?: string; // JSON string of AgentVerification
+  retry_policy?: string; // JSON string of RetryPolicy
   created_at: string;
   updated_at: string;
 }
 
+/**
+ * A named input an agent declares, substituted for `{{name}}` in its task and system prompt
+ */
+export type AgentParameter = {
+  name: string;
+  description?: string;
+  default?: string | number;
+  /** Parameters without a default must be given a value unless optional; defaults to true */
+  required?: boolean;
+} & (
+  | { type: 'string'; pattern?: string }
+  | { type: 'enum'; options: string[] }
+  | { type: 'path'; must_exist?: boolean }
+  | { type: 'number'; min?: number; max?: number }
+);
+
+/**
+ * A snapshot of an agent's configuration, recorded whenever it changes
+ */
+export interface AgentVersion {
+  id: number;
+  agent_id: number;
+  version: number;
+  name: string;
+  icon: string;
+  system_prompt: string;
+  default_task?: string;
+  model: string;
+  enable_file_read: boolean;
+  enable_file_write: boolean;
+  enable_network: boolean;
+  hooks?: string;
+  resource_limits?: string;
+  permissions?: string;
+  parameters?: string;
+  budget?: string;
+  verification?: string;
+  retry_policy?: string;
+  note?: string;
+  created_at: string;
+}
+
+export interface AgentVersionDiff {
+  from_version: number;
+  to_version: number;
+  changes: { field: string; before: string | null; after: string | null }[];
+  /** Line diff of the system prompt, empty when it didn't change */
+  system_prompt_diff: { op: 'equal' | 'insert' | 'delete'; text: string }[];
+}
+
 export interface AgentExport {
   version: number;
   exported_at: string;
@@ -132,6 +272,7 @@ export interface AgentExport {
     default_task?: string;
     model: string;
     hooks?: string;
+    parameters?: string;
   };
 }
 
@@ -152,11 +293,263 @@ export interface AgentRun {
   model: string;
   project_path: string;
   session_id: string;
-  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled'
+  status: string; // 'pending', 'queued', 'running', 'paused', 'completed', 'failed', 'cancelled', 'timed_out', 'oom_killed', 'limit_exceeded', 'budget_exceeded', 'verifying', 'succeeded', 'verification_failed'
   pid?: number;
   process_started_at?: string;
   created_at: string;
   completed_at?: string;
+  status_reason?: string;
+  priority?: number;
+  paused_at?: string;
+  paused_ms?: number;
+  interactive?: boolean;
+  agent_version?: number; // Version of the agent the run uses
+  parameters?: string; // JSON object of the resolved parameter values
+  trigger_cause?: string; // What started the run; unset when started by hand
+  worktree_path?: string; // Where an isolated run works instead of project_path
+  worktree_branch?: string; // The worktree's branch; unset for copies of non-git projects
+  worktree_base?: string; // Commit the worktree's branch started from
+  worktree_state?: 'active' | 'merged' | 'cherry_picked' | 'discarded' | 'retried';
+  budget?: string; // JSON string of the RunBudget enforced on the run
+  verification?: string; // JSON string of the VerificationReport once verified
+  retry_of?: number; // First attempt of the run this retries; unset for first attempts
+  attempt?: number; // 1 for first attempts, counting up with each retry
+  not_before?: string; // Earliest time a queued retry may start
+  resume_session?: string; // Claude session the run continues with --resume
+}
+
+/**
+ * What to do with a finished isolated run's changes
+ */
+export type WorktreeAction = 'merge' | 'cherry_pick' | 'discard';
+
+/**
+ * Grace periods used when escalating SIGINT -> SIGTERM -> SIGKILL on a run's process group
+ */
+export interface KillPolicy {
+  interrupt_grace_ms: number;
+  terminate_grace_ms: number;
+}
+
+/**
+ * Input sent to a session started in interactive mode
+ */
+export type SessionInput =
+  | { type: 'message'; text: string }
+  | { type: 'interrupt' }
+  | { type: 'close' }
+  | { type: 'permission_response'; request_id: string; allow: boolean; input?: any; message?: string };
+
+export type PermissionMode = 'plan' | 'default' | 'acceptEdits' | 'bypassPermissions';
+
+/**
+ * Tool permissions for a project or an agent; an agent's settings are limited by its project's
+ */
+export interface PermissionSettings {
+  mode: PermissionMode;
+  allowed_tools: string[];
+  disallowed_tools: string[];
+  /** Ask the user to approve tool use; requires an interactive session */
+  require_approval: boolean;
+}
+
+/**
+ * A tool call waiting for the user's approval
+ */
+export interface PermissionRequest {
+  request_id: string;
+  tool_name: string;
+  input: any;
+}
+
+export type SessionState = 'running' | 'completed' | 'failed' | 'cancelled';
+
+export interface TokenUsage {
+  input_tokens: number;
+  output_tokens: number;
+  cache_creation_input_tokens: number;
+  cache_read_input_tokens: number;
+}
+
+/**
+ * A typed event published for an interactive session on `session-event:{sessionId}`
+ */
+export type SessionEvent =
+  | { kind: 'message_added'; message: any }
+  | { kind: 'tool_started'; tool_use_id: string; name: string; input: any }
+  | { kind: 'tool_finished'; tool_use_id: string; is_error: boolean }
+  | ({ kind: 'permission_requested' } & PermissionRequest)
+  | { kind: 'usage_updated'; usage: TokenUsage; cost_usd: number | null }
+  | { kind: 'checkpoint_created'; checkpoint_id: string; description: string | null }
+  | { kind: 'state_changed'; state: SessionState; reason?: string }
+  | { kind: 'error'; message: string };
+
+/**
+ * A session event with its per-session sequence number; a jump in `seq` means events were missed
+ */
+export interface SessionEnvelope {
+  session_id: string;
+  seq: number;
+  event: SessionEvent;
+}
+
+/**
+ * Buffered events returned when resyncing after a gap
+ */
+export interface SessionEventsPage {
+  events: SessionEnvelope[];
+  truncated: boolean;
+}
+
+/**
+ * A session's transcript together with the sequence number of its latest live event
+ * when the transcript was read; reconnecting clients only need the events after it
+ */
+export interface SessionHistory {
+  messages: any[];
+  last_seq: number;
+}
+
+/**
+ * Concurrency limits and ordering for the agent run queue
+ */
+export interface QueueSettings {
+  max_concurrent_runs: number;
+  max_runs_per_project: number;
+  ordering: 'fifo' | 'priority';
+}
+
+/**
+ * One agent run within a workflow. The task and string parameters may use
+ * `{{steps.<id>.result}}`, `{{steps.<id>.status}}`, `{{steps.<id>.output}}` and
+ * `{{steps.<id>.output.<field>}}` to refer to steps this one depends on
+ */
+export interface WorkflowStep {
+  id: string;
+  agent_id: number;
+  task: string;
+  depends_on?: string[];
+  /** Defaults to 'on_success' */
+  condition?: 'on_success' | 'on_failure' | 'always';
+  model?: string;
+  parameters?: Record<string, string | number>;
+}
+
+export interface Workflow {
+  id?: number;
+  name: string;
+  description?: string;
+  steps: WorkflowStep[];
+  created_at: string;
+  updated_at: string;
+}
+
+export type WorkflowStepStatus = 'pending' | 'running' | 'completed' | 'failed' | 'skipped' | 'cancelled';
+
+export interface WorkflowStepRun {
+  step_id: string;
+  status: WorkflowStepStatus;
+  agent_run_id?: number;
+  result?: string; // Final result text of the agent run
+  status_reason?: string;
+}
+
+/**
+ * One execution of a workflow; progress is published on `workflow-progress:{id}`
+ * and `workflow-complete:{id}`
+ */
+export interface WorkflowRun {
+  id: number;
+  workflow_id: number;
+  workflow_name: string;
+  project_path: string;
+  status: 'running' | 'completed' | 'failed' | 'cancelled';
+  status_reason?: string;
+  steps: WorkflowStep[]; // The workflow's steps when the run started
+  step_runs: WorkflowStepRun[];
+  created_at: string;
+  completed_at?: string;
+}
+
+/**
+ * Runs an agent in a project on a cron schedule while the app is running
+ */
+export interface AgentSchedule {
+  id?: number;
+  agent_id: number;
+  project_path: string;
+  /** Five-field cron expression in local time, or @hourly/@daily/@weekly/@monthly/@yearly */
+  cron: string;
+  task?: string; // Falls back to the agent's default task
+  model?: string;
+  parameters?: string; // JSON object of parameter values
+  /** Whether a run missed while the app was closed is skipped or run once on startup */
+  missed_run_policy: 'skip' | 'catch_up';
+  enabled: boolean;
+  next_run_at?: string;
+  last_run_at?: string;
+  last_run_id?: number;
+  last_error?: string;
+  created_at: string;
+  updated_at: string;
+}
+
+/**
+ * What a trigger reacts to: files matching glob patterns (relative to the project) changing,
+ * or a new commit on a branch (the checked-out branch when none is given)
+ */
+export type TriggerEvent =
+  | { type: 'file_change'; patterns: string[] }
+  | { type: 'git_commit'; branch?: string };
+
+/**
+ * Runs an agent when files change or commits land in a project while the app is running
+ */
+export interface AgentTrigger {
+  id?: number;
+  agent_id: number;
+  project_path: string;
+  event: TriggerEvent;
+  task?: string; // Falls back to the agent's default task
+  model?: string;
+  parameters?: string; // JSON object of parameter values
+  debounce_ms: number; // Quiet time after the last change before the run starts
+  cooldown_secs: number; // Least time between two runs of the trigger
+  enabled: boolean;
+  last_fired_at?: string;
+  last_run_id?: number;
+  last_error?: string;
+  created_at: string;
+  updated_at: string;
+}
+
+/**
+ * What a set of environment variables belongs to; agent variables override project ones
+ */
+export type EnvScope =
+  | { type: 'agent'; agent_id: number }
+  | { type: 'project'; project_path: string };
+
+/**
+ * An environment variable set for agent runs (and, for projects, sessions)
+ */
+export interface EnvVar {
+  id: number;
+  name: string;
+  value?: string; // Unset for secrets, whose values never leave the backend
+  secret: boolean;
+  created_at: string;
+  updated_at: string;
+}
+
+/**
+ * A range of live output lines read from a line offset
+ */
+export interface OutputChunk {
+  start_line: number;
+  lines: string[];
+  next_offset: number;
+  total_lines: number;
 }
 
 export interface AgentRunMetrics {
@@ -175,11 +568,13 @@ export interface AgentRunWithMetrics {
   model: string;
   project_path: string;
   session_id: string;
-  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled'
+  status: string; // 'pending', 'queued', 'running', 'paused', 'completed', 'failed', 'cancelled', 'timed_out', 'oom_killed', 'limit_exceeded', 'budget_exceeded', 'verifying', 'succeeded', 'verification_failed'
   pid?: number;
   process_started_at?: string;
   created_at: string;
   completed_at?: string;
+  worktree_branch?: string; // The worktree's branch; unset for copies of non-git projects
+  worktree_state?: 'active' | 'merged' | 'cherry_picked' | 'discarded' | 'retried';
   metrics?: AgentRunMetrics;
   output?: string; // Real-time JSONL content
 }
@@ -259,6 +654,8 @@ export interface CheckpointMetadata {
   userPrompt: string;
   fileChanges: number;
   snapshotSize: number;
+  /** Files left out of the checkpoint because of size or content limits */
+  skippedFiles?: string[];
 }
 
 /**
@@ -293,6 +690,19 @@ export interface SessionTimeline {
   autoCheckpointEnabled: boolean;
   checkpointStrategy: CheckpointStrategy;
   totalCheckpoints: number;
+  limits?: CheckpointLimits;
+}
+
+/**
+ * Size and content limits for checkpointed files
+ */
+export interface CheckpointLimits {
+  /** Files larger than this many bytes are not snapshotted */
+  maxFileSize: number;
+  /** Total bytes of file content a single checkpoint may hold */
+  maxCheckpointSize: number;
+  /** File extensions (without the dot) that are never snapshotted */
+  excludedExtensions: string[];
 }
 
 /**
@@ -669,6 +1079,10 @@ export const api = {
    * @param default_task - Optional default task
    * @param model - Optional model (defaults to 'sonnet')
    * @param hooks - Optional hooks configuration as JSON string
+   * @param parameters - Optional input parameters as a JSON string of AgentParameter[]
+   * @param budget - Optional budget for each run as a JSON string of RunBudget
+   * @param verification - Optional checks for finished runs as a JSON string of AgentVerification
+   * @param retryPolicy - Optional retry policy for failed runs as a JSON string of RetryPolicy
    * @returns Promise resolving to the created agent
    */
   async createAgent(
@@ -677,7 +1091,13 @@ export const api = {
     system_prompt: string, 
     default_task?: string, 
     model?: string,
-    hooks?: string
+    hooks?: string,
+    resource_limits?: string,
+    permissions?: string,
+    parameters?: string,
+    budget?: string,
+    verification?: string,
+    retryPolicy?: string
   ): Promise<Agent> {
     try {
       return await invoke<Agent>('create_agent', { 
@@ -686,7 +1106,13 @@ export const api = {
         systemPrompt: system_prompt,
         defaultTask: default_task,
         model,
-        hooks
+        hooks,
+        resourceLimits: resource_limits,
+        permissions,
+        parameters,
+        budget,
+        verification,
+        retryPolicy
       });
     } catch (error) {
       console.error("Failed to create agent:", error);
@@ -703,6 +1129,11 @@ export const api = {
    * @param default_task - Optional default task
    * @param model - Optional model
    * @param hooks - Optional hooks configuration as JSON string
+   * @param parameters - Optional input parameters as a JSON string of AgentParameter[]; an empty string removes them
+   * @param budget - Optional budget for each run as a JSON string of RunBudget; an empty string removes it
+   * @param verification - Optional checks for finished runs as a JSON string of AgentVerification; an empty string turns them off
+   * @param retryPolicy - Optional retry policy for failed runs as a JSON string of RetryPolicy; an empty string turns retries off
+   * @param note - Optional note describing the change, kept with the new version
    * @returns Promise resolving to the updated agent
    */
   async updateAgent(
@@ -712,7 +1143,14 @@ export const api = {
     system_prompt: string, 
     default_task?: string, 
     model?: string,
-    hooks?: string
+    hooks?: string,
+    resource_limits?: string,
+    permissions?: string,
+    parameters?: string,
+    budget?: string,
+    verification?: string,
+    retryPolicy?: string,
+    note?: string
   ): Promise<Agent> {
     try {
       return await invoke<Agent>('update_agent', { 
@@ -722,7 +1160,14 @@ export const api = {
         systemPrompt: system_prompt,
         defaultTask: default_task,
         model,
-        hooks
+        hooks,
+        resourceLimits: resource_limits,
+        permissions,
+        parameters,
+        budget,
+        verification,
+        retryPolicy,
+        note
       });
     } catch (error) {
       console.error("Failed to update agent:", error);
@@ -730,6 +1175,43 @@ export const api = {
     }
   },
 
+  /**
+   * Lists an agent's versions, newest first
+   * @param agentId - The agent ID
+   */
+  async listAgentVersions(agentId: number): Promise<AgentVersion[]> {
+    return invoke<AgentVersion[]>('list_agent_versions', { agentId });
+  },
+
+  /**
+   * Gets one version of an agent
+   * @param agentId - The agent ID
+   * @param version - The version number
+   */
+  async getAgentVersion(agentId: number, version: number): Promise<AgentVersion> {
+    return invoke<AgentVersion>('get_agent_version', { agentId, version });
+  },
+
+  /**
+   * Shows what changed between two versions of an agent
+   * @param agentId - The agent ID
+   * @param fromVersion - The older version
+   * @param toVersion - The newer version
+   */
+  async diffAgentVersions(agentId: number, fromVersion: number, toVersion: number): Promise<AgentVersionDiff> {
+    return invoke<AgentVersionDiff>('diff_agent_versions', { agentId, fromVersion, toVersion });
+  },
+
+  /**
+   * Rolls an agent back to an earlier version, recorded as a new version
+   * @param agentId - The agent ID
+   * @param version - The version to restore
+   * @param note - Optional note explaining the rollback
+   */
+  async rollbackAgent(agentId: number, version: number, note?: string): Promise<AgentVersion> {
+    return invoke<AgentVersion>('rollback_agent', { agentId, version, note });
+  },
+
   /**
    * Deletes an agent
    * @param id - The agent ID to delete
@@ -806,11 +1288,16 @@ export const api = {
    * @param projectPath - The project path to run the agent in
    * @param task - The task description
    * @param model - Optional model override
-   * @returns Promise resolving to the run ID when execution starts
+   * @param priority - Optional queue priority; higher runs start first
+   * @param interactive - Keep stdin open so follow-up input can be sent with sendSessionInput
+   * @param parameters - Values for the agent's declared parameters, by name
+   * @param isolated - Run in a temporary git worktree (or a copy of the project) on its own branch
+   * @param budget - Limits for this run; those left unset come from the agent's budget
+   * @returns Promise resolving to the run ID once the run is queued
    */
-  async executeAgent(agentId: number, projectPath: string, task: string, model?: string): Promise<number> {
+  async executeAgent(agentId: number, projectPath: string, task: string, model?: string, priority?: number, interactive?: boolean, parameters?: Record<string, string | number>, isolated?: boolean, budget?: RunBudget): Promise<number> {
     try {
-      return await invoke<number>('execute_agent', { agentId, projectPath, task, model });
+      return await invoke<number>('execute_agent', { agentId, projectPath, task, model, priority, interactive, parameters, isolated, budget });
     } catch (error) {
       console.error("Failed to execute agent:", error);
       // Return a sentinel value to indicate error
@@ -877,17 +1364,419 @@ export const api = {
   /**
    * Kills a running agent session
    * @param runId - The run ID to kill
+   * @param policy - Optional grace period overrides for this kill
    * @returns Promise resolving to whether the session was successfully killed
    */
-  async killAgentSession(runId: number): Promise<boolean> {
+  async killAgentSession(runId: number, policy?: Partial<KillPolicy>): Promise<boolean> {
     try {
-      return await invoke<boolean>('kill_agent_session', { runId });
+      return await invoke<boolean>('kill_agent_session', {
+        runId,
+        interruptGraceMs: policy?.interrupt_grace_ms,
+        terminateGraceMs: policy?.terminate_grace_ms,
+      });
     } catch (error) {
       console.error("Failed to kill agent session:", error);
       throw new Error(`Failed to kill agent session: ${error instanceof Error ? error.message : 'Unknown error'}`);
     }
   },
 
+  /**
+   * Pauses a running agent session
+   * @param runId - The run ID to pause
+   * @returns Promise resolving to whether the session was paused
+   */
+  async pauseAgentSession(runId: number): Promise<boolean> {
+    try {
+      return await invoke<boolean>('pause_agent_session', { runId });
+    } catch (error) {
+      console.error("Failed to pause agent session:", error);
+      throw new Error(`Failed to pause agent session: ${error instanceof Error ? error.message : 'Unknown error'}`);
+    }
+  },
+
+  /**
+   * Resumes a paused agent session
+   * @param runId - The run ID to resume
+   * @returns Promise resolving to whether the session was resumed
+   */
+  async resumeAgentSession(runId: number): Promise<boolean> {
+    try {
+      return await invoke<boolean>('resume_agent_session', { runId });
+    } catch (error) {
+      console.error("Failed to resume agent session:", error);
+      throw new Error(`Failed to resume agent session: ${error instanceof Error ? error.message : 'Unknown error'}`);
+    }
+  },
+
+  /**
+   * Gets the grace periods used when killing agent sessions
+   * @returns Promise resolving to the current kill policy
+   */
+  async getKillPolicy(): Promise<KillPolicy> {
+    try {
+      return await invoke<KillPolicy>('get_kill_policy');
+    } catch (error) {
+      console.error("Failed to get kill policy:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Sets the grace periods used when killing agent sessions
+   * @param policy - The new kill policy
+   */
+  async setKillPolicy(policy: KillPolicy): Promise<void> {
+    try {
+      return await invoke('set_kill_policy', { policy });
+    } catch (error) {
+      console.error("Failed to set kill policy:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Lists queued agent runs in the order they will start
+   * @returns Promise resolving to the queued runs
+   */
+  async listQueuedRuns(): Promise<AgentRun[]> {
+    try {
+      return await invoke<AgentRun[]>('list_queued_runs');
+    } catch (error) {
+      console.error("Failed to list queued runs:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets the agent run queue settings
+   * @returns Promise resolving to the queue settings
+   */
+  async getQueueSettings(): Promise<QueueSettings> {
+    try {
+      return await invoke<QueueSettings>('get_queue_settings');
+    } catch (error) {
+      console.error("Failed to get queue settings:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Updates the agent run queue settings
+   * @param settings - The new queue settings
+   */
+  async updateQueueSettings(settings: QueueSettings): Promise<void> {
+    try {
+      return await invoke('update_queue_settings', { settings });
+    } catch (error) {
+      console.error("Failed to update queue settings:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Changes the priority of a queued run
+   * @param runId - The queued run ID
+   * @param priority - The new priority
+   * @returns Promise resolving to whether the run was still queued
+   */
+  async setQueuedRunPriority(runId: number, priority: number): Promise<boolean> {
+    try {
+      return await invoke<boolean>('set_queued_run_priority', { runId, priority });
+    } catch (error) {
+      console.error("Failed to set queued run priority:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Moves queued runs to the front of the queue in the given order
+   * @param runIds - The queued run IDs
+   */
+  async reorderQueuedRuns(runIds: number[]): Promise<void> {
+    try {
+      return await invoke('reorder_queued_runs', { runIds });
+    } catch (error) {
+      console.error("Failed to reorder queued runs:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Cancels a run that hasn't started yet
+   * @param runId - The queued run ID
+   * @returns Promise resolving to whether the run was cancelled
+   */
+  async cancelQueuedRun(runId: number): Promise<boolean> {
+    try {
+      return await invoke<boolean>('cancel_queued_run', { runId });
+    } catch (error) {
+      console.error("Failed to cancel queued run:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Lists all workflows
+   */
+  async listWorkflows(): Promise<Workflow[]> {
+    return invoke<Workflow[]>('list_workflows');
+  },
+
+  /**
+   * Gets a single workflow by ID
+   * @param id - The workflow ID
+   */
+  async getWorkflow(id: number): Promise<Workflow> {
+    return invoke<Workflow>('get_workflow', { id });
+  },
+
+  /**
+   * Creates a workflow
+   * @param name - The workflow name
+   * @param description - Optional description
+   * @param steps - The steps, which must form a DAG
+   */
+  async createWorkflow(name: string, description: string | undefined, steps: WorkflowStep[]): Promise<Workflow> {
+    return invoke<Workflow>('create_workflow', { name, description, steps });
+  },
+
+  /**
+   * Updates a workflow; runs already started keep their steps
+   * @param id - The workflow ID
+   * @param name - The workflow name
+   * @param description - Optional description
+   * @param steps - The steps, which must form a DAG
+   */
+  async updateWorkflow(id: number, name: string, description: string | undefined, steps: WorkflowStep[]): Promise<Workflow> {
+    return invoke<Workflow>('update_workflow', { id, name, description, steps });
+  },
+
+  /**
+   * Deletes a workflow and its run history
+   * @param id - The workflow ID
+   */
+  async deleteWorkflow(id: number): Promise<void> {
+    return invoke('delete_workflow', { id });
+  },
+
+  /**
+   * Starts a workflow in a project
+   * @param workflowId - The workflow ID
+   * @param projectPath - The project path every step runs in
+   * @returns Promise resolving to the workflow run ID
+   */
+  async runWorkflow(workflowId: number, projectPath: string): Promise<number> {
+    return invoke<number>('run_workflow', { workflowId, projectPath });
+  },
+
+  /**
+   * Lists workflow runs, newest first
+   * @param workflowId - Optional workflow ID to filter by
+   */
+  async listWorkflowRuns(workflowId?: number): Promise<WorkflowRun[]> {
+    return invoke<WorkflowRun[]>('list_workflow_runs', { workflowId });
+  },
+
+  /**
+   * Gets a workflow run with the progress of each step
+   * @param id - The workflow run ID
+   */
+  async getWorkflowRun(id: number): Promise<WorkflowRun> {
+    return invoke<WorkflowRun>('get_workflow_run', { id });
+  },
+
+  /**
+   * Cancels a running workflow and stops its unfinished steps
+   * @param id - The workflow run ID
+   * @returns Promise resolving to whether the run was still running
+   */
+  async cancelWorkflowRun(id: number): Promise<boolean> {
+    return invoke<boolean>('cancel_workflow_run', { id });
+  },
+
+  /**
+   * Lists agent schedules
+   * @param agentId - Optional agent ID to filter by
+   */
+  async listSchedules(agentId?: number): Promise<AgentSchedule[]> {
+    return invoke<AgentSchedule[]>('list_schedules', { agentId });
+  },
+
+  /**
+   * Creates a schedule that runs an agent in a project
+   * @param agentId - The agent ID
+   * @param projectPath - The project path to run the agent in
+   * @param cron - Cron expression, evaluated in local time
+   * @param task - Optional task; defaults to the agent's default task
+   * @param model - Optional model override
+   * @param parameters - Values for the agent's declared parameters
+   * @param missedRunPolicy - What to do about runs missed while the app was closed
+   */
+  async createSchedule(
+    agentId: number,
+    projectPath: string,
+    cron: string,
+    task?: string,
+    model?: string,
+    parameters?: Record<string, string | number>,
+    missedRunPolicy?: 'skip' | 'catch_up'
+  ): Promise<AgentSchedule> {
+    return invoke<AgentSchedule>('create_schedule', { agentId, projectPath, cron, task, model, parameters, missedRunPolicy });
+  },
+
+  /**
+   * Updates a schedule; its next run time is worked out again from now
+   * @param id - The schedule ID
+   */
+  async updateSchedule(
+    id: number,
+    projectPath: string,
+    cron: string,
+    task?: string,
+    model?: string,
+    parameters?: Record<string, string | number>,
+    missedRunPolicy?: 'skip' | 'catch_up'
+  ): Promise<AgentSchedule> {
+    return invoke<AgentSchedule>('update_schedule', { id, projectPath, cron, task, model, parameters, missedRunPolicy });
+  },
+
+  /**
+   * Enables or disables a schedule
+   * @param id - The schedule ID
+   * @param enabled - Whether the schedule should run
+   */
+  async setScheduleEnabled(id: number, enabled: boolean): Promise<AgentSchedule> {
+    return invoke<AgentSchedule>('set_schedule_enabled', { id, enabled });
+  },
+
+  /**
+   * Deletes a schedule
+   * @param id - The schedule ID
+   */
+  async deleteSchedule(id: number): Promise<void> {
+    return invoke('delete_schedule', { id });
+  },
+
+  /**
+   * Lists the next times a cron expression matches
+   * @param cron - The cron expression
+   * @param count - How many times to list (default 5)
+   */
+  async previewSchedule(cron: string, count?: number): Promise<string[]> {
+    return invoke<string[]>('preview_schedule', { cron, count });
+  },
+
+  /**
+   * Lists agent triggers
+   * @param agentId - Optional agent ID to filter by
+   */
+  async listTriggers(agentId?: number): Promise<AgentTrigger[]> {
+    return invoke<AgentTrigger[]>('list_triggers', { agentId });
+  },
+
+  /**
+   * Creates a trigger that runs an agent on file changes or commits in a project
+   * @param agentId - The agent ID
+   * @param projectPath - The project path to watch and run the agent in
+   * @param event - What the trigger reacts to
+   * @param task - Optional task; defaults to the agent's default task
+   * @param model - Optional model override
+   * @param parameters - Values for the agent's declared parameters
+   * @param debounceMs - Quiet time after the last change before the run starts (default 2000)
+   * @param cooldownSecs - Least time between two runs of the trigger (default 60)
+   */
+  async createTrigger(
+    agentId: number,
+    projectPath: string,
+    event: TriggerEvent,
+    task?: string,
+    model?: string,
+    parameters?: Record<string, string | number>,
+    debounceMs?: number,
+    cooldownSecs?: number
+  ): Promise<AgentTrigger> {
+    return invoke<AgentTrigger>('create_trigger', { agentId, projectPath, event, task, model, parameters, debounceMs, cooldownSecs });
+  },
+
+  /**
+   * Updates a trigger; it starts watching again from the project's current state
+   * @param id - The trigger ID
+   */
+  async updateTrigger(
+    id: number,
+    projectPath: string,
+    event: TriggerEvent,
+    task?: string,
+    model?: string,
+    parameters?: Record<string, string | number>,
+    debounceMs?: number,
+    cooldownSecs?: number
+  ): Promise<AgentTrigger> {
+    return invoke<AgentTrigger>('update_trigger', { id, projectPath, event, task, model, parameters, debounceMs, cooldownSecs });
+  },
+
+  /**
+   * Enables or disables a trigger
+   * @param id - The trigger ID
+   * @param enabled - Whether the trigger should watch its project
+   */
+  async setTriggerEnabled(id: number, enabled: boolean): Promise<AgentTrigger> {
+    return invoke<AgentTrigger>('set_trigger_enabled', { id, enabled });
+  },
+
+  /**
+   * Deletes a trigger
+   * @param id - The trigger ID
+   */
+  async deleteTrigger(id: number): Promise<void> {
+    return invoke('delete_trigger', { id });
+  },
+
+  /**
+   * Lists the files an isolated run changed in its worktree
+   * @param runId - The run ID
+   */
+  async listRunWorktreeChanges(runId: number): Promise<string[]> {
+    return invoke<string[]>('list_run_worktree_changes', { runId });
+  },
+
+  /**
+   * Merges, cherry-picks or discards a finished isolated run's changes and removes its worktree
+   * @param runId - The run ID
+   * @param action - What to do with the changes
+   * @returns Promise resolving to the updated run
+   */
+  async finishRunWorktree(runId: number, action: WorktreeAction): Promise<AgentRun> {
+    return invoke<AgentRun>('finish_run_worktree', { runId, action });
+  },
+
+  /**
+   * Lists the environment variables of an agent or project; secret values are left out
+   * @param scope - The agent or project
+   */
+  async listEnvVars(scope: EnvScope): Promise<EnvVar[]> {
+    return invoke<EnvVar[]>('list_env_vars', { scope });
+  },
+
+  /**
+   * Sets an environment variable of an agent or project, replacing one with the same name
+   * @param scope - The agent or project
+   * @param name - The variable name
+   * @param value - The value; secret values are stored encrypted and redacted from run output
+   * @param secret - Whether the value is a secret
+   */
+  async setEnvVar(scope: EnvScope, name: string, value: string, secret: boolean): Promise<void> {
+    return invoke('set_env_var', { scope, name, value, secret });
+  },
+
+  /**
+   * Deletes an environment variable
+   * @param id - The variable ID
+   */
+  async deleteEnvVar(id: number): Promise<void> {
+    return invoke('delete_env_var', { id });
+  },
+
   /**
    * Gets the status of a specific agent session
    * @param runId - The run ID to check
@@ -943,6 +1832,22 @@ export const api = {
     }
   },
 
+  /**
+   * Read live output lines starting at a line offset, for incremental polling
+   * @param runId - The run ID to read output for
+   * @param offset - Line offset to start from (use the previous chunk's next_offset)
+   * @param limit - Optional maximum number of lines to return
+   * @returns Promise resolving to the chunk, or null once the process is no longer tracked
+   */
+  async readLiveSessionOutput(runId: number, offset: number, limit?: number): Promise<OutputChunk | null> {
+    try {
+      return await invoke<OutputChunk | null>('read_live_session_output', { runId, offset, limit });
+    } catch (error) {
+      console.error("Failed to read live session output:", error);
+      throw new Error(`Failed to read live session output: ${error instanceof Error ? error.message : 'Unknown error'}`);
+    }
+  },
+
   /**
    * Start streaming real-time output for a running session
    * @param runId - The run ID to stream output for
@@ -960,7 +1865,7 @@ export const api = {
   /**
    * Loads the JSONL history for a specific session
    */
-  async loadSessionHistory(sessionId: string, projectId: string): Promise<any[]> {
+  async loadSessionHistory(sessionId: string, projectId: string): Promise<SessionHistory> {
     return invoke("load_session_history", { sessionId, projectId });
   },
 
@@ -981,33 +1886,71 @@ export const api = {
 
   /**
    * Executes a new interactive Claude Code session with streaming output
+   * @returns Promise resolving to the Claude session ID once the session has started
    */
-  async executeClaudeCode(projectPath: string, prompt: string, model: string): Promise<void> {
-    return invoke("execute_claude_code", { projectPath, prompt, model });
+  async executeClaudeCode(projectPath: string, prompt: string, model: string, resourceLimits?: ResourceLimits, interactive?: boolean): Promise<string> {
+    return invoke<string>("execute_claude_code", { projectPath, prompt, model, resourceLimits, interactive });
   },
 
   /**
    * Continues an existing Claude Code conversation with streaming output
+   * @returns Promise resolving to the Claude session ID once the session has started
    */
-  async continueClaudeCode(projectPath: string, prompt: string, model: string): Promise<void> {
-    return invoke("continue_claude_code", { projectPath, prompt, model });
+  async continueClaudeCode(projectPath: string, prompt: string, model: string, resourceLimits?: ResourceLimits, interactive?: boolean): Promise<string> {
+    return invoke<string>("continue_claude_code", { projectPath, prompt, model, resourceLimits, interactive });
   },
 
   /**
    * Resumes an existing Claude Code session by ID with streaming output
+   * @returns Promise resolving to the Claude session ID, which may differ from the one resumed
+   */
+  async resumeClaudeCode(projectPath: string, sessionId: string, prompt: string, model: string, resourceLimits?: ResourceLimits, interactive?: boolean): Promise<string> {
+    return invoke<string>("resume_claude_code", { projectPath, sessionId, prompt, model, resourceLimits, interactive });
+  },
+
+  /**
+   * Sends a follow-up message, an interrupt, or end-of-input to an interactive session
+   * @param target - The Claude session ID of an interactive session, or the run ID of an interactive agent run
+   * @param input - The input to send
    */
-  async resumeClaudeCode(projectPath: string, sessionId: string, prompt: string, model: string): Promise<void> {
-    return invoke("resume_claude_code", { projectPath, sessionId, prompt, model });
+  async sendSessionInput(target: { sessionId?: string; runId?: number }, input: SessionInput): Promise<void> {
+    return invoke("send_session_input", { sessionId: target.sessionId, runId: target.runId, input });
   },
 
   /**
-   * Cancels the currently running Claude Code execution
-   * @param sessionId - Optional session ID to cancel a specific session
+   * Cancels a running Claude Code session
+   * @param sessionId - The session ID to cancel
    */
-  async cancelClaudeExecution(sessionId?: string): Promise<void> {
+  async cancelClaudeExecution(sessionId: string): Promise<void> {
     return invoke("cancel_claude_execution", { sessionId });
   },
 
+  /**
+   * Gets the permission settings for a project
+   * @param projectPath - The project directory
+   */
+  async getProjectPermissions(projectPath: string): Promise<PermissionSettings> {
+    return invoke("get_project_permissions", { projectPath });
+  },
+
+  /**
+   * Updates the permission settings for a project
+   * @param projectPath - The project directory
+   * @param settings - The new settings
+   */
+  async setProjectPermissions(projectPath: string, settings: PermissionSettings): Promise<void> {
+    return invoke("set_project_permissions", { projectPath, settings });
+  },
+
+  /**
+   * Gets a session's buffered events after a sequence number, to fill a gap
+   * @param sessionId - The session ID
+   * @param afterSeq - The last sequence number the client has seen
+   */
+  async getSessionEvents(sessionId: string, afterSeq: number): Promise<SessionEventsPage> {
+    return invoke("get_session_events", { sessionId, afterSeq });
+  },
+
   /**
    * Lists all currently running Claude sessions
    * @returns Promise resolving to list of running Claude sessions
@@ -1200,14 +2143,18 @@ export const api = {
     projectId: string,
     projectPath: string,
     autoCheckpointEnabled: boolean,
-    checkpointStrategy: CheckpointStrategy
+    checkpointStrategy: CheckpointStrategy,
+    limits?: Partial<CheckpointLimits>
   ): Promise<void> {
     return invoke("update_checkpoint_settings", {
       sessionId,
       projectId,
       projectPath,
       autoCheckpointEnabled,
-      checkpointStrategy
+      checkpointStrategy,
+      maxFileSize: limits?.maxFileSize,
+      maxCheckpointSize: limits?.maxCheckpointSize,
+      excludedExtensions: limits?.excludedExtensions
     });
   },
 
@@ -1311,6 +2258,7 @@ export const api = {
     checkpoint_strategy: CheckpointStrategy;
     total_checkpoints: number;
     current_checkpoint_id?: string;
+    limits: CheckpointLimits;
   }> {
     try {
       return await invoke("get_checkpoint_settings", {
@@ -0,0 +1,63 @@
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import { api, type SessionEnvelope, type SessionEvent } from "./api";
+
+/**
+ * Subscribes to a session's typed events, delivering each exactly once and in order.
+ *
+ * Events published before the listener was attached, or skipped in transit, are
+ * detected by their sequence numbers and fetched from the backend's buffer.
+ *
+ * @param sessionId - The session to follow
+ * @param onEvent - Called for each event in sequence order
+ * @param onTruncated - Called when missed events are no longer buffered and the
+ *   session transcript must be reloaded instead
+ * @param afterSeq - The last sequence number already accounted for, e.g. the
+ *   `last_seq` returned with the session history; earlier events are skipped
+ */
+export async function listenToSessionEvents(
+  sessionId: string,
+  onEvent: (event: SessionEvent) => void,
+  onTruncated?: () => void,
+  afterSeq = 0
+): Promise<UnlistenFn> {
+  let lastSeq = afterSeq;
+  let resyncing = false;
+  const pending: SessionEnvelope[] = [];
+
+  const apply = (envelope: SessionEnvelope) => {
+    if (envelope.seq <= lastSeq) return;
+    lastSeq = envelope.seq;
+    onEvent(envelope.event);
+  };
+
+  const resync = async () => {
+    resyncing = true;
+    try {
+      const page = await api.getSessionEvents(sessionId, lastSeq);
+      if (page.truncated) onTruncated?.();
+      page.events.forEach(apply);
+    } catch (err) {
+      console.error("Failed to resync session events:", err);
+    } finally {
+      resyncing = false;
+      pending.splice(0).sort((a, b) => a.seq - b.seq).forEach(apply);
+    }
+  };
+
+  const unlisten = await listen<SessionEnvelope>(`session-event:${sessionId}`, (event) => {
+    const envelope = event.payload;
+    if (resyncing) {
+      pending.push(envelope);
+    } else if (envelope.seq > lastSeq + 1) {
+      pending.push(envelope);
+      void resync();
+    } else {
+      apply(envelope);
+    }
+  });
+
+  // Catch up on anything published before the listener was attached
+  await resync();
+
+  return unlisten;
+}
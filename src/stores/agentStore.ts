@@ -59,7 +59,7 @@ export const useAgentStore = create<AgentState>()(
       try {
         const runs = await api.listAgentRuns();
         const runningIds = runs
-          .filter(r => r.status === 'running' || r.status === 'pending')
+          .filter(r => r.status === 'running' || r.status === 'paused' || r.status === 'queued' || r.status === 'pending')
           .map(r => r.id?.toString() || '')
           .filter(Boolean);
         
@@ -148,7 +148,7 @@ export const useAgentStore = create<AgentState>()(
       try {
         // First ensure the run is cancelled if it's still running
         const run = get().agentRuns.find(r => r.id === runId);
-        if (run && (run.status === 'running' || run.status === 'pending')) {
+        if (run && (run.status === 'running' || run.status === 'paused' || run.status === 'queued' || run.status === 'pending')) {
           await api.killAgentSession(runId);
         }
         
@@ -189,7 +189,7 @@ export const useAgentStore = create<AgentState>()(
         }
         
         const runningIds = updatedRuns
-          .filter(r => r.status === 'running' || r.status === 'pending')
+          .filter(r => r.status === 'running' || r.status === 'paused' || r.status === 'queued' || r.status === 'pending')
           .map(r => r.id?.toString() || '')
           .filter(Boolean);
         